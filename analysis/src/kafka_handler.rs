@@ -1,4 +1,5 @@
 use model::general_model::VersionWithTag;
+use model::message::decode;
 use rdkafka::consumer::{BaseConsumer, Consumer};
 use rdkafka::{ClientConfig, Message};
 use std::time::Duration;
@@ -27,7 +28,7 @@ impl KafkaReader {
             match self.consumer.poll(Duration::from_secs(1)) {
                 Some(Ok(message)) => {
                     if let Some(payload) = message.payload() {
-                        match serde_json::from_slice::<VersionWithTag>(payload) {
+                        match decode::<VersionWithTag>(payload) {
                             Ok(version_with_tag) => return Some(version_with_tag),
                             Err(e) => eprintln!("Failed to deserialize message: {:?}", e),
                         }
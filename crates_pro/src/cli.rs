@@ -1,19 +1,362 @@
-use structopt::StructOpt;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
 
-#[derive(StructOpt, Debug, Default, Clone)]
+/// Import the rust ecosystem into a graph, export and analyze it.
+///
+/// Without a command, run the services enabled by `CRATES_PRO_IMPORT`,
+/// `CRATES_PRO_ANALYSIS` and `CRATES_PRO_PACKAGE`.
+#[derive(Parser, Debug, Default, Clone)]
+#[command(name = "crates-pro", version, about, long_about = None)]
 pub struct CratesProCli {
-    #[structopt(subcommand)]
-    pub(crate) _command: Option<Command>,
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
 
-    #[structopt(short, long)]
+    #[arg(short = 'm', long = "mega-base", global = true)]
     pub(crate) _mega_base: Option<String>,
 
-    #[structopt(short, long)]
+    /// Import the repos of mega without cloning them
+    #[arg(short, long, global = true)]
     pub(crate) dont_clone: bool,
+
+    /// The TOML config file, `CRATES_PRO_CONFIG` or `crates-pro.toml` by default
+    #[arg(short, long, global = true)]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Override a config key, such as `--set kafka.broker=localhost:9092`
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    pub(crate) overrides: Vec<String>,
 }
 
-#[derive(StructOpt, Debug, Default, Clone)]
+#[derive(Subcommand, Debug, Clone)]
 pub enum Command {
-    #[default]
-    Mega,
+    /// Run the services enabled by `CRATES_PRO_IMPORT`, `CRATES_PRO_ANALYSIS`
+    /// and `CRATES_PRO_PACKAGE`, the default
+    Run,
+    /// Import crates and repos, and write the tugraph import files
+    #[command(subcommand)]
+    Import(ImportCommand),
+    /// Export the graph or the schemas
+    #[command(subcommand)]
+    Export(ExportCommand),
+    /// Analyze the imported crates
+    #[command(subcommand)]
+    Analyze(AnalyzeCommand),
+    /// Keep the graph in sync with a source
+    #[command(subcommand)]
+    Sync(SyncCommand),
+    /// Take, export and restore the named snapshots of the graph
+    #[command(subcommand)]
+    Snapshot(SnapshotCommand),
+    /// Compare two outputs of the import, and print the vertices and edges
+    /// added, removed or changed as json
+    Diff {
+        /// the old graph: a directory of the tugraph import files, a checkpoint,
+        /// or the id or the name of a snapshot
+        old: PathBuf,
+        /// the new graph, as `old`
+        new: PathBuf,
+
+        /// only compare the labels, such as `--label program --label depends_on`
+        #[arg(long)]
+        label: Vec<String>,
+
+        /// print only the numbers of the changes per label
+        #[arg(long)]
+        summary: bool,
+    },
+    /// Serve the REST and GraphQL APIs to query the crates in TuGraph
+    Serve {
+        #[arg(long, default_value = "0.0.0.0:6889")]
+        addr: String,
+
+        /// serve the gRPC import and query services too, such as `0.0.0.0:6890`
+        #[arg(long)]
+        grpc_addr: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ImportCommand {
+    /// Import an extracted crates.io database dump
+    CratesioDump {
+        /// the directory of the extracted dump
+        path: PathBuf,
+    },
+    /// Clone the repos in a url list, one per line, and import them
+    Repos {
+        /// the file of the urls
+        list: PathBuf,
+
+        #[command(flatten)]
+        clone: CloneArgs,
+    },
+    /// Import a local repo, or all the repos in a directory of repos
+    Local {
+        /// a path or a `file://` url
+        path: String,
+    },
+    /// Import the repos requested in `KAFKA_REPO_REQUEST_TOPIC` as a worker,
+    /// and publish the results to kafka
+    Worker,
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct CloneArgs {
+    /// the directory to clone into, `NEW_CRATES_DIR` by default
+    #[arg(long)]
+    pub(crate) dir: Option<PathBuf>,
+
+    /// the clone depth, 0 for the full history, `CLONE_DEPTH` or 1 by default
+    #[arg(long)]
+    pub(crate) depth: Option<i32>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ExportCommand {
+    /// Write the outputs of `IMPORT_OUTPUT_FORMATS` from a checkpoint
+    Graph {
+        #[command(flatten)]
+        checkpoint: CheckpointArgs,
+    },
+    /// Write the Avro schemas of the messages between the services
+    MessageSchemas {
+        /// the directory to write the `.avsc` files into
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum AnalyzeCommand {
+    /// Run the analysis tools on the next version in `KAFKA_ANALYSIS_TOPIC`
+    Once {
+        /// the directory of the analysis results
+        #[arg(long, default_value = "/home/rust/output/analysis")]
+        output: String,
+    },
+    /// Diff the public API of two versions of a library, and print the changes as json
+    ApiDiff {
+        name: String,
+        old_version: String,
+        new_version: String,
+
+        #[command(flatten)]
+        checkpoint: CheckpointArgs,
+    },
+    /// Print the versions of a crate whose MSRV is satisfied by a rustc version
+    Compatible {
+        name: String,
+        /// such as `1.70`
+        rustc: String,
+
+        #[command(flatten)]
+        checkpoint: CheckpointArgs,
+    },
+    /// Print the transitive dependencies of a crate as json, with their depths
+    Closure {
+        name: String,
+        /// all the versions of the crate by default
+        #[arg(long)]
+        version: Option<String>,
+
+        #[command(flatten)]
+        checkpoint: CheckpointArgs,
+    },
+    /// Print the direct and the transitive dependents of a version of a crate as json
+    Dependents {
+        name: String,
+        /// the max version of the crate by default
+        #[arg(long)]
+        version: Option<String>,
+
+        #[command(flatten)]
+        checkpoint: CheckpointArgs,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SyncCommand {
+    /// Sync the crates.io index into a bare repo continuously,
+    /// and write the tugraph import files when there are new versions
+    CratesioIndex {
+        /// the bare repo of the index
+        path: PathBuf,
+
+        /// seconds between two syncs
+        #[arg(long, default_value_t = 300)]
+        interval: u64,
+    },
+    /// Re-import the sources of a schedule file on their cron schedules
+    Schedule {
+        /// the TOML file of the `[[source]]`s, with their `name`, `kind`, `path` and `cron`
+        file: PathBuf,
+    },
+    /// Check whether docs.rs built the docs of the library versions not checked yet,
+    /// and save them into the checkpoint, to be written by the next import
+    Docsrs {
+        /// the checkpoint to update, `CHECKPOINT_DIR/latest.json` by default
+        #[arg(long)]
+        checkpoint: Option<PathBuf>,
+
+        /// the versions to check at most, the latest published first
+        #[arg(long, default_value_t = 1000)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SnapshotCommand {
+    /// Take a snapshot of a checkpoint, and print its id
+    Create {
+        /// the unique name, such as `nightly-2024-06-01`
+        name: String,
+
+        #[command(flatten)]
+        checkpoint: CheckpointArgs,
+    },
+    /// Print the snapshots as json, the oldest first
+    List,
+    /// Export a snapshot into a `.tar.gz`
+    Export {
+        /// the id or the name
+        snapshot: String,
+        archive: PathBuf,
+    },
+    /// Import a snapshot exported by `snapshot export`
+    Import { archive: PathBuf },
+    /// Restore a snapshot as the checkpoint `CHECKPOINT_DIR/latest.json`,
+    /// which the import continues from
+    Restore {
+        /// the id or the name
+        snapshot: String,
+    },
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct CheckpointArgs {
+    /// the checkpoint to read, `CHECKPOINT_DIR/latest.json` by default
+    #[arg(long, conflicts_with = "snapshot")]
+    pub(crate) checkpoint: Option<PathBuf>,
+
+    /// read the snapshot of the id or the name rather than a checkpoint
+    #[arg(long)]
+    pub(crate) snapshot: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_parse_commands() {
+        CratesProCli::command().debug_assert();
+
+        let cli = CratesProCli::try_parse_from(["crates-pro"]).unwrap();
+        assert!(cli.command.is_none());
+
+        let cli = CratesProCli::try_parse_from([
+            "crates-pro",
+            "import",
+            "worker",
+            "--set",
+            "kafka.broker=a:1",
+            "--set",
+            "clone.depth=0",
+        ])
+        .unwrap();
+        assert_eq!(cli.overrides, ["kafka.broker=a:1", "clone.depth=0"]);
+
+        let cli = CratesProCli::try_parse_from([
+            "crates-pro",
+            "import",
+            "repos",
+            "urls.txt",
+            "--depth",
+            "0",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Import(ImportCommand::Repos { list, clone })) => {
+                assert_eq!(list, PathBuf::from("urls.txt"));
+                assert_eq!(clone.depth, Some(0));
+            }
+            command => panic!("unexpected {:?}", command),
+        }
+
+        let cli = CratesProCli::try_parse_from(["crates-pro", "sync", "cratesio-index", "index"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Sync(SyncCommand::CratesioIndex {
+                interval: 300,
+                ..
+            }))
+        ));
+        let cli = CratesProCli::try_parse_from(["crates-pro", "sync", "schedule", "schedule.toml"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Sync(SyncCommand::Schedule { .. }))
+        ));
+        let cli = CratesProCli::try_parse_from([
+            "crates-pro",
+            "analyze",
+            "dependents",
+            "bytes",
+            "--version",
+            "1.6.0",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Analyze(AnalyzeCommand::Dependents {
+                version: Some(_),
+                ..
+            }))
+        ));
+        let cli = CratesProCli::try_parse_from([
+            "crates-pro",
+            "diff",
+            "old",
+            "new",
+            "--label",
+            "program",
+            "--summary",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Diff { label, summary, .. }) => {
+                assert_eq!(label, ["program"]);
+                assert!(summary);
+            }
+            command => panic!("unexpected {:?}", command),
+        }
+        let cli = CratesProCli::try_parse_from([
+            "crates-pro",
+            "analyze",
+            "closure",
+            "tokio",
+            "--snapshot",
+            "nightly",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Analyze(AnalyzeCommand::Closure { checkpoint, .. })) => {
+                assert_eq!(checkpoint.snapshot.as_deref(), Some("nightly"));
+            }
+            command => panic!("unexpected {:?}", command),
+        }
+        assert!(CratesProCli::try_parse_from([
+            "crates-pro",
+            "snapshot",
+            "create",
+            "nightly",
+            "--checkpoint",
+            "a.bin",
+            "--snapshot",
+            "b",
+        ])
+        .is_err());
+        assert!(CratesProCli::try_parse_from(["crates-pro", "export"]).is_err());
+    }
 }
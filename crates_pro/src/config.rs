@@ -0,0 +1,149 @@
+//! The layered configuration: a TOML file, overridden by the environment
+//! (including `.env`), overridden by `--set` on the command line.
+//!
+//! The services read their configuration from the environment, so each key of the file
+//! is applied as the env var of its path in upper case, e.g.
+//!
+//! ```toml
+//! checkpoint_dir = "target/checkpoints"   # CHECKPOINT_DIR
+//!
+//! [kafka]
+//! broker = "172.17.0.1:30092"            # KAFKA_BROKER
+//!
+//! [import]
+//! output_formats = ["csv", "parquet"]    # IMPORT_OUTPUT_FORMATS=csv,parquet
+//! kafka_sink = true                      # IMPORT_KAFKA_SINK=1
+//! ```
+//!
+//! The file is `--config`, `CRATES_PRO_CONFIG`, or `crates-pro.toml` if it exists.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::Value;
+
+const DEFAULT_CONFIG_FILE: &str = "crates-pro.toml";
+
+/// Flatten a table into the env vars of its values.
+fn flatten(prefix: &str, table: &toml::Table, vars: &mut Vec<(String, String)>) {
+    for (key, value) in table {
+        let name = if prefix.is_empty() {
+            key.to_uppercase()
+        } else {
+            format!("{}_{}", prefix, key.to_uppercase())
+        };
+        let value = match value {
+            Value::Table(table) => {
+                flatten(&name, table, vars);
+                continue;
+            }
+            Value::String(s) => s.clone(),
+            Value::Boolean(b) => if *b { "1" } else { "0" }.to_string(),
+            Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(s) => s.clone(),
+                    item => item.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            value => value.to_string(),
+        };
+        vars.push((name, value));
+    }
+}
+
+/// The env vars of a config file.
+fn read_config(path: &Path) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let table: toml::Table = fs::read_to_string(path)?.parse()?;
+    let mut vars = vec![];
+    flatten("", &table, &mut vars);
+    Ok(vars)
+}
+
+/// The env var of `--set kafka.broker=localhost:9092`.
+fn parse_override(item: &str) -> Result<(String, String), String> {
+    let (key, value) = item
+        .split_once('=')
+        .ok_or_else(|| format!("expected <key>=<value>, got {}", item))?;
+    let name = key.trim().replace(['.', '-'], "_").to_uppercase();
+    if name.is_empty() {
+        return Err(format!("empty key in {}", item));
+    }
+    Ok((name, value.to_string()))
+}
+
+/// Apply the config file and the overrides to the environment,
+/// where the file does not override a variable that is already set.
+/// It returns the config file applied.
+///
+/// It sets the env, so it must be called before any other thread is started,
+/// i.e. before the tokio runtime is built.
+pub(crate) fn load_config(
+    path: Option<&Path>,
+    overrides: &[String],
+) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let path = match path {
+        Some(path) => Some(path.to_path_buf()),
+        None => env::var("CRATES_PRO_CONFIG")
+            .ok()
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|p| p.is_file())),
+    };
+
+    if let Some(path) = &path {
+        let vars = read_config(path).map_err(|e| format!("invalid config {:?}: {}", path, e))?;
+        for (name, value) in vars {
+            if env::var_os(&name).is_none() {
+                env::set_var(name, value);
+            }
+        }
+    }
+    for item in overrides {
+        let (name, value) = parse_override(item)?;
+        env::set_var(name, value);
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten() {
+        let table: toml::Table = r#"
+            checkpoint_dir = "target/checkpoints"
+            [kafka]
+            broker = "localhost:9092"
+            [import]
+            output_formats = ["csv", "parquet"]
+            kafka_sink = true
+            [clone]
+            concurrency = 8
+        "#
+        .parse()
+        .unwrap();
+        let mut vars = vec![];
+        flatten("", &table, &mut vars);
+        vars.sort();
+        assert_eq!(
+            vars,
+            [
+                ("CHECKPOINT_DIR", "target/checkpoints"),
+                ("CLONE_CONCURRENCY", "8"),
+                ("IMPORT_KAFKA_SINK", "1"),
+                ("IMPORT_OUTPUT_FORMATS", "csv,parquet"),
+                ("KAFKA_BROKER", "localhost:9092"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+
+        assert_eq!(
+            parse_override("kafka.broker=a:1").unwrap(),
+            ("KAFKA_BROKER".to_string(), "a:1".to_string())
+        );
+        assert!(parse_override("kafka.broker").is_err());
+    }
+}
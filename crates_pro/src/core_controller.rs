@@ -5,11 +5,19 @@
 
 use analysis::analyse_once;
 #[allow(unused_imports)]
-use data_transporter::{run_api_server, Transporter};
-use repo_import::ImportDriver;
+use data_transporter::{run_api_server, run_grpc_server, run_query_server, Transporter};
+use repo_import::{
+    latest_checkpoint_path, CloneOptions, ClosureCache, DocsRsClient, ImportContext, ImportDriver,
+    ImportWorker, SnapshotStore,
+};
 
-use crate::cli::CratesProCli;
+use crate::cli::{
+    AnalyzeCommand, CheckpointArgs, Command, CratesProCli, ExportCommand, ImportCommand,
+    SnapshotCommand, SyncCommand,
+};
 use futures_util::future::FutureExt;
+use serde_json::json;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 #[allow(unused_imports)]
 use std::{env, fs, sync::Arc, time::Duration};
@@ -41,6 +49,35 @@ impl CoreController {
     }
 
     pub async fn run(&self) {
+        match &self.cli.command {
+            Some(Command::Import(command)) => return run_import(command).await,
+            Some(Command::Export(command)) => return run_export(command).await,
+            Some(Command::Analyze(command)) => return run_analyze(command).await,
+            Some(Command::Sync(command)) => return run_sync(command).await,
+            Some(Command::Snapshot(command)) => return run_snapshot(command).await,
+            Some(Command::Diff {
+                old,
+                new,
+                label,
+                summary,
+            }) => return run_diff(old, new, label, *summary).await,
+            Some(Command::Serve { addr, grpc_addr }) => {
+                if let Some(grpc_addr) = grpc_addr.clone() {
+                    tokio::spawn(async move {
+                        if let Err(e) = run_grpc_server(&grpc_addr).await {
+                            tracing::error!("Failed to serve gRPC: {}", e);
+                        }
+                    });
+                }
+                if let Err(e) = run_query_server(addr).await {
+                    tracing::error!("Failed to serve the query API: {}", e);
+                }
+                return;
+            }
+            Some(Command::Run) | None => {}
+        }
+        repo_import::spawn_metrics_server();
+
         let import = self.import;
         let analysis = self.analysis;
         let package = self.package;
@@ -62,7 +99,14 @@ impl CoreController {
                         .unwrap_or_else(|x| panic!("{}", x));
                 }
 
-                let mut import_driver = ImportDriver::new(dont_clone).await;
+                let mut import_driver = match ImportDriver::new(dont_clone).await {
+                    Ok(import_driver) => import_driver,
+                    Err(e) => {
+                        // not to overwrite the checkpoint
+                        tracing::error!("Failed to start importing: {}", e);
+                        std::process::exit(1);
+                    }
+                };
                 let mut count = 0;
                 let is_importing = Arc::new(AtomicBool::new(false));
                 let is_importing_clone = Arc::clone(&is_importing);
@@ -194,3 +238,242 @@ impl CoreController {
         }
     }
 }
+
+/// The snapshot given by `--snapshot`, the checkpoint given by `--checkpoint`,
+/// or the latest one in `CHECKPOINT_DIR`.
+async fn load_checkpoint(args: &CheckpointArgs) -> Option<ImportContext> {
+    if let Some(snapshot) = &args.snapshot {
+        return match SnapshotStore::from_env().load(snapshot) {
+            Ok(context) => Some(context),
+            Err(e) => {
+                tracing::error!("Failed to load snapshot {}: {}", snapshot, e);
+                None
+            }
+        };
+    }
+    let path = args
+        .checkpoint
+        .clone()
+        .unwrap_or_else(latest_checkpoint_path);
+    match ImportContext::load_from_file(&path.to_string_lossy()).await {
+        Ok(context) => Some(context),
+        Err(e) => {
+            tracing::error!("Failed to load checkpoint {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+async fn run_import(command: &ImportCommand) {
+    match command {
+        ImportCommand::CratesioDump { path } => {
+            let mut context = ImportContext::default();
+            match context.import_cratesio_dump(path).await {
+                Ok(()) => context.write_tugraph_import_files().await,
+                Err(e) => tracing::error!("Failed to import crates.io dump: {}", e),
+            }
+        }
+        ImportCommand::Repos { list, clone } => {
+            let dir = clone.dir.clone().unwrap_or_else(|| {
+                env::var("NEW_CRATES_DIR")
+                    .unwrap_or_else(|_| "/mnt/crates/local_crates_file/".to_string())
+                    .into()
+            });
+            let mut options = CloneOptions::from_env();
+            if let Some(depth) = clone.depth {
+                options.depth = depth;
+            }
+            let mut context = match ImportContext::resume_run().await {
+                Ok(context) => context,
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    return;
+                }
+            };
+            context.load_import_cache();
+            match context.import_from_url_list(list, &dir, options).await {
+                Ok(()) => {
+                    context.write_tugraph_import_files().await;
+                    context.finish_run().await;
+                }
+                Err(e) => tracing::error!("Failed to import the repos: {}", e),
+            }
+            context.save_import_cache();
+        }
+        ImportCommand::Local { path } => {
+            let mut context = match ImportContext::resume_run().await {
+                Ok(context) => context,
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    return;
+                }
+            };
+            context.load_import_cache();
+            match context.import_local(path).await {
+                Ok(()) => {
+                    context.write_tugraph_import_files().await;
+                    context.finish_run().await;
+                }
+                Err(e) => tracing::error!("Failed to import {}: {}", path, e),
+            }
+            context.save_import_cache();
+        }
+        ImportCommand::Worker => match ImportWorker::from_env().await {
+            Ok(mut worker) => {
+                repo_import::spawn_metrics_server();
+                worker.run().await
+            }
+            Err(e) => tracing::error!("Failed to start the import worker: {}", e),
+        },
+    }
+}
+
+async fn run_export(command: &ExportCommand) {
+    match command {
+        ExportCommand::Graph { checkpoint } => {
+            if let Some(mut context) = load_checkpoint(checkpoint).await {
+                context.write_tugraph_import_files().await;
+            }
+        }
+        ExportCommand::MessageSchemas { dir } => {
+            if let Err(e) = model::message::write_message_schemas(dir) {
+                tracing::error!("Failed to write the message schemas: {}", e);
+            }
+        }
+    }
+}
+
+async fn run_analyze(command: &AnalyzeCommand) {
+    match command {
+        AnalyzeCommand::Once { output } => {
+            if let Err(e) = analyse_once(output).await {
+                tracing::error!("Failed to analyze: {}", e);
+            }
+        }
+        AnalyzeCommand::ApiDiff {
+            name,
+            old_version,
+            new_version,
+            checkpoint,
+        } => {
+            if let Some(context) = load_checkpoint(checkpoint).await {
+                match context.api_diff(name, old_version, new_version) {
+                    Some(diff) => println!("{}", serde_json::to_string_pretty(&diff).unwrap()),
+                    None => tracing::error!(
+                        "The API of {} {} or {} is not extracted",
+                        name,
+                        old_version,
+                        new_version
+                    ),
+                }
+            }
+        }
+        AnalyzeCommand::Compatible {
+            name,
+            rustc,
+            checkpoint,
+        } => {
+            if let Some(context) = load_checkpoint(checkpoint).await {
+                let versions = context.versions_compatible_with(name, rustc);
+                println!("{}", serde_json::to_string_pretty(&versions).unwrap());
+            }
+        }
+        AnalyzeCommand::Closure {
+            name,
+            version,
+            checkpoint,
+        } => {
+            if let Some(context) = load_checkpoint(checkpoint).await {
+                let mut cache = ClosureCache::default();
+                let closures = context.dependency_closures(name, version.as_deref(), &mut cache);
+                println!("{}", serde_json::to_string_pretty(&closures).unwrap());
+            }
+        }
+        AnalyzeCommand::Dependents {
+            name,
+            version,
+            checkpoint,
+        } => {
+            if let Some(context) = load_checkpoint(checkpoint).await {
+                match context.dependents(name, version.as_deref()) {
+                    Some(dependents) => {
+                        println!("{}", serde_json::to_string_pretty(&dependents).unwrap())
+                    }
+                    None => tracing::error!("{} {:?} is not imported", name, version),
+                }
+            }
+        }
+    }
+}
+
+async fn run_diff(old: &Path, new: &Path, labels: &[String], summary: bool) {
+    match repo_import::diff_graphs(old, new, labels).await {
+        Ok(diff) if summary => {
+            for line in diff.summary() {
+                println!("{}", line);
+            }
+        }
+        Ok(diff) => println!("{}", serde_json::to_string_pretty(&diff).unwrap()),
+        Err(e) => tracing::error!("Failed to diff {:?} and {:?}: {}", old, new, e),
+    }
+}
+
+async fn run_snapshot(command: &SnapshotCommand) {
+    let store = SnapshotStore::from_env();
+    let result = match command {
+        SnapshotCommand::Create { name, checkpoint } => match load_checkpoint(checkpoint).await {
+            Some(mut context) => store.create(name, &mut context).await.map(|s| json!(s)),
+            None => return,
+        },
+        SnapshotCommand::List => store.list().map(|s| json!(s)),
+        SnapshotCommand::Export { snapshot, archive } => {
+            store.export(snapshot, archive).map(|s| json!(s))
+        }
+        SnapshotCommand::Import { archive } => store.import(archive).map(|s| json!(s)),
+        SnapshotCommand::Restore { snapshot } => store
+            .restore(snapshot, &latest_checkpoint_path())
+            .map(|s| json!(s)),
+    };
+    match result {
+        Ok(output) => println!("{}", serde_json::to_string_pretty(&output).unwrap()),
+        Err(e) => tracing::error!("Failed to run {:?}: {}", command, e),
+    }
+}
+
+async fn run_sync(command: &SyncCommand) {
+    match command {
+        SyncCommand::CratesioIndex { path, interval } => {
+            let mut context = ImportContext::default();
+            context
+                .watch_cratesio_index(path, Duration::from_secs(*interval))
+                .await;
+        }
+        SyncCommand::Schedule { file } => {
+            if let Err(e) = repo_import::run_schedule(file).await {
+                tracing::error!("Failed to run the schedule {:?}: {}", file, e);
+            }
+        }
+        SyncCommand::Docsrs { checkpoint, limit } => {
+            let path = checkpoint.clone().unwrap_or_else(latest_checkpoint_path);
+            let mut context = match ImportContext::load_from_file(&path.to_string_lossy()).await {
+                Ok(context) => context,
+                Err(e) => {
+                    tracing::error!("Failed to load checkpoint {:?}: {}", path, e);
+                    return;
+                }
+            };
+            let mut client = match DocsRsClient::new() {
+                Ok(client) => client,
+                Err(e) => {
+                    tracing::error!("Failed to create docs.rs client: {}", e);
+                    return;
+                }
+            };
+            if context.check_docsrs_builds(&mut client, *limit).await > 0 {
+                if let Err(e) = context.save_to_file(&path.to_string_lossy()).await {
+                    tracing::error!("Failed to save checkpoint {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+}
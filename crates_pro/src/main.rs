@@ -1,17 +1,40 @@
 mod cli;
+mod config;
 mod core_controller;
+mod telemetry;
 
+use clap::Parser;
 use cli::CratesProCli;
 use core_controller::CoreController;
 use std::fs::File;
+use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
-use structopt::StructOpt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
-#[tokio::main]
-async fn main() {
+fn main() {
     dotenvy::dotenv().ok();
 
+    let cli = CratesProCli::parse();
+    // the config is applied to the env before the runtime starts its threads,
+    // since setting the env is unsound with other threads running
+    let config_path = match config::load_config(cli.config.as_deref(), &cli.overrides) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("Failed to load the config: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build the tokio runtime")
+        .block_on(run(cli, config_path));
+}
+
+async fn run(cli: CratesProCli, config_path: Option<PathBuf>) {
     // 获取当前时间戳
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -22,15 +45,26 @@ async fn main() {
     let log_path = format!("target/log_{}.ans", timestamp);
     let file = File::create(&log_path).expect("Unable to create log file");
 
-    // 设置日志记录器
-    tracing_subscriber::fmt()
-        .with_writer(file)
-        .with_env_filter(EnvFilter::from_default_env())
+    // 设置日志记录器, 并导出 spans 到 OTLP (如果配置了)
+    let (otlp_layer, otlp_error) = match telemetry::otlp_layer() {
+        Ok(layer) => (layer, None),
+        Err(e) => (None, Some(e)),
+    };
+    tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(tracing_subscriber::fmt::layer().with_writer(file))
+        .with(otlp_layer)
         .init();
+    if let Some(e) = otlp_error {
+        tracing::error!("Failed to set up the OTLP exporter: {}", e);
+    }
 
     tracing::info!("Starting with log file: {}", log_path);
+    if let Some(path) = config_path {
+        tracing::info!("Loaded the config {:?}", path);
+    }
 
-    let cli = CratesProCli::from_args();
     let core_controller = CoreController::new(cli).await;
     core_controller.run().await;
+    telemetry::shutdown();
 }
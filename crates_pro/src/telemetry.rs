@@ -0,0 +1,50 @@
+//! Export the tracing spans of the imports to an OpenTelemetry collector over OTLP/gRPC,
+//! so that an import can be inspected in Jaeger or Tempo.
+//!
+//! It's enabled by `OTEL_EXPORTER_OTLP_ENDPOINT`, such as `http://localhost:4317`,
+//! and the service is named by `OTEL_SERVICE_NAME`, `crates-pro` by default.
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use std::env;
+use std::error::Error;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+const DEFAULT_SERVICE_NAME: &str = "crates-pro";
+
+/// The layer exporting the spans, if `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+pub(crate) fn otlp_layer<S>() -> Result<Option<OpenTelemetryLayer<S, Tracer>>, Box<dyn Error>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let Ok(endpoint) = env::var("OTEL_EXPORTER_OTLP_ENDPOINT") else {
+        return Ok(None);
+    };
+    let service_name =
+        env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| DEFAULT_SERVICE_NAME.to_string());
+
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new(vec![KeyValue::new(
+            "service.name",
+            service_name,
+        )]))
+        .build();
+    let tracer = provider.tracer(DEFAULT_SERVICE_NAME);
+    opentelemetry::global::set_tracer_provider(provider);
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+}
+
+/// Flush the spans not exported yet.
+pub(crate) fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
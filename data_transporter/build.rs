@@ -0,0 +1,17 @@
+use std::env;
+use std::process::Command;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // tonic-build runs `protoc`, the one of `PROTOC` or the one in the PATH
+    let protoc = env::var("PROTOC").unwrap_or_else(|_| "protoc".to_string());
+    if Command::new(&protoc).arg("--version").output().is_err() {
+        return Err(format!(
+            "{} is not found, install protoc (e.g. `apt install protobuf-compiler`) \
+             or set PROTOC to its path",
+            protoc
+        )
+        .into());
+    }
+    tonic_build::compile_protos("proto/crates_pro.proto")?;
+    Ok(())
+}
@@ -0,0 +1,254 @@
+//! A GraphQL API over the crate graph in TuGraph, served with the query API on `/graphql`,
+//! with a GraphiQL playground on `GET /graphql`. Such as
+//!
+//! ```graphql
+//! {
+//!   crate(name: "tokio") {
+//!     license
+//!     owners { kind login }
+//!     versions { version dependencies { name version } }
+//!   }
+//! }
+//! ```
+
+use crate::data_reader::{DataReader, DataReaderTrait};
+use crate::query_api::{find_program, find_versions, is_valid_ident};
+use crate::NameVersion;
+use actix_web::{web, HttpResponse};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Error, Object, Result, Schema, SimpleObject,
+};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+use model::tugraph_model::Program;
+use serde_json::Value;
+
+/// the max depth of a query, since the dependencies of a version can be nested
+const MAX_DEPTH: usize = 12;
+/// the max crates returned by `crates`
+const MAX_CRATES: usize = 100;
+
+pub(crate) type CrateSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub(crate) fn build_schema(reader: DataReader) -> CrateSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(reader)
+        .limit_depth(MAX_DEPTH)
+        .finish()
+}
+
+fn check_ident(s: &str) -> Result<()> {
+    if is_valid_ident(s) {
+        Ok(())
+    } else {
+        Err(Error::new(format!("invalid name or version: {}", s)))
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The crate of a name.
+    #[graphql(name = "crate")]
+    async fn krate(&self, ctx: &Context<'_>, name: String) -> Result<Option<Crate>> {
+        check_ident(&name)?;
+        let reader = ctx.data::<DataReader>()?;
+        let program = find_program(reader, &name)
+            .await
+            .map_err(|e| Error::new(e.to_string()))?;
+        Ok(program.map(Crate))
+    }
+
+    /// The crates whose name contains a string, at most 100.
+    async fn crates(
+        &self,
+        ctx: &Context<'_>,
+        name_contains: String,
+        #[graphql(default = 20)] first: usize,
+    ) -> Result<Vec<Crate>> {
+        check_ident(&name_contains)?;
+        let reader = ctx.data::<DataReader>()?;
+        let query = format!(
+            "MATCH (p:program) WHERE p.name CONTAINS '{}' RETURN p LIMIT {}",
+            name_contains,
+            first.min(MAX_CRATES)
+        );
+        let results = reader
+            .client
+            .exec_query(&query)
+            .await
+            .map_err(|e| Error::new(e.to_string()))?;
+        let mut crates = vec![];
+        for result in results {
+            let json: Value = serde_json::from_str(&result)?;
+            crates.push(Crate(serde_json::from_value(json["p"].clone())?));
+        }
+        Ok(crates)
+    }
+
+    /// A version of a crate.
+    async fn version(&self, name: String, version: String) -> Result<Version> {
+        check_ident(&name)?;
+        check_ident(&version)?;
+        Ok(Version(NameVersion { name, version }))
+    }
+}
+
+pub struct Crate(Program);
+
+#[Object]
+impl Crate {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn namespace(&self) -> Option<&str> {
+        self.0.namespace.as_deref()
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn max_version(&self) -> Option<&str> {
+        self.0.max_version.as_deref()
+    }
+
+    async fn license(&self) -> Option<&str> {
+        self.0.license.as_deref()
+    }
+
+    async fn github_url(&self) -> Option<&str> {
+        self.0.github_url.as_deref()
+    }
+
+    async fn doc_url(&self) -> Option<&str> {
+        self.0.doc_url.as_deref()
+    }
+
+    /// The versions, sorted by semver if possible.
+    async fn versions(&self, ctx: &Context<'_>) -> Result<Vec<Version>> {
+        let reader = ctx.data::<DataReader>()?;
+        let mut versions = find_versions(reader, &self.0.name)
+            .await
+            .map_err(|e| Error::new(e.to_string()))?;
+        versions.sort_by(
+            |a, b| match (semver::Version::parse(a), semver::Version::parse(b)) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                _ => a.cmp(b),
+            },
+        );
+        Ok(versions
+            .into_iter()
+            .map(|version| {
+                Version(NameVersion {
+                    name: self.0.name.clone(),
+                    version,
+                })
+            })
+            .collect())
+    }
+
+    /// The crates.io users and teams owning the crate.
+    async fn owners(&self, ctx: &Context<'_>) -> Result<Vec<Owner>> {
+        let reader = ctx.data::<DataReader>()?;
+        let query = format!(
+            "MATCH (o)-[:owns]->(p:program {{id: '{}'}}) RETURN o, label(o) as o_label",
+            self.0.id
+        );
+        let results = reader
+            .client
+            .exec_query(&query)
+            .await
+            .map_err(|e| Error::new(e.to_string()))?;
+        let mut owners = vec![];
+        for result in results {
+            let json: Value = serde_json::from_str(&result)?;
+            let owner = &json["o"];
+            owners.push(Owner {
+                kind: json["o_label"].as_str().unwrap_or_default().to_string(),
+                login: owner["login"].as_str().unwrap_or_default().to_string(),
+                name: owner["name"].as_str().map(String::from),
+                url: owner["url"].as_str().map(String::from),
+            });
+        }
+        Ok(owners)
+    }
+}
+
+pub struct Version(NameVersion);
+
+#[Object]
+impl Version {
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn version(&self) -> &str {
+        &self.0.version
+    }
+
+    /// The versions it depends on directly.
+    async fn dependencies(&self, ctx: &Context<'_>) -> Result<Vec<Version>> {
+        let reader = ctx.data::<DataReader>()?;
+        let name_and_version = format!("{}/{}", self.0.name, self.0.version);
+        let nodes = reader
+            .get_direct_dependency_nodes(&name_and_version)
+            .await
+            .map_err(|e| Error::new(e.to_string()))?;
+        Ok(nodes.into_iter().map(Version).collect())
+    }
+
+    /// The versions depending on it directly.
+    async fn dependents(&self, ctx: &Context<'_>) -> Result<Vec<Version>> {
+        let reader = ctx.data::<DataReader>()?;
+        let name_and_version = format!("{}/{}", self.0.name, self.0.version);
+        let nodes = reader
+            .get_direct_dependent_nodes(&name_and_version)
+            .await
+            .map_err(|e| Error::new(e.to_string()))?;
+        Ok(nodes.into_iter().map(Version).collect())
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Owner {
+    /// `person` or `team`
+    kind: String,
+    login: String,
+    name: Option<String>,
+    url: Option<String>,
+}
+
+pub(crate) async fn graphql(
+    schema: web::Data<CrateSchema>,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    schema.execute(request.into_inner()).await.into()
+}
+
+pub(crate) async fn graphiql() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema() {
+        let sdl = Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+            .finish()
+            .sdl();
+        assert!(sdl.contains("crate(name: String!): Crate"));
+        assert!(sdl.contains("owners: [Owner!]!"));
+        assert!(sdl.contains("dependents: [Version!]!"));
+    }
+}
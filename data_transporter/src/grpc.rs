@@ -0,0 +1,249 @@
+//! The gRPC services of `proto/crates_pro.proto`: the `ImportService` submitting the
+//! repos to the import workers by [`ImportJobs`], and the `QueryService` over the crate
+//! graph in TuGraph, such as the REST API.
+//!
+//! The `ImportService` is only served if `KAFKA_BROKER` and `KAFKA_REPO_REQUEST_TOPIC` are set.
+
+use crate::data_reader::{DataReader, DataReaderTrait};
+use crate::query_api::{find_program, find_versions, is_valid_ident, max_version};
+use proto::import_service_server::{ImportService, ImportServiceServer};
+use proto::query_service_server::{QueryService, QueryServiceServer};
+use proto::repo_status::State;
+use repo_import::{ImportJobStatus, ImportJobs};
+use std::error::Error;
+use std::sync::Arc;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("crates_pro.v1");
+}
+
+impl From<ImportJobStatus> for proto::ImportJob {
+    fn from(status: ImportJobStatus) -> Self {
+        let repos = status
+            .results
+            .into_iter()
+            .map(|(url, result)| match result {
+                Some(result) => proto::RepoStatus {
+                    url,
+                    state: if result.success {
+                        State::Succeeded
+                    } else {
+                        State::Failed
+                    } as i32,
+                    error: result.error,
+                    programs: result.programs,
+                    versions: result.versions,
+                },
+                None => proto::RepoStatus {
+                    url,
+                    state: State::Pending as i32,
+                    ..Default::default()
+                },
+            })
+            .collect();
+        proto::ImportJob {
+            id: status.job.id,
+            submitted_at: status.job.submitted_at,
+            repos,
+        }
+    }
+}
+
+fn internal(e: Box<dyn Error>) -> Status {
+    tracing::error!("Failed to query the graph: {}", e);
+    Status::internal(e.to_string())
+}
+
+fn check_ident(s: &str) -> Result<(), Status> {
+    if is_valid_ident(s) {
+        Ok(())
+    } else {
+        Err(Status::invalid_argument(format!(
+            "invalid name or version: {}",
+            s
+        )))
+    }
+}
+
+pub struct ImportServiceImpl {
+    jobs: Arc<ImportJobs>,
+}
+
+#[tonic::async_trait]
+impl ImportService for ImportServiceImpl {
+    async fn submit_import(
+        &self,
+        request: Request<proto::SubmitImportRequest>,
+    ) -> Result<Response<proto::ImportJob>, Status> {
+        let urls = request.into_inner().urls;
+        if urls.is_empty() {
+            return Err(Status::invalid_argument("no urls to import"));
+        }
+        let job = self.jobs.submit(urls).await;
+        self.jobs
+            .status(&job.id)
+            .map(|status| Response::new(status.into()))
+            .ok_or_else(|| Status::internal("the submitted job is lost"))
+    }
+
+    async fn get_import_job(
+        &self,
+        request: Request<proto::GetImportJobRequest>,
+    ) -> Result<Response<proto::ImportJob>, Status> {
+        let id = request.into_inner().id;
+        self.jobs
+            .status(&id)
+            .map(|status| Response::new(status.into()))
+            .ok_or_else(|| Status::not_found(format!("import job {} not found", id)))
+    }
+}
+
+pub struct QueryServiceImpl {
+    reader: DataReader,
+}
+
+impl QueryServiceImpl {
+    async fn relation(
+        &self,
+        request: proto::VersionRequest,
+        dependents: bool,
+    ) -> Result<Response<proto::VersionList>, Status> {
+        check_ident(&request.name)?;
+        let version = match request.version {
+            Some(version) => {
+                check_ident(&version)?;
+                version
+            }
+            None => find_versions(&self.reader, &request.name)
+                .await
+                .map(|versions| max_version(&versions))
+                .map_err(internal)?
+                .ok_or_else(|| Status::not_found(format!("crate {} not found", request.name)))?,
+        };
+        let name_and_version = format!("{}/{}", request.name, version);
+        let nodes = if dependents {
+            self.reader
+                .get_direct_dependent_nodes(&name_and_version)
+                .await
+        } else {
+            self.reader
+                .get_direct_dependency_nodes(&name_and_version)
+                .await
+        }
+        .map_err(internal)?;
+        Ok(Response::new(proto::VersionList {
+            name: request.name,
+            version,
+            versions: nodes
+                .into_iter()
+                .map(|n| proto::NameVersion {
+                    name: n.name,
+                    version: n.version,
+                })
+                .collect(),
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl QueryService for QueryServiceImpl {
+    async fn get_crate(
+        &self,
+        request: Request<proto::GetCrateRequest>,
+    ) -> Result<Response<proto::Crate>, Status> {
+        let name = request.into_inner().name;
+        check_ident(&name)?;
+        let program = find_program(&self.reader, &name)
+            .await
+            .map_err(internal)?
+            .ok_or_else(|| Status::not_found(format!("crate {} not found", name)))?;
+        let versions = find_versions(&self.reader, &name).await.map_err(internal)?;
+        Ok(Response::new(proto::Crate {
+            id: program.id,
+            name: program.name,
+            namespace: program.namespace,
+            description: program.description,
+            max_version: program.max_version,
+            license: program.license,
+            github_url: program.github_url,
+            versions,
+        }))
+    }
+
+    async fn get_dependencies(
+        &self,
+        request: Request<proto::VersionRequest>,
+    ) -> Result<Response<proto::VersionList>, Status> {
+        self.relation(request.into_inner(), false).await
+    }
+
+    async fn get_dependents(
+        &self,
+        request: Request<proto::VersionRequest>,
+    ) -> Result<Response<proto::VersionList>, Status> {
+        self.relation(request.into_inner(), true).await
+    }
+}
+
+/// Serve the gRPC services on `addr`, such as `0.0.0.0:6890`.
+pub async fn run_grpc_server(addr: &str) -> Result<(), Box<dyn Error>> {
+    let reader = DataReader::new(
+        &std::env::var("TUGRAPH_BOLT_URL")?,
+        &std::env::var("TUGRAPH_USER_NAME")?,
+        &std::env::var("TUGRAPH_USER_PASSWORD")?,
+        &std::env::var("TUGRAPH_CRATESPRO_DB")?,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    let import_service = match ImportJobs::from_env("grpc") {
+        Ok(jobs) => Some(ImportServiceServer::new(ImportServiceImpl { jobs })),
+        Err(e) => {
+            tracing::warn!("Serve gRPC without the import service: {}", e);
+            None
+        }
+    };
+
+    tracing::info!("Serve the gRPC services on {}", addr);
+    Server::builder()
+        .add_optional_service(import_service)
+        .add_service(QueryServiceServer::new(QueryServiceImpl { reader }))
+        .serve(addr.parse()?)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::general_model::RepoImportResult;
+    use repo_import::ImportJob;
+
+    #[test]
+    fn test_import_job_status() {
+        let status = ImportJobStatus {
+            job: ImportJob {
+                id: "1".to_string(),
+                urls: vec!["a".to_string(), "b".to_string()],
+                submitted_at: 1718000000,
+            },
+            results: vec![
+                (
+                    "a".to_string(),
+                    Some(RepoImportResult {
+                        url: "a".to_string(),
+                        success: false,
+                        error: Some("timeout".to_string()),
+                        ..Default::default()
+                    }),
+                ),
+                ("b".to_string(), None),
+            ],
+        };
+        let job = proto::ImportJob::from(status);
+        assert_eq!(job.repos[0].state(), State::Failed);
+        assert_eq!(job.repos[0].error.as_deref(), Some("timeout"));
+        assert_eq!(job.repos[1].state(), State::Pending);
+    }
+}
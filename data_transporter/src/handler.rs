@@ -1145,6 +1145,45 @@ pub async fn dependent_cache(
         HttpResponse::Ok().json(res[0].clone())
     }
 }
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct SimilarCrate {
+    name: String,
+    similarity: f64,
+}
+
+/// 查询语义相似的 crates
+#[utoipa::path(
+    get,
+    path = "/api/crates/{cratename}/similar",
+    params(
+        ("cratename" = String, Path, description = "crate 名称")
+    ),
+    responses(
+        (status = 200, description = "查询成功", body = Vec<SimilarCrate>),
+        (status = 500, description = "查询失败")
+    ),
+    tag = "search"
+)]
+pub async fn get_similar_crates(name: String) -> impl Responder {
+    let db_connection_config = db_connection_config_from_env();
+    let (client, connection) = match tokio_postgres::connect(&db_connection_config, NoTls).await {
+        Ok(res) => res,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("connection error: {}", e);
+        }
+    });
+    match search::embedding::find_similar_crates(&client, &name, 20).await {
+        Ok(res) => HttpResponse::Ok().json(
+            res.into_iter()
+                .map(|(name, similarity)| SimilarCrate { name, similarity })
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
 /// 查询 crates
 #[utoipa::path(
     post,
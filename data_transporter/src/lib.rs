@@ -1,13 +1,19 @@
 mod data_packer;
 mod data_reader;
 mod db;
+mod graphql;
+mod grpc;
 mod handler;
+mod query_api;
 mod transporter;
+mod webhook;
 
 use model::tugraph_model::UVersion;
 use search::search_prepare;
 use serde::{Deserialize, Serialize};
 use tokio_postgres::NoTls;
+pub use grpc::run_grpc_server;
+pub use query_api::run_query_server;
 pub use transporter::Transporter;
 
 use crate::data_reader::DataReader; // 确保导入你的 DataReader
@@ -88,6 +94,7 @@ async fn get_tugraph_api_handler() -> ApiHandler {
         handler::dependent_cache,
         handler::new_get_crates_front_info,
         handler::query_crates,
+        handler::get_similar_crates,
         //handler::get_graph,
         //route::get_version_page,
         // route::get_graph,
@@ -115,6 +122,7 @@ async fn get_tugraph_api_handler() -> ApiHandler {
             VersionInfo,
             Query,
             handler::QueryCratesInfo,
+            handler::SimilarCrate,
             //handler::Deptree,
             // Query, 
             // Pagination,
@@ -246,6 +254,10 @@ pub async fn run_api_server() -> std::io::Result<()> {
                     //println!("query {:?}",query);
                     handler::query_crates(query).await
             },),)
+            .route("/api/crates/{cratename}/similar",
+            web::get().to(|path: web::Path<String>|async move{
+                handler::get_similar_crates(path.into_inner()).await
+            }))
             .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/dependencies", 
             web::get().to(|path: web::Path<(String, String,String,String)>|async move{
                 println!("2");
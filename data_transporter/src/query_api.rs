@@ -0,0 +1,567 @@
+//! A small REST API over the crate graph in TuGraph, so that the consumers of the
+//! imported crates don't need to query the database directly:
+//!
+//! - `GET /crates/{name}`, the crate and its versions,
+//! - `GET /crates/{name}/dependencies?version=1.0.0`, the direct dependencies of a version,
+//! - `GET /crates/{name}/dependents?version=1.0.0`, the versions depending on a version,
+//! - `GET /crates/{name}/dependents/all?version=1.0.0`, the versions depending on a version
+//!   directly or transitively, with their counts,
+//! - `GET /crates/{name}/closure?version=1.0.0`, the transitive dependencies of a version
+//!   with their depths, cached for `QUERY_CLOSURE_CACHE_SECS`, 3600 by default,
+//! - `GET /search?q=http client&limit=20&offset=0`, the crates matching the words by their
+//!   names, descriptions, keywords, categories and readmes, if the full-text index built by
+//!   the import is in `SEARCH_INDEX_DIR`,
+//!
+//! where the version is the max version of the crate by default,
+//! and the GraphQL API on `/graphql`, and the GitHub webhook on `/webhooks/github`.
+//!
+//! The transitive dependents and dependencies are walked one depth at a time, with one query
+//! per depth, and a walk of more than `QUERY_MAX_VERSIONS` versions, 10000 by default, is
+//! answered with 413 rather than going on.
+
+use crate::data_reader::{DataReader, DataReaderTrait};
+use crate::graphql::{build_schema, graphiql, graphql};
+use crate::webhook::{github_webhook, GithubWebhook};
+use crate::NameVersion;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use model::tugraph_model::Program;
+use repo_import::{ClosureCache, ClosureEntry, DependencyClosure, SearchIndex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Serialize)]
+pub struct CrateResponse {
+    #[serde(flatten)]
+    pub program: Program,
+    pub versions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelationResponse {
+    pub name: String,
+    pub version: String,
+    pub crates: Vec<NameVersion>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DependentsResponse {
+    pub name: String,
+    pub version: String,
+    pub direct: Vec<NameVersion>,
+    /// the versions depending on it only through other versions
+    pub transitive: Vec<NameVersion>,
+    pub direct_count: usize,
+    pub transitive_count: usize,
+    /// the crates of all the dependents
+    pub crate_count: usize,
+}
+
+/// The closures computed by the API, all dropped once they're older than `ttl`,
+/// since the graph may be imported again meanwhile.
+pub struct ClosureState {
+    cache: Mutex<(Instant, ClosureCache)>,
+    ttl: Duration,
+}
+
+impl ClosureState {
+    fn from_env() -> Self {
+        let ttl = std::env::var("QUERY_CLOSURE_CACHE_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3600);
+        ClosureState {
+            cache: Mutex::new((Instant::now(), ClosureCache::default())),
+            ttl: Duration::from_secs(ttl),
+        }
+    }
+
+    fn get(&self, name_and_version: &str) -> Option<Arc<Vec<ClosureEntry>>> {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.0.elapsed() > self.ttl {
+            *cache = (Instant::now(), ClosureCache::default());
+        }
+        cache.1.get(name_and_version)
+    }
+
+    fn insert(&self, name_and_version: &str, closure: Arc<Vec<ClosureEntry>>) {
+        self.cache
+            .lock()
+            .unwrap()
+            .1
+            .insert(name_and_version, closure);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VersionQuery {
+    version: Option<String>,
+}
+
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+const MAX_SEARCH_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    q: String,
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+impl SearchQuery {
+    fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT)
+    }
+}
+
+/// Whether a crate name or a version can be quoted in a cypher query as is.
+pub(crate) fn is_valid_ident(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'))
+}
+
+/// The max semver version, or the max one by string if none of them is semver.
+pub(crate) fn max_version(versions: &[String]) -> Option<String> {
+    versions
+        .iter()
+        .filter_map(|v| semver::Version::parse(v).ok().map(|s| (s, v)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, v)| v.clone())
+        .or_else(|| versions.iter().max().cloned())
+}
+
+const DEFAULT_MAX_VERSIONS: usize = 10000;
+
+/// The versions a walk of the graph may visit at most.
+fn max_versions() -> usize {
+    std::env::var("QUERY_MAX_VERSIONS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_MAX_VERSIONS)
+}
+
+/// A walk of the graph stopped at `QUERY_MAX_VERSIONS` versions.
+#[derive(Debug)]
+struct TooManyVersions(usize);
+
+impl fmt::Display for TooManyVersions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "more than {} versions to walk", self.0)
+    }
+}
+
+impl Error for TooManyVersions {}
+
+fn error_response(e: Box<dyn Error>) -> HttpResponse {
+    if let Some(e) = e.downcast_ref::<TooManyVersions>() {
+        return HttpResponse::PayloadTooLarge().body(e.to_string());
+    }
+    tracing::error!("Failed to query the graph: {}", e);
+    HttpResponse::InternalServerError().body(e.to_string())
+}
+
+/// The versions quoted in a cypher query at most.
+const VERSIONS_PER_QUERY: usize = 500;
+
+/// The `depends_on` edges from the versions `ids` to their direct dependencies,
+/// or from their direct dependents to them if `dependents`, as `(id, other)`.
+async fn direct_edges(
+    reader: &DataReader,
+    ids: &[String],
+    dependents: bool,
+) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let pattern = if dependents {
+        "(n:version)<-[:depends_on]-(m:version)"
+    } else {
+        "(n:version)-[:depends_on]->(m:version)"
+    };
+    // the ids are read from the graph, but quoted only if they can't break the query
+    let ids: Vec<&String> = ids
+        .iter()
+        .filter(|id| {
+            id.split_once('/')
+                .is_some_and(|(name, version)| is_valid_ident(name) && is_valid_ident(version))
+        })
+        .collect();
+    let mut edges = HashSet::new();
+    for chunk in ids.chunks(VERSIONS_PER_QUERY) {
+        let list = chunk
+            .iter()
+            .map(|id| format!("'{}'", id))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let query = format!(
+            "MATCH {} WHERE n.name_and_version IN [{}] RETURN n.name_and_version as id, m.name_and_version as other",
+            pattern, list
+        );
+        for result in reader.client.exec_query(&query).await? {
+            let json: Value = serde_json::from_str(&result)?;
+            if let (Some(id), Some(other)) = (json["id"].as_str(), json["other"].as_str()) {
+                edges.insert((id.to_string(), other.to_string()));
+            }
+        }
+    }
+    Ok(edges.into_iter().collect())
+}
+
+pub(crate) async fn find_program(
+    reader: &DataReader,
+    name: &str,
+) -> Result<Option<Program>, Box<dyn Error>> {
+    let query = format!("MATCH (p:program {{name: '{}'}}) RETURN p LIMIT 1", name);
+    let results = reader.client.exec_query(&query).await?;
+    match results.first() {
+        Some(result) => {
+            let json: Value = serde_json::from_str(result)?;
+            Ok(Some(serde_json::from_value(json["p"].clone())?))
+        }
+        None => Ok(None),
+    }
+}
+
+pub(crate) async fn find_versions(
+    reader: &DataReader,
+    name: &str,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let query = format!(
+        "MATCH (p:program {{name: '{}'}})-[:has_type]->(t)-[:has_version]->(v) RETURN DISTINCT v.version as version",
+        name
+    );
+    let mut versions = vec![];
+    for result in reader.client.exec_query(&query).await? {
+        let json: Value = serde_json::from_str(&result)?;
+        if let Some(version) = json["version"].as_str() {
+            versions.push(version.to_string());
+        }
+    }
+    Ok(versions)
+}
+
+/// The requested version, or the max version of the crate.
+async fn resolve_version(
+    reader: &DataReader,
+    name: &str,
+    query: &VersionQuery,
+) -> Result<Option<String>, Box<dyn Error>> {
+    if let Some(version) = &query.version {
+        return Ok(Some(version.clone()));
+    }
+    Ok(max_version(&find_versions(reader, name).await?))
+}
+
+async fn get_crate(reader: web::Data<DataReader>, name: web::Path<String>) -> HttpResponse {
+    let name = name.into_inner();
+    if !is_valid_ident(&name) {
+        return HttpResponse::BadRequest().body("invalid crate name");
+    }
+    let program = match find_program(&reader, &name).await {
+        Ok(Some(program)) => program,
+        Ok(None) => return HttpResponse::NotFound().body(format!("crate {} not found", name)),
+        Err(e) => return error_response(e),
+    };
+    match find_versions(&reader, &name).await {
+        Ok(versions) => HttpResponse::Ok().json(CrateResponse { program, versions }),
+        Err(e) => error_response(e),
+    }
+}
+
+/// The direct dependencies, or the direct dependents of a version.
+async fn get_relation(
+    reader: &DataReader,
+    name: String,
+    query: VersionQuery,
+    dependents: bool,
+) -> HttpResponse {
+    if !is_valid_ident(&name) || query.version.as_deref().is_some_and(|v| !is_valid_ident(v)) {
+        return HttpResponse::BadRequest().body("invalid crate name or version");
+    }
+    let version = match resolve_version(reader, &name, &query).await {
+        Ok(Some(version)) => version,
+        Ok(None) => return HttpResponse::NotFound().body(format!("crate {} not found", name)),
+        Err(e) => return error_response(e),
+    };
+    let name_and_version = format!("{}/{}", name, version);
+    let res = if dependents {
+        reader.get_direct_dependent_nodes(&name_and_version).await
+    } else {
+        reader.get_direct_dependency_nodes(&name_and_version).await
+    };
+    match res {
+        Ok(mut crates) => {
+            crates.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+            HttpResponse::Ok().json(RelationResponse {
+                name,
+                version,
+                crates,
+            })
+        }
+        Err(e) => error_response(e),
+    }
+}
+
+/// The direct dependents of a version, and the transitive ones breadth first.
+async fn find_all_dependents(
+    reader: &DataReader,
+    name_and_version: &str,
+) -> Result<(Vec<NameVersion>, Vec<NameVersion>), Box<dyn Error>> {
+    let max = max_versions();
+    let mut visited: HashSet<String> = HashSet::from([name_and_version.to_string()]);
+    let mut depths: Vec<Vec<String>> = vec![];
+    let mut frontier = vec![name_and_version.to_string()];
+    while !frontier.is_empty() {
+        let mut next = vec![];
+        for (_, dependent) in direct_edges(reader, &frontier, true).await? {
+            if visited.insert(dependent.clone()) {
+                next.push(dependent);
+            }
+        }
+        if visited.len() > max + 1 {
+            return Err(TooManyVersions(max).into());
+        }
+        depths.push(next.clone());
+        frontier = next;
+    }
+    let sorted = |ids: Vec<String>| {
+        let mut versions: Vec<NameVersion> = ids
+            .iter()
+            .filter_map(|id| NameVersion::from_string(id))
+            .collect();
+        versions.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+        versions
+    };
+    let mut depths = depths.into_iter();
+    let direct = sorted(depths.next().unwrap_or_default());
+    let transitive = sorted(depths.flatten().collect());
+    Ok((direct, transitive))
+}
+
+async fn get_all_dependents(
+    reader: web::Data<DataReader>,
+    name: web::Path<String>,
+    query: web::Query<VersionQuery>,
+) -> HttpResponse {
+    let (name, query) = (name.into_inner(), query.into_inner());
+    if !is_valid_ident(&name) || query.version.as_deref().is_some_and(|v| !is_valid_ident(v)) {
+        return HttpResponse::BadRequest().body("invalid crate name or version");
+    }
+    let version = match resolve_version(&reader, &name, &query).await {
+        Ok(Some(version)) => version,
+        Ok(None) => return HttpResponse::NotFound().body(format!("crate {} not found", name)),
+        Err(e) => return error_response(e),
+    };
+    match find_all_dependents(&reader, &format!("{}/{}", name, version)).await {
+        Ok((direct, transitive)) => {
+            let crates: HashSet<&str> = direct
+                .iter()
+                .chain(&transitive)
+                .map(|d| d.name.as_str())
+                .collect();
+            HttpResponse::Ok().json(DependentsResponse {
+                direct_count: direct.len(),
+                transitive_count: transitive.len(),
+                crate_count: crates.len(),
+                name,
+                version,
+                direct,
+                transitive,
+            })
+        }
+        Err(e) => error_response(e),
+    }
+}
+
+/// The closure of a version, walking the direct dependencies in the graph one depth at a time,
+/// but not the ones of a version whose closure is cached.
+async fn find_closure(
+    reader: &DataReader,
+    state: &ClosureState,
+    root: &str,
+) -> Result<Arc<Vec<ClosureEntry>>, Box<dyn Error>> {
+    let max = max_versions();
+    // the cached closures are copied, so they're not dropped by another request meanwhile
+    let mut cache = ClosureCache::default();
+    let mut dependencies: HashMap<String, Vec<String>> = HashMap::new();
+    let mut frontier = vec![root.to_string()];
+    while !frontier.is_empty() {
+        let mut walked = vec![];
+        for current in frontier {
+            if dependencies.contains_key(&current) || cache.get(&current).is_some() {
+                continue;
+            }
+            if let Some(closure) = state.get(&current) {
+                cache.insert(&current, closure);
+                continue;
+            }
+            dependencies.insert(current.clone(), vec![]);
+            walked.push(current);
+        }
+        if dependencies.len() > max {
+            return Err(TooManyVersions(max).into());
+        }
+        let mut next = vec![];
+        for (src, dst) in direct_edges(reader, &walked, false).await? {
+            if let Some(direct) = dependencies.get_mut(&src) {
+                direct.push(dst.clone());
+            }
+            next.push(dst);
+        }
+        frontier = next;
+    }
+    let closure = cache.closure(root, |v| dependencies.get(v).cloned().unwrap_or_default());
+    state.insert(root, closure.clone());
+    Ok(closure)
+}
+
+async fn get_closure(
+    reader: web::Data<DataReader>,
+    state: web::Data<ClosureState>,
+    name: web::Path<String>,
+    query: web::Query<VersionQuery>,
+) -> HttpResponse {
+    let (name, query) = (name.into_inner(), query.into_inner());
+    if !is_valid_ident(&name) || query.version.as_deref().is_some_and(|v| !is_valid_ident(v)) {
+        return HttpResponse::BadRequest().body("invalid crate name or version");
+    }
+    let version = match resolve_version(&reader, &name, &query).await {
+        Ok(Some(version)) => version,
+        Ok(None) => return HttpResponse::NotFound().body(format!("crate {} not found", name)),
+        Err(e) => return error_response(e),
+    };
+    let root = format!("{}/{}", name, version);
+    match find_closure(&reader, &state, &root).await {
+        Ok(closure) => HttpResponse::Ok().json(DependencyClosure::new(&root, closure)),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn search_crates(
+    index: web::Data<SearchIndex>,
+    query: web::Query<SearchQuery>,
+) -> HttpResponse {
+    let query = query.into_inner();
+    if query.q.trim().is_empty() {
+        return HttpResponse::BadRequest().body("empty query");
+    }
+    let (limit, offset) = (query.limit(), query.offset.unwrap_or(0));
+    match web::block(move || index.search(&query.q, limit, offset)).await {
+        Ok(Ok(results)) => HttpResponse::Ok().json(results),
+        Ok(Err(e)) => error_response(e.into()),
+        Err(e) => error_response(e.into()),
+    }
+}
+
+async fn get_dependencies(
+    reader: web::Data<DataReader>,
+    name: web::Path<String>,
+    query: web::Query<VersionQuery>,
+) -> HttpResponse {
+    get_relation(&reader, name.into_inner(), query.into_inner(), false).await
+}
+
+async fn get_dependents(
+    reader: web::Data<DataReader>,
+    name: web::Path<String>,
+    query: web::Query<VersionQuery>,
+) -> HttpResponse {
+    get_relation(&reader, name.into_inner(), query.into_inner(), true).await
+}
+
+/// The full-text index in `SEARCH_INDEX_DIR`, if any.
+fn open_search_index() -> Option<SearchIndex> {
+    let dir = std::env::var("SEARCH_INDEX_DIR").ok()?;
+    match SearchIndex::open(Path::new(&dir)) {
+        Ok(index) => Some(index),
+        Err(e) => {
+            tracing::error!("Failed to open the search index in {}: {}", dir, e);
+            None
+        }
+    }
+}
+
+/// Serve the query API on `addr`, such as `0.0.0.0:6889`.
+pub async fn run_query_server(addr: &str) -> std::io::Result<()> {
+    let var = |key: &str| {
+        std::env::var(key).map_err(|e| std::io::Error::other(format!("{}: {}", key, e)))
+    };
+    let reader = DataReader::new(
+        &var("TUGRAPH_BOLT_URL")?,
+        &var("TUGRAPH_USER_NAME")?,
+        &var("TUGRAPH_USER_PASSWORD")?,
+        &var("TUGRAPH_CRATESPRO_DB")?,
+    )
+    .await
+    .map_err(|e| std::io::Error::other(e.to_string()))?;
+    let schema = web::Data::new(build_schema(reader.clone()));
+    let reader = web::Data::new(reader);
+    let closures = web::Data::new(ClosureState::from_env());
+    let webhook = GithubWebhook::from_env().map(web::Data::new);
+    let search_index = open_search_index().map(web::Data::new);
+
+    tracing::info!("Serve the query API on http://{}", addr);
+    HttpServer::new(move || {
+        App::new()
+            .app_data(reader.clone())
+            .app_data(schema.clone())
+            .app_data(closures.clone())
+            .route("/graphql", web::post().to(graphql))
+            .route("/graphql", web::get().to(graphiql))
+            .route("/crates/{name}", web::get().to(get_crate))
+            .route(
+                "/crates/{name}/dependencies",
+                web::get().to(get_dependencies),
+            )
+            .route("/crates/{name}/dependents", web::get().to(get_dependents))
+            .route(
+                "/crates/{name}/dependents/all",
+                web::get().to(get_all_dependents),
+            )
+            .route("/crates/{name}/closure", web::get().to(get_closure))
+            .configure(|cfg| {
+                if let Some(webhook) = &webhook {
+                    cfg.app_data(webhook.clone())
+                        .route("/webhooks/github", web::post().to(github_webhook));
+                }
+                if let Some(search_index) = &search_index {
+                    cfg.app_data(search_index.clone())
+                        .route("/search", web::get().to(search_crates));
+                }
+            })
+    })
+    .bind(addr)?
+    .run()
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_params() {
+        assert!(is_valid_ident("tokio-util"));
+        assert!(is_valid_ident("1.0.0-rc.1+build"));
+        assert!(!is_valid_ident("tokio' OR 1=1"));
+        assert!(!is_valid_ident(""));
+
+        let versions = ["0.9.0", "1.10.0", "1.2.0"].map(String::from);
+        assert_eq!(max_version(&versions).as_deref(), Some("1.10.0"));
+        assert_eq!(max_version(&[]), None);
+
+        let search = |limit| SearchQuery {
+            q: "http".to_string(),
+            limit,
+            offset: None,
+        };
+        assert_eq!(search(None).limit(), DEFAULT_SEARCH_LIMIT);
+        assert_eq!(search(Some(0)).limit(), 1);
+        assert_eq!(search(Some(1000)).limit(), MAX_SEARCH_LIMIT);
+    }
+}
@@ -0,0 +1,159 @@
+//! The GitHub webhooks re-importing a repo when it's pushed or released, served on
+//! `POST /webhooks/github` of the query API, to keep the graph near real-time.
+//!
+//! The webhook is added in GitHub with the `application/json` content type and the
+//! secret in `GITHUB_WEBHOOK_SECRET`, and the requests are signed by it. The pushes to the
+//! default branch or a tag, and the published releases submit the repo to the import
+//! workers by [`ImportJobs`], the other events are ignored.
+//!
+//! It's only served if `GITHUB_WEBHOOK_SECRET` is set, and the import jobs can be submitted.
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use hmac::{Hmac, Mac};
+use repo_import::ImportJobs;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::env;
+use std::sync::Arc;
+
+pub(crate) struct GithubWebhook {
+    secret: Vec<u8>,
+    jobs: Arc<ImportJobs>,
+}
+
+impl GithubWebhook {
+    pub(crate) fn from_env() -> Option<Self> {
+        let secret = env::var("GITHUB_WEBHOOK_SECRET").ok()?;
+        match ImportJobs::from_env("webhook") {
+            Ok(jobs) => Some(GithubWebhook {
+                secret: secret.into_bytes(),
+                jobs,
+            }),
+            Err(e) => {
+                tracing::warn!("Serve without the GitHub webhook: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    html_url: String,
+    default_branch: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PushEvent {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    #[serde(default)]
+    deleted: bool,
+    repository: Repository,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReleaseEvent {
+    action: String,
+    repository: Repository,
+}
+
+/// Whether `X-Hub-Signature-256` is the HMAC of the body, such as `sha256=7571...`.
+fn verify_signature(secret: &[u8], body: &[u8], signature: &str) -> bool {
+    let Some(signature) = signature
+        .strip_prefix("sha256=")
+        .and_then(|s| hex::decode(s).ok())
+    else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes a key of any size");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// The url of the repo to re-import for an event, if any.
+fn affected_repo(event: &str, payload: &[u8]) -> Result<Option<String>, serde_json::Error> {
+    match event {
+        "push" => {
+            let push: PushEvent = serde_json::from_slice(payload)?;
+            let default_branch = push
+                .repository
+                .default_branch
+                .map(|branch| format!("refs/heads/{}", branch));
+            let is_tracked = push.git_ref.starts_with("refs/tags/")
+                || default_branch.as_deref() == Some(push.git_ref.as_str());
+            Ok((is_tracked && !push.deleted).then_some(push.repository.html_url))
+        }
+        "release" => {
+            let release: ReleaseEvent = serde_json::from_slice(payload)?;
+            Ok(matches!(release.action.as_str(), "published" | "released")
+                .then_some(release.repository.html_url))
+        }
+        _ => Ok(None),
+    }
+}
+
+pub(crate) async fn github_webhook(
+    webhook: web::Data<GithubWebhook>,
+    request: HttpRequest,
+    body: web::Bytes,
+) -> HttpResponse {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    };
+    if !verify_signature(&webhook.secret, &body, header("X-Hub-Signature-256")) {
+        tracing::warn!("Reject a webhook with an invalid signature");
+        return HttpResponse::Unauthorized().body("invalid signature");
+    }
+    let event = header("X-GitHub-Event");
+    match affected_repo(event, &body) {
+        Ok(Some(url)) => {
+            tracing::info!("Re-import {} for a {} event", url, event);
+            let job = webhook.jobs.submit(vec![url]).await;
+            HttpResponse::Accepted().json(serde_json::json!({ "job": job.id }))
+        }
+        Ok(None) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::BadRequest().body(format!("invalid {} event: {}", event, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_webhook() {
+        // the example of the GitHub docs
+        let secret = b"It's a Secret to Everybody";
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert!(verify_signature(secret, b"Hello, World!", signature));
+        assert!(!verify_signature(secret, b"Hello, World?", signature));
+        assert!(!verify_signature(secret, b"Hello, World!", "sha1=757107ea"));
+
+        let push = |git_ref: &str| {
+            format!(
+                r#"{{"ref": "{}", "repository": {{"html_url": "https://github.com/tokio-rs/tokio", "default_branch": "master"}}}}"#,
+                git_ref
+            )
+        };
+        let url = Some("https://github.com/tokio-rs/tokio".to_string());
+        assert_eq!(
+            affected_repo("push", push("refs/heads/master").as_bytes()).unwrap(),
+            url
+        );
+        assert_eq!(
+            affected_repo("push", push("refs/tags/v1.0.0").as_bytes()).unwrap(),
+            url
+        );
+        assert_eq!(
+            affected_repo("push", push("refs/heads/dev").as_bytes()).unwrap(),
+            None
+        );
+        assert_eq!(affected_repo("ping", b"{}").unwrap(), None);
+        assert!(affected_repo("release", b"{}").is_err());
+    }
+}
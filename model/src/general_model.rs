@@ -1,3 +1,4 @@
+use apache_avro::AvroSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -15,7 +16,9 @@ impl Version {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(
+    Debug, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, AvroSchema,
+)]
 pub struct VersionWithTag {
     pub name: String,
     pub version: String,
@@ -34,6 +37,53 @@ impl VersionWithTag {
     }
 }
 
+/// A request to import a repo, consumed by the import workers.
+///
+/// The url can be a git url, a local path or a `file://` url.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, AvroSchema)]
+pub struct RepoImportRequest {
+    pub url: String,
+    /// the import job requesting it, if any
+    #[serde(default)]
+    #[avro(default = "null")]
+    pub job_id: Option<String>,
+}
+
+/// The result of a repo import request, published by the import workers.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Default, AvroSchema)]
+pub struct RepoImportResult {
+    pub url: String,
+    /// the import job of the request, if any
+    #[serde(default)]
+    #[avro(default = "null")]
+    pub job_id: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub programs: i64,
+    pub versions: i64,
+}
+
+/// A version is yanked or unyanked in the registry.
+#[derive(
+    Debug, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, AvroSchema,
+)]
+pub struct YankEvent {
+    pub name: String,
+    pub version: String,
+    /// true for a yank, false for an unyank
+    pub yanked: bool,
+}
+
+impl YankEvent {
+    pub fn new(name: &str, version: &str, yanked: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            yanked,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct Program {
     pub name: String,
@@ -48,3 +98,160 @@ impl Program {
         }
     }
 }
+
+/// Which table of `Cargo.toml` a dependency is declared in
+#[derive(
+    Debug, Default, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy,
+)]
+pub enum DependencyKind {
+    /// `[dependencies]`
+    #[default]
+    Normal,
+    /// `[dev-dependencies]`
+    Dev,
+    /// `[build-dependencies]`
+    Build,
+}
+
+/// Where a dependency is fetched from
+#[derive(Debug, Default, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum DependencySource {
+    /// a registry, such as crates.io
+    #[default]
+    Registry,
+    /// `path = "../foo"`, relative to the directory of the dependent crate
+    Path(String),
+    /// `git = "https://github.com/foo/bar"`
+    Git(String),
+}
+
+/// A dependency declared in a `Cargo.toml`,
+/// such as `tokio = { version = "1", features = ["full"], optional = true }`
+#[derive(Debug, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct Dependency {
+    pub name: String,
+    pub version_req: String,
+    pub features: Vec<String>,
+    pub optional: bool,
+    pub kind: DependencyKind,
+    #[serde(default)]
+    pub source: DependencySource,
+    /// the platform of `[target.'cfg(windows)'.dependencies]`, such as `cfg(windows)`
+    #[serde(default)]
+    pub target: Option<String>,
+    /// false for `default-features = false`
+    #[serde(default = "enabled")]
+    pub default_features: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Dependency {
+    pub fn new(
+        name: &str,
+        version_req: &str,
+        features: Vec<String>,
+        optional: bool,
+        kind: DependencyKind,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            version_req: version_req.to_string(),
+            features,
+            optional,
+            kind,
+            source: DependencySource::Registry,
+            target: None,
+            default_features: true,
+        }
+    }
+
+    pub fn with_source(mut self, source: DependencySource) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_target(mut self, target: Option<String>) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn with_default_features(mut self, default_features: bool) -> Self {
+        self.default_features = default_features;
+        self
+    }
+}
+
+/// Which table of the workspace root `Cargo.toml` an override is declared in
+#[derive(
+    Debug, Default, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy,
+)]
+pub enum OverrideKind {
+    /// `[patch.crates-io]` or `[patch."https://github.com/foo/bar"]`
+    #[default]
+    Patch,
+    /// `[replace]`, deprecated by cargo
+    Replace,
+}
+
+impl OverrideKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverrideKind::Patch => "patch",
+            OverrideKind::Replace => "replace",
+        }
+    }
+}
+
+/// A crate used instead of a dependency everywhere in a workspace,
+/// such as `foo = { path = "../foo" }` in `[patch.crates-io]`
+#[derive(Debug, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct DependencyOverride {
+    pub kind: OverrideKind,
+    /// the overridden source, such as `crates-io` or a git url,
+    /// or the overridden package of `[replace]`, such as `foo:1.0.0`
+    pub source: String,
+    pub replacement: Dependency,
+}
+
+/// The kind of a target of a package, as in `[lib] crate-type` and `[[bin]]`
+#[derive(Debug, Deserialize, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum TargetKind {
+    Lib,
+    Rlib,
+    Dylib,
+    Cdylib,
+    Staticlib,
+    ProcMacro,
+    Bin,
+}
+
+impl TargetKind {
+    /// Parse a `crate-type` value, such as `cdylib`
+    pub fn from_crate_type(crate_type: &str) -> Option<Self> {
+        match crate_type {
+            "lib" => Some(Self::Lib),
+            "rlib" => Some(Self::Rlib),
+            "dylib" => Some(Self::Dylib),
+            "cdylib" => Some(Self::Cdylib),
+            "staticlib" => Some(Self::Staticlib),
+            "proc-macro" => Some(Self::ProcMacro),
+            "bin" => Some(Self::Bin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lib => "lib",
+            Self::Rlib => "rlib",
+            Self::Dylib => "dylib",
+            Self::Cdylib => "cdylib",
+            Self::Staticlib => "staticlib",
+            Self::ProcMacro => "proc-macro",
+            Self::Bin => "bin",
+        }
+    }
+}
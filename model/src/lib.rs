@@ -1,3 +1,4 @@
 pub mod general_model;
+pub mod message;
 pub mod repo_sync_model;
 pub mod tugraph_model;
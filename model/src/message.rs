@@ -0,0 +1,261 @@
+//! The messages between the services, such as the new versions sent to the analysis.
+//!
+//! A message is encoded as json, or as Avro if `KAFKA_MESSAGE_FORMAT=avro`.
+//! The Avro schema of a message is generated from its model type, and wrapped in
+//! a record with the `schema_version`, such as
+//!
+//! ```json
+//! {"type": "record", "name": "VersionWithTagMessage", "namespace": "crates_pro",
+//!  "fields": [{"name": "schema_version", "type": "int"},
+//!             {"name": "payload", "type": {"type": "record", "name": "VersionWithTag", ...}}]}
+//! ```
+//!
+//! The Avro messages follow the single object encoding, i.e. `0xC3 0x01`, the fingerprint
+//! of the writer schema and the datum, so a consumer knowing the older schemas of a message
+//! can still read it after the producer evolves.
+
+use crate::general_model::{RepoImportRequest, RepoImportResult, VersionWithTag, YankEvent};
+use apache_avro::rabin::Rabin;
+use apache_avro::types::Value;
+use apache_avro::{from_avro_datum, from_value, to_avro_datum, to_value, AvroSchema, Schema};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::json;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// The version of the message schemas, bumped when one of them changes.
+pub const SCHEMA_VERSION: i32 = 2;
+
+const SINGLE_OBJECT_MAGIC: [u8; 2] = [0xC3, 0x01];
+
+pub type MessageError = Box<dyn Error + Send + Sync>;
+
+/// A message between the services.
+pub trait Message: Serialize + DeserializeOwned + AvroSchema {
+    const NAME: &'static str;
+
+    /// The schemas the message may be written with, the current one first.
+    /// The older ones are added here when the schema evolves.
+    fn writer_schemas() -> Vec<Schema> {
+        vec![message_schema::<Self>()]
+    }
+}
+
+impl Message for VersionWithTag {
+    const NAME: &'static str = "VersionWithTag";
+}
+
+impl Message for YankEvent {
+    const NAME: &'static str = "YankEvent";
+}
+
+impl Message for RepoImportRequest {
+    const NAME: &'static str = "RepoImportRequest";
+
+    fn writer_schemas() -> Vec<Schema> {
+        // v1 without the job id
+        vec![
+            message_schema::<Self>(),
+            older_message_schema::<Self>(&["job_id"]),
+        ]
+    }
+}
+
+impl Message for RepoImportResult {
+    const NAME: &'static str = "RepoImportResult";
+
+    fn writer_schemas() -> Vec<Schema> {
+        // v1 without the job id
+        vec![
+            message_schema::<Self>(),
+            older_message_schema::<Self>(&["job_id"]),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    Json,
+    Avro,
+}
+
+impl MessageFormat {
+    /// `KAFKA_MESSAGE_FORMAT`, json by default.
+    pub fn from_env() -> Self {
+        match env::var("KAFKA_MESSAGE_FORMAT").as_deref() {
+            Ok("avro") => MessageFormat::Avro,
+            _ => MessageFormat::Json,
+        }
+    }
+}
+
+/// The schema of a message, its model wrapped with the `schema_version`.
+pub fn message_schema<T: Message>() -> Schema {
+    let payload = serde_json::to_value(T::get_schema()).expect("the schema is serializable");
+    wrap_payload::<T>(payload)
+}
+
+/// The schema of a message before the `added` fields of its model.
+fn older_message_schema<T: Message>(added: &[&str]) -> Schema {
+    let mut payload = serde_json::to_value(T::get_schema()).expect("the schema is serializable");
+    if let Some(fields) = payload
+        .get_mut("fields")
+        .and_then(serde_json::Value::as_array_mut)
+    {
+        fields.retain(|field| !added.iter().any(|name| field["name"] == *name));
+    }
+    wrap_payload::<T>(payload)
+}
+
+fn wrap_payload<T: Message>(payload: serde_json::Value) -> Schema {
+    let schema = json!({
+        "type": "record",
+        "name": format!("{}Message", T::NAME),
+        "namespace": "crates_pro",
+        "fields": [
+            {"name": "schema_version", "type": "int"},
+            {"name": "payload", "type": payload},
+        ],
+    });
+    Schema::parse(&schema).expect("the message schema is valid")
+}
+
+fn fingerprint(schema: &Schema) -> Vec<u8> {
+    schema.fingerprint::<Rabin>().bytes
+}
+
+/// Encode a message in a format.
+pub fn encode<T: Message>(message: &T, format: MessageFormat) -> Result<Vec<u8>, MessageError> {
+    match format {
+        MessageFormat::Json => Ok(serde_json::to_vec(message)?),
+        MessageFormat::Avro => {
+            let schema = message_schema::<T>();
+            let value = Value::Record(vec![
+                ("schema_version".to_string(), Value::Int(SCHEMA_VERSION)),
+                ("payload".to_string(), to_value(message)?),
+            ])
+            .resolve(&schema)?;
+            let mut res = SINGLE_OBJECT_MAGIC.to_vec();
+            res.extend(fingerprint(&schema));
+            res.extend(to_avro_datum(&schema, value)?);
+            Ok(res)
+        }
+    }
+}
+
+/// Decode a message in json or Avro, which is told by the magic of Avro.
+pub fn decode<T: Message>(bytes: &[u8]) -> Result<T, MessageError> {
+    let datum = match bytes.strip_prefix(&SINGLE_OBJECT_MAGIC[..]) {
+        Some(datum) => datum,
+        None => return Ok(serde_json::from_slice(bytes)?),
+    };
+    if datum.len() < 8 {
+        return Err("the message is truncated".into());
+    }
+    let (writer_fingerprint, mut datum) = datum.split_at(8);
+    let writer = T::writer_schemas()
+        .into_iter()
+        .find(|schema| fingerprint(schema) == writer_fingerprint)
+        .ok_or_else(|| format!("unknown schema of {}", T::NAME))?;
+
+    let reader = message_schema::<T>();
+    let value = from_avro_datum(&writer, &mut datum, Some(&reader))?;
+    let payload = match value {
+        Value::Record(fields) => fields
+            .into_iter()
+            .find(|(name, _)| name == "payload")
+            .map(|(_, payload)| payload),
+        _ => None,
+    }
+    .ok_or("the message has no payload")?;
+    Ok(from_value(&payload)?)
+}
+
+/// Write the schema of every message into `<dir>/<name>.v<version>.avsc`.
+pub fn write_message_schemas(dir: &Path) -> Result<(), MessageError> {
+    fs::create_dir_all(dir)?;
+    for (name, schema) in [
+        (VersionWithTag::NAME, message_schema::<VersionWithTag>()),
+        (YankEvent::NAME, message_schema::<YankEvent>()),
+        (
+            RepoImportRequest::NAME,
+            message_schema::<RepoImportRequest>(),
+        ),
+        (RepoImportResult::NAME, message_schema::<RepoImportResult>()),
+    ] {
+        let path = dir.join(format!("{}.v{}.avsc", name, SCHEMA_VERSION));
+        fs::write(path, serde_json::to_string_pretty(&schema)?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_and_decode() {
+        let version = VersionWithTag::new(
+            "tokio",
+            "1.38.0",
+            "https://github.com/tokio-rs/tokio",
+            "tokio-1.38.0",
+        );
+        for format in [MessageFormat::Json, MessageFormat::Avro] {
+            let bytes = encode(&version, format).unwrap();
+            assert_eq!(decode::<VersionWithTag>(&bytes).unwrap(), version);
+        }
+
+        let result = RepoImportResult {
+            url: "https://github.com/tokio-rs/tokio".to_string(),
+            error: Some("timeout".to_string()),
+            ..Default::default()
+        };
+        let bytes = encode(&result, MessageFormat::Avro).unwrap();
+        assert_eq!(&bytes[..2], &SINGLE_OBJECT_MAGIC);
+        assert_eq!(decode::<RepoImportResult>(&bytes).unwrap(), result);
+        assert!(decode::<VersionWithTag>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_decode_an_older_schema() {
+        // a result of v1, without the job id
+        let writer = older_message_schema::<RepoImportResult>(&["job_id"]);
+        let payload = Value::Record(vec![
+            ("url".to_string(), Value::String("a".to_string())),
+            ("success".to_string(), Value::Boolean(true)),
+            ("error".to_string(), Value::Union(0, Box::new(Value::Null))),
+            ("programs".to_string(), Value::Long(1)),
+            ("versions".to_string(), Value::Long(2)),
+        ]);
+        let value = Value::Record(vec![
+            ("schema_version".to_string(), Value::Int(1)),
+            ("payload".to_string(), payload),
+        ]);
+        let mut bytes = SINGLE_OBJECT_MAGIC.to_vec();
+        bytes.extend(fingerprint(&writer));
+        bytes.extend(to_avro_datum(&writer, value).unwrap());
+
+        let result = decode::<RepoImportResult>(&bytes).unwrap();
+        assert_eq!(result.job_id, None);
+        assert!(result.success);
+        assert_eq!(result.versions, 2);
+    }
+
+    #[test]
+    fn test_write_message_schemas() {
+        let dir = env::temp_dir().join(format!("message_schemas_{}", std::process::id()));
+        write_message_schemas(&dir).unwrap();
+
+        let path = dir.join(format!("YankEvent.v{}.avsc", SCHEMA_VERSION));
+        let schema: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(schema["name"], "YankEventMessage");
+        assert_eq!(schema["fields"][0]["name"], "schema_version");
+        assert_eq!(schema["fields"][1]["type"]["name"], "YankEvent");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
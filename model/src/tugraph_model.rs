@@ -24,6 +24,69 @@ pub struct Program {
     pub mega_url: Option<String>,
     /// An optional URL pointing to the crate's documentation.
     pub doc_url: Option<String>,
+    /// The SPDX license expression, such as `MIT OR Apache-2.0`.
+    pub license: Option<String>,
+    /// An optional URL pointing to the crate's homepage.
+    pub homepage: Option<String>,
+    /// The keywords, separated by commas.
+    pub keywords: Option<String>,
+    /// The crates.io categories, separated by commas.
+    pub categories: Option<String>,
+    /// The rust edition, such as `2021`.
+    pub edition: Option<String>,
+    /// The minimum supported rust version.
+    pub rust_version: Option<String>,
+    /// The path of the readme file.
+    pub readme: Option<String>,
+    /// The authors, separated by commas.
+    pub authors: Option<String>,
+    /// The kinds of the targets, separated by commas, such as `proc-macro` or `cdylib,bin`.
+    pub target_kinds: Option<String>,
+    /// The licenses detected from the LICENSE/COPYING files, such as `Apache-2.0 OR MIT`.
+    pub detected_license: Option<String>,
+    /// The number of `unsafe` blocks.
+    pub unsafe_blocks: Option<i64>,
+    /// The number of `unsafe` functions and methods.
+    pub unsafe_fns: Option<i64>,
+    /// The number of `unsafe impl`s.
+    pub unsafe_impls: Option<i64>,
+    /// The number of `unsafe trait`s.
+    pub unsafe_traits: Option<i64>,
+    /// The lines of Rust code, without comments and blank lines.
+    pub rust_code_lines: Option<i64>,
+    /// The lines of Rust comments.
+    pub rust_comment_lines: Option<i64>,
+    /// The lines of Rust code for tests, a part of `rust_code_lines`.
+    pub rust_test_lines: Option<i64>,
+    /// The lines of code of the other languages, such as `C:1200,Python:30`.
+    pub languages: Option<String>,
+    /// The manifest fields which are unknown rather than empty, separated by commas,
+    /// such as `version,license` inherited from a workspace which does not define them.
+    pub unknown_fields: Option<String>,
+    /// The path of the build script, such as `build.rs`.
+    pub build_script: Option<String>,
+    /// The native library it links, the `links` key, such as `z` of `libz-sys`.
+    pub links: Option<String>,
+    /// The support of `no_std` by the library, `std`, `no_std` or `no_std+alloc`.
+    pub std_support: Option<String>,
+    /// Whether it supports `wasm32`, by its dependencies, its code or the CI of the repo.
+    pub wasm_compatible: bool,
+    /// The content of the readme in a single line as the description, maybe truncated.
+    pub readme_content: Option<String>,
+    /// The stars of its GitHub repo.
+    pub stars: Option<i64>,
+    /// From 0 to 1, by the downloads, dependents, stars and recency.
+    pub popularity: Option<f64>,
+    /// The fewest contributors who make half of the commits of its repo.
+    pub bus_factor: Option<i64>,
+    /// The median hours to close the issues and pull requests of its GitHub repo lately.
+    pub issue_close_hours: Option<f64>,
+    /// From 0 to 1, by the contributor concentration, responsiveness and release recency.
+    pub maintenance: Option<f64>,
+    /// From 0 to 1, by the tests, docs coverage, CI, license clarity and unsafe density.
+    pub quality: Option<f64>,
+    /// The factors of the quality known, separated by commas, such as `ci:1.00,docs:0.82`.
+    pub quality_factors: Option<String>,
 }
 
 impl Program {
@@ -48,6 +111,7 @@ impl Program {
             github_url,
             mega_url,
             doc_url,
+            ..Default::default()
         }
     }
 }
@@ -76,6 +140,8 @@ pub struct Library {
     pub downloads: i64,
     /// An optional URL pointing to the library's crates.io page.
     pub cratesio: Option<String>,
+    /// The number of downloads in the last 90 days.
+    pub recent_downloads: Option<i64>,
 }
 
 impl Library {
@@ -85,6 +151,7 @@ impl Library {
             name: name.to_string(),
             downloads,
             cratesio: cratesio.map(|s| s.to_string()),
+            recent_downloads: None,
         }
     }
 }
@@ -131,6 +198,8 @@ impl UVersion {
     }
 }
 
+/// A release of a library, a child of its program by `has_version`,
+/// which the dependency edges of the release start from.
 #[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
 pub struct LibraryVersion {
     /// The id
@@ -141,8 +210,59 @@ pub struct LibraryVersion {
     pub name: String,
     /// The version of the library.
     pub version: String,
-    /// The documentation URL for the library.
+    /// The documentation URL for the library, the docs.rs page of the version if not declared.
     pub documentation: String,
+    /// Whether docs.rs built the docs of the version, unknown if not checked.
+    pub docs_built: Option<bool>,
+    /// The number of downloads of this version.
+    pub downloads: Option<i64>,
+    /// Whether the version is yanked from the registry, unknown if not from a registry.
+    pub yanked: Option<bool>,
+    /// Whether the version bump mismatches the API changes since the previous release,
+    /// unknown if the API is not extracted.
+    pub semver_violation: Option<bool>,
+    /// The minimum supported Rust version, such as `1.56`.
+    pub msrv: Option<String>,
+    /// Where the MSRV is detected: `rust-version`, `clippy`, `ci` or `bisect`.
+    pub msrv_source: Option<String>,
+    /// The git tag of the version, unknown if not from a git repo.
+    pub tag_name: Option<String>,
+    /// The commit hash of the tag.
+    pub commit: Option<String>,
+    /// The commit time of the tag, in seconds since the epoch.
+    pub commit_time: Option<i64>,
+    /// The urls of the repos the version is found in, separated by commas.
+    pub sources: Option<String>,
+    /// The time it's published to the registry, in seconds since the epoch,
+    /// unknown if not from a registry.
+    pub published_at: Option<i64>,
+    /// The sha256 of the `.crate` tarball, unknown if not from a registry.
+    pub checksum: Option<String>,
+    /// The size of the `.crate` tarball in bytes, unknown if not from a registry.
+    pub crate_size: Option<i64>,
+    /// The size of the files packaged into the `.crate` in bytes, before compression.
+    pub unpacked_size: Option<i64>,
+    /// The unsafe blocks, functions, impls and traits of it and its dependencies.
+    pub transitive_unsafe: Option<i64>,
+    /// The number of its dependencies with unsafe code.
+    pub unsafe_dependencies: Option<i64>,
+    /// The unsafe items per thousand lines of Rust code of it and its dependencies.
+    pub unsafety_score: Option<f64>,
+    /// The cargo-vet criteria it's audited for, separated by commas,
+    /// unknown if no audits are imported.
+    pub audit_criteria: Option<String>,
+    /// Whether a cargo-vet audit reports a violation of it.
+    pub audit_violation: Option<bool>,
+    /// The number of its cargo-crev reviews.
+    pub crev_reviews: Option<i64>,
+    /// The lowest rating of the reviews, from `dangerous` to `strong`.
+    pub crev_rating: Option<String>,
+    /// The wall time of a clean build of a project depending only on it, in milliseconds,
+    /// unknown if not measured.
+    pub build_time_ms: Option<i64>,
+    /// The number of units compiled by that build, i.e. the crates and build scripts
+    /// of its dependency tree.
+    pub build_units: Option<i64>,
 }
 
 impl LibraryVersion {
@@ -154,10 +274,34 @@ impl LibraryVersion {
             name: name.to_string(),
             version: version.to_string(),
             documentation: documentation.to_string(),
+            docs_built: None,
+            downloads: None,
+            yanked: None,
+            semver_violation: None,
+            msrv: None,
+            msrv_source: None,
+            tag_name: None,
+            commit: None,
+            commit_time: None,
+            sources: None,
+            published_at: None,
+            checksum: None,
+            crate_size: None,
+            unpacked_size: None,
+            transitive_unsafe: None,
+            unsafe_dependencies: None,
+            unsafety_score: None,
+            audit_criteria: None,
+            audit_violation: None,
+            crev_reviews: None,
+            crev_rating: None,
+            build_time_ms: None,
+            build_units: None,
         }
     }
 }
 
+/// A release of an application, as [`LibraryVersion`].
 #[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
 pub struct ApplicationVersion {
     /// The id
@@ -168,6 +312,44 @@ pub struct ApplicationVersion {
     pub name: String,
     /// The version
     pub version: String,
+    /// Whether the version is yanked from the registry, unknown if not from a registry.
+    pub yanked: Option<bool>,
+    /// The minimum supported Rust version, such as `1.56`.
+    pub msrv: Option<String>,
+    /// Where the MSRV is detected: `rust-version`, `clippy`, `ci` or `bisect`.
+    pub msrv_source: Option<String>,
+    /// The git tag of the version, unknown if not from a git repo.
+    pub tag_name: Option<String>,
+    /// The commit hash of the tag.
+    pub commit: Option<String>,
+    /// The commit time of the tag, in seconds since the epoch.
+    pub commit_time: Option<i64>,
+    /// The urls of the repos the version is found in, separated by commas.
+    pub sources: Option<String>,
+    /// The time it's published to the registry, in seconds since the epoch,
+    /// unknown if not from a registry.
+    pub published_at: Option<i64>,
+    /// The sha256 of the `.crate` tarball, unknown if not from a registry.
+    pub checksum: Option<String>,
+    /// The size of the `.crate` tarball in bytes, unknown if not from a registry.
+    pub crate_size: Option<i64>,
+    /// The size of the files packaged into the `.crate` in bytes, before compression.
+    pub unpacked_size: Option<i64>,
+    /// The unsafe blocks, functions, impls and traits of it and its dependencies.
+    pub transitive_unsafe: Option<i64>,
+    /// The number of its dependencies with unsafe code.
+    pub unsafe_dependencies: Option<i64>,
+    /// The unsafe items per thousand lines of Rust code of it and its dependencies.
+    pub unsafety_score: Option<f64>,
+    /// The cargo-vet criteria it's audited for, separated by commas,
+    /// unknown if no audits are imported.
+    pub audit_criteria: Option<String>,
+    /// Whether a cargo-vet audit reports a violation of it.
+    pub audit_violation: Option<bool>,
+    /// The number of its cargo-crev reviews.
+    pub crev_reviews: Option<i64>,
+    /// The lowest rating of the reviews, from `dangerous` to `strong`.
+    pub crev_rating: Option<String>,
 }
 
 impl ApplicationVersion {
@@ -178,6 +360,24 @@ impl ApplicationVersion {
             name_and_version: name.to_string() + "/" + &version,
             name,
             version,
+            yanked: None,
+            msrv: None,
+            msrv_source: None,
+            tag_name: None,
+            commit: None,
+            commit_time: None,
+            sources: None,
+            published_at: None,
+            checksum: None,
+            crate_size: None,
+            unpacked_size: None,
+            transitive_unsafe: None,
+            unsafe_dependencies: None,
+            unsafety_score: None,
+            audit_criteria: None,
+            audit_violation: None,
+            crev_reviews: None,
+            crev_rating: None,
         }
     }
 }
@@ -197,6 +397,204 @@ impl Version {
     }
 }
 
+/// A feature declared in the `[features]` table of a version.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct Feature {
+    /// The id, such as `tokio/1.0.0/full`.
+    pub id: String,
+    /// The name and version of the crate.
+    pub name_and_version: String,
+    /// The name of the feature.
+    pub feature: String,
+    /// Whether it's the implicit feature of an optional dependency, `foo = ["dep:foo"]`.
+    pub implicit: bool,
+}
+
+impl Feature {
+    /// Creates a new `Feature` instance.
+    pub fn new(name: &str, version: &str, feature: &str) -> Self {
+        Feature {
+            id: name.to_string() + "/" + version + "/" + feature,
+            name_and_version: name.to_string() + "/" + version,
+            feature: feature.to_string(),
+            implicit: false,
+        }
+    }
+}
+
+/// A crates.io user who owns crates, or an author in the `authors` of packages.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct Person {
+    /// The id, such as `user/1234`, or `author/<email>` of an author.
+    pub id: String,
+    /// The login, such as `dtolnay`, or the name or email of an author.
+    pub login: String,
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub avatar: Option<String>,
+    /// The lowercase email of an author, if it's given.
+    pub email: Option<String>,
+}
+
+/// A GitHub team which owns crates, such as `github:rust-lang:libs`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct Team {
+    /// The id, such as `team/56`.
+    pub id: String,
+    /// The login, such as `github:rust-lang:libs`.
+    pub login: String,
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub avatar: Option<String>,
+}
+
+/// A security advisory of the RustSec advisory database.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct Advisory {
+    /// The id, such as `RUSTSEC-2021-0001`.
+    pub id: String,
+    /// The name of the affected crate.
+    pub package: String,
+    pub title: Option<String>,
+    /// The date reported, such as `2021-01-01`.
+    pub date: String,
+    /// `none`, `low`, `medium`, `high` or `critical`, computed from the CVSS vector.
+    pub severity: Option<String>,
+    /// The CVSS vector.
+    pub cvss: Option<String>,
+    /// The kind of an informational advisory, such as `unmaintained` or `unsound`.
+    pub informational: Option<String>,
+    /// The version requirements of the patched versions, separated by semicolons.
+    pub patched: Option<String>,
+    /// The aliases such as CVE ids, separated by commas.
+    pub aliases: Option<String>,
+    pub url: Option<String>,
+}
+
+/// A public item of a library version, such as a function, a struct or a method.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct ApiItem {
+    /// The id, such as `tokio/1.0.0/tokio::spawn`.
+    pub id: String,
+    /// The name and version of the crate.
+    pub name_and_version: String,
+    /// The path, such as `tokio::spawn`.
+    pub path: String,
+    /// The kind, such as `fn`, `struct`, `field`, `method` or `trait_fn`.
+    pub kind: String,
+    /// The signature without the body, such as `fn spawn < F > (future : F) -> JoinHandle < F :: Output >`.
+    pub signature: String,
+    /// Whether it has doc comments.
+    pub documented: bool,
+}
+
+impl ApiItem {
+    /// Creates a new `ApiItem` instance.
+    pub fn new(name_and_version: &str, path: &str, kind: &str, signature: &str) -> Self {
+        ApiItem {
+            id: name_and_version.to_string() + "/" + path,
+            name_and_version: name_and_version.to_string(),
+            path: path.to_string(),
+            kind: kind.to_string(),
+            signature: signature.to_string(),
+            documented: false,
+        }
+    }
+}
+
+/// A build target of a package, such as a binary of `[[bin]]` or an example in `examples/`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct Target {
+    /// The id, such as `<program id>/bin/rustfmt`.
+    pub id: String,
+    /// The id of the program.
+    pub program_id: String,
+    /// The name of the target.
+    pub name: String,
+    /// The kind, `bin`, `example`, `test` or `bench`.
+    pub kind: String,
+    /// The path of the root source file, relative to the package, such as `src/bin/rustfmt.rs`.
+    pub path: String,
+}
+
+impl Target {
+    /// Creates a new `Target` instance.
+    pub fn new(program_id: &str, kind: &str, name: &str, path: &str) -> Self {
+        Target {
+            id: format!("{}/{}/{}", program_id, kind, name),
+            program_id: program_id.to_string(),
+            name: name.to_string(),
+            kind: kind.to_string(),
+            path: path.to_string(),
+        }
+    }
+}
+
+/// The activity of a git repo, computed from its history.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct RepoMetrics {
+    /// The id, the namespace of the repo such as `tokio-rs/tokio`.
+    pub id: String,
+    /// The number of commits reachable from HEAD.
+    pub commits: i64,
+    /// The average commits per month in the last year.
+    pub commits_per_month: f64,
+    /// The time of the first commit, in seconds since the epoch.
+    pub first_commit: i64,
+    /// The time of the last commit, in seconds since the epoch.
+    pub last_commit: i64,
+    /// The number of release tags.
+    pub releases: i64,
+    /// The average days between two releases, unknown if less than two releases.
+    pub release_cadence_days: Option<f64>,
+    /// The number of authors committing in the last 90 days.
+    pub active_contributors: i64,
+}
+
+/// The owner of git repos, such as `tokio-rs`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct Organization {
+    /// The id, the namespace without the repo name, such as `tokio-rs` or `group/subgroup`.
+    pub id: String,
+    /// The number of its repos imported.
+    pub repositories: i64,
+    /// The number of the programs in its repos.
+    pub programs: i64,
+}
+
+/// A git repo of an organization.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct Repo {
+    /// The id, the namespace of the repo such as `tokio-rs/tokio`.
+    pub id: String,
+    /// The id of the organization.
+    pub organization: String,
+    /// The url the repo is imported from.
+    pub url: Option<String>,
+}
+
+/// The CI of a git repo, from its configs at HEAD.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct CiSummary {
+    /// The id, the namespace of the repo such as `tokio-rs/tokio`.
+    pub id: String,
+    /// The CI services, separated by commas, such as `github-actions,travis`.
+    pub providers: String,
+    /// The toolchains tested, such as `1.63.0,nightly,stable`.
+    pub toolchains: Option<String>,
+    /// The targets built, such as `wasm32-unknown-unknown,x86_64-pc-windows-msvc`.
+    pub targets: Option<String>,
+}
+
+/// A git author, identified by the normalized email.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct Contributor {
+    /// The id, the lowercase email.
+    pub id: String,
+    /// The latest name used in the commits.
+    pub name: String,
+}
+
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct HasType {
@@ -218,11 +616,242 @@ pub struct HasDepVersion {
     pub DST_ID: String,
 }
 
+/// a version depends on another one, the kind of the dependency is the label of the edge
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct DependsOn {
     pub SRC_ID: String,
     pub DST_ID: String,
+    /// the platforms it's only for, such as `cfg(windows)`, none for all
+    pub target: Option<String>,
+    /// whether it's optional, activated only by the features
+    pub optional: bool,
+    /// the version requirement as declared, such as `^0.3` or `~1.2`, none for a git
+    /// dependency without one
+    pub req: Option<String>,
+    /// the requirement as explicit bounds, such as `>=0.3.0, <0.4.0`
+    pub range: Option<String>,
+    /// the features it enables, joined by `,`, without the default ones
+    pub features: Option<String>,
+    /// false for `default-features = false`
+    pub default_features: bool,
+    /// whether it's in a cycle of the dependencies, such as through a dev-dependency
+    pub in_cycle: bool,
+}
+
+/// a version depends on another one only for tests, examples and benchmarks
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct DevDependsOn {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+    /// see [`DependsOn::target`]
+    pub target: Option<String>,
+    pub optional: bool,
+    /// see [`DependsOn::req`]
+    pub req: Option<String>,
+    pub range: Option<String>,
+    pub features: Option<String>,
+    pub default_features: bool,
+    pub in_cycle: bool,
+}
+
+/// a version depends on another one only in its build script
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct BuildDependsOn {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+    /// see [`DependsOn::target`]
+    pub target: Option<String>,
+    pub optional: bool,
+    /// see [`DependsOn::req`]
+    pub req: Option<String>,
+    pub range: Option<String>,
+    pub features: Option<String>,
+    pub default_features: bool,
+    pub in_cycle: bool,
+}
+
+/// version -> version, a dependency of a version resolved by the `Cargo.lock` of its repo
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ResolvesTo {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+    /// such as `registry+https://github.com/rust-lang/crates.io-index`, none for a path dependency
+    pub source: Option<String>,
+    /// the sha256 of the `.crate` file, none for a git or path dependency
+    pub checksum: Option<String>,
+}
+
+/// program -> program, a library whose name looks like a typo of a popular library
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Typosquats {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+    /// the typo, such as `adjacent_key`, `transposition` or `omission`
+    pub kind: String,
+    /// the edit distance between the names
+    pub distance: i64,
+    /// from 0 to 1, lower if the suspect is downloaded as much as the popular library
+    pub confidence: f64,
+}
+
+/// program -> program, an alternative of a library, by the keywords and categories in common
+/// and the few dependents using both
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SimilarTo {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+    /// from 0 to 1, the weighted sum of the overlaps, lowered by the co-occurrence
+    pub score: f64,
+    /// the rank among the alternatives of the source, from 1
+    pub rank: i64,
+    /// from 0 to 1, the cosine similarity of the crates depending on them
+    pub cooccurrence: f64,
+    pub shared_keywords: i64,
+    pub shared_categories: i64,
+}
+
+/// version -> version, the workspace of a version overrides a dependency by another version
+/// in `[patch]` or `[replace]`
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Overrides {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+    /// `patch` or `replace`
+    pub kind: String,
+    /// the overridden source, such as `crates-io`, or the overridden package of `[replace]`
+    pub source: String,
+}
+
+/// version -> feature
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HasFeature {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// feature -> feature, of the same crate or of a dependency
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct EnablesFeature {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// feature -> version, a feature activates an optional dependency
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct EnablesDependency {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// version -> feature, a version enables a feature of its dependency
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct DependsOnFeature {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// version -> advisory, a version is affected by a security advisory
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct AffectedBy {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// library_version -> api_item
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HasApiItem {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// person/team -> program, an owner of the crate on crates.io
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Owns {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// person -> program, an author in the `authors` of the package
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct AuthorOf {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// person -> contributor, an author committing with the same email
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct IsContributor {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// program -> repo_metrics, the activity of the repo of the program
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HasRepoMetrics {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// program -> ci_summary, the CI of the repo of the program
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HasCi {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// organization -> repository
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HasRepository {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// organization -> program, a program in a repo of the organization
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HasProgram {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// program -> target
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HasTarget {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+}
+
+/// contributor -> program, the commits in the repo of the program
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ContributedTo {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+    pub commits: i64,
+    /// The time of the first commit, in seconds since the epoch.
+    pub first_commit: i64,
+    /// The time of the last commit, in seconds since the epoch.
+    pub last_commit: i64,
 }
 
 pub trait CrateType2Idx {
@@ -0,0 +1,225 @@
+//! Compare the public API surfaces of two versions of a library,
+//! and classify the changes as additive, breaking or internal.
+
+use crate::utils::name_join_version;
+use crate::ImportContext;
+use model::tugraph_model::ApiItem;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeClass {
+    /// no public API is changed
+    Internal,
+    /// new APIs which don't break the users
+    Additive,
+    Breaking,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiChange {
+    pub path: String,
+    pub kind: String,
+    pub change: ChangeKind,
+    pub class: ChangeClass,
+    pub old_signature: Option<String>,
+    pub new_signature: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiDiff {
+    pub crate_name: String,
+    pub old_version: String,
+    pub new_version: String,
+    /// the most severe class of the changes, `internal` if nothing changes
+    pub class: ChangeClass,
+    pub changes: Vec<ApiChange>,
+}
+
+/// `foo::Bar::baz` -> `foo::Bar`
+fn parent_path(path: &str) -> &str {
+    path.rsplit_once("::").map_or("", |(parent, _)| parent)
+}
+
+/// Whether adding the item into an existing parent breaks the users.
+fn is_breaking_addition(item: &ApiItem, old_parent: Option<&ApiItem>) -> bool {
+    let parent = match old_parent {
+        Some(parent) => parent,
+        None => return false,
+    };
+    let exhaustive = !parent.signature.starts_with("#[non_exhaustive]");
+    match item.kind.as_str() {
+        // the implementors have to implement it
+        "trait_fn" | "trait_type" | "trait_const" => true,
+        // the exhaustive matches break
+        "variant" => exhaustive,
+        // the struct literals break
+        "field" => {
+            parent.kind == "struct" && exhaustive && !parent.signature.contains("private fields")
+        }
+        _ => false,
+    }
+}
+
+/// Compare the API items of two versions.
+pub fn diff_api<'a>(
+    old: impl IntoIterator<Item = &'a ApiItem>,
+    new: impl IntoIterator<Item = &'a ApiItem>,
+) -> Vec<ApiChange> {
+    let old: BTreeMap<&str, &ApiItem> = old.into_iter().map(|i| (i.path.as_str(), i)).collect();
+    let new: BTreeMap<&str, &ApiItem> = new.into_iter().map(|i| (i.path.as_str(), i)).collect();
+
+    let mut changes = vec![];
+    for (path, item) in &old {
+        match new.get(path) {
+            None => changes.push(ApiChange {
+                path: path.to_string(),
+                kind: item.kind.clone(),
+                change: ChangeKind::Removed,
+                class: ChangeClass::Breaking,
+                old_signature: Some(item.signature.clone()),
+                new_signature: None,
+            }),
+            Some(new_item)
+                if new_item.kind != item.kind || new_item.signature != item.signature =>
+            {
+                // a default implementation is added to a trait method
+                let class = if item.kind == "trait_fn"
+                    && new_item.kind == "trait_provided_fn"
+                    && new_item.signature == item.signature
+                {
+                    ChangeClass::Additive
+                } else {
+                    ChangeClass::Breaking
+                };
+                changes.push(ApiChange {
+                    path: path.to_string(),
+                    kind: new_item.kind.clone(),
+                    change: ChangeKind::Changed,
+                    class,
+                    old_signature: Some(item.signature.clone()),
+                    new_signature: Some(new_item.signature.clone()),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+    for (path, item) in &new {
+        if old.contains_key(path) {
+            continue;
+        }
+        let old_parent = old.get(parent_path(path)).copied();
+        let class = if is_breaking_addition(item, old_parent) {
+            ChangeClass::Breaking
+        } else {
+            ChangeClass::Additive
+        };
+        changes.push(ApiChange {
+            path: path.to_string(),
+            kind: item.kind.clone(),
+            change: ChangeKind::Added,
+            class,
+            old_signature: None,
+            new_signature: Some(item.signature.clone()),
+        });
+    }
+    changes
+}
+
+/// The most severe class of the changes.
+pub fn classify(changes: &[ApiChange]) -> ChangeClass {
+    changes
+        .iter()
+        .map(|c| c.class)
+        .max()
+        .unwrap_or(ChangeClass::Internal)
+}
+
+impl ImportContext {
+    /// Diff the public API of two versions of a library,
+    /// `None` if the API of either version is not extracted.
+    pub fn api_diff(
+        &self,
+        crate_name: &str,
+        old_version: &str,
+        new_version: &str,
+    ) -> Option<ApiDiff> {
+        let old_id = name_join_version(crate_name, old_version);
+        let new_id = name_join_version(crate_name, new_version);
+        let old: Vec<&ApiItem> = self
+            .api_items
+            .iter()
+            .filter(|i| i.name_and_version == old_id)
+            .collect();
+        let new: Vec<&ApiItem> = self
+            .api_items
+            .iter()
+            .filter(|i| i.name_and_version == new_id)
+            .collect();
+        if old.is_empty() || new.is_empty() {
+            return None;
+        }
+
+        let changes = diff_api(old, new);
+        Some(ApiDiff {
+            crate_name: crate_name.to_string(),
+            old_version: old_version.to_string(),
+            new_version: new_version.to_string(),
+            class: classify(&changes),
+            changes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(path: &str, kind: &str, signature: &str) -> ApiItem {
+        ApiItem::new("foo/1.0.0", path, kind, signature)
+    }
+
+    #[test]
+    fn test_diff_api() {
+        let old = vec![
+            item("foo::S", "struct", "struct S"),
+            item("foo::S::a", "field", "a: u8"),
+            item("foo::E", "enum", "#[non_exhaustive] enum E"),
+            item("foo::T", "trait", "trait T"),
+            item("foo::f", "fn", "fn f (x : u8)"),
+        ];
+        let new = vec![
+            item("foo::S", "struct", "struct S"),
+            item("foo::S::a", "field", "a: u8"),
+            item("foo::S::b", "field", "b: u8"),
+            item("foo::E", "enum", "#[non_exhaustive] enum E"),
+            item("foo::E::V", "variant", "V"),
+            item("foo::T", "trait", "trait T"),
+            item("foo::T::g", "trait_provided_fn", "fn g (& self)"),
+            item("foo::f", "fn", "fn f (x : u16)"),
+        ];
+        let diff = diff_api(&old, &new);
+        let changes: Vec<(&str, ChangeKind, ChangeClass)> = diff
+            .iter()
+            .map(|c| (c.path.as_str(), c.change, c.class))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("foo::f", ChangeKind::Changed, ChangeClass::Breaking),
+                ("foo::E::V", ChangeKind::Added, ChangeClass::Additive),
+                ("foo::S::b", ChangeKind::Added, ChangeClass::Breaking),
+                ("foo::T::g", ChangeKind::Added, ChangeClass::Additive),
+            ]
+        );
+    }
+}
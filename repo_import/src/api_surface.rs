@@ -0,0 +1,486 @@
+//! Extract the public API surface of a library with syn,
+//! i.e. the public items reachable through public modules and their signatures,
+//! so the APIs can be searched and diffed between versions.
+//!
+//! The sources are read by a callback, so a version can be read from a git tree
+//! without checking it out. The `#[cfg]` attributes are not evaluated,
+//! and the items re-exported by `pub use` are recorded as the `use` itself.
+
+use crate::manifest::Manifest;
+use git2::{Repository, Tree};
+use model::tugraph_model::ApiItem;
+use quote::ToTokens;
+use std::path::Path;
+use syn::{Attribute, Fields, ImplItem, Item, TraitItem, Type, Visibility};
+
+/// Guard against the modules including each other by `#[path]`.
+const MAX_MODULE_DEPTH: usize = 32;
+
+struct ApiExtractor<'a, F: Fn(&str) -> Option<String>> {
+    name_and_version: &'a str,
+    read: F,
+    items: Vec<ApiItem>,
+}
+
+fn is_public(vis: &Visibility) -> bool {
+    matches!(vis, Visibility::Public(_))
+}
+
+fn has_attr(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|a| a.path().is_ident(name))
+}
+
+/// `#[doc(hidden)]` items are not a part of the API.
+fn is_doc_hidden(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|a| {
+        a.path().is_ident("doc") && a.parse_args::<syn::Ident>().is_ok_and(|i| i == "hidden")
+    })
+}
+
+/// `///` and `//!` comments are `#[doc = "..."]` attributes.
+fn is_documented(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .any(|a| matches!(&a.meta, syn::Meta::NameValue(nv) if nv.path.is_ident("doc")))
+}
+
+fn non_exhaustive(attrs: &[Attribute]) -> &'static str {
+    if has_attr(attrs, "non_exhaustive") {
+        "#[non_exhaustive] "
+    } else {
+        ""
+    }
+}
+
+fn tokens<T: ToTokens>(t: &T) -> String {
+    t.to_token_stream().to_string()
+}
+
+/// `Foo<T>` -> `Foo`
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(p) => p
+            .path
+            .segments
+            .last()
+            .map(|s| s.ident.to_string())
+            .unwrap_or_default(),
+        _ => tokens(ty),
+    }
+}
+
+/// `#[path = "x.rs"]`
+fn path_attr(attrs: &[Attribute]) -> Option<String> {
+    attrs.iter().find_map(|a| match &a.meta {
+        syn::Meta::NameValue(nv) if nv.path.is_ident("path") => match &nv.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(s),
+                ..
+            }) => Some(s.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// The directory of the file, e.g. `src/lib.rs` -> `src/`
+fn parent_dir(file: &str) -> &str {
+    match file.rfind('/') {
+        Some(i) => &file[..=i],
+        None => "",
+    }
+}
+
+impl<F: Fn(&str) -> Option<String>> ApiExtractor<'_, F> {
+    fn push(&mut self, path: String, kind: &str, signature: String, attrs: &[Attribute]) {
+        let signature: Vec<&str> = signature.split_whitespace().collect();
+        let mut item = ApiItem::new(self.name_and_version, &path, kind, &signature.join(" "));
+        item.documented = is_documented(attrs);
+        self.items.push(item);
+    }
+
+    /// Extract the items of a module file, whose submodules are in `mod_dir`.
+    fn extract_file(&mut self, file: &str, module: &str, mod_dir: &str, depth: usize) {
+        if depth > MAX_MODULE_DEPTH {
+            return;
+        }
+        let source = match (self.read)(file) {
+            Some(source) => source,
+            None => return,
+        };
+        match syn::parse_file(&source) {
+            Ok(ast) => self.extract_items(&ast.items, file, module, mod_dir, depth),
+            Err(e) => tracing::debug!("Failed to parse {}: {}", file, e),
+        }
+    }
+
+    fn extract_items(
+        &mut self,
+        items: &[Item],
+        file: &str,
+        module: &str,
+        mod_dir: &str,
+        depth: usize,
+    ) {
+        for item in items {
+            match item {
+                Item::Fn(f) if is_public(&f.vis) && !is_doc_hidden(&f.attrs) => {
+                    self.push(
+                        format!("{}::{}", module, f.sig.ident),
+                        "fn",
+                        tokens(&f.sig),
+                        &f.attrs,
+                    );
+                }
+                Item::Struct(s) if is_public(&s.vis) && !is_doc_hidden(&s.attrs) => {
+                    let path = format!("{}::{}", module, s.ident);
+                    let private = s.fields.iter().any(|f| !is_public(&f.vis));
+                    let signature = format!(
+                        "{}struct {} {} {}{}",
+                        non_exhaustive(&s.attrs),
+                        s.ident,
+                        tokens(&s.generics),
+                        tokens(&s.generics.where_clause),
+                        if private {
+                            " { /* private fields */ }"
+                        } else {
+                            ""
+                        }
+                    );
+                    self.push(path.clone(), "struct", signature, &s.attrs);
+                    self.extract_fields(&path, &s.fields);
+                }
+                Item::Union(u) if is_public(&u.vis) && !is_doc_hidden(&u.attrs) => {
+                    let path = format!("{}::{}", module, u.ident);
+                    let signature = format!("union {} {}", u.ident, tokens(&u.generics));
+                    self.push(path.clone(), "union", signature, &u.attrs);
+                    self.extract_fields(&path, &Fields::Named(u.fields.clone()));
+                }
+                Item::Enum(e) if is_public(&e.vis) && !is_doc_hidden(&e.attrs) => {
+                    let path = format!("{}::{}", module, e.ident);
+                    let signature = format!(
+                        "{}enum {} {} {}",
+                        non_exhaustive(&e.attrs),
+                        e.ident,
+                        tokens(&e.generics),
+                        tokens(&e.generics.where_clause)
+                    );
+                    self.push(path.clone(), "enum", signature, &e.attrs);
+                    for variant in &e.variants {
+                        if is_doc_hidden(&variant.attrs) {
+                            continue;
+                        }
+                        let mut signature = variant.clone();
+                        signature.attrs.clear();
+                        self.push(
+                            format!("{}::{}", path, variant.ident),
+                            "variant",
+                            tokens(&signature),
+                            &variant.attrs,
+                        );
+                    }
+                }
+                Item::Trait(t) if is_public(&t.vis) && !is_doc_hidden(&t.attrs) => {
+                    let path = format!("{}::{}", module, t.ident);
+                    let mut header = t.clone();
+                    header.attrs.clear();
+                    header.vis = Visibility::Inherited;
+                    header.items.clear();
+                    self.push(path.clone(), "trait", tokens(&header), &t.attrs);
+                    for trait_item in &t.items {
+                        self.extract_trait_item(&path, trait_item);
+                    }
+                }
+                Item::Impl(i) => {
+                    let self_ty = type_name(&i.self_ty);
+                    match &i.trait_ {
+                        Some((_, trait_path, _)) => {
+                            let signature = format!(
+                                "{}impl {} {} for {} {}",
+                                if i.unsafety.is_some() { "unsafe " } else { "" },
+                                tokens(&i.generics),
+                                tokens(trait_path),
+                                tokens(&i.self_ty),
+                                tokens(&i.generics.where_clause)
+                            );
+                            self.push(
+                                format!(
+                                    "{}::<impl {} for {}>",
+                                    module,
+                                    tokens(trait_path),
+                                    self_ty
+                                ),
+                                "impl",
+                                signature,
+                                &i.attrs,
+                            );
+                        }
+                        None => {
+                            for impl_item in &i.items {
+                                self.extract_impl_item(
+                                    &format!("{}::{}", module, self_ty),
+                                    impl_item,
+                                );
+                            }
+                        }
+                    }
+                }
+                Item::Const(c) if is_public(&c.vis) && !is_doc_hidden(&c.attrs) => {
+                    self.push(
+                        format!("{}::{}", module, c.ident),
+                        "const",
+                        format!("const {}: {}", c.ident, tokens(&c.ty)),
+                        &c.attrs,
+                    );
+                }
+                Item::Static(s) if is_public(&s.vis) && !is_doc_hidden(&s.attrs) => {
+                    self.push(
+                        format!("{}::{}", module, s.ident),
+                        "static",
+                        format!(
+                            "static {} {}: {}",
+                            tokens(&s.mutability),
+                            s.ident,
+                            tokens(&s.ty)
+                        ),
+                        &s.attrs,
+                    );
+                }
+                Item::Type(t) if is_public(&t.vis) && !is_doc_hidden(&t.attrs) => {
+                    let mut alias = t.clone();
+                    alias.attrs.clear();
+                    alias.vis = Visibility::Inherited;
+                    self.push(
+                        format!("{}::{}", module, t.ident),
+                        "type",
+                        tokens(&alias),
+                        &t.attrs,
+                    );
+                }
+                Item::Use(u) if is_public(&u.vis) && !is_doc_hidden(&u.attrs) => {
+                    let tree = tokens(&u.tree);
+                    self.push(format!("{}::{{{}}}", module, tree), "use", tree, &u.attrs);
+                }
+                Item::Macro(m) if has_attr(&m.attrs, "macro_export") => {
+                    if let Some(ident) = &m.ident {
+                        // exported macros are at the crate root
+                        let root = module.split("::").next().unwrap_or(module);
+                        self.push(
+                            format!("{}::{}", root, ident),
+                            "macro",
+                            format!("macro_rules! {}", ident),
+                            &m.attrs,
+                        );
+                    }
+                }
+                Item::Mod(m) if is_public(&m.vis) && !is_doc_hidden(&m.attrs) => {
+                    let child = format!("{}::{}", module, m.ident);
+                    self.push(child.clone(), "mod", format!("mod {}", m.ident), &m.attrs);
+                    let child_dir = format!("{}{}/", mod_dir, m.ident);
+                    match &m.content {
+                        Some((_, items)) => {
+                            self.extract_items(items, file, &child, &child_dir, depth + 1)
+                        }
+                        None => {
+                            if let Some(path) = path_attr(&m.attrs) {
+                                let path = format!("{}{}", parent_dir(file), path);
+                                let dir = parent_dir(&path).to_string();
+                                self.extract_file(&path, &child, &dir, depth + 1);
+                                continue;
+                            }
+                            let flat = format!("{}{}.rs", mod_dir, m.ident);
+                            if (self.read)(&flat).is_some() {
+                                self.extract_file(&flat, &child, &child_dir, depth + 1);
+                            } else {
+                                let nested = format!("{}mod.rs", child_dir);
+                                self.extract_file(&nested, &child, &child_dir, depth + 1);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn extract_fields(&mut self, path: &str, fields: &Fields) {
+        for (i, field) in fields.iter().enumerate() {
+            if !is_public(&field.vis) || is_doc_hidden(&field.attrs) {
+                continue;
+            }
+            let name = field
+                .ident
+                .as_ref()
+                .map_or_else(|| i.to_string(), |ident| ident.to_string());
+            self.push(
+                format!("{}::{}", path, name),
+                "field",
+                format!("{}: {}", name, tokens(&field.ty)),
+                &field.attrs,
+            );
+        }
+    }
+
+    fn extract_trait_item(&mut self, path: &str, item: &TraitItem) {
+        match item {
+            TraitItem::Fn(f) if !is_doc_hidden(&f.attrs) => {
+                let kind = if f.default.is_some() {
+                    "trait_provided_fn"
+                } else {
+                    "trait_fn"
+                };
+                self.push(
+                    format!("{}::{}", path, f.sig.ident),
+                    kind,
+                    tokens(&f.sig),
+                    &f.attrs,
+                );
+            }
+            TraitItem::Type(t) if !is_doc_hidden(&t.attrs) => {
+                let mut signature = t.clone();
+                signature.attrs.clear();
+                signature.default = None;
+                self.push(
+                    format!("{}::{}", path, t.ident),
+                    "trait_type",
+                    tokens(&signature),
+                    &t.attrs,
+                );
+            }
+            TraitItem::Const(c) if !is_doc_hidden(&c.attrs) => {
+                self.push(
+                    format!("{}::{}", path, c.ident),
+                    "trait_const",
+                    format!("const {}: {}", c.ident, tokens(&c.ty)),
+                    &c.attrs,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn extract_impl_item(&mut self, path: &str, item: &ImplItem) {
+        match item {
+            ImplItem::Fn(f) if is_public(&f.vis) && !is_doc_hidden(&f.attrs) => {
+                self.push(
+                    format!("{}::{}", path, f.sig.ident),
+                    "method",
+                    tokens(&f.sig),
+                    &f.attrs,
+                );
+            }
+            ImplItem::Const(c) if is_public(&c.vis) && !is_doc_hidden(&c.attrs) => {
+                self.push(
+                    format!("{}::{}", path, c.ident),
+                    "assoc_const",
+                    format!("const {}: {}", c.ident, tokens(&c.ty)),
+                    &c.attrs,
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Extract the public API of a library, whose root file is `lib_path`, such as `src/lib.rs`.
+///
+/// `read` reads a file by its path relative to the crate directory.
+pub(crate) fn extract_api<F: Fn(&str) -> Option<String>>(
+    crate_name: &str,
+    version: &str,
+    lib_path: &str,
+    read: F,
+) -> Vec<ApiItem> {
+    let name_and_version = format!("{}/{}", crate_name, version);
+    let mut extractor = ApiExtractor {
+        name_and_version: &name_and_version,
+        read,
+        items: vec![],
+    };
+    let root = crate_name.replace('-', "_");
+    extractor.extract_file(lib_path, &root, parent_dir(lib_path), 0);
+
+    // the same path may be declared several times under different `#[cfg]`s
+    let mut items = extractor.items;
+    let mut seen = std::collections::HashSet::new();
+    items.retain(|item| seen.insert(item.id.clone()));
+    items
+}
+
+/// Extract the public API of a library in a git tree,
+/// `root` is the directory of its `Cargo.toml` in the tree, such as `crates/foo/`.
+pub(crate) fn extract_api_from_tree(
+    repo: &Repository,
+    tree: &Tree,
+    root: &str,
+    cargo_toml: &Manifest,
+    crate_name: &str,
+    version: &str,
+) -> Vec<ApiItem> {
+    let lib_path = cargo_toml
+        .lib
+        .as_ref()
+        .and_then(|l| l.path.as_deref())
+        .unwrap_or("src/lib.rs");
+    let read = |path: &str| {
+        let entry = tree
+            .get_path(Path::new(&format!("{}{}", root, path)))
+            .ok()?;
+        let object = entry.to_object(repo).ok()?;
+        let blob = object.as_blob()?;
+        Some(String::from_utf8_lossy(blob.content()).into_owned())
+    };
+    extract_api(crate_name, version, lib_path, read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_extract_api() {
+        let files: HashMap<&str, &str> = HashMap::from([
+            (
+                "src/lib.rs",
+                r#"
+                pub mod a;
+                mod private;
+                /// A struct.
+                pub struct S { pub x: u32, y: u32 }
+                impl S { pub fn new() -> Self { todo!() } fn hidden(&self) {} }
+                impl Clone for S { fn clone(&self) -> Self { todo!() } }
+                pub trait T { fn required(&self); fn provided(&self) {} }
+                #[doc(hidden)]
+                pub fn internal() {}
+                "#,
+            ),
+            ("src/a.rs", "pub fn f(x: u8) -> u8 { x }"),
+            ("src/private.rs", "pub fn g() {}"),
+        ]);
+        let items = extract_api("foo-bar", "1.0.0", "src/lib.rs", |p| {
+            files.get(p).map(|s| s.to_string())
+        });
+        let paths: Vec<(&str, &str)> = items
+            .iter()
+            .map(|i| (i.path.as_str(), i.kind.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("foo_bar::a", "mod"),
+                ("foo_bar::a::f", "fn"),
+                ("foo_bar::S", "struct"),
+                ("foo_bar::S::x", "field"),
+                ("foo_bar::S::new", "method"),
+                ("foo_bar::<impl Clone for S>", "impl"),
+                ("foo_bar::T", "trait"),
+                ("foo_bar::T::required", "trait_fn"),
+                ("foo_bar::T::provided", "trait_provided_fn"),
+            ]
+        );
+        assert!(items[2].signature.ends_with("/* private fields */ }"));
+        assert!(items[2].documented);
+        assert!(!items[3].documented);
+    }
+}
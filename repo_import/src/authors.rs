@@ -0,0 +1,147 @@
+//! The `authors` of the packages as person vertices, deduplicated by the email,
+//! or by the name if there's no email, with `author_of` edges to the programs.
+//!
+//! An author whose email is a git contributor's, such as `Alice <alice@example.com>` who
+//! committed as `alice@example.com`, is linked to the contributor by `is_contributor`.
+
+use crate::git_history::normalize_email;
+use crate::ImportContext;
+use model::tugraph_model::{AuthorOf, IsContributor, Person};
+use std::collections::{HashMap, HashSet};
+
+/// An entry of `authors`, such as `Alice <alice@example.com>`, `Alice` or `<alice@example.com>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Author {
+    name: Option<String>,
+    email: Option<String>,
+}
+
+impl Author {
+    fn parse(entry: &str) -> Option<Self> {
+        let entry = entry.trim();
+        let (name, email) = match entry.split_once('<') {
+            Some((name, rest)) => (name, Some(rest.trim_end_matches('>'))),
+            // a bare email
+            None if entry.contains('@') && !entry.contains(' ') => ("", Some(entry)),
+            None => (entry, None),
+        };
+        let name = Some(name.trim()).filter(|n| !n.is_empty());
+        let email = email.map(normalize_email).filter(|e| e.contains('@'));
+        if name.is_none() && email.is_none() {
+            return None;
+        }
+        Some(Author {
+            name: name.map(String::from),
+            email,
+        })
+    }
+
+    fn id(&self) -> String {
+        match (&self.email, &self.name) {
+            (Some(email), _) => format!("author/{}", email),
+            (None, Some(name)) => format!("author/{}", name.to_lowercase()),
+            (None, None) => unreachable!("an author has a name or an email"),
+        }
+    }
+}
+
+impl ImportContext {
+    /// Rebuild the authors of the programs, and link them to the git contributors.
+    pub(crate) fn import_authors(&mut self) {
+        let mut authors: HashMap<String, Person> = HashMap::new();
+        let mut author_of = vec![];
+        for program in &self.programs {
+            let Some(entries) = &program.authors else {
+                continue;
+            };
+            // the entries are joined by commas
+            let mut seen = HashSet::new();
+            for author in entries.split(',').filter_map(Author::parse) {
+                let id = author.id();
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                let person = authors.entry(id.clone()).or_insert_with(|| Person {
+                    id: id.clone(),
+                    login: author
+                        .name
+                        .clone()
+                        .or(author.email.clone())
+                        .unwrap_or_default(),
+                    name: None,
+                    url: None,
+                    avatar: None,
+                    email: author.email.clone(),
+                });
+                // the name from any of the packages
+                if person.name.is_none() {
+                    person.name = author.name;
+                }
+                author_of.push(AuthorOf {
+                    SRC_ID: id,
+                    DST_ID: program.id.clone(),
+                });
+            }
+        }
+
+        let contributors: HashMap<&str, &str> = self
+            .contributors
+            .iter()
+            .map(|c| (c.id.as_str(), c.name.as_str()))
+            .collect();
+        let mut is_contributor = vec![];
+        for person in authors.values_mut() {
+            let Some(email) = &person.email else {
+                continue;
+            };
+            if let Some(name) = contributors.get(email.as_str()) {
+                if person.name.is_none() {
+                    person.name = Some(name.to_string());
+                }
+                is_contributor.push(IsContributor {
+                    SRC_ID: person.id.clone(),
+                    DST_ID: email.clone(),
+                });
+            }
+        }
+
+        self.authors = authors.into_values().collect();
+        self.authors.sort_by(|a, b| a.id.cmp(&b.id));
+        self.author_of = author_of;
+        self.is_contributor = is_contributor;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_author() {
+        let author = |name: Option<&str>, email: Option<&str>| Author {
+            name: name.map(String::from),
+            email: email.map(String::from),
+        };
+        assert_eq!(
+            Author::parse(" Alice Liddell <Alice@Example.com>"),
+            Some(author(Some("Alice Liddell"), Some("alice@example.com")))
+        );
+        assert_eq!(
+            Author::parse("<123+bob@users.noreply.github.com>"),
+            Some(author(None, Some("bob@users.noreply.github.com")))
+        );
+        assert_eq!(
+            Author::parse("carol@example.com"),
+            Some(author(None, Some("carol@example.com")))
+        );
+        assert_eq!(
+            Author::parse("The Rust Project Developers"),
+            Some(author(Some("The Rust Project Developers"), None))
+        );
+        assert_eq!(Author::parse(" "), None);
+        assert_eq!(
+            Author::parse("Dave <dave>").map(|a| a.id()),
+            Some("author/dave".to_string())
+        );
+    }
+}
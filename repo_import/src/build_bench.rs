@@ -0,0 +1,215 @@
+//! Measure how heavy a dependency is: the wall time of a clean build of a scratch project
+//! depending only on the latest version of a library, and the number of units compiled,
+//! i.e. the crates and build scripts of its dependency tree.
+//!
+//! It's opt-in, only the libraries matching the comma separated globs in
+//! `IMPORT_BUILD_BENCH_CRATES` are built, such as `tokio,serde*`. Each one is built in a
+//! [`crate::sandbox`] in a temporary directory, with its own cargo home and target directory,
+//! so nothing is cached between the builds. The sources are fetched before the timer starts.
+//! A build taking more than `IMPORT_BUILD_BENCH_TIMEOUT_SECS` (900 by default) is killed and
+//! not recorded.
+
+use crate::discovery_filter::PatternSet;
+use crate::sandbox::{fetch_command, sandbox, Sandbox};
+use crate::ImportContext;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 900;
+
+/// The name of the scratch project, which is not counted as a dependency unit.
+const BENCH_PACKAGE: &str = "crates-pro-build-bench";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BuildMeasurement {
+    pub(crate) wall_time_ms: i64,
+    pub(crate) units: i64,
+}
+
+/// The libraries to build, none if `IMPORT_BUILD_BENCH_CRATES` is not set.
+fn bench_crates() -> Option<PatternSet> {
+    let patterns = env::var("IMPORT_BUILD_BENCH_CRATES").ok()?;
+    let patterns: Vec<&str> = patterns
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+    if patterns.is_empty() {
+        return None;
+    }
+    match PatternSet::new(&patterns, &[]) {
+        Ok(crates) => Some(crates),
+        Err(e) => {
+            tracing::error!("Invalid IMPORT_BUILD_BENCH_CRATES: {}", e);
+            None
+        }
+    }
+}
+
+fn bench_manifest(name: &str, version: &str) -> String {
+    format!(
+        "[package]\n\
+         name = \"{}\"\n\
+         version = \"0.0.0\"\n\
+         edition = \"2021\"\n\
+         publish = false\n\
+         \n\
+         [dependencies]\n\
+         {} = \"={}\"\n\
+         \n\
+         [workspace]\n",
+        BENCH_PACKAGE, name, version
+    )
+}
+
+/// The units compiled by a build, by its `--message-format=json` output,
+/// without the scratch project.
+fn count_units(output: &str) -> i64 {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|message| message["reason"] == "compiler-artifact")
+        .filter(|message| message["target"]["name"] != BENCH_PACKAGE.replace('-', "_"))
+        .count() as i64
+}
+
+/// Build a scratch project depending on `name = "=version"` in the temporary `dir`.
+async fn measure_build(
+    sandbox: &Sandbox,
+    dir: &Path,
+    name: &str,
+    version: &str,
+    timeout: Duration,
+) -> Result<BuildMeasurement, String> {
+    fs::create_dir_all(dir.join("src")).map_err(|e| e.to_string())?;
+    fs::write(dir.join("Cargo.toml"), bench_manifest(name, version)).map_err(|e| e.to_string())?;
+    fs::write(dir.join("src").join("lib.rs"), "").map_err(|e| e.to_string())?;
+    let manifest_path = dir.join("Cargo.toml");
+    let target_dir = dir.join("target");
+
+    let fetch = Command::from(fetch_command(dir, None, &manifest_path))
+        .kill_on_drop(true)
+        .output();
+    match tokio::time::timeout(timeout, fetch).await {
+        Ok(Ok(output)) if output.status.success() => {}
+        Ok(Ok(output)) => return Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+        Ok(Err(e)) => return Err(e.to_string()),
+        Err(_) => return Err("timed out to fetch".to_string()),
+    }
+
+    let start = Instant::now();
+    let mut build = sandbox.command(dir, timeout);
+    build
+        .args([
+            "cargo",
+            "build",
+            "--offline",
+            "--message-format=json",
+            "--target-dir",
+        ])
+        .arg(&target_dir)
+        .arg("--manifest-path")
+        .arg(&manifest_path);
+    let build = Command::from(build)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(timeout, build).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(e.to_string()),
+        Err(_) => return Err(format!("timed out after {}s", timeout.as_secs())),
+    };
+    let wall_time_ms = start.elapsed().as_millis() as i64;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(BuildMeasurement {
+        wall_time_ms,
+        units: count_units(&String::from_utf8_lossy(&output.stdout)),
+    })
+}
+
+impl ImportContext {
+    /// Measure the builds of the latest versions of the libraries in `IMPORT_BUILD_BENCH_CRATES`,
+    /// the versions measured before are skipped.
+    pub(crate) async fn measure_builds(&mut self) {
+        let Some(crates) = bench_crates() else {
+            return;
+        };
+        let Some(sandbox) = sandbox() else {
+            return;
+        };
+        let timeout = Duration::from_secs(
+            env::var("IMPORT_BUILD_BENCH_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or(DEFAULT_TIMEOUT_SECS),
+        );
+        let latest: Vec<(String, String)> = self
+            .programs
+            .iter()
+            .filter(|p| crates.matches(&p.name))
+            .filter_map(|p| Some((p.name.clone(), p.max_version.clone()?)))
+            .collect();
+        tracing::info!("Start to measure the builds of {} libraries", latest.len());
+
+        let mut measured = 0;
+        for (name, version) in latest {
+            let Some(index) = self
+                .library_versions
+                .iter()
+                .position(|v| v.name == name && v.version == version)
+            else {
+                continue;
+            };
+            if self.library_versions[index].build_time_ms.is_some() {
+                continue;
+            }
+            let dir = match tempfile::tempdir() {
+                Ok(dir) => dir,
+                Err(e) => {
+                    tracing::error!("Failed to create a temporary directory: {}", e);
+                    return;
+                }
+            };
+            match measure_build(sandbox, dir.path(), &name, &version, timeout).await {
+                Ok(measurement) => {
+                    tracing::info!(
+                        "Built {}/{} in {}ms, {} units",
+                        name,
+                        version,
+                        measurement.wall_time_ms,
+                        measurement.units
+                    );
+                    let library_version = &mut self.library_versions[index];
+                    library_version.build_time_ms = Some(measurement.wall_time_ms);
+                    library_version.build_units = Some(measurement.units);
+                    measured += 1;
+                }
+                Err(e) => tracing::warn!("Failed to build {}/{}: {}", name, version, e),
+            }
+        }
+        tracing::info!("Finish to measure the builds of {} libraries", measured);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_units() {
+        let output = r#"{"reason":"compiler-artifact","package_id":"registry+https://github.com/rust-lang/crates.io-index#proc-macro2@1.0.86","target":{"kind":["custom-build"],"name":"build-script-build"}}
+{"reason":"build-script-executed","package_id":"registry+https://github.com/rust-lang/crates.io-index#proc-macro2@1.0.86"}
+{"reason":"compiler-artifact","package_id":"registry+https://github.com/rust-lang/crates.io-index#proc-macro2@1.0.86","target":{"kind":["lib"],"name":"proc_macro2"}}
+{"reason":"compiler-artifact","package_id":"path+file:///tmp/bench#crates-pro-build-bench@0.0.0","target":{"kind":["lib"],"name":"crates_pro_build_bench"}}
+{"reason":"build-finished","success":true}"#;
+        assert_eq!(count_units(output), 2);
+        assert!(bench_manifest("serde", "1.0.203").contains("serde = \"=1.0.203\""));
+    }
+}
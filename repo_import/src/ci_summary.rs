@@ -0,0 +1,180 @@
+//! Summarize the CI of a repo from its configs at HEAD: the CI services, the toolchains
+//! tested, such as `1.63.0` or `nightly`, and the targets built, such as `wasm32-wasi`.
+
+use crate::msrv::{rust_versions_in, toolchain_lines, CI_CONFIGS};
+use crate::ImportContext;
+use model::tugraph_model::{CiSummary, HasCi};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// The architectures which the target triples start with
+const TARGET_ARCHS: &[&str] = &[
+    "x86_64",
+    "i686",
+    "i586",
+    "aarch64",
+    "arm",
+    "armv7",
+    "thumbv",
+    "wasm32",
+    "riscv",
+    "mips",
+    "powerpc",
+    "s390x",
+    "sparc",
+    "loongarch64",
+];
+
+fn provider_of(config: &str) -> &'static str {
+    match config {
+        ".travis.yml" => "travis",
+        ".gitlab-ci.yml" => "gitlab-ci",
+        "appveyor.yml" | ".appveyor.yml" => "appveyor",
+        "azure-pipelines.yml" => "azure-pipelines",
+        ".circleci/config.yml" => "circleci",
+        _ => "github-actions",
+    }
+}
+
+/// The CI configs of a repo, by the CI service.
+pub(crate) fn read_ci_configs(repo_path: &Path) -> Vec<(&'static str, String)> {
+    let mut configs: Vec<(&str, String)> = CI_CONFIGS
+        .iter()
+        .filter_map(|config| {
+            let content = fs::read_to_string(repo_path.join(config)).ok()?;
+            Some((provider_of(config), content))
+        })
+        .collect();
+    if let Ok(entries) = fs::read_dir(repo_path.join(".github/workflows")) {
+        let mut workflows: Vec<_> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                p.extension()
+                    .is_some_and(|ext| ext == "yml" || ext == "yaml")
+            })
+            .collect();
+        workflows.sort();
+        for workflow in workflows {
+            if let Ok(content) = fs::read_to_string(workflow) {
+                configs.push(("github-actions", content));
+            }
+        }
+    }
+    configs
+}
+
+fn tokens(line: &str) -> impl Iterator<Item = &str> {
+    line.split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')))
+        .map(|token| token.trim_end_matches('.'))
+        .filter(|token| !token.is_empty())
+}
+
+/// The rust versions and the channels, such as `stable` and `nightly-2024-01-01`.
+fn toolchains_in(config: &str) -> BTreeSet<String> {
+    let mut toolchains = BTreeSet::new();
+    for line in toolchain_lines(config) {
+        toolchains.extend(rust_versions_in(line).iter().map(|v| v.to_string()));
+        toolchains.extend(
+            tokens(line)
+                .filter(|t| {
+                    matches!(*t, "stable" | "beta" | "nightly") || t.starts_with("nightly-")
+                })
+                .map(String::from),
+        );
+    }
+    toolchains
+}
+
+/// The target triples, such as `x86_64-pc-windows-msvc` and `wasm32-wasi`.
+fn targets_in(config: &str) -> BTreeSet<String> {
+    tokens(config)
+        .filter(|token| {
+            let Some((arch, rest)) = token.split_once('-') else {
+                return false;
+            };
+            let arch_known = TARGET_ARCHS.iter().any(|a| arch.starts_with(a));
+            // `wasm32-wasi` has only two parts
+            arch_known && (arch == "wasm32" || rest.contains('-'))
+        })
+        .map(String::from)
+        .collect()
+}
+
+fn join(values: BTreeSet<String>) -> Option<String> {
+    (!values.is_empty()).then(|| values.into_iter().collect::<Vec<_>>().join(","))
+}
+
+/// The CI summary of a repo, none if it has no CI configs.
+pub(crate) fn ci_summary(namespace: &str, repo_path: &Path) -> Option<CiSummary> {
+    let configs = read_ci_configs(repo_path);
+    if configs.is_empty() {
+        return None;
+    }
+    let providers: BTreeSet<String> = configs.iter().map(|(p, _)| p.to_string()).collect();
+    let toolchains = configs.iter().flat_map(|(_, c)| toolchains_in(c)).collect();
+    let targets = configs.iter().flat_map(|(_, c)| targets_in(c)).collect();
+    Some(CiSummary {
+        id: namespace.to_string(),
+        providers: join(providers).unwrap_or_default(),
+        toolchains: join(toolchains),
+        targets: join(targets),
+    })
+}
+
+impl ImportContext {
+    /// Add the CI summary of a repo for the programs in it, replacing the one of an
+    /// earlier import.
+    pub(crate) fn import_ci_summary(
+        &mut self,
+        repo_path: &Path,
+        namespace: Option<&str>,
+        program_ids: &[String],
+    ) {
+        let Some(namespace) = namespace else {
+            return;
+        };
+        let Some(summary) = ci_summary(namespace, repo_path) else {
+            return;
+        };
+        self.ci_summaries.retain(|s| s.id != namespace);
+        self.has_ci.retain(|e| e.DST_ID != namespace);
+        self.ci_summaries.push(summary);
+        for program_id in program_ids {
+            self.has_ci.push(HasCi {
+                SRC_ID: program_id.clone(),
+                DST_ID: namespace.to_string(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ci_summary() {
+        let workflow = r#"
+jobs:
+  test:
+    strategy:
+      matrix:
+        rust: [1.63, stable, nightly]
+        target: [x86_64-unknown-linux-gnu, wasm32-wasi]
+    runs-on: ubuntu-latest
+    steps:
+      - uses: dtolnay/rust-toolchain@nightly-2024-05-01
+      - run: cargo build --target aarch64-apple-darwin
+"#;
+        assert_eq!(
+            join(toolchains_in(workflow)).as_deref(),
+            Some("1.63.0,nightly,nightly-2024-05-01,stable")
+        );
+        assert_eq!(
+            join(targets_in(workflow)).as_deref(),
+            Some("aarch64-apple-darwin,wasm32-wasi,x86_64-unknown-linux-gnu")
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Clone the repos of a url list, so that they can be imported
+//! without being cloned in advance.
+//!
+//! The clones are shallow by default, which is enough for the versions
+//! since the tags are fetched. Set the depth to 0 for the full history,
+//! which the contributors and the activity metrics need.
+//!
+//! The private repos are cloned with the credentials in [`crate::credentials`].
+//! The local paths and `file://` urls in the list are imported in place.
+
+use crate::credentials::Credentials;
+use crate::error::ImportError;
+use crate::local::local_path;
+use crate::report::record_repo_failed;
+use crate::retry::{is_transient_git, RetryPolicy};
+use crate::stage_progress::StageProgress;
+use crate::utils::{extract_namespace, insert_namespace_by_repo_path};
+use crate::ImportContext;
+use futures::stream::{self, StreamExt};
+use git2::build::RepoBuilder;
+use git2::{AutotagOption, FetchOptions, Repository, ResetType};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct CloneOptions {
+    /// the number of commits fetched from each ref, 0 for the full history
+    pub depth: i32,
+    /// the number of repos cloned at the same time
+    pub concurrency: usize,
+    pub credentials: Credentials,
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        CloneOptions {
+            depth: 1,
+            concurrency: 4,
+            credentials: Credentials::default(),
+        }
+    }
+}
+
+impl CloneOptions {
+    /// Read `CLONE_DEPTH`, `CLONE_CONCURRENCY` and `CLONE_CREDENTIALS`.
+    pub fn from_env() -> Self {
+        let default = CloneOptions::default();
+        CloneOptions {
+            depth: env::var("CLONE_DEPTH")
+                .ok()
+                .and_then(|d| d.parse().ok())
+                .unwrap_or(default.depth),
+            concurrency: env::var("CLONE_CONCURRENCY")
+                .ok()
+                .and_then(|c| c.parse().ok())
+                .filter(|c| *c > 0)
+                .unwrap_or(default.concurrency),
+            credentials: Credentials::from_env(),
+        }
+    }
+}
+
+/// Read the urls in a file, one per line, skipping the blank lines and `#` comments.
+pub fn read_url_list(path: &Path) -> Result<Vec<String>, ImportError> {
+    Ok(fs::read_to_string(path)
+        .map_err(ImportError::io(path))?
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+fn fetch_options(url: &str, depth: i32, credentials: &Credentials) -> FetchOptions<'static> {
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.download_tags(AutotagOption::All);
+    fetch_options.remote_callbacks(credentials.remote_callbacks(url));
+    if depth > 0 {
+        fetch_options.depth(depth);
+    }
+    fetch_options
+}
+
+/// Clone a repo into `path`, or fetch it and reset to the remote HEAD if it's already cloned,
+/// so that the repos changed since the last import are found by their HEAD.
+///
+/// The transient network errors are retried by [`RetryPolicy`].
+pub(crate) fn clone_repo(
+    url: &str,
+    path: &Path,
+    depth: i32,
+    credentials: &Credentials,
+) -> Result<(), git2::Error> {
+    let retry = RetryPolicy::from_env();
+    if path.join(".git").is_dir() {
+        tracing::info!("Directory {:?} is already cloned, fetching it", path);
+        let repo = Repository::open(path)?;
+        retry.retry_blocking(&format!("Fetching {}", url), is_transient_git, || {
+            repo.find_remote("origin")?.fetch(
+                &["HEAD"],
+                Some(&mut fetch_options(url, depth, credentials)),
+                None,
+            )
+        })?;
+        let fetch_head = repo.find_reference("FETCH_HEAD")?.peel_to_commit()?;
+        repo.reset(fetch_head.as_object(), ResetType::Hard, None)?;
+        return Ok(());
+    }
+    // a failed clone removes the directory it creates, so it can be cloned again
+    retry.retry_blocking(&format!("Cloning {}", url), is_transient_git, || {
+        RepoBuilder::new()
+            .fetch_options(fetch_options(url, depth, credentials))
+            .clone(url, path)
+            .map(|_| ())
+    })
+}
+
+/// Clone the repos into `dir/<namespace>`, return the `(url, path)` of the cloned ones.
+#[tracing::instrument(name = "clone", skip_all, fields(repos = urls.len()))]
+pub async fn clone_repos(
+    urls: &[String],
+    dir: &Path,
+    options: &CloneOptions,
+) -> Vec<(String, PathBuf)> {
+    let progress = Arc::new(StageProgress::new("clone", "repos", Some(urls.len())));
+    // collected, so that the stream owns the tasks rather than borrowing `dir`
+    let tasks: Vec<_> = urls
+        .iter()
+        .filter_map(|url| {
+            let namespace = match extract_namespace(url) {
+                Ok(namespace) => namespace,
+                Err(e) => {
+                    tracing::error!("Failed to parse url {}: {}", url, e);
+                    record_repo_failed(url, &e);
+                    progress.fail();
+                    return None;
+                }
+            };
+            let progress = Arc::clone(&progress);
+            let url = url.clone();
+            let path = dir.join(namespace);
+            let depth = options.depth;
+            let credentials = options.credentials.clone();
+            Some(async move {
+                let task_url = url.clone();
+                let task_path = path.clone();
+                let span = tracing::info_span!("clone_repo", repo = %url);
+                let res = tokio::task::spawn_blocking(move || {
+                    span.in_scope(|| clone_repo(&task_url, &task_path, depth, &credentials))
+                })
+                .await;
+                match res {
+                    Ok(Ok(())) => {
+                        progress.inc();
+                        Some((url, path))
+                    }
+                    Ok(Err(e)) => {
+                        tracing::error!("Failed to clone {}: {}", url, e);
+                        record_repo_failed(&url, &e);
+                        progress.fail();
+                        None
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to clone {}: {}", url, e);
+                        record_repo_failed(&url, &e);
+                        progress.fail();
+                        None
+                    }
+                }
+            })
+        })
+        .collect();
+
+    let repos = stream::iter(tasks)
+        .buffer_unordered(options.concurrency)
+        .filter_map(|res| async move { res })
+        .collect()
+        .await;
+    progress.finish();
+    repos
+}
+
+impl ImportContext {
+    /// Clone a repo into `dir` and import it, or import it in place if it's local.
+    pub async fn import_repo(
+        &mut self,
+        url: &str,
+        dir: &Path,
+        options: &CloneOptions,
+    ) -> Result<(), ImportError> {
+        if local_path(url).is_some() {
+            return self.import_local(url).await;
+        }
+        let namespace = extract_namespace(url)?;
+        let path = dir.join(&namespace);
+        let task_url = url.to_string();
+        let task_path = path.clone();
+        let depth = options.depth;
+        let credentials = options.credentials.clone();
+        let span = tracing::info_span!("clone_repo", repo = %url);
+        tokio::task::spawn_blocking(move || {
+            span.in_scope(|| clone_repo(&task_url, &task_path, depth, &credentials))
+        })
+        .await
+        .map_err(|e| ImportError::Repo {
+            url: url.to_string(),
+            message: e.to_string(),
+        })?
+        .map_err(ImportError::git(url))?;
+
+        insert_namespace_by_repo_path(path.to_string_lossy().to_string(), namespace);
+        self.parse_a_local_repo_and_return_new_versions(path, url.to_string())
+            .await?;
+        Ok(())
+    }
+
+    /// Clone the repos in a url list into `dir`, and import them.
+    #[tracing::instrument(name = "import", skip_all, fields(list = ?list))]
+    pub async fn import_from_url_list(
+        &mut self,
+        list: &Path,
+        dir: &Path,
+        options: CloneOptions,
+    ) -> Result<(), ImportError> {
+        let (local, urls): (Vec<String>, Vec<String>) = read_url_list(list)?
+            .into_iter()
+            .partition(|url| local_path(url).is_some());
+        // a bad repo is skipped, and the others are still imported
+        for source in local {
+            if let Err(e) = self.import_local(&source).await {
+                tracing::error!("Failed to import {}: {}", source, e);
+                record_repo_failed(&source, &e);
+            }
+        }
+        // the repos imported before the run is interrupted
+        let urls: Vec<String> = urls
+            .into_iter()
+            .filter(|url| !self.progress.is_done(url))
+            .collect();
+
+        tracing::info!("Start to clone {} repos into {:?}", urls.len(), dir);
+        let repos = clone_repos(&urls, dir, &options).await;
+        tracing::info!("Cloned {} of {} repos", repos.len(), urls.len());
+
+        let progress =
+            StageProgress::new("import", "repos", Some(repos.len())).with_items("crates");
+        for (url, path) in repos {
+            let namespace = match extract_namespace(&url) {
+                Ok(namespace) => namespace,
+                Err(e) => {
+                    tracing::error!("Failed to parse url {}: {}", url, e);
+                    record_repo_failed(&url, &e);
+                    progress.fail();
+                    continue;
+                }
+            };
+            insert_namespace_by_repo_path(path.to_string_lossy().to_string(), namespace);
+            let programs = self.programs.len();
+            match self
+                .parse_a_local_repo_and_return_new_versions(path, url.clone())
+                .await
+            {
+                Ok(_) => {
+                    self.mark_repo_done(&url).await;
+                    progress.add_items(self.programs.len().saturating_sub(programs));
+                    progress.inc();
+                }
+                Err(e) => {
+                    tracing::error!("Failed to import {}: {}", url, e);
+                    record_repo_failed(&url, &e);
+                    progress.fail();
+                }
+            }
+        }
+        progress.finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local::init_crate_repo;
+
+    #[tokio::test]
+    async fn test_import_from_url_list_with_a_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repos/ok");
+        init_crate_repo(&repo, "url_list_ok");
+        let missing = dir.path().join("repos/missing");
+        let list = dir.path().join("urls.txt");
+        fs::write(
+            &list,
+            format!("file://{}\n{}\n", missing.display(), repo.display()),
+        )
+        .unwrap();
+
+        let mut context = ImportContext::default();
+        context
+            .import_from_url_list(&list, &dir.path().join("clones"), CloneOptions::default())
+            .await
+            .unwrap();
+        // the failing url is recorded, and the next one still imported
+        assert!(context.programs.iter().any(|p| p.name == "url_list_ok"));
+        assert!(context
+            .import_report()
+            .failed_repos
+            .iter()
+            .any(|f| f.url.ends_with("repos/missing")));
+    }
+}
@@ -1,110 +1,424 @@
 use crate::{
-    utils::{get_namespace_by_repo_path, insert_program_by_name},
+    discovery_filter::discovery_filter,
+    error::ImportError,
+    license_detect::detect_license,
+    loc_metrics::count_loc,
+    lockfile::{generate_lockfiles, locked_dependencies, read_lockfile, GeneratedLockfiles},
+    manifest::{join_relative, workspace_members, BuildScript, DependencySpec, Manifest, Readme},
+    metrics::PARSE_FAILURES,
+    msrv::msrv_from_manifest,
+    readme::read_readme,
+    report::record_skipped_manifest,
+    std_support::detect_std_support,
+    targets::package_targets,
+    unsafe_metrics::count_unsafe,
+    utils::{generate_program_id, get_namespace_by_repo_path, insert_program_by_name},
+    version_info::Dependencies,
+    wasm_support::detect_wasm_support,
     Licenses,
 };
-use model::tugraph_model::{Application, HasType, Library, Program, UProgram};
+use model::general_model::{
+    Dependency, DependencyKind, DependencyOverride, DependencySource, OverrideKind, TargetKind,
+};
+use model::tugraph_model::{Application, HasType, Library, Program, Target, UProgram};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use std::{
-    fs,
+    collections::HashSet,
+    env, fs,
     path::{Path, PathBuf},
 };
-use toml::Value;
-use uuid::Uuid;
-use walkdir::WalkDir;
+use walkdir::{DirEntry, WalkDir};
+
+/// The directories that never contain the crates of the repo itself
+const EXCLUDED_DIRS: &[&str] = &["target", "node_modules", "vendor"];
+
+/// The thread pool to parse the manifests of a repo,
+/// with `IMPORT_WORKER_THREADS` threads (the number of CPUs by default).
+static PARSE_POOL: Lazy<ThreadPool> = Lazy::new(|| {
+    let num_threads = env::var("IMPORT_WORKER_THREADS")
+        .ok()
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(0);
+    ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .thread_name(|i| format!("repo-import-{}", i))
+        .build()
+        .expect("Failed to build the thread pool")
+});
+
+/// All the info extracted from a Cargo.toml
+struct ParsedCrate {
+    name: String,
+    program: Program,
+    has_type: HasType,
+    uprogram: UProgram,
+    license: Licenses,
+    dependencies: Option<Dependencies>,
+    targets: Vec<Target>,
+}
 
 // Given a project path, parse the metadata
 pub(crate) async fn extract_info_local(
     local_repo_path: PathBuf,
     git_url: String,
     lic: &mut Vec<Licenses>,
+    deps: &mut Vec<Dependencies>,
+    targets: &mut Vec<Target>,
 ) -> Vec<(Program, HasType, UProgram)> {
     let mut res = vec![];
 
-    // walk the directories of the project
-    for entry in WalkDir::new(local_repo_path.clone())
+    // walk the directories of the project, filtered by `CRATE_DISCOVERY_*_PATHS`,
+    // the depth is unlimited unless `CRATE_DISCOVERY_MAX_DEPTH` is set.
+    let max_depth = env::var("CRATE_DISCOVERY_MAX_DEPTH")
+        .ok()
+        .and_then(|d| d.parse::<usize>().ok())
+        .unwrap_or(usize::MAX);
+    let cargo_toml_paths: Vec<PathBuf> = WalkDir::new(local_repo_path.clone())
+        .max_depth(max_depth)
         .into_iter()
+        .filter_entry(|e| {
+            e.depth() == 0
+                || !(is_excluded_dir(e)
+                    || discovery_filter()
+                        .is_path_excluded(&relative_path(e.path(), &local_repo_path)))
+        })
         .filter_map(|x| x.ok())
-    {
-        let entry_path = entry.path();
-
         // if entry is Cargo.toml, ...
-        if entry_path.file_name().and_then(|n| n.to_str()) == Some("Cargo.toml") {
-            tracing::trace!("entry_path: {:?}", entry_path);
-            let crate_name_result = parse_crate_name(entry_path).await;
-            match crate_name_result {
-                Ok(name) => {
-                    tracing::trace!("package name: {}", name);
-                    let islib_result = is_crate_lib(
-                        entry_path
-                            .to_str()
-                            .unwrap()
-                            .strip_suffix("Cargo.toml")
-                            .unwrap(),
-                    )
-                    .await;
-                    let islib = match islib_result {
-                        Ok(islib) => islib,
-                        Err(e) => {
-                            tracing::error!("parse error: {}", e);
-                            continue;
-                        }
-                    };
-
-                    tracing::debug!("Found Crate: {}, islib: {}", name, islib);
-                    let id = Uuid::new_v4().to_string();
-                    let mut program = from_cargo_toml(
-                        local_repo_path.clone(),
-                        entry_path.to_path_buf(),
-                        &id,
-                        lic,
+        .filter(|e| e.path().file_name().and_then(|n| n.to_str()) == Some("Cargo.toml"))
+        .filter(|e| {
+            discovery_filter().is_manifest_included(&relative_path(e.path(), &local_repo_path))
+        })
+        .map(|e| e.into_path())
+        .collect();
+    let cargo_toml_paths = with_workspace_members(cargo_toml_paths, &local_repo_path);
+    let lockfiles = generate_lockfiles(&cargo_toml_paths, &local_repo_path).await;
+
+    // the same crate in the same repo always gets the same id,
+    // so that re-importing a repo does not duplicate the nodes.
+    let namespace = get_namespace_by_repo_path(local_repo_path.to_str().unwrap())
+        .unwrap_or_else(|| git_url.clone());
+
+    // parse the manifests in parallel, the order is preserved.
+    // the pool blocks until the whole repo is parsed, so it's waited for in a blocking task.
+    let parse = tokio::task::spawn_blocking(move || {
+        PARSE_POOL.install(|| {
+            cargo_toml_paths
+                .par_iter()
+                .filter_map(|entry_path| {
+                    parse_a_crate(
+                        entry_path,
+                        &local_repo_path,
+                        &namespace,
+                        &git_url,
+                        &lockfiles,
                     )
-                    .await
-                    .unwrap();
-                    /*let mut name2 = "".to_string();
-                    if program.name.is_empty() {
-                        if let Some(namespace) = program.namespace.clone() {
-                            let namespace_str = namespace.as_str();
-                            let parts: Vec<&str> = namespace_str.split('/').collect();
-                            if parts.len() == 2 {
-                                program.name = parts[1].to_string();
-                                name2 = parts[1].to_string();
-                            }
-                        }
-                    }
-                    program.mega_url = Some(git_url.clone());
-                    let uprogram = if islib {
-                        if program.name == "" {
-                            UProgram::Library(Library::new(&id.to_string(), &name2, -1, None))
-                        } else {
-                            UProgram::Library(Library::new(&id.to_string(), &name, -1, None))
-                        }
-                    } else if program.name == "" {
-                        UProgram::Application(Application::new(id.to_string(), &name2))
-                    } else {
-                        UProgram::Application(Application::new(id.to_string(), &name))
-                    };*/
-                    program.mega_url = Some(git_url.clone());
-                    let uprogram = if islib {
-                        UProgram::Library(Library::new(&id.to_string(), &name, -1, None))
-                    } else {
-                        UProgram::Application(Application::new(id.to_string(), &name))
-                    };
-
-                    let has_type = HasType {
-                        SRC_ID: program.id.clone(),
-                        DST_ID: program.id.clone(),
-                    };
-
-                    tracing::trace!(
-                        "program: {:?}, has_type: {:?}, uprogram: {:?}",
-                        program,
-                        has_type,
-                        uprogram
-                    );
-                    insert_program_by_name(name.clone(), (program.clone(), uprogram.clone()));
-
-                    res.push((program, has_type, uprogram));
-                }
-                Err(e) => tracing::warn!("Error parsing name {}: {}", entry_path.display(), e),
+                })
+                .collect::<Vec<ParsedCrate>>()
+        })
+    });
+    let parsed_crates = match parse.await {
+        Ok(parsed_crates) => parsed_crates,
+        Err(e) => {
+            tracing::error!("Failed to parse the crates: {}", e);
+            PARSE_FAILURES.inc();
+            return res;
+        }
+    };
+
+    for parsed in parsed_crates {
+        insert_program_by_name(
+            parsed.name,
+            (parsed.program.clone(), parsed.uprogram.clone()),
+        );
+        lic.push(parsed.license);
+        if let Some(dependencies) = parsed.dependencies {
+            deps.push(dependencies);
+        }
+        targets.extend(parsed.targets);
+        res.push((parsed.program, parsed.has_type, parsed.uprogram));
+    }
+
+    res
+}
+
+/// Add the members of the workspaces in the repo, which may be deeper than
+/// `CRATE_DISCOVERY_MAX_DEPTH` or in the directories skipped by the walk.
+fn with_workspace_members(mut cargo_toml_paths: Vec<PathBuf>, repo_path: &Path) -> Vec<PathBuf> {
+    let mut known: HashSet<PathBuf> = cargo_toml_paths.iter().cloned().collect();
+    let members: Vec<PathBuf> = cargo_toml_paths
+        .iter()
+        .filter_map(|path| {
+            let manifest: Manifest = toml::from_str(&fs::read_to_string(path).ok()?).ok()?;
+            Some(workspace_members(path, &manifest.workspace?))
+        })
+        .flatten()
+        .collect();
+    for member in members {
+        let relative = relative_path(&member, repo_path);
+        let dir = relative
+            .trim_end_matches("Cargo.toml")
+            .trim_end_matches('/');
+        if !discovery_filter().is_path_excluded(dir)
+            && discovery_filter().is_manifest_included(&relative)
+            && known.insert(member.clone())
+        {
+            tracing::debug!("Found the workspace member {}", relative);
+            cargo_toml_paths.push(member);
+        }
+    }
+    cargo_toml_paths
+}
+
+fn parse_a_crate(
+    entry_path: &Path,
+    local_repo_path: &Path,
+    namespace: &str,
+    git_url: &str,
+    lockfiles: &GeneratedLockfiles,
+) -> Option<ParsedCrate> {
+    tracing::trace!("entry_path: {:?}", entry_path);
+    let name = match parse_crate_name(entry_path) {
+        Ok(Some(name)) => name,
+        Ok(None) => {
+            tracing::debug!("Skip the virtual manifest {}", entry_path.display());
+            return None;
+        }
+        Err(e) => {
+            tracing::warn!("Error parsing name {}: {}", entry_path.display(), e);
+            record_skipped_manifest(entry_path, &e);
+            PARSE_FAILURES.inc();
+            return None;
+        }
+    };
+    tracing::trace!("package name: {}", name);
+    if !discovery_filter().is_crate_included(&name) {
+        tracing::debug!("Skip crate {}, it's filtered out", name);
+        record_skipped_manifest(entry_path, "filtered out");
+        return None;
+    }
+
+    let target_kinds = match detect_target_kinds(entry_path.parent().unwrap()) {
+        Ok(target_kinds) => target_kinds,
+        Err(e) => {
+            tracing::error!("parse error: {}", e);
+            record_skipped_manifest(entry_path, &e);
+            PARSE_FAILURES.inc();
+            return None;
+        }
+    };
+    let islib = is_crate_lib(&target_kinds);
+
+    tracing::debug!("Found Crate: {}, islib: {}", name, islib);
+    let id = generate_program_id(namespace, &name);
+    let (mut program, license) = match from_cargo_toml(local_repo_path, entry_path, &id) {
+        Ok(x) => x,
+        Err(e) => {
+            tracing::error!("parse error: {}", e);
+            record_skipped_manifest(entry_path, &e);
+            PARSE_FAILURES.inc();
+            return None;
+        }
+    };
+    let kinds: Vec<&str> = target_kinds.iter().map(|k| k.as_str()).collect();
+    program.target_kinds = Some(kinds.join(","));
+    program.mega_url = Some(git_url.to_owned());
+    if env::var("IMPORT_UNSAFE_METRICS").is_ok_and(|v| v == "1") {
+        let metrics = count_unsafe(entry_path.parent().unwrap());
+        program.unsafe_blocks = Some(metrics.unsafe_blocks);
+        program.unsafe_fns = Some(metrics.unsafe_fns);
+        program.unsafe_impls = Some(metrics.unsafe_impls);
+        program.unsafe_traits = Some(metrics.unsafe_traits);
+    }
+    if env::var("IMPORT_LOC_METRICS").is_ok_and(|v| v == "1") {
+        let metrics = count_loc(entry_path.parent().unwrap());
+        program.rust_code_lines = Some(metrics.rust.code);
+        program.rust_comment_lines = Some(metrics.rust.comments);
+        program.rust_test_lines = Some(metrics.rust.tests);
+        program.languages = metrics.languages_summary();
+    }
+    let uprogram = if islib {
+        UProgram::Library(Library::new(&id.to_string(), &name, -1, None))
+    } else {
+        UProgram::Application(Application::new(id.to_string(), &name))
+    };
+
+    let has_type = HasType {
+        SRC_ID: program.id.clone(),
+        DST_ID: program.id.clone(),
+    };
+
+    tracing::trace!(
+        "program: {:?}, has_type: {:?}, uprogram: {:?}",
+        program,
+        has_type,
+        uprogram
+    );
+
+    let dependencies =
+        match parse_head_dependencies(entry_path, local_repo_path, &name, git_url, lockfiles) {
+            Ok(dependencies) => dependencies,
+            Err(e) => {
+                tracing::warn!("Error parsing dependencies {}: {}", entry_path.display(), e);
+                None
+            }
+        };
+
+    let targets = package_targets(&program.id, entry_path.parent().unwrap());
+
+    Some(ParsedCrate {
+        name,
+        program,
+        has_type,
+        uprogram,
+        license,
+        dependencies,
+        targets,
+    })
+}
+
+/// The path relative to the repo, with `/` as the separator.
+fn relative_path(path: &Path, repo_path: &Path) -> String {
+    path.strip_prefix(repo_path)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn is_excluded_dir(entry: &DirEntry) -> bool {
+    entry.file_type().is_dir()
+        && is_excluded_dir_name(entry.file_name().to_str().unwrap_or_default())
+}
+
+/// hidden directories (e.g. `.git`) and build/vendor directories are skipped
+pub(crate) fn is_excluded_dir_name(name: &str) -> bool {
+    name.starts_with('.') || EXCLUDED_DIRS.contains(&name)
+}
+
+/// The files of a crate, without the nested crates which have their own `Cargo.toml`.
+pub(crate) fn crate_files(crate_dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(crate_dir)
+        .into_iter()
+        .filter_entry(|e| {
+            e.depth() == 0
+                || !e.file_type().is_dir()
+                || !(is_excluded_dir(e) || e.path().join("Cargo.toml").is_file())
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect()
+}
+
+/// The package name, no matter lib or bin, or none for a virtual workspace manifest.
+fn parse_crate_name(path: &Path) -> Result<Option<String>, ImportError> {
+    let content = fs::read_to_string(path).map_err(ImportError::io(path))?;
+    let manifest: Manifest = toml::from_str(&content).map_err(ImportError::toml(path))?;
+    if manifest.is_virtual() {
+        return Ok(None);
+    }
+
+    manifest
+        .package
+        .map(|p| Some(p.name))
+        .ok_or_else(|| ImportError::Metadata {
+            path: path.to_path_buf(),
+            message: "Failed to find [package] or [workspace]".to_string(),
+        })
+}
+
+/// Parse the dependencies of a crate in the working tree (HEAD).
+///
+/// Returns `None` if the version is unknown or not a valid semver,
+/// since dependency edges are keyed by `name/version`.
+/// A package without a version is `0.0.0`, as cargo does.
+fn parse_head_dependencies(
+    path: &Path,
+    local_repo_path: &Path,
+    crate_name: &str,
+    git_url: &str,
+    lockfiles: &GeneratedLockfiles,
+) -> Result<Option<Dependencies>, ImportError> {
+    let manifest = Manifest::read(path, local_repo_path)?;
+
+    let version = match manifest.package.as_ref().and_then(|p| p.resolved_version()) {
+        Some(version) if semver::Version::parse(version).is_ok() => version.to_owned(),
+        _ => return Ok(None),
+    };
+
+    let mut dependencies = parse_dependencies(&manifest);
+    let mut overrides = parse_overrides(&manifest);
+    let crate_dir = relative_path(path.parent().unwrap_or(path), local_repo_path);
+    let read_manifest = |dir: &str| {
+        Manifest::read(
+            &local_repo_path.join(dir).join("Cargo.toml"),
+            local_repo_path,
+        )
+        .ok()
+    };
+    resolve_path_dependencies(&mut dependencies, &crate_dir, read_manifest);
+    resolve_path_dependencies(
+        overrides.iter_mut().map(|o| &mut o.replacement),
+        &crate_dir,
+        read_manifest,
+    );
+
+    let locked = read_lockfile(path.parent().unwrap_or(path), local_repo_path, lockfiles)
+        .map(|lockfile| locked_dependencies(&lockfile, crate_name, &version))
+        .unwrap_or_default();
+
+    Ok(Some(Dependencies {
+        crate_name: crate_name.to_owned(),
+        version,
+        dependencies,
+        overrides,
+        features: manifest.features.clone(),
+        git_url: git_url.to_owned(),
+        tag_name: "HEAD".to_owned(),
+        api: vec![],
+        msrv: msrv_from_manifest(&manifest),
+        commit: None,
+        commit_time: None,
+        published_at: None,
+        locked,
+        crate_size: None,
+        unpacked_size: None,
+        checksum: None,
+    }))
+}
+
+/// Parse the `[dependencies]`, `[dev-dependencies]` and `[build-dependencies]`
+/// tables of a Cargo.toml, and those of `[target.'cfg(...)']` with their platform.
+///
+/// Both `foo = "1.0"` and `foo = { version = "1.0", features = [...], optional = true }`
+/// are supported. A renamed dependency (`bar = { package = "foo", ... }`)
+/// is recorded under its real package name.
+///
+/// A git dependency without a version requires the version of its tag, if any.
+/// The path dependencies are pinned later by [`resolve_path_dependencies`].
+pub(crate) fn parse_dependencies(manifest: &Manifest) -> Vec<Dependency> {
+    let mut res = vec![];
+
+    for (deps_table, kind) in [
+        (&manifest.dependencies, DependencyKind::Normal),
+        (&manifest.dev_dependencies, DependencyKind::Dev),
+        (&manifest.build_dependencies, DependencyKind::Build),
+    ] {
+        for (name, spec) in deps_table {
+            res.push(to_dependency(name, spec, kind));
+        }
+    }
+    for (target, tables) in &manifest.target {
+        for (deps_table, kind) in [
+            (&tables.dependencies, DependencyKind::Normal),
+            (&tables.dev_dependencies, DependencyKind::Dev),
+            (&tables.build_dependencies, DependencyKind::Build),
+        ] {
+            for (name, spec) in deps_table {
+                res.push(to_dependency(name, spec, kind).with_target(Some(target.clone())));
             }
         }
     }
@@ -112,100 +426,253 @@ pub(crate) async fn extract_info_local(
     res
 }
 
-async fn parse_crate_name(path: &Path) -> Result<String, String> {
-    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    let value = content.parse::<Value>().map_err(|e| e.to_string())?;
+fn to_dependency(name: &str, spec: &DependencySpec, kind: DependencyKind) -> Dependency {
+    match spec {
+        DependencySpec::Version(version) => Dependency::new(name, version, vec![], false, kind),
+        DependencySpec::Detailed(detail) => {
+            let name = detail.package.as_deref().unwrap_or(name);
+            let source = detail.source();
+            let version_req = match (&detail.version, &source) {
+                (Some(version), _) => version.clone(),
+                (None, DependencySource::Git(_)) => detail
+                    .tag_version()
+                    .map(|v| format!("={}", v))
+                    .unwrap_or_default(),
+                (None, _) => String::new(),
+            };
+            Dependency::new(
+                name,
+                &version_req,
+                detail.features.clone(),
+                detail.optional,
+                kind,
+            )
+            .with_source(source)
+            .with_default_features(detail.default_features.unwrap_or(true))
+        }
+    }
+}
 
-    // a package name, no matter lib or bin
-    let package_name = value
-        .get("package")
-        .and_then(|p| p.get("name"))
-        .and_then(|n| n.as_str())
-        .ok_or("Failed to find package name, it is a workspace")?
-        .to_owned();
+/// Parse the `[patch]` and `[replace]` tables of a Cargo.toml,
+/// including those inherited from the workspace root.
+pub(crate) fn parse_overrides(manifest: &Manifest) -> Vec<DependencyOverride> {
+    let patches = manifest.patch.iter().flat_map(|(source, table)| {
+        table.iter().map(|(name, spec)| DependencyOverride {
+            kind: OverrideKind::Patch,
+            source: source.clone(),
+            replacement: to_dependency(name, spec, DependencyKind::Normal),
+        })
+    });
+    // `foo:1.0.0` or `https://github.com/rust-lang/crates.io-index#foo:1.0.0`
+    let replaces = manifest.replace.iter().map(|(package, spec)| {
+        let name = package.rsplit('#').next().unwrap_or(package);
+        let name = name.split(':').next().unwrap_or(name);
+        DependencyOverride {
+            kind: OverrideKind::Replace,
+            source: package.clone(),
+            replacement: to_dependency(name, spec, DependencyKind::Normal),
+        }
+    });
+    patches.chain(replaces).collect()
+}
+
+/// Pin the path dependencies to the version of the crate at their path,
+/// since cargo builds that copy even if a version is declared too.
+///
+/// `crate_dir` is the directory of the dependent crate relative to the repo, such as
+/// `crates/foo`, and `read_manifest` reads the manifest in a directory relative to the repo.
+/// A path inherited from `[workspace.dependencies]` is relative to the workspace root,
+/// so the path is tried from each ancestor of `crate_dir`, until the crate is found.
+pub(crate) fn resolve_path_dependencies<'a>(
+    dependencies: impl IntoIterator<Item = &'a mut Dependency>,
+    crate_dir: &str,
+    read_manifest: impl Fn(&str) -> Option<Manifest>,
+) {
+    for dep in dependencies {
+        let DependencySource::Path(path) = &dep.source else {
+            continue;
+        };
+        let version = Path::new(crate_dir)
+            .ancestors()
+            .filter_map(|dir| join_relative(&dir.to_string_lossy(), path))
+            .filter_map(|dir| read_manifest(&dir)?.package)
+            .find(|package| package.name == dep.name)
+            .and_then(|package| package.resolved_version().map(str::to_owned));
+        match version {
+            Some(version) => dep.version_req = format!("={}", version),
+            None => tracing::debug!(
+                "Cannot resolve the path dependency {} at {}",
+                dep.name,
+                path
+            ),
+        }
+    }
+}
 
-    Ok(package_name)
+/// A package is regarded as a library only if it has library targets but no binary targets.
+/// Without any target found, it is assumed not to be a library.
+fn is_crate_lib(target_kinds: &[TargetKind]) -> bool {
+    !target_kinds.is_empty() && !target_kinds.contains(&TargetKind::Bin)
 }
 
-async fn is_crate_lib(crate_path: &str) -> Result<bool, String> {
-    let cargo_toml_path = Path::new(crate_path).join("Cargo.toml");
+/// Detect all the target kinds of a package.
+///
+/// The library target is `lib` unless `[lib] crate-type` or `proc-macro = true` says otherwise,
+/// and exists if `[lib]` is declared or `src/lib.rs` exists.
+/// The binary target exists if `[[bin]]` is declared, or `src/main.rs` or `src/bin/` exists.
+///
+/// Only if nothing is found this way and `CARGO_METADATA_FALLBACK=1`,
+/// `cargo metadata` is invoked to ask cargo itself.
+fn detect_target_kinds(crate_path: &Path) -> Result<Vec<TargetKind>, String> {
+    let cargo_toml_path = crate_path.join("Cargo.toml");
     let cargo_toml_content = fs::read_to_string(cargo_toml_path)
         .map_err(|e| format!("Failed to read Cargo.toml: {}", e))?;
-
-    let cargo_toml: Value = cargo_toml_content
-        .parse::<Value>()
+    let cargo_toml: Manifest = toml::from_str(&cargo_toml_content)
         .map_err(|e| format!("Failed to parse Cargo.toml: {}", e))?;
 
-    // 优先检查 Cargo.toml 中的 '[lib]' 和 '[[bin]]'
-    let has_lib_in_toml = cargo_toml.get("lib").is_some();
-    let has_bin_in_toml = cargo_toml
-        .get("bin")
-        .is_some_and(|bins| bins.as_array().is_some_and(|b| !b.is_empty()));
+    let mut kinds = vec![];
 
-    if has_lib_in_toml || has_bin_in_toml {
-        return Ok(has_lib_in_toml && !has_bin_in_toml);
-    }
+    let lib = cargo_toml.lib.as_ref();
+    if lib.is_some() || crate_path.join("src/lib.rs").exists() {
+        let is_proc_macro = lib.and_then(|l| l.proc_macro).unwrap_or(false);
+        let crate_types = lib.and_then(|l| l.crate_type.as_ref());
 
-    // 如果 Cargo.toml 中无明显标识，退回到检查文件
-    let lib_rs_exists = Path::new(crate_path).join("src/lib.rs").exists();
-    let main_rs_exists = Path::new(crate_path).join("src/main.rs").exists();
+        if is_proc_macro {
+            kinds.push(TargetKind::ProcMacro);
+        } else if let Some(crate_types) = crate_types {
+            kinds.extend(
+                crate_types
+                    .iter()
+                    .map(String::as_str)
+                    .filter_map(TargetKind::from_crate_type),
+            );
+        } else {
+            kinds.push(TargetKind::Lib);
+        }
+    }
 
-    // 如果 'src/lib.rs' 存在，且 'src/main.rs' 不存在，更可能是库
-    if lib_rs_exists && !main_rs_exists {
-        return Ok(true);
+    let has_bin = !cargo_toml.bin.is_empty()
+        || crate_path.join("src/main.rs").exists()
+        || crate_path.join("src/bin").is_dir();
+    if has_bin && !kinds.contains(&TargetKind::Bin) {
+        kinds.push(TargetKind::Bin);
     }
 
-    // 如果存在 'src/main.rs'，则倾向于不是库
-    if main_rs_exists {
-        return Ok(false);
+    let use_cargo_metadata = env::var("CARGO_METADATA_FALLBACK").is_ok_and(|v| v.eq("1"));
+    if kinds.is_empty() && use_cargo_metadata {
+        tracing::debug!("Fallback to cargo metadata for {:?}", crate_path);
+        return detect_target_kinds_by_cargo_metadata(crate_path);
     }
 
-    // 如果没有明显的线索，回退为默认假设不是库
-    Ok(false)
+    Ok(kinds)
 }
 
-async fn from_cargo_toml(
-    local_repo_path: PathBuf,
-    cargo_toml_path: PathBuf,
+/// It is slow, and may require network access to the registry.
+fn detect_target_kinds_by_cargo_metadata(crate_path: &Path) -> Result<Vec<TargetKind>, String> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .manifest_path(crate_path.join("Cargo.toml"))
+        .no_deps()
+        .exec()
+        .map_err(|e| format!("Failed to run cargo metadata: {}", e))?;
+
+    let package = metadata
+        .root_package()
+        .ok_or("Failed to find the package by cargo metadata")?;
+
+    let mut kinds = vec![];
+    for kind in package.targets.iter().flat_map(|t| t.kind.iter()) {
+        if let Some(kind) = TargetKind::from_crate_type(kind) {
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+    }
+    Ok(kinds)
+}
+
+fn from_cargo_toml(
+    local_repo_path: &Path,
+    cargo_toml_path: &Path,
     id: &str,
-    lic: &mut Vec<Licenses>,
-) -> Result<Program, Box<dyn std::error::Error>> {
-    let content = fs::read_to_string(cargo_toml_path)?;
-    let parsed = content.parse::<Value>()?;
+) -> Result<(Program, Licenses), Box<dyn std::error::Error>> {
+    // the inherited fields like `version.workspace = true` are resolved here
+    let manifest = Manifest::read(cargo_toml_path, local_repo_path)?;
+    let crate_dir = cargo_toml_path.parent().unwrap_or(local_repo_path);
+    let lib_path = manifest.lib.as_ref().and_then(|lib| lib.path.clone());
+    let wasm_compatible = detect_wasm_support(&manifest, crate_dir, local_repo_path);
+    let package = manifest.package.ok_or_else(|| ImportError::Metadata {
+        path: cargo_toml_path.to_path_buf(),
+        message: "Failed to find package, it is a workspace".to_string(),
+    })?;
 
     // 处理description,将多行字符串转换为单行,用\n替换换行符
-    let description = parsed["package"]
-        .get("description")
-        .unwrap_or(&Value::String(String::from("None")))
-        .as_str()
+    let description = or_none(package.description.as_deref())
         .map(|desc| desc.replace('\n', "\\n").replace('\r', ""));
 
     let mut program = Program::new(
         id.to_string(),
-        parsed["package"]["name"]
-            .as_str()
-            .unwrap_or_default()
-            .to_string(),
+        package.name.clone(),
         description,
         get_namespace_by_repo_path(local_repo_path.to_str().unwrap()),
         None,
-        parsed["package"]
-            .get("repository")
-            .unwrap_or(&Value::String(String::from("None")))
-            .as_str()
-            .map(String::from),
+        or_none(package.repository.as_deref()),
         None,
-        parsed["package"]
-            .get("documentation")
-            .unwrap_or(&Value::String(String::from("None")))
-            .as_str()
-            .map(String::from),
+        or_none(package.documentation.as_deref()),
     );
-    let license = parsed["package"]
-        .get("license")
-        .unwrap_or(&Value::String(String::from("None")))
-        .as_str()
-        .map(String::from);
+    let license = or_none(package.license.as_deref());
+
+    program.license = license.clone();
+    program.homepage = package.homepage.clone();
+    program.keywords = join_array_field(package.keywords.as_deref());
+    program.categories = join_array_field(package.categories.as_deref());
+    program.edition = package.edition.clone();
+    program.rust_version = package.rust_version.clone();
+    program.readme = match &package.readme {
+        Some(Readme::Path(readme)) => Some(readme.clone()),
+        // `readme = false` disables the readme
+        Some(Readme::Enabled(false)) => None,
+        // README.md in the crate root is used by default
+        _ => ["README.md", "README.txt", "README"]
+            .into_iter()
+            .find(|f| crate_dir.join(f).is_file())
+            .map(String::from),
+    };
+    program.readme_content = program
+        .readme
+        .as_deref()
+        .and_then(|readme| read_readme(crate_dir, local_repo_path, readme));
+    program.authors = join_array_field(package.authors.as_deref());
+    program.build_script = match &package.build {
+        Some(BuildScript::Path(build)) => Some(build.clone()),
+        // `build = false` disables the build script
+        Some(BuildScript::Enabled(false)) => None,
+        _ => crate_dir
+            .join("build.rs")
+            .is_file()
+            .then(|| "build.rs".to_string()),
+    };
+    program.links = package.links.clone();
+    let lib_path = crate_dir.join(lib_path.as_deref().unwrap_or("src/lib.rs"));
+    program.std_support = detect_std_support(&lib_path).map(|s| s.as_str().to_string());
+    program.wasm_compatible = wasm_compatible;
+    if !package.unresolved.is_empty() {
+        tracing::debug!(
+            "{}: the workspace does not define {:?}",
+            package.name,
+            package.unresolved
+        );
+        program.unknown_fields = Some(package.unresolved.join(","));
+    }
+    program.detected_license =
+        detect_license(crate_dir, local_repo_path, package.license_file.as_deref());
+    if program.detected_license.is_some() && program.detected_license != program.license {
+        tracing::debug!(
+            "{}: declared license {:?}, detected {:?}",
+            program.name,
+            program.license,
+            program.detected_license
+        );
+    }
     let newlicense = Licenses {
         program_id: program.id.clone(),
         program_name: program.name.clone(),
@@ -231,6 +698,82 @@ async fn from_cargo_toml(
             program.github_url = Some("None".to_string());
         }
     }
-    lic.push(newlicense);
-    Ok(program)
+    Ok((program, newlicense))
+}
+
+/// A missing field is `"None"` in the graph, as the data transporter expects.
+fn or_none(value: Option<&str>) -> Option<String> {
+    Some(value.unwrap_or("None").to_string())
+}
+
+/// e.g. `keywords = ["async", "io"]` => "async,io"
+fn join_array_field(values: Option<&[String]>) -> Option<String> {
+    match values {
+        Some(values) if !values.is_empty() => Some(values.join(",")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_overrides() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [package]
+            name = "foo"
+
+            [patch.crates-io]
+            serde = { path = "vendor/serde" }
+            log = { git = "https://github.com/rust-lang/log", tag = "v0.4.22" }
+
+            [patch."https://github.com/tokio-rs/tokio"]
+            tokio = { package = "tokio-fork", version = "1.38" }
+
+            [replace]
+            "https://github.com/rust-lang/crates.io-index#rand:0.8.5" = { path = "rand" }
+            "libc:0.2.155" = "0.2.158"
+            "#,
+        )
+        .unwrap();
+        let overrides = parse_overrides(&manifest);
+        let summary: Vec<(OverrideKind, &str, &str, &str)> = overrides
+            .iter()
+            .map(|o| {
+                (
+                    o.kind,
+                    o.source.as_str(),
+                    o.replacement.name.as_str(),
+                    o.replacement.version_req.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (OverrideKind::Patch, "crates-io", "log", "=0.4.22"),
+                (OverrideKind::Patch, "crates-io", "serde", ""),
+                (
+                    OverrideKind::Patch,
+                    "https://github.com/tokio-rs/tokio",
+                    "tokio-fork",
+                    "1.38"
+                ),
+                (
+                    OverrideKind::Replace,
+                    "https://github.com/rust-lang/crates.io-index#rand:0.8.5",
+                    "rand",
+                    ""
+                ),
+                (OverrideKind::Replace, "libc:0.2.155", "libc", "0.2.158"),
+            ]
+        );
+        assert_eq!(
+            overrides[1].replacement.source,
+            DependencySource::Path("vendor/serde".to_string())
+        );
+        assert!(parse_overrides(&Manifest::default()).is_empty());
+    }
 }
@@ -0,0 +1,291 @@
+//! A client of the crates.io web API, used to enrich the imported crates
+//! with the data which is not in the index, such as the download counts and owners.
+//!
+//! crates.io asks crawlers for at most one request per second,
+//! so the requests are throttled, and the responses are cached for a while.
+
+use crate::retry::{is_transient_http, RateLimiter, RetryPolicy};
+use crate::ImportContext;
+use lazy_static::lazy_static;
+use model::tugraph_model::{Owns, Person, Team};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const CRATESIO_API_URL: &str = "https://crates.io/api/v1/";
+
+/// Where the `.crate` tarballs are downloaded from.
+const CRATESIO_STATIC_URL: &str = "https://static.crates.io/crates/";
+
+/// The cached responses expire after a day.
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct CrateDownloads {
+    pub(crate) downloads: i64,
+    pub(crate) recent_downloads: Option<i64>,
+    /// version -> downloads
+    #[serde(skip)]
+    pub(crate) versions: HashMap<String, i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CrateVersionDownloads {
+    num: String,
+    downloads: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct CrateResponse {
+    #[serde(rename = "crate")]
+    krate: CrateDownloads,
+    #[serde(default)]
+    versions: Vec<CrateVersionDownloads>,
+}
+
+/// An owner of a crate, either a user or a team
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct CrateOwner {
+    pub(crate) id: i64,
+    pub(crate) login: String,
+    /// `user` or `team`
+    pub(crate) kind: String,
+    pub(crate) name: Option<String>,
+    pub(crate) url: Option<String>,
+    pub(crate) avatar: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OwnersResponse {
+    users: Vec<CrateOwner>,
+}
+
+lazy_static! {
+    static ref DOWNLOADS_CACHE: Mutex<HashMap<String, (Instant, CrateDownloads)>> =
+        Mutex::new(HashMap::new());
+    static ref OWNERS_CACHE: Mutex<HashMap<String, (Instant, Vec<CrateOwner>)>> =
+        Mutex::new(HashMap::new());
+}
+
+pub struct CratesIoClient {
+    client: Client,
+    base_url: String,
+    limiter: RateLimiter,
+    retry: RetryPolicy,
+}
+
+impl CratesIoClient {
+    /// The interval between requests is `CRATESIO_API_INTERVAL_MS`, 1000 by default.
+    pub fn new() -> Result<Self, reqwest::Error> {
+        let client = Client::builder()
+            .user_agent(concat!("crates-pro/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self {
+            client,
+            base_url: CRATESIO_API_URL.to_string(),
+            limiter: RateLimiter::from_env("CRATESIO_API_INTERVAL_MS", 1000),
+            retry: RetryPolicy::from_env(),
+        })
+    }
+
+    /// GET `<base>/<path>`, `None` if not found.
+    pub(crate) async fn get<T: for<'de> Deserialize<'de>>(
+        &mut self,
+        path: &str,
+    ) -> Result<Option<T>, reqwest::Error> {
+        self.limiter.wait().await;
+        let url = format!("{}{}", self.base_url, path);
+        let client = &self.client;
+        let url = &url;
+        self.retry
+            .retry(url, is_transient_http, || async move {
+                let resp = client.get(url).send().await?;
+                if resp.status() == StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+                Ok(Some(resp.error_for_status()?.json().await?))
+            })
+            .await
+    }
+
+    /// The `.crate` tarball of a version, `None` if not found.
+    pub(crate) async fn download_crate(
+        &mut self,
+        name: &str,
+        version: &str,
+    ) -> Result<Option<Vec<u8>>, reqwest::Error> {
+        self.limiter.wait().await;
+        let url = format!("{}{}/{}-{}.crate", CRATESIO_STATIC_URL, name, name, version);
+        let client = &self.client;
+        let url = &url;
+        self.retry
+            .retry(url, is_transient_http, || async move {
+                let resp = client.get(url).send().await?;
+                if resp.status() == StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+                Ok(Some(resp.error_for_status()?.bytes().await?.to_vec()))
+            })
+            .await
+    }
+
+    /// The total, recent and per version downloads of a crate.
+    pub(crate) async fn crate_downloads(
+        &mut self,
+        name: &str,
+    ) -> Result<Option<CrateDownloads>, reqwest::Error> {
+        if let Some((time, downloads)) = DOWNLOADS_CACHE.lock().unwrap().get(name) {
+            if time.elapsed() < CACHE_TTL {
+                return Ok(Some(downloads.clone()));
+            }
+        }
+
+        let resp: CrateResponse = match self.get(&format!("crates/{}", name)).await? {
+            Some(resp) => resp,
+            None => return Ok(None),
+        };
+        let mut downloads = resp.krate;
+        downloads.versions = resp
+            .versions
+            .into_iter()
+            .map(|v| (v.num, v.downloads))
+            .collect();
+
+        DOWNLOADS_CACHE
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), (Instant::now(), downloads.clone()));
+        Ok(Some(downloads))
+    }
+
+    /// The users and teams owning a crate.
+    pub(crate) async fn crate_owners(
+        &mut self,
+        name: &str,
+    ) -> Result<Option<Vec<CrateOwner>>, reqwest::Error> {
+        if let Some((time, owners)) = OWNERS_CACHE.lock().unwrap().get(name) {
+            if time.elapsed() < CACHE_TTL {
+                return Ok(Some(owners.clone()));
+            }
+        }
+
+        let owners = match self
+            .get::<OwnersResponse>(&format!("crates/{}/owners", name))
+            .await?
+        {
+            Some(resp) => resp.users,
+            None => return Ok(None),
+        };
+
+        OWNERS_CACHE
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), (Instant::now(), owners.clone()));
+        Ok(Some(owners))
+    }
+}
+
+impl ImportContext {
+    /// Fill the downloads of libraries and library versions from crates.io.
+    pub async fn enrich_downloads(&mut self, client: &mut CratesIoClient) {
+        tracing::info!("Start to enrich downloads");
+        let names: HashSet<String> = self.libraries.iter().map(|l| l.name.clone()).collect();
+
+        let mut all_downloads = HashMap::new();
+        for name in names {
+            match client.crate_downloads(&name).await {
+                Ok(Some(downloads)) => {
+                    all_downloads.insert(name, downloads);
+                }
+                Ok(None) => tracing::debug!("{} is not on crates.io", name),
+                Err(e) => tracing::warn!("Failed to get downloads of {}: {}", name, e),
+            }
+        }
+
+        for library in &mut self.libraries {
+            if let Some(downloads) = all_downloads.get(&library.name) {
+                library.downloads = downloads.downloads;
+                library.recent_downloads = downloads.recent_downloads;
+            }
+        }
+        for version in &mut self.library_versions {
+            if let Some(downloads) = all_downloads
+                .get(&version.name)
+                .and_then(|d| d.versions.get(&version.version))
+            {
+                version.downloads = Some(*downloads);
+            }
+        }
+        tracing::info!(
+            "Finish to enrich downloads of {} crates",
+            all_downloads.len()
+        );
+    }
+    /// Fetch the owners of the libraries from crates.io,
+    /// as person and team vertices with `owns` edges to the programs.
+    pub async fn enrich_owners(&mut self, client: &mut CratesIoClient) {
+        tracing::info!("Start to enrich owners");
+        let libraries: Vec<(String, String)> = self
+            .libraries
+            .iter()
+            .map(|l| (l.id.clone(), l.name.clone()))
+            .collect();
+
+        let mut persons = HashMap::new();
+        let mut teams = HashMap::new();
+        let mut person_owns = vec![];
+        let mut team_owns = vec![];
+        for (program_id, name) in libraries {
+            let owners = match client.crate_owners(&name).await {
+                Ok(Some(owners)) => owners,
+                Ok(None) => {
+                    tracing::debug!("{} is not on crates.io", name);
+                    continue;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to get owners of {}: {}", name, e);
+                    continue;
+                }
+            };
+            for owner in owners {
+                let id = format!("{}/{}", owner.kind, owner.id);
+                let owns = Owns {
+                    SRC_ID: id.clone(),
+                    DST_ID: program_id.clone(),
+                };
+                if owner.kind == "team" {
+                    teams.entry(id.clone()).or_insert_with(|| Team {
+                        id,
+                        login: owner.login,
+                        name: owner.name,
+                        url: owner.url,
+                        avatar: owner.avatar,
+                    });
+                    team_owns.push(owns);
+                } else {
+                    persons.entry(id.clone()).or_insert_with(|| Person {
+                        id,
+                        login: owner.login,
+                        name: owner.name,
+                        url: owner.url,
+                        avatar: owner.avatar,
+                        email: None,
+                    });
+                    person_owns.push(owns);
+                }
+            }
+        }
+
+        self.persons = persons.into_values().collect();
+        self.teams = teams.into_values().collect();
+        self.person_owns = person_owns;
+        self.team_owns = team_owns;
+        tracing::info!(
+            "Finish to enrich owners, {} persons and {} teams",
+            self.persons.len(),
+            self.teams.len()
+        );
+    }
+}
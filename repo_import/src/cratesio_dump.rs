@@ -0,0 +1,250 @@
+//! Import the official crates.io database dump (<https://static.crates.io/db-dump.tar.gz>),
+//! so the graph can be seeded with the whole registry without cloning every repo.
+//!
+//! Only `crates.csv`, `versions.csv` and `dependencies.csv` of the extracted dump are read.
+
+use crate::msrv::Msrv;
+use crate::registry::RegistryCrate;
+use crate::version_info::Dependencies;
+use crate::ImportContext;
+use chrono::NaiveDateTime;
+use model::general_model::{Dependency, DependencyKind};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[derive(Debug, Deserialize)]
+struct CrateRow {
+    id: i64,
+    name: String,
+    description: Option<String>,
+    documentation: Option<String>,
+    homepage: Option<String>,
+    repository: Option<String>,
+    /// only in the dumps before 2024, later moved to `crate_downloads.csv`
+    #[serde(default)]
+    downloads: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VersionRow {
+    id: i64,
+    crate_id: i64,
+    num: String,
+    /// the `[features]` table in json
+    #[serde(default)]
+    features: Option<String>,
+    license: Option<String>,
+    #[serde(default)]
+    rust_version: Option<String>,
+    #[serde(default)]
+    edition: Option<String>,
+    /// `t` or `f`
+    #[serde(default)]
+    yanked: Option<String>,
+    /// such as `2017-01-20 21:45:08.186209` in UTC
+    #[serde(default)]
+    created_at: Option<String>,
+    /// the size of the `.crate` in bytes
+    #[serde(default)]
+    crate_size: Option<i64>,
+    /// the sha256 of the `.crate`
+    #[serde(default)]
+    checksum: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DependencyRow {
+    version_id: i64,
+    /// the crate depended on
+    crate_id: i64,
+    req: String,
+    /// a postgres array, such as `{derive,std}`
+    features: String,
+    /// `t` or `f`
+    optional: String,
+    /// `t` or `f`
+    #[serde(default)]
+    default_features: String,
+    /// 0: normal, 1: build, 2: dev
+    kind: i32,
+    /// such as `cfg(windows)`, empty for all the platforms
+    #[serde(default)]
+    target: String,
+}
+
+/// `{derive,std}` -> `["derive", "std"]`
+fn parse_pg_array(s: &str) -> Vec<String> {
+    s.trim_start_matches('{')
+        .trim_end_matches('}')
+        .split(',')
+        .map(|x| x.trim_matches('"'))
+        .filter(|x| !x.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn parse_kind(kind: i32) -> DependencyKind {
+    match kind {
+        1 => DependencyKind::Build,
+        2 => DependencyKind::Dev,
+        _ => DependencyKind::Normal,
+    }
+}
+
+/// `2017-01-20 21:45:08.186209` -> seconds since the epoch
+fn parse_timestamp(s: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|t| t.and_utc().timestamp())
+}
+
+/// The extracted dump contains a `data` directory, both it and its parent are accepted.
+fn data_dir(dump_dir: &Path) -> PathBuf {
+    let data = dump_dir.join("data");
+    if data.join("crates.csv").is_file() {
+        data
+    } else {
+        dump_dir.to_path_buf()
+    }
+}
+
+fn read_rows<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Vec<T>, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut rows = vec![];
+    for row in reader.deserialize() {
+        match row {
+            Ok(row) => rows.push(row),
+            Err(e) => tracing::warn!("Skip a bad row of {}: {}", path.display(), e),
+        }
+    }
+    Ok(rows)
+}
+
+impl ImportContext {
+    /// Import the crates, versions and dependencies of an extracted crates.io dump.
+    ///
+    /// The crates already imported from their repos are kept as they are,
+    /// and only their unknown versions are added.
+    pub async fn import_cratesio_dump(&mut self, dump_dir: &Path) -> Result<(), Box<dyn Error>> {
+        tracing::info!("Start to import crates.io dump {:?}", dump_dir);
+        let import_time = Instant::now();
+        let data = data_dir(dump_dir);
+
+        let crates: HashMap<i64, CrateRow> = read_rows::<CrateRow>(&data.join("crates.csv"))?
+            .into_iter()
+            .map(|c| (c.id, c))
+            .collect();
+        let versions: Vec<VersionRow> = read_rows(&data.join("versions.csv"))?;
+        tracing::info!("Read {} crates, {} versions", crates.len(), versions.len());
+
+        let mut dependencies: HashMap<i64, Vec<Dependency>> = HashMap::new();
+        for dep in read_rows::<DependencyRow>(&data.join("dependencies.csv"))? {
+            let name = match crates.get(&dep.crate_id) {
+                Some(c) => &c.name,
+                None => continue,
+            };
+            dependencies.entry(dep.version_id).or_default().push(
+                Dependency::new(
+                    name,
+                    &dep.req,
+                    parse_pg_array(&dep.features),
+                    dep.optional == "t",
+                    parse_kind(dep.kind),
+                )
+                .with_target(Some(dep.target).filter(|t| !t.is_empty()))
+                .with_default_features(dep.default_features != "f"),
+            );
+        }
+
+        // the max version of each crate
+        let mut max_versions: HashMap<i64, semver::Version> = HashMap::new();
+        for version in &versions {
+            if let Ok(v) = semver::Version::parse(&version.num) {
+                let entry = max_versions.entry(version.crate_id).or_insert(v.clone());
+                if v > *entry {
+                    *entry = v;
+                }
+            }
+        }
+
+        // the license, msrv and edition of the latest version describe the crate
+        let latest: HashMap<i64, &VersionRow> = versions
+            .iter()
+            .filter(|v| {
+                max_versions
+                    .get(&v.crate_id)
+                    .is_some_and(|m| m.to_string() == v.num)
+            })
+            .map(|v| (v.crate_id, v))
+            .collect();
+
+        // crate id in the dump -> (program id, is library)
+        let mut program_ids: HashMap<i64, (String, bool)> = HashMap::new();
+        for (crate_id, row) in &crates {
+            let latest = latest.get(crate_id);
+            let krate = RegistryCrate {
+                name: row.name.clone(),
+                description: row.description.clone(),
+                documentation: row.documentation.clone(),
+                homepage: row.homepage.clone(),
+                repository: row.repository.clone(),
+                downloads: row.downloads,
+                max_version: max_versions.get(crate_id).map(|v| v.to_string()),
+                license: latest.and_then(|v| v.license.clone()),
+                rust_version: latest.and_then(|v| v.rust_version.clone()),
+                edition: latest.and_then(|v| v.edition.clone()),
+            };
+            program_ids.insert(*crate_id, self.registry_program(&krate));
+        }
+
+        for version in &versions {
+            let (row, (program_id, islib)) = match (
+                crates.get(&version.crate_id),
+                program_ids.get(&version.crate_id),
+            ) {
+                (Some(row), Some(program_id)) => (row, program_id),
+                _ => continue,
+            };
+
+            let features: BTreeMap<String, Vec<String>> = version
+                .features
+                .as_deref()
+                .and_then(|f| serde_json::from_str(f).ok())
+                .unwrap_or_default();
+            let info = Dependencies {
+                crate_name: row.name.clone(),
+                version: version.num.clone(),
+                dependencies: dependencies.remove(&version.id).unwrap_or_default(),
+                overrides: vec![],
+                features,
+                git_url: row.repository.clone().unwrap_or_default(),
+                tag_name: String::new(),
+                api: vec![],
+                msrv: version.rust_version.as_deref().and_then(Msrv::declared),
+                commit: None,
+                commit_time: None,
+                published_at: version.created_at.as_deref().and_then(parse_timestamp),
+                locked: vec![],
+                crate_size: version.crate_size,
+                checksum: version.checksum.clone(),
+                unpacked_size: None,
+            };
+            self.add_registry_version(program_id, *islib, &info).await;
+            self.set_yanked(
+                &row.name,
+                &version.num,
+                version.yanked.as_deref() == Some("t"),
+            );
+        }
+
+        tracing::info!("Finish to import crates.io dump {:?}", dump_dir);
+        tracing::trace!(
+            "import crates.io dump need time: {:?}",
+            import_time.elapsed()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,441 @@
+//! Incremental sync from the crates.io index (<https://github.com/rust-lang/crates.io-index>).
+//!
+//! The index is fetched into a bare repo, and only the files changed since the
+//! last synced commit are parsed, so new versions are ingested continuously
+//! instead of re-importing everything.
+
+use crate::kafka_handler::KafkaHandler;
+use crate::msrv::Msrv;
+use crate::registry::RegistryCrate;
+use crate::retry::{is_transient_git, RetryPolicy};
+use crate::version_info::Dependencies;
+use crate::ImportContext;
+use chrono::DateTime;
+use git2::build::RepoBuilder;
+use git2::{ObjectType, Oid, Repository, Tree, TreeWalkMode, TreeWalkResult};
+use model::general_model::{Dependency, DependencyKind};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
+
+const CRATESIO_INDEX_URL: &str = "https://github.com/rust-lang/crates.io-index";
+
+const INDEX_REFSPEC: &str = "+refs/heads/master:refs/remotes/origin/master";
+const INDEX_REF: &str = "refs/remotes/origin/master";
+
+/// A line of an index file, i.e. a version of a crate
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct IndexEntry {
+    pub(crate) name: String,
+    pub(crate) vers: String,
+    #[serde(default)]
+    pub(crate) deps: Vec<IndexDependency>,
+    #[serde(default)]
+    pub(crate) features: BTreeMap<String, Vec<String>>,
+    /// the features using `dep:` or `?/` syntax, separated for old cargo
+    #[serde(default)]
+    pub(crate) features2: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub(crate) yanked: bool,
+    #[serde(default)]
+    pub(crate) rust_version: Option<String>,
+    /// the publish time such as `2024-11-12T10:00:00Z`, only in the entries since 2025
+    #[serde(default)]
+    pub(crate) pubtime: Option<String>,
+    /// the sha256 of the `.crate`
+    #[serde(default)]
+    pub(crate) cksum: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct IndexDependency {
+    pub(crate) name: String,
+    pub(crate) req: String,
+    #[serde(default)]
+    pub(crate) features: Vec<String>,
+    #[serde(default)]
+    pub(crate) optional: bool,
+    /// `normal`, `dev` or `build`
+    #[serde(default)]
+    pub(crate) kind: Option<String>,
+    /// the real name of a renamed dependency
+    #[serde(default)]
+    pub(crate) package: Option<String>,
+    /// such as `cfg(windows)`
+    #[serde(default)]
+    pub(crate) target: Option<String>,
+    #[serde(default)]
+    pub(crate) default_features: Option<bool>,
+}
+
+impl IndexEntry {
+    pub(crate) fn to_dependencies(&self) -> Dependencies {
+        let dependencies = self
+            .deps
+            .iter()
+            .map(|d| {
+                let kind = match d.kind.as_deref() {
+                    Some("dev") => DependencyKind::Dev,
+                    Some("build") => DependencyKind::Build,
+                    _ => DependencyKind::Normal,
+                };
+                Dependency::new(
+                    d.package.as_deref().unwrap_or(&d.name),
+                    &d.req,
+                    d.features.clone(),
+                    d.optional,
+                    kind,
+                )
+                .with_target(d.target.clone())
+                .with_default_features(d.default_features.unwrap_or(true))
+            })
+            .collect();
+        let mut features = self.features.clone();
+        features.extend(self.features2.clone());
+
+        Dependencies {
+            crate_name: self.name.clone(),
+            version: self.vers.clone(),
+            dependencies,
+            overrides: vec![],
+            features,
+            git_url: String::new(),
+            tag_name: String::new(),
+            api: vec![],
+            msrv: self.rust_version.as_deref().and_then(Msrv::declared),
+            commit: None,
+            commit_time: None,
+            published_at: self
+                .pubtime
+                .as_deref()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.timestamp()),
+            locked: vec![],
+            crate_size: None,
+            unpacked_size: None,
+            checksum: self.cksum.clone(),
+        }
+    }
+}
+
+/// An index file has one json object per line.
+pub(crate) fn parse_index_file(content: &str) -> Vec<IndexEntry> {
+    content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| match serde_json::from_str(l) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                tracing::warn!("Bad index entry {}: {}", l, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// `config.json` and the dot files are not crates.
+fn is_crate_file(path: &str) -> bool {
+    path != "config.json" && !path.split('/').any(|p| p.starts_with('.'))
+}
+
+fn open_or_clone_index(index_path: &Path) -> Result<Repository, git2::Error> {
+    if index_path.is_dir() {
+        Repository::open_bare(index_path)
+    } else {
+        tracing::info!("Cloning crates.io index into {:?}", index_path);
+        RetryPolicy::from_env().retry_blocking("Cloning crates.io index", is_transient_git, || {
+            RepoBuilder::new()
+                .bare(true)
+                .clone(CRATESIO_INDEX_URL, index_path)
+        })
+    }
+}
+
+/// Clone the index into `index_path`, or fetch it if it's already cloned.
+fn fetch_index(index_path: &Path) -> Result<Repository, git2::Error> {
+    let repo = open_or_clone_index(index_path)?;
+    RetryPolicy::from_env().retry_blocking("Fetching crates.io index", is_transient_git, || {
+        repo.find_remote("origin")?
+            .fetch(&[INDEX_REFSPEC], None, None)
+    })?;
+    Ok(repo)
+}
+
+/// The crate files changed between two trees, as `(path, blob id)`
+fn changed_files(
+    repo: &Repository,
+    old: &Tree,
+    new: &Tree,
+) -> Result<Vec<(String, Oid)>, git2::Error> {
+    let diff = repo.diff_tree_to_tree(Some(old), Some(new), None)?;
+    Ok(diff
+        .deltas()
+        .filter(|d| !d.new_file().id().is_zero())
+        .filter_map(|d| {
+            let path = d.new_file().path()?.to_str()?.to_string();
+            Some((path, d.new_file().id()))
+        })
+        .filter(|(path, _)| is_crate_file(path))
+        .collect())
+}
+
+fn all_files(tree: &Tree) -> Result<Vec<(String, Oid)>, git2::Error> {
+    let mut res = vec![];
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            let path = format!("{}{}", root, entry.name().unwrap_or_default());
+            if is_crate_file(&path) {
+                res.push((path, entry.id()));
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(res)
+}
+
+/// The head commit of the index and the crate files changed since then.
+type IndexChanges = (String, Vec<(String, Oid)>);
+
+/// The head commit of the index and the crate files changed since `last_commit`,
+/// none if the head is `last_commit`.
+fn index_changes(
+    repo: &Repository,
+    last_commit: Option<&str>,
+) -> Result<Option<IndexChanges>, git2::Error> {
+    let new_commit = repo.find_reference(INDEX_REF)?.peel_to_commit()?;
+    let new_id = new_commit.id().to_string();
+    if last_commit == Some(new_id.as_str()) {
+        return Ok(None);
+    }
+    let new_tree = new_commit.tree()?;
+
+    let old_tree = last_commit
+        .and_then(|id| Oid::from_str(id).ok())
+        .and_then(|id| repo.find_commit(id).ok())
+        .and_then(|c| c.tree().ok());
+    let files = match &old_tree {
+        Some(old_tree) => changed_files(repo, old_tree, &new_tree)?,
+        None => all_files(&new_tree)?,
+    };
+    Ok(Some((new_id, files)))
+}
+
+impl ImportContext {
+    /// Add the versions of a crate in the index, return the number of new versions.
+    pub(crate) async fn add_index_entries(&mut self, entries: &[IndexEntry]) -> usize {
+        let latest = entries
+            .iter()
+            .filter(|e| !e.yanked)
+            .filter_map(|e| semver::Version::parse(&e.vers).ok().map(|v| (v, e)))
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, e)| e);
+        let krate = match entries.first() {
+            Some(first) => RegistryCrate {
+                name: first.name.clone(),
+                max_version: latest.map(|e| e.vers.clone()),
+                rust_version: latest.and_then(|e| e.rust_version.clone()),
+                ..Default::default()
+            },
+            None => return 0,
+        };
+        let (program_id, islib) = self.registry_program(&krate);
+
+        let mut count = 0;
+        for entry in entries {
+            if self
+                .add_registry_version(&program_id, islib, &entry.to_dependencies())
+                .await
+            {
+                count += 1;
+            }
+            self.set_yanked(&entry.name, &entry.vers, entry.yanked);
+        }
+        count
+    }
+
+    /// Fetch the crates.io index into `index_path` (a bare repo),
+    /// and add the versions changed since the last sync.
+    /// Return the number of new versions.
+    ///
+    /// The index is squashed from time to time, if the last synced commit
+    /// is gone, the whole index is parsed again and the known versions are skipped.
+    pub async fn sync_cratesio_index(
+        &mut self,
+        index_path: &Path,
+    ) -> Result<usize, Box<dyn Error>> {
+        let task_path = index_path.to_path_buf();
+        let repo = tokio::task::spawn_blocking(move || fetch_index(&task_path)).await??;
+        let Some((new_id, files)) = index_changes(&repo, self.cratesio_index_commit.as_deref())?
+        else {
+            return Ok(0);
+        };
+        tracing::info!("{} crates changed in the crates.io index", files.len());
+
+        let mut count = 0;
+        for (path, blob_id) in files {
+            // the blobs are not `Send`, so only their contents are kept over the awaits
+            let content = match String::from_utf8(repo.find_blob(blob_id)?.content().to_vec()) {
+                Ok(content) => content,
+                Err(e) => {
+                    tracing::warn!("Bad index file {}: {}", path, e);
+                    continue;
+                }
+            };
+            count += self.add_index_entries(&parse_index_file(&content)).await;
+        }
+
+        self.cratesio_index_commit = Some(new_id);
+        tracing::info!("Synced {} new versions from the crates.io index", count);
+        Ok(count)
+    }
+
+    /// Sync the crates.io index every `interval`,
+    /// and write the tugraph import files when there are new versions.
+    ///
+    /// If `KAFKA_YANK_TOPIC` is set, the yank/unyank events are sent to it.
+    pub async fn watch_cratesio_index(&mut self, index_path: &Path, interval: Duration) {
+        let yank_sender = match (env::var("KAFKA_BROKER"), env::var("KAFKA_YANK_TOPIC")) {
+            (Ok(broker), Ok(topic)) => match KafkaHandler::new_producer(&broker) {
+                Ok(sender) => Some((sender, topic)),
+                Err(e) => {
+                    tracing::error!("Failed to create kafka producer: {}", e);
+                    None
+                }
+            },
+            _ => None,
+        };
+        loop {
+            match self.sync_cratesio_index(index_path).await {
+                Ok(0) => {}
+                Ok(_) => self.write_tugraph_import_files().await,
+                Err(e) => tracing::error!("Failed to sync the crates.io index: {}", e),
+            }
+            match &yank_sender {
+                Some((sender, topic)) => self.send_yank_events(sender, topic).await,
+                None => self.yank_events.clear(),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{IndexAddOption, Signature};
+    use std::fs;
+
+    const ENTRIES: &str = r#"{"name":"index_test","vers":"1.0.0","deps":[{"name":"serde","req":"^1","features":["derive"],"optional":true,"default_features":false}],"features":{"default":["std"]},"features2":{"serde":["dep:serde"]},"cksum":"ab12","pubtime":"2024-11-12T10:00:00Z"}
+{"name":"index_test","vers":"1.1.0","deps":[{"name":"trybuild","req":"^1","kind":"dev"},{"name":"derive","package":"serde_derive","req":"=1.0.0","target":"cfg(unix)"}]}
+not json
+{"name":"index_test","vers":"2.0.0","yanked":true}
+"#;
+
+    #[test]
+    fn test_parse_index_file() {
+        let entries = parse_index_file(ENTRIES);
+        assert_eq!(entries.len(), 3);
+
+        let deps = entries[0].to_dependencies();
+        assert_eq!(deps.version, "1.0.0");
+        assert!(deps.dependencies[0].optional);
+        assert!(!deps.dependencies[0].default_features);
+        assert_eq!(deps.features.len(), 2);
+        assert_eq!(deps.published_at, Some(1731405600));
+        assert_eq!(deps.checksum.as_deref(), Some("ab12"));
+
+        let deps = entries[1].to_dependencies();
+        assert_eq!(deps.dependencies[0].kind, DependencyKind::Dev);
+        assert_eq!(deps.dependencies[1].name, "serde_derive");
+        assert_eq!(deps.dependencies[1].target.as_deref(), Some("cfg(unix)"));
+        assert!(deps.dependencies[1].default_features);
+        assert!(entries[2].yanked);
+
+        assert!(is_crate_file("in/de/index_test"));
+        assert!(!is_crate_file("config.json"));
+        assert!(!is_crate_file(".github/workflows/ci.yml"));
+    }
+
+    /// Commit the files as the fetched head of the index.
+    fn commit(repo: &Repository, files: &[(&str, &str)]) -> String {
+        let workdir = repo.workdir().unwrap();
+        for (path, content) in files {
+            let path = workdir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        let id = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "update",
+                &tree,
+                parent.as_ref().into_iter().collect::<Vec<_>>().as_slice(),
+            )
+            .unwrap();
+        repo.reference(INDEX_REF, id, true, "fetch").unwrap();
+        id.to_string()
+    }
+
+    fn paths(changes: IndexChanges) -> Vec<String> {
+        let mut paths: Vec<String> = changes.1.into_iter().map(|(path, _)| path).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_index_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let first = commit(&repo, &[("config.json", "{}"), ("in/de/index_test", "a")]);
+        let changes = index_changes(&repo, None).unwrap().unwrap();
+        assert_eq!(changes.0, first);
+        assert_eq!(paths(changes), ["in/de/index_test"]);
+
+        let second = commit(
+            &repo,
+            &[
+                ("config.json", "{\"dl\":\"x\"}"),
+                ("in/de/index_test", "b"),
+                ("se/rd/serde", "c"),
+            ],
+        );
+        let changes = index_changes(&repo, Some(&first)).unwrap().unwrap();
+        assert_eq!(paths(changes), ["in/de/index_test", "se/rd/serde"]);
+        assert!(index_changes(&repo, Some(&second)).unwrap().is_none());
+
+        // the last synced commit is squashed away, so all the files are parsed again
+        let gone = "0123456789012345678901234567890123456789";
+        let changes = index_changes(&repo, Some(gone)).unwrap().unwrap();
+        assert_eq!(paths(changes), ["in/de/index_test", "se/rd/serde"]);
+    }
+
+    #[tokio::test]
+    async fn test_add_index_entries() {
+        let entries = parse_index_file(ENTRIES);
+        let mut context = ImportContext::default();
+        assert_eq!(context.add_index_entries(&entries[..1]).await, 1);
+        assert_eq!(context.add_index_entries(&entries).await, 2);
+        assert_eq!(context.add_index_entries(&entries).await, 0);
+
+        // the versions synced later raise the max version, but not the yanked ones
+        context.apply_yanked();
+        context.apply_max_versions();
+        let program = context
+            .programs
+            .iter()
+            .find(|p| p.name == "index_test")
+            .unwrap();
+        assert_eq!(program.max_version.as_deref(), Some("1.1.0"));
+    }
+}
@@ -0,0 +1,204 @@
+//! The credentials for cloning private repos, per host.
+//!
+//! They are read from the toml file at `CLONE_CREDENTIALS`, such as
+//!
+//! ```toml
+//! [[hosts]]
+//! host = "github.com"
+//! token_env = "GITHUB_TOKEN"
+//!
+//! [[hosts]]
+//! host = "gitlab.example.com"
+//! ssh_key = "/home/rust/.ssh/id_ed25519"
+//! ```
+//!
+//! or from the env `CLONE_TOKEN_<HOST>` and `CLONE_SSH_KEY_<HOST>`,
+//! where `<HOST>` is the uppercase host with `_` for the other chars, e.g. `GITHUB_COM`.
+
+use git2::{Cred, CredentialType, RemoteCallbacks};
+use serde::Deserialize;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+#[derive(Default, Clone, Deserialize)]
+pub struct HostCredential {
+    pub host: String,
+    /// the user of the token or the ssh key,
+    /// `oauth2` for the tokens of GitLab and `x-access-token` for the others by default
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+    /// the env holding the token, to keep it out of the file
+    #[serde(default)]
+    pub token_env: Option<String>,
+    /// the private key, or the ssh agent is used
+    #[serde(default)]
+    pub ssh_key: Option<PathBuf>,
+    #[serde(default)]
+    pub ssh_passphrase_env: Option<String>,
+}
+
+/// never print the secrets
+impl fmt::Debug for HostCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostCredential")
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("token", &self.token.as_ref().map(|_| "***"))
+            .field("token_env", &self.token_env)
+            .field("ssh_key", &self.ssh_key)
+            .finish()
+    }
+}
+
+impl HostCredential {
+    pub(crate) fn token(&self) -> Option<String> {
+        self.token
+            .clone()
+            .or_else(|| self.token_env.as_ref().and_then(|e| env::var(e).ok()))
+    }
+
+    fn token_username(&self) -> &str {
+        match &self.username {
+            Some(username) => username,
+            None if self.host.contains("gitlab") => "oauth2",
+            None => "x-access-token",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Credentials {
+    #[serde(default)]
+    pub hosts: Vec<HostCredential>,
+}
+
+/// `github.com` -> `GITHUB_COM`
+fn env_suffix(host: &str) -> String {
+    host.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The host of an url, including the scp-like `git@github.com:owner/repo.git`.
+pub(crate) fn host_of(url: &str) -> Option<String> {
+    if url.contains("://") {
+        return Url::parse(url).ok()?.host_str().map(String::from);
+    }
+    let (_, rest) = url.split_once('@')?;
+    let (host, _) = rest.split_once(':')?;
+    Some(host.to_string())
+}
+
+impl Credentials {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Read the file at `CLONE_CREDENTIALS` if it's set.
+    pub fn from_env() -> Self {
+        match env::var("CLONE_CREDENTIALS") {
+            Ok(path) => Credentials::from_file(Path::new(&path)).unwrap_or_else(|e| {
+                tracing::error!("Failed to read the credentials {}: {}", path, e);
+                Credentials::default()
+            }),
+            Err(_) => Credentials::default(),
+        }
+    }
+
+    /// The credential of a host, where the env overrides the file.
+    pub(crate) fn for_host(&self, host: &str) -> HostCredential {
+        let mut credential = self
+            .hosts
+            .iter()
+            .find(|c| c.host.eq_ignore_ascii_case(host))
+            .cloned()
+            .unwrap_or_else(|| HostCredential {
+                host: host.to_string(),
+                ..Default::default()
+            });
+        let suffix = env_suffix(host);
+        if let Ok(token) = env::var(format!("CLONE_TOKEN_{}", suffix)) {
+            credential.token = Some(token);
+        }
+        if let Ok(ssh_key) = env::var(format!("CLONE_SSH_KEY_{}", suffix)) {
+            credential.ssh_key = Some(ssh_key.into());
+        }
+        credential
+    }
+
+    /// The callbacks answering the credential requests of cloning `url`.
+    ///
+    /// Each kind of credential is tried only once, since libgit2 asks again on failure.
+    pub(crate) fn remote_callbacks(&self, url: &str) -> RemoteCallbacks<'static> {
+        let credential = host_of(url)
+            .map(|host| self.for_host(&host))
+            .unwrap_or_default();
+        let mut tried_ssh = false;
+        let mut tried_token = false;
+
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(move |_url, username_from_url, allowed| {
+            let ssh_user = username_from_url
+                .or(credential.username.as_deref())
+                .unwrap_or("git");
+            if allowed.contains(CredentialType::USERNAME) {
+                return Cred::username(ssh_user);
+            }
+            if allowed.contains(CredentialType::SSH_KEY) && !tried_ssh {
+                tried_ssh = true;
+                return match &credential.ssh_key {
+                    Some(key) => {
+                        let passphrase = credential
+                            .ssh_passphrase_env
+                            .as_ref()
+                            .and_then(|e| env::var(e).ok());
+                        Cred::ssh_key(ssh_user, None, key, passphrase.as_deref())
+                    }
+                    None => Cred::ssh_key_from_agent(ssh_user),
+                };
+            }
+            if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !tried_token {
+                tried_token = true;
+                if let Some(token) = credential.token() {
+                    return Cred::userpass_plaintext(credential.token_username(), &token);
+                }
+            }
+            Err(git2::Error::from_str(&format!(
+                "no credentials for {}",
+                credential.host
+            )))
+        });
+        callbacks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(
+            host_of("https://github.com/rust-lang/rust.git").as_deref(),
+            Some("github.com")
+        );
+        assert_eq!(
+            host_of("git@gitlab.com:group/sub/project.git").as_deref(),
+            Some("gitlab.com")
+        );
+        assert_eq!(host_of("/local/path"), None);
+        assert_eq!(env_suffix("git.example-corp.com"), "GIT_EXAMPLE_CORP_COM");
+    }
+}
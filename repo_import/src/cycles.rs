@@ -0,0 +1,169 @@
+//! Detect the cycles of the dependency edges, usually through a dev-dependency such as
+//! `serde_derive` -> `serde` -> `serde_derive`, which cargo allows but the walks of
+//! the graph must stop at.
+//!
+//! The versions depending on each other are grouped, and the edges between them are
+//! flagged by `in_cycle`.
+
+use crate::report::{record_cycles, DependencyCycle};
+use crate::ImportContext;
+use std::collections::{BTreeSet, HashMap};
+
+const UNVISITED: usize = usize::MAX;
+
+/// The strongly connected components with several nodes or a self loop, by Tarjan's
+/// algorithm without recursion, since the chains of dependencies may be long.
+fn cyclic_components(adjacency: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let n = adjacency.len();
+    let mut index = vec![UNVISITED; n];
+    let mut lowlink = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut next = 0;
+    let mut components = vec![];
+    for start in 0..n {
+        if index[start] != UNVISITED {
+            continue;
+        }
+        // (node, the next edge of it to visit)
+        let mut call_stack = vec![(start, 0)];
+        index[start] = next;
+        lowlink[start] = next;
+        next += 1;
+        stack.push(start);
+        on_stack[start] = true;
+        while let Some((v, i)) = call_stack.pop() {
+            if let Some(&w) = adjacency[v].get(i) {
+                call_stack.push((v, i + 1));
+                if index[w] == UNVISITED {
+                    index[w] = next;
+                    lowlink[w] = next;
+                    next += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    call_stack.push((w, 0));
+                } else if on_stack[w] {
+                    lowlink[v] = lowlink[v].min(index[w]);
+                }
+                continue;
+            }
+            if lowlink[v] == index[v] {
+                let mut component = vec![];
+                while let Some(w) = stack.pop() {
+                    on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                if component.len() > 1 || adjacency[v].contains(&v) {
+                    components.push(component);
+                }
+            }
+            if let Some(&(parent, _)) = call_stack.last() {
+                lowlink[parent] = lowlink[parent].min(lowlink[v]);
+            }
+        }
+    }
+    components
+}
+
+impl ImportContext {
+    /// Flag the dependency edges in cycles, and record the cycles in the import report.
+    pub(crate) fn detect_cycles(&mut self) {
+        let edges: Vec<(&str, &str)> = self
+            .depends_on
+            .iter()
+            .map(|e| (e.SRC_ID.as_str(), e.DST_ID.as_str()))
+            .chain(
+                self.dev_depends_on
+                    .iter()
+                    .map(|e| (e.SRC_ID.as_str(), e.DST_ID.as_str())),
+            )
+            .chain(
+                self.build_depends_on
+                    .iter()
+                    .map(|e| (e.SRC_ID.as_str(), e.DST_ID.as_str())),
+            )
+            .collect();
+        let mut ids: HashMap<&str, usize> = HashMap::new();
+        let mut names: Vec<&str> = vec![];
+        let mut adjacency: Vec<Vec<usize>> = vec![];
+        for (src, dst) in edges.iter().copied() {
+            for node in [src, dst] {
+                ids.entry(node).or_insert_with(|| {
+                    names.push(node);
+                    adjacency.push(vec![]);
+                    names.len() - 1
+                });
+            }
+            adjacency[ids[src]].push(ids[dst]);
+        }
+        let components: Vec<Vec<String>> = cyclic_components(&adjacency)
+            .into_iter()
+            .map(|c| {
+                let mut versions: Vec<String> = c.iter().map(|&v| names[v].to_string()).collect();
+                versions.sort();
+                versions
+            })
+            .collect();
+        // name_and_version -> the component it's in
+        let component_of: HashMap<String, usize> = components
+            .iter()
+            .enumerate()
+            .flat_map(|(i, c)| c.iter().map(move |v| (v.clone(), i)))
+            .collect();
+
+        let mut labels: Vec<BTreeSet<&str>> = vec![BTreeSet::new(); components.len()];
+        let mut in_cycle = |label: &'static str, src: &str, dst: &str| match (
+            component_of.get(src),
+            component_of.get(dst),
+        ) {
+            (Some(a), Some(b)) if a == b => {
+                labels[*a].insert(label);
+                true
+            }
+            _ => false,
+        };
+        for edge in &mut self.depends_on {
+            edge.in_cycle = in_cycle("depends_on", &edge.SRC_ID, &edge.DST_ID);
+        }
+        for edge in &mut self.dev_depends_on {
+            edge.in_cycle = in_cycle("dev_depends_on", &edge.SRC_ID, &edge.DST_ID);
+        }
+        for edge in &mut self.build_depends_on {
+            edge.in_cycle = in_cycle("build_depends_on", &edge.SRC_ID, &edge.DST_ID);
+        }
+
+        let mut cycles: Vec<DependencyCycle> = components
+            .into_iter()
+            .zip(labels)
+            .map(|(versions, labels)| DependencyCycle {
+                versions,
+                labels: labels.into_iter().map(String::from).collect(),
+            })
+            .collect();
+        cycles.sort_by(|a, b| a.versions.cmp(&b.versions));
+        if !cycles.is_empty() {
+            tracing::info!("Found {} dependency cycles", cycles.len());
+        }
+        record_cycles(cycles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cyclic_components() {
+        // 0 -> 1 -> 2 -> 0, 2 -> 3, 4 -> 4
+        let adjacency = vec![vec![1], vec![2], vec![0, 3], vec![], vec![4]];
+        let mut components = cyclic_components(&adjacency);
+        for c in &mut components {
+            c.sort();
+        }
+        components.sort();
+        assert_eq!(components, [vec![0, 1, 2], vec![4]]);
+    }
+}
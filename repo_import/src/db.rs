@@ -0,0 +1,203 @@
+//! A sink which upserts the extracted programs into PostgreSQL directly,
+//! instead of writing csv files and loading them by `COPY`.
+
+use model::tugraph_model::{Application, Library, Program};
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use sqlx::{Postgres, QueryBuilder};
+use std::collections::HashMap;
+use std::env;
+
+/// The number of rows in one `INSERT` statement,
+/// postgres allows at most 65535 bind parameters in a statement.
+const UPSERT_BATCH_SIZE: usize = 1000;
+
+const CREATE_TABLES: &str = r#"
+CREATE TABLE IF NOT EXISTS "import_programs" (
+  "id" TEXT PRIMARY KEY,
+  "name" TEXT NOT NULL,
+  "description" TEXT,
+  "namespace" TEXT,
+  "max_version" TEXT,
+  "github_url" TEXT,
+  "mega_url" TEXT,
+  "doc_url" TEXT,
+  "license" TEXT,
+  "homepage" TEXT,
+  "keywords" TEXT,
+  "categories" TEXT,
+  "edition" TEXT,
+  "rust_version" TEXT,
+  "readme" TEXT,
+  "authors" TEXT,
+  "target_kinds" TEXT,
+  "detected_license" TEXT,
+  "updated_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+ALTER TABLE "import_programs" ADD COLUMN IF NOT EXISTS "detected_license" TEXT;
+
+CREATE TABLE IF NOT EXISTS "import_libraries" (
+  "id" TEXT PRIMARY KEY,
+  "name" TEXT NOT NULL,
+  "downloads" BIGINT NOT NULL DEFAULT 0,
+  "cratesio" TEXT,
+  "updated_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS "import_applications" (
+  "id" TEXT PRIMARY KEY,
+  "name" TEXT NOT NULL,
+  "updated_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+"#;
+
+fn env_var(key: &str) -> Result<String, sqlx::Error> {
+    env::var(key).map_err(|e| sqlx::Error::Configuration(format!("{}: {}", key, e).into()))
+}
+
+/// Read the `POSTGRES_*` env, the error names the variable missing or invalid.
+pub fn pg_connect_options_from_env() -> Result<PgConnectOptions, sqlx::Error> {
+    let port = env_var("POSTGRES_HOST_PORT")?
+        .parse::<u16>()
+        .map_err(|e| sqlx::Error::Configuration(format!("POSTGRES_HOST_PORT: {}", e).into()))?;
+    Ok(PgConnectOptions::new()
+        .host(&env_var("POSTGRES_HOST_IP")?)
+        .port(port)
+        .username(&env_var("POSTGRES_USER_NAME")?)
+        .password(&env_var("POSTGRES_USER_PASSWORD")?)
+        .database(&env_var("POSTGRES_CRATESPRO_DB")?))
+}
+
+/// A statement can't upsert the same row twice, so keep the last record of each id.
+fn dedup_by_id<T>(items: &[T], id: impl Fn(&T) -> &str) -> Vec<&T> {
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    let mut res: Vec<&T> = vec![];
+    for item in items {
+        match index.get(id(item)) {
+            Some(&i) => res[i] = item,
+            None => {
+                index.insert(id(item), res.len());
+                res.push(item);
+            }
+        }
+    }
+    res
+}
+
+pub struct PgSink {
+    pool: PgPool,
+}
+
+impl PgSink {
+    /// Connect by the `POSTGRES_*` env, and create the tables if not exist.
+    pub async fn connect() -> Result<Self, sqlx::Error> {
+        let pool = PgPoolOptions::new()
+            .max_connections(4)
+            .connect_with(pg_connect_options_from_env()?)
+            .await?;
+        sqlx::raw_sql(CREATE_TABLES).execute(&pool).await?;
+        Ok(Self { pool })
+    }
+
+    pub async fn upsert_programs(&self, programs: &[Program]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let programs = dedup_by_id(programs, |x| &x.id);
+        for chunk in programs.chunks(UPSERT_BATCH_SIZE) {
+            let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+                r#"INSERT INTO "import_programs" ("id", "name", "description", "namespace",
+                "max_version", "github_url", "mega_url", "doc_url", "license", "homepage",
+                "keywords", "categories", "edition", "rust_version", "readme", "authors",
+                "target_kinds", "detected_license") "#,
+            );
+            builder.push_values(chunk, |mut b, p| {
+                b.push_bind(&p.id)
+                    .push_bind(&p.name)
+                    .push_bind(&p.description)
+                    .push_bind(&p.namespace)
+                    .push_bind(&p.max_version)
+                    .push_bind(&p.github_url)
+                    .push_bind(&p.mega_url)
+                    .push_bind(&p.doc_url)
+                    .push_bind(&p.license)
+                    .push_bind(&p.homepage)
+                    .push_bind(&p.keywords)
+                    .push_bind(&p.categories)
+                    .push_bind(&p.edition)
+                    .push_bind(&p.rust_version)
+                    .push_bind(&p.readme)
+                    .push_bind(&p.authors)
+                    .push_bind(&p.target_kinds)
+                    .push_bind(&p.detected_license);
+            });
+            builder.push(
+                r#" ON CONFLICT ("id") DO UPDATE SET
+                "name" = EXCLUDED."name",
+                "description" = EXCLUDED."description",
+                "namespace" = EXCLUDED."namespace",
+                "max_version" = EXCLUDED."max_version",
+                "github_url" = EXCLUDED."github_url",
+                "mega_url" = EXCLUDED."mega_url",
+                "doc_url" = EXCLUDED."doc_url",
+                "license" = EXCLUDED."license",
+                "homepage" = EXCLUDED."homepage",
+                "keywords" = EXCLUDED."keywords",
+                "categories" = EXCLUDED."categories",
+                "edition" = EXCLUDED."edition",
+                "rust_version" = EXCLUDED."rust_version",
+                "readme" = EXCLUDED."readme",
+                "authors" = EXCLUDED."authors",
+                "target_kinds" = EXCLUDED."target_kinds",
+                "detected_license" = EXCLUDED."detected_license",
+                "updated_at" = NOW()"#,
+            );
+            builder.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn upsert_libraries(&self, libraries: &[Library]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let libraries = dedup_by_id(libraries, |x| &x.id);
+        for chunk in libraries.chunks(UPSERT_BATCH_SIZE) {
+            let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+                r#"INSERT INTO "import_libraries" ("id", "name", "downloads", "cratesio") "#,
+            );
+            builder.push_values(chunk, |mut b, l| {
+                b.push_bind(&l.id)
+                    .push_bind(&l.name)
+                    .push_bind(l.downloads)
+                    .push_bind(&l.cratesio);
+            });
+            builder.push(
+                r#" ON CONFLICT ("id") DO UPDATE SET
+                "name" = EXCLUDED."name",
+                "downloads" = EXCLUDED."downloads",
+                "cratesio" = EXCLUDED."cratesio",
+                "updated_at" = NOW()"#,
+            );
+            builder.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn upsert_applications(
+        &self,
+        applications: &[Application],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let applications = dedup_by_id(applications, |x| &x.id);
+        for chunk in applications.chunks(UPSERT_BATCH_SIZE) {
+            let mut builder: QueryBuilder<Postgres> =
+                QueryBuilder::new(r#"INSERT INTO "import_applications" ("id", "name") "#);
+            builder.push_values(chunk, |mut b, a| {
+                b.push_bind(&a.id).push_bind(&a.name);
+            });
+            builder.push(
+                r#" ON CONFLICT ("id") DO UPDATE SET
+                "name" = EXCLUDED."name",
+                "updated_at" = NOW()"#,
+            );
+            builder.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await
+    }
+}
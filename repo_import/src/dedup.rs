@@ -0,0 +1,153 @@
+//! Deduplication of the versions by `(name, version)` across repos.
+//!
+//! A version found in several repos, such as a mirror or a fork, is a single node, whose
+//! `sources` are the urls of all the repos. If the copies are of different commits, they
+//! conflict, and `IMPORT_DEDUP_POLICY` decides the copy whose tag, commit, dependencies and
+//! features are kept:
+//! - `first` (by default), the copy imported first,
+//! - `latest`, the copy of the latest commit.
+
+use crate::utils::name_join_version;
+use crate::version_info::Dependencies;
+use crate::ImportContext;
+use serde::{Deserialize, Serialize};
+use std::env;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DedupPolicy {
+    First,
+    Latest,
+}
+
+impl DedupPolicy {
+    pub(crate) fn from_env() -> Self {
+        match env::var("IMPORT_DEDUP_POLICY").as_deref() {
+            Ok("latest") => DedupPolicy::Latest,
+            Ok("first") | Err(_) => DedupPolicy::First,
+            Ok(policy) => {
+                tracing::warn!("Unknown IMPORT_DEDUP_POLICY {}, use first", policy);
+                DedupPolicy::First
+            }
+        }
+    }
+
+    /// The index of the copy to keep.
+    fn pick(self, sources: &[VersionSource]) -> usize {
+        match self {
+            DedupPolicy::First => 0,
+            DedupPolicy::Latest => sources
+                .iter()
+                .enumerate()
+                // the first of the latest ones
+                .max_by_key(|(i, s)| (s.commit_time, std::cmp::Reverse(*i)))
+                .map(|(i, _)| i)
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// A repo which a version is found in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct VersionSource {
+    pub(crate) git_url: String,
+    pub(crate) tag_name: Option<String>,
+    pub(crate) commit: Option<String>,
+    pub(crate) commit_time: Option<i64>,
+}
+
+impl From<&Dependencies> for VersionSource {
+    fn from(dependencies: &Dependencies) -> Self {
+        VersionSource {
+            git_url: dependencies.git_url.clone(),
+            // the versions at HEAD are not tagged
+            tag_name: dependencies
+                .commit
+                .as_ref()
+                .map(|_| dependencies.tag_name.clone()),
+            commit: dependencies.commit.clone(),
+            commit_time: dependencies.commit_time,
+        }
+    }
+}
+
+impl ImportContext {
+    /// Record a repo which a version is found in, once for each repo.
+    /// Return true if the policy keeps this copy instead of the ones found before.
+    pub(crate) fn record_version_source(&mut self, dependencies: &Dependencies) -> bool {
+        let source = VersionSource::from(dependencies);
+        let name_and_version = name_join_version(&dependencies.crate_name, &dependencies.version);
+        let sources = self.version_sources.entry(name_and_version).or_default();
+        if sources.iter().any(|s| s.git_url == source.git_url) {
+            return false;
+        }
+        if let Some(first) = sources.first() {
+            if first.commit != source.commit {
+                tracing::warn!(
+                    "Conflicting copies of {}/{}: {:?} in {} and {:?} in {}",
+                    dependencies.crate_name,
+                    dependencies.version,
+                    first.commit,
+                    first.git_url,
+                    source.commit,
+                    source.git_url
+                );
+            }
+        }
+        sources.push(source);
+        sources.len() > 1 && DedupPolicy::from_env().pick(sources) == sources.len() - 1
+    }
+
+    /// Fill the `sources` of the library and application versions found in several repos,
+    /// and their tag and commit by the policy.
+    pub(crate) fn apply_version_sources(&mut self) {
+        let policy = DedupPolicy::from_env();
+        let merged = |name_and_version: &str| {
+            let sources = self
+                .version_sources
+                .get(name_and_version)
+                .filter(|s| s.len() > 1)?;
+            let urls: Vec<&str> = sources.iter().map(|s| s.git_url.as_str()).collect();
+            Some((urls.join(","), &sources[policy.pick(sources)]))
+        };
+        for version in &mut self.library_versions {
+            if let Some((urls, kept)) = merged(&version.name_and_version) {
+                version.sources = Some(urls);
+                version.tag_name.clone_from(&kept.tag_name);
+                version.commit.clone_from(&kept.commit);
+                version.commit_time = kept.commit_time;
+            }
+        }
+        for version in &mut self.application_versions {
+            if let Some((urls, kept)) = merged(&version.name_and_version) {
+                version.sources = Some(urls);
+                version.tag_name.clone_from(&kept.tag_name);
+                version.commit.clone_from(&kept.commit);
+                version.commit_time = kept.commit_time;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_policy() {
+        let source = |git_url: &str, commit_time| VersionSource {
+            git_url: git_url.to_string(),
+            tag_name: Some("v1.0.0".to_string()),
+            commit: Some(git_url.to_string()),
+            commit_time,
+        };
+        let sources = [
+            source("a", Some(2)),
+            source("b", Some(3)),
+            source("c", Some(3)),
+            source("d", None),
+        ];
+        assert_eq!(DedupPolicy::First.pick(&sources), 0);
+        assert_eq!(DedupPolicy::Latest.pick(&sources), 1);
+        assert_eq!(DedupPolicy::Latest.pick(&sources[3..]), 0);
+    }
+}
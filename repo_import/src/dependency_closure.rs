@@ -0,0 +1,210 @@
+//! The transitive dependencies of a version by the `depends_on` edges, with the depth of
+//! each one, 1 for a direct dependency, so that the consumers don't walk the graph themselves.
+//!
+//! The closures are cached by the version, and a cached closure is reused when another one
+//! reaches its version, so the closures of all the versions of a crate share their subtrees.
+
+use crate::utils::name_join_version;
+use crate::ImportContext;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClosureEntry {
+    pub name_and_version: String,
+    /// the length of the shortest path to it
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyClosure {
+    pub name_and_version: String,
+    pub count: usize,
+    pub max_depth: usize,
+    pub dependencies: Arc<Vec<ClosureEntry>>,
+}
+
+impl DependencyClosure {
+    pub fn new(name_and_version: &str, dependencies: Arc<Vec<ClosureEntry>>) -> Self {
+        DependencyClosure {
+            name_and_version: name_and_version.to_string(),
+            count: dependencies.len(),
+            max_depth: dependencies.iter().map(|e| e.depth).max().unwrap_or(0),
+            dependencies,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ClosureCache {
+    closures: HashMap<String, Arc<Vec<ClosureEntry>>>,
+}
+
+impl ClosureCache {
+    pub fn get(&self, name_and_version: &str) -> Option<Arc<Vec<ClosureEntry>>> {
+        self.closures.get(name_and_version).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.closures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.closures.is_empty()
+    }
+
+    pub fn insert(&mut self, name_and_version: &str, closure: Arc<Vec<ClosureEntry>>) {
+        self.closures.insert(name_and_version.to_string(), closure);
+    }
+
+    pub fn clear(&mut self) {
+        self.closures.clear();
+    }
+
+    /// The closure of `root`, walking the direct dependencies given by `dependencies`
+    /// breadth first, but not the ones of a version whose closure is cached.
+    pub fn closure<F>(&mut self, root: &str, dependencies: F) -> Arc<Vec<ClosureEntry>>
+    where
+        F: Fn(&str) -> Vec<String>,
+    {
+        if let Some(closure) = self.closures.get(root) {
+            return closure.clone();
+        }
+        let mut depths: HashMap<String, usize> = HashMap::new();
+        let mut min_depth = |name: &str, depth: usize| {
+            if name != root {
+                let d = depths.entry(name.to_string()).or_insert(depth);
+                *d = (*d).min(depth);
+            }
+        };
+        let mut visited: HashSet<String> = HashSet::from([root.to_string()]);
+        let mut queue: VecDeque<(String, usize)> = VecDeque::from([(root.to_string(), 0)]);
+        while let Some((current, depth)) = queue.pop_front() {
+            for dependency in dependencies(&current) {
+                if !visited.insert(dependency.clone()) {
+                    continue;
+                }
+                min_depth(&dependency, depth + 1);
+                match self.closures.get(&dependency) {
+                    // the shortest paths through it are in its closure
+                    Some(closure) => {
+                        for entry in closure.iter() {
+                            min_depth(&entry.name_and_version, depth + 1 + entry.depth);
+                        }
+                    }
+                    None => queue.push_back((dependency, depth + 1)),
+                }
+            }
+        }
+        let mut depths: Vec<(usize, String)> = depths.into_iter().map(|(n, d)| (d, n)).collect();
+        depths.sort();
+        let closure: Arc<Vec<ClosureEntry>> = Arc::new(
+            depths
+                .into_iter()
+                .map(|(depth, name_and_version)| ClosureEntry {
+                    name_and_version,
+                    depth,
+                })
+                .collect(),
+        );
+        self.closures.insert(root.to_string(), closure.clone());
+        closure
+    }
+}
+
+impl ImportContext {
+    /// The closures of a version of a crate, or of all its versions if the version is not
+    /// given, the imported versions only.
+    pub fn dependency_closures(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        cache: &mut ClosureCache,
+    ) -> Vec<DependencyClosure> {
+        let mut dependencies: HashMap<&str, Vec<String>> = HashMap::new();
+        for edge in &self.depends_on {
+            dependencies
+                .entry(edge.SRC_ID.as_str())
+                .or_default()
+                .push(edge.DST_ID.clone());
+        }
+        let roots: Vec<String> = match version {
+            Some(version) => vec![name_join_version(name, version)],
+            None => self
+                .library_versions
+                .iter()
+                .map(|v| (&v.name, &v.version))
+                .chain(
+                    self.application_versions
+                        .iter()
+                        .map(|v| (&v.name, &v.version)),
+                )
+                .filter(|(n, _)| *n == name)
+                .map(|(n, v)| name_join_version(n, v))
+                .collect(),
+        };
+        roots
+            .iter()
+            .filter(|root| self.versions.iter().any(|v| &v.name_and_version == *root))
+            .map(|root| {
+                let closure =
+                    cache.closure(root, |v| dependencies.get(v).cloned().unwrap_or_default());
+                DependencyClosure::new(root, closure)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closure() {
+        let edges: HashMap<&str, Vec<&str>> = HashMap::from([
+            ("app/1.0.0", vec!["a/1.0.0", "b/1.0.0"]),
+            ("a/1.0.0", vec!["c/1.0.0"]),
+            ("b/1.0.0", vec!["d/1.0.0"]),
+            ("d/1.0.0", vec!["c/1.0.0", "app/1.0.0"]),
+        ]);
+        let dependencies = |v: &str| -> Vec<String> {
+            edges
+                .get(v)
+                .into_iter()
+                .flatten()
+                .map(|d| d.to_string())
+                .collect()
+        };
+        let depths = |closure: &[ClosureEntry]| -> Vec<(String, usize)> {
+            closure
+                .iter()
+                .map(|e| (e.name_and_version.clone(), e.depth))
+                .collect()
+        };
+
+        let mut cache = ClosureCache::default();
+        let b = cache.closure("b/1.0.0", dependencies);
+        assert_eq!(
+            depths(&b),
+            [
+                ("d/1.0.0".to_string(), 1),
+                ("app/1.0.0".to_string(), 2),
+                ("c/1.0.0".to_string(), 2),
+                ("a/1.0.0".to_string(), 3),
+            ]
+        );
+        // reuses the closure of b, without the cycle back to app
+        let app = cache.closure("app/1.0.0", dependencies);
+        assert_eq!(
+            depths(&app),
+            [
+                ("a/1.0.0".to_string(), 1),
+                ("b/1.0.0".to_string(), 1),
+                ("c/1.0.0".to_string(), 2),
+                ("d/1.0.0".to_string(), 2),
+            ]
+        );
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,112 @@
+//! The reverse dependencies of a version in the imported graph: the versions depending on it
+//! by `depends_on` edges, directly or through other versions.
+
+use crate::utils::name_join_version;
+use crate::ImportContext;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Dependents {
+    pub name: String,
+    pub version: String,
+    /// the versions depending on it directly
+    pub direct: Vec<String>,
+    /// the versions depending on it only through other versions
+    pub transitive: Vec<String>,
+    pub direct_count: usize,
+    pub transitive_count: usize,
+    /// the crates of all the dependents
+    pub crate_count: usize,
+}
+
+pub(crate) fn crate_of(name_and_version: &str) -> &str {
+    name_and_version
+        .split_once('/')
+        .map_or(name_and_version, |(name, _)| name)
+}
+
+/// Walk the dependents of `root` breadth first, by the reversed edges `(dependent, dependency)`.
+fn walk_dependents<'a>(
+    edges: impl Iterator<Item = (&'a str, &'a str)>,
+    root: &'a str,
+) -> (Vec<String>, Vec<String>) {
+    let mut reverse: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (src, dst) in edges {
+        reverse.entry(dst).or_default().push(src);
+    }
+    let direct: BTreeSet<&str> = reverse.get(root).into_iter().flatten().copied().collect();
+    let mut visited: HashSet<&str> = HashSet::from([root]);
+    let mut transitive = BTreeSet::new();
+    let mut queue: VecDeque<&str> = direct.iter().copied().collect();
+    while let Some(current) = queue.pop_front() {
+        if !visited.insert(current) {
+            continue;
+        }
+        if !direct.contains(current) {
+            transitive.insert(current);
+        }
+        queue.extend(reverse.get(current).into_iter().flatten());
+    }
+    let to_vec = |set: BTreeSet<&str>| set.into_iter().map(String::from).collect();
+    (to_vec(direct), to_vec(transitive))
+}
+
+impl ImportContext {
+    /// The dependents of a version of a crate, the max version by default,
+    /// none if the crate or the version is not imported.
+    pub fn dependents(&self, name: &str, version: Option<&str>) -> Option<Dependents> {
+        let version = match version {
+            Some(version) => version.to_string(),
+            None => self
+                .programs
+                .iter()
+                .find(|p| p.name == name)
+                .and_then(|p| p.max_version.clone())?,
+        };
+        let root = name_join_version(name, &version);
+        if !self.versions.iter().any(|v| v.name_and_version == root) {
+            return None;
+        }
+        let edges = self
+            .depends_on
+            .iter()
+            .map(|e| (e.SRC_ID.as_str(), e.DST_ID.as_str()));
+        let (direct, transitive) = walk_dependents(edges, &root);
+        let crates: HashSet<&str> = direct
+            .iter()
+            .chain(&transitive)
+            .map(|d| crate_of(d))
+            .collect();
+        Some(Dependents {
+            name: name.to_string(),
+            version,
+            direct_count: direct.len(),
+            transitive_count: transitive.len(),
+            crate_count: crates.len(),
+            direct,
+            transitive,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walk_dependents() {
+        let edges = [
+            ("tokio-util/0.7.0", "bytes/1.0.0"),
+            ("tokio/1.0.0", "bytes/1.0.0"),
+            ("tokio-util/0.7.0", "tokio/1.0.0"),
+            ("hyper/1.0.0", "tokio-util/0.7.0"),
+            ("bytes/1.0.0", "hyper/1.0.0"),
+            ("serde/1.0.0", "serde_derive/1.0.0"),
+        ];
+        let (direct, transitive) = walk_dependents(edges.into_iter(), "bytes/1.0.0");
+        assert_eq!(direct, ["tokio-util/0.7.0", "tokio/1.0.0"]);
+        // the cycle back to bytes is not a dependent
+        assert_eq!(transitive, ["hyper/1.0.0"]);
+    }
+}
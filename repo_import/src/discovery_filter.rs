@@ -0,0 +1,194 @@
+//! Include/exclude filters of the crate discovery, on the repo names, the crate names,
+//! and the paths in a repo, such as skipping `fuzz/*` or only importing `tokio-*`.
+//!
+//! The filters are comma separated patterns in
+//! - `CRATE_DISCOVERY_INCLUDE_REPOS` and `CRATE_DISCOVERY_EXCLUDE_REPOS`, matched with
+//!   the repo name and `<owner>/<repo>`,
+//! - `CRATE_DISCOVERY_INCLUDE_CRATES` and `CRATE_DISCOVERY_EXCLUDE_CRATES`, matched with
+//!   the package name,
+//! - `CRATE_DISCOVERY_INCLUDE_PATHS` and `CRATE_DISCOVERY_EXCLUDE_PATHS`, matched with
+//!   the path relative to the repo, where an excluded directory is not walked into,
+//!   and an included path is the one of a `Cargo.toml`.
+//!
+//! A pattern is a glob, where `*` and `?` do not match `/` but `**` does,
+//! or a regex prefixed by `re:`, such as `re:^tokio(-.*)?$`. A name is kept if it matches
+//! one of the include patterns (if any), and none of the exclude patterns.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::env;
+
+static DISCOVERY_FILTER: Lazy<DiscoveryFilter> = Lazy::new(DiscoveryFilter::from_env);
+
+/// The filter of `CRATE_DISCOVERY_*`.
+pub(crate) fn discovery_filter() -> &'static DiscoveryFilter {
+    &DISCOVERY_FILTER
+}
+
+/// The regex of a glob, anchored at both ends.
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+    let mut res = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                res.push_str(".*");
+            }
+            '*' => res.push_str("[^/]*"),
+            '?' => res.push_str("[^/]"),
+            c => res.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    res.push('$');
+    res
+}
+
+fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+    match pattern.strip_prefix("re:") {
+        Some(re) => Regex::new(re),
+        None => Regex::new(&glob_to_regex(pattern)),
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PatternSet {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl PatternSet {
+    /// The patterns in `CRATE_DISCOVERY_{INCLUDE,EXCLUDE}_<kind>`, the invalid ones are skipped.
+    fn from_env(kind: &str) -> Self {
+        let read = |key: String| -> Vec<Regex> {
+            let patterns = env::var(&key).unwrap_or_default();
+            patterns
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .filter_map(|p| match compile(p) {
+                    Ok(re) => Some(re),
+                    Err(e) => {
+                        tracing::warn!("Skip the invalid pattern {} in {}: {}", p, key, e);
+                        None
+                    }
+                })
+                .collect()
+        };
+        PatternSet {
+            include: read(format!("CRATE_DISCOVERY_INCLUDE_{}", kind)),
+            exclude: read(format!("CRATE_DISCOVERY_EXCLUDE_{}", kind)),
+        }
+    }
+
+    pub(crate) fn new(include: &[&str], exclude: &[&str]) -> Result<Self, regex::Error> {
+        Ok(PatternSet {
+            include: include
+                .iter()
+                .map(|p| compile(p))
+                .collect::<Result<_, _>>()?,
+            exclude: exclude
+                .iter()
+                .map(|p| compile(p))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn is_excluded<'a>(&self, mut candidates: impl Iterator<Item = &'a str>) -> bool {
+        candidates.any(|c| self.exclude.iter().any(|re| re.is_match(c)))
+    }
+
+    fn is_included<'a>(&self, candidates: impl Iterator<Item = &'a str> + Clone) -> bool {
+        (self.include.is_empty()
+            || candidates
+                .clone()
+                .any(|c| self.include.iter().any(|re| re.is_match(c))))
+            && !self.is_excluded(candidates)
+    }
+
+    pub(crate) fn matches(&self, candidate: &str) -> bool {
+        self.is_included(std::iter::once(candidate))
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct DiscoveryFilter {
+    repos: PatternSet,
+    crates: PatternSet,
+    paths: PatternSet,
+}
+
+impl DiscoveryFilter {
+    fn from_env() -> Self {
+        DiscoveryFilter {
+            repos: PatternSet::from_env("REPOS"),
+            crates: PatternSet::from_env("CRATES"),
+            paths: PatternSet::from_env("PATHS"),
+        }
+    }
+
+    /// Whether to import a repo, given by its url or namespace.
+    pub(crate) fn is_repo_included(&self, repo: &str) -> bool {
+        let segments: Vec<&str> = repo
+            .trim_end_matches('/')
+            .trim_end_matches(".git")
+            .rsplit('/')
+            .take(2)
+            .collect();
+        let name = segments.first().copied().unwrap_or_default();
+        let owner_repo = segments
+            .get(1)
+            .map(|owner| format!("{}/{}", owner, name))
+            .unwrap_or_else(|| name.to_string());
+        self.repos
+            .is_included([name, owner_repo.as_str()].into_iter())
+    }
+
+    pub(crate) fn is_crate_included(&self, name: &str) -> bool {
+        self.crates.is_included(std::iter::once(name))
+    }
+
+    /// Whether a directory or a file in a repo, such as `fuzz/fuzz_targets`, is excluded.
+    pub(crate) fn is_path_excluded(&self, relative: &str) -> bool {
+        !relative.is_empty() && self.paths.is_excluded(std::iter::once(relative))
+    }
+
+    /// Whether a `Cargo.toml` in a repo, such as `crates/foo/Cargo.toml`, is included.
+    pub(crate) fn is_manifest_included(&self, relative: &str) -> bool {
+        self.paths.is_included(std::iter::once(relative))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discovery_filter() {
+        let filter = DiscoveryFilter {
+            repos: PatternSet::new(&["tokio-rs/*", "re:^rust-lang/"], &["*-archive"]).unwrap(),
+            crates: PatternSet::new(&[], &["*-fuzz"]).unwrap(),
+            paths: PatternSet::new(&["Cargo.toml", "crates/**"], &["fuzz/*", "**/benches"])
+                .unwrap(),
+        };
+        assert!(filter.is_repo_included("https://github.com/tokio-rs/tokio.git"));
+        assert!(filter.is_repo_included("https://github.com/rust-lang/cargo"));
+        assert!(!filter.is_repo_included("https://github.com/serde-rs/serde"));
+        assert!(!filter.is_repo_included("https://github.com/tokio-rs/tokio-archive"));
+
+        assert!(filter.is_crate_included("tokio"));
+        assert!(!filter.is_crate_included("tokio-fuzz"));
+
+        assert!(filter.is_path_excluded("fuzz/fuzz_targets"));
+        assert!(filter.is_path_excluded("crates/foo/benches"));
+        assert!(!filter.is_path_excluded("fuzz"));
+        assert!(!filter.is_path_excluded(""));
+        assert!(filter.is_manifest_included("Cargo.toml"));
+        assert!(filter.is_manifest_included("crates/foo/Cargo.toml"));
+        assert!(!filter.is_manifest_included("examples/foo/Cargo.toml"));
+
+        let all = DiscoveryFilter::default();
+        assert!(all.is_repo_included("tokio"));
+        assert!(all.is_manifest_included("examples/foo/Cargo.toml"));
+    }
+}
@@ -0,0 +1,156 @@
+//! The documentation url of the library versions, the `documentation` of the manifest if
+//! it's an http(s) url, or the page of the version on docs.rs.
+//!
+//! Whether docs.rs built the docs of each version is checked by its status API, apart from
+//! the import by `crates-pro sync docsrs`, which updates the checkpoint with at most `--limit`
+//! versions per run, the latest published first, and at most one request per
+//! `DOCSRS_API_INTERVAL_MS`, 1000 by default. A version missing on docs.rs is recorded as not
+//! built, so it's not requested again.
+
+use crate::retry::{is_transient_http, RateLimiter, RetryPolicy};
+use crate::ImportContext;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+use url::Url;
+
+const DOCSRS_URL: &str = "https://docs.rs/";
+
+/// The documentation url of a version before it's resolved
+const UNRESOLVED: &str = "???";
+
+fn docsrs_url(name: &str, version: &str) -> String {
+    format!("{}{}/{}", DOCSRS_URL, name, version)
+}
+
+/// The declared url if it's valid, or the docs.rs one.
+fn documentation_url(declared: Option<&str>, name: &str, version: &str) -> String {
+    // an omitted `documentation` is "None" in the program
+    let declared = declared.map(str::trim).filter(|d| *d != "None");
+    match declared.map(|d| (d, Url::parse(d))) {
+        Some((d, Ok(url))) if matches!(url.scheme(), "http" | "https") => d.to_string(),
+        Some((d, _)) => {
+            tracing::debug!("{}: invalid documentation url {}", name, d);
+            docsrs_url(name, version)
+        }
+        None => docsrs_url(name, version),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DocStatus {
+    doc_status: bool,
+}
+
+pub struct DocsRsClient {
+    client: Client,
+    limiter: RateLimiter,
+    retry: RetryPolicy,
+}
+
+impl DocsRsClient {
+    pub fn new() -> Result<Self, reqwest::Error> {
+        let client = Client::builder()
+            .user_agent(concat!("crates-pro/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self {
+            client,
+            limiter: RateLimiter::from_env("DOCSRS_API_INTERVAL_MS", 1000),
+            retry: RetryPolicy::from_env(),
+        })
+    }
+
+    /// Whether docs.rs built the docs of a version, false if it's not on docs.rs.
+    pub(crate) async fn doc_status(
+        &mut self,
+        name: &str,
+        version: &str,
+    ) -> Result<bool, reqwest::Error> {
+        self.limiter.wait().await;
+        let url = format!("{}crate/{}/{}/status.json", DOCSRS_URL, name, version);
+        let client = &self.client;
+        let url = &url;
+        self.retry
+            .retry(url, is_transient_http, || async move {
+                let resp = client.get(url).send().await?;
+                if resp.status() == StatusCode::NOT_FOUND {
+                    return Ok(false);
+                }
+                let status: DocStatus = resp.error_for_status()?.json().await?;
+                Ok(status.doc_status)
+            })
+            .await
+    }
+}
+
+impl ImportContext {
+    /// Fill the documentation urls of the library versions not resolved yet.
+    pub(crate) fn resolve_documentation(&mut self) {
+        let declared: HashMap<&str, &str> = self
+            .programs
+            .iter()
+            .filter_map(|p| Some((p.id.as_str(), p.doc_url.as_deref()?)))
+            .collect();
+        for version in &mut self.library_versions {
+            if version.documentation == UNRESOLVED {
+                version.documentation = documentation_url(
+                    declared.get(version.id.as_str()).copied(),
+                    &version.name,
+                    &version.version,
+                );
+            }
+        }
+    }
+
+    /// Check whether docs.rs built the docs of at most `limit` library versions
+    /// not checked yet, the latest published first, and return how many are checked.
+    pub async fn check_docsrs_builds(&mut self, client: &mut DocsRsClient, limit: usize) -> usize {
+        tracing::info!("Start to check the docs.rs builds");
+        let mut unchecked: Vec<usize> = (0..self.library_versions.len())
+            .filter(|i| self.library_versions[*i].docs_built.is_none())
+            .collect();
+        unchecked.sort_by_key(|i| std::cmp::Reverse(self.library_versions[*i].published_at));
+        let mut checked = 0;
+        for i in unchecked.into_iter().take(limit) {
+            let version = &mut self.library_versions[i];
+            match client.doc_status(&version.name, &version.version).await {
+                Ok(built) => {
+                    version.docs_built = Some(built);
+                    checked += 1;
+                }
+                Err(e) => tracing::warn!(
+                    "Failed to get the docs.rs status of {}: {}",
+                    version.name_and_version,
+                    e
+                ),
+            }
+        }
+        tracing::info!("Finish to check the docs.rs builds of {} versions", checked);
+        checked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_documentation_url() {
+        assert_eq!(
+            documentation_url(Some("https://docs.rs/tokio"), "tokio", "1.0.0"),
+            "https://docs.rs/tokio"
+        );
+        assert_eq!(
+            documentation_url(Some("None"), "tokio", "1.0.0"),
+            "https://docs.rs/tokio/1.0.0"
+        );
+        assert_eq!(
+            documentation_url(Some("ftp://example.com/doc"), "foo", "0.1.0"),
+            "https://docs.rs/foo/0.1.0"
+        );
+        assert_eq!(
+            documentation_url(None, "foo", "0.1.0"),
+            "https://docs.rs/foo/0.1.0"
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Report the crates built in several semver-incompatible versions by the dependency tree
+//! of each application, such as both `syn 1.0.109` and `syn 2.0.66`, which bloat the builds.
+//!
+//! The tree is the one of the latest version of the application by the `depends_on` and
+//! `build_depends_on` edges, since the dev-dependencies of the dependencies are not built.
+//! The duplicates only reached through the optional or platform-specific edges are labelled
+//! `conditional`, since their versions may never be built together.
+
+use crate::ImportContext;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DuplicateCrate {
+    pub name: String,
+    /// the versions in the tree, in different semver-compatible series
+    pub versions: Vec<String>,
+    /// whether the versions are in different series only through the optional
+    /// or platform-specific dependencies
+    pub conditional: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateReport {
+    /// the name and version of the application
+    pub application: String,
+    pub duplicates: Vec<DuplicateCrate>,
+}
+
+/// The semver-compatible series of a version in the cargo flavor,
+/// such as `1` of `1.2.3`, `0.3` of `0.3.1` and `0.0.4` of `0.0.4`.
+fn compatible_series(version: &str) -> String {
+    match semver::Version::parse(version) {
+        Ok(v) if v.major > 0 => v.major.to_string(),
+        Ok(v) if v.minor > 0 => format!("0.{}", v.minor),
+        Ok(v) => format!("0.0.{}", v.patch),
+        Err(_) => version.to_string(),
+    }
+}
+
+/// The crates of several series among the versions of a tree, `name/version`.
+fn find_duplicates<'a>(versions: impl Iterator<Item = &'a str>) -> Vec<DuplicateCrate> {
+    let mut series: BTreeMap<&str, BTreeMap<String, BTreeSet<&str>>> = BTreeMap::new();
+    for name_and_version in versions {
+        let Some((name, version)) = name_and_version.split_once('/') else {
+            continue;
+        };
+        series
+            .entry(name)
+            .or_default()
+            .entry(compatible_series(version))
+            .or_default()
+            .insert(version);
+    }
+    series
+        .into_iter()
+        .filter(|(_, series)| series.len() > 1)
+        .map(|(name, series)| DuplicateCrate {
+            name: name.to_string(),
+            versions: series.into_values().flatten().map(String::from).collect(),
+            conditional: false,
+        })
+        .collect()
+}
+
+/// The dependencies of a version, with whether they're built on all the platforms
+/// whatever the features.
+type DependencyMap<'a> = HashMap<&'a str, Vec<(&'a str, bool)>>;
+
+/// The versions in the tree of `root`, without it, only by the unconditional edges if
+/// `unconditional`.
+fn tree<'a>(
+    dependencies: &DependencyMap<'a>,
+    root: &'a str,
+    unconditional: bool,
+) -> HashSet<&'a str> {
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut queue: VecDeque<&str> = VecDeque::from([root]);
+    while let Some(current) = queue.pop_front() {
+        if visited.insert(current) {
+            queue.extend(
+                dependencies
+                    .get(current)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, always)| *always || !unconditional)
+                    .map(|(dst, _)| *dst),
+            );
+        }
+    }
+    visited.remove(root);
+    visited
+}
+
+impl ImportContext {
+    /// The duplicate crates in the tree of the latest version of each application,
+    /// the applications without any are skipped.
+    pub fn find_duplicate_versions(&self) -> Vec<DuplicateReport> {
+        let mut dependencies: DependencyMap = HashMap::new();
+        for (src, dst, always) in self
+            .depends_on
+            .iter()
+            .map(|e| (&e.SRC_ID, &e.DST_ID, !e.optional && e.target.is_none()))
+            .chain(
+                self.build_depends_on
+                    .iter()
+                    .map(|e| (&e.SRC_ID, &e.DST_ID, !e.optional && e.target.is_none())),
+            )
+        {
+            dependencies
+                .entry(src.as_str())
+                .or_default()
+                .push((dst.as_str(), always));
+        }
+
+        let mut latest: BTreeMap<&str, (semver::Version, &str)> = BTreeMap::new();
+        for v in &self.application_versions {
+            let Ok(version) = semver::Version::parse(&v.version) else {
+                continue;
+            };
+            let entry = latest
+                .entry(v.name.as_str())
+                .or_insert_with(|| (version.clone(), v.name_and_version.as_str()));
+            if version > entry.0 {
+                *entry = (version, v.name_and_version.as_str());
+            }
+        }
+
+        let mut reports = vec![];
+        for (_, root) in latest.into_values() {
+            let unconditional: HashSet<String> =
+                find_duplicates(tree(&dependencies, root, true).into_iter())
+                    .into_iter()
+                    .map(|d| d.name)
+                    .collect();
+            let mut duplicates = find_duplicates(tree(&dependencies, root, false).into_iter());
+            for duplicate in &mut duplicates {
+                duplicate.conditional = !unconditional.contains(&duplicate.name);
+            }
+            if !duplicates.is_empty() {
+                reports.push(DuplicateReport {
+                    application: root.to_string(),
+                    duplicates,
+                });
+            }
+        }
+        reports
+    }
+
+    /// Write the duplicate crates of the applications into `output` as json.
+    pub(crate) fn write_duplicate_report(&self, output: &Path) -> Result<(), Box<dyn Error>> {
+        let reports = self.find_duplicate_versions();
+        tracing::info!(
+            "{} applications build several versions of a crate",
+            reports.len()
+        );
+        fs::write(output, serde_json::to_string_pretty(&reports)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::tugraph_model::{ApplicationVersion, DependsOn};
+
+    #[test]
+    fn test_find_duplicates() {
+        assert_eq!(compatible_series("1.2.3"), "1");
+        assert_eq!(compatible_series("0.3.1"), "0.3");
+        assert_eq!(compatible_series("0.0.4"), "0.0.4");
+
+        let tree = [
+            "syn/1.0.109",
+            "syn/2.0.66",
+            "bitflags/1.3.2",
+            "bitflags/1.2.1",
+            "rand/0.7.3",
+            "rand/0.8.5",
+            "serde/1.0.203",
+        ];
+        assert_eq!(
+            find_duplicates(tree.into_iter()),
+            [
+                DuplicateCrate {
+                    name: "rand".to_string(),
+                    versions: vec!["0.7.3".to_string(), "0.8.5".to_string()],
+                    conditional: false,
+                },
+                DuplicateCrate {
+                    name: "syn".to_string(),
+                    versions: vec!["1.0.109".to_string(), "2.0.66".to_string()],
+                    conditional: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conditional_duplicates() {
+        let mut context = ImportContext::default();
+        context.application_versions.push(ApplicationVersion {
+            name: "app".to_string(),
+            version: "1.0.0".to_string(),
+            name_and_version: "app/1.0.0".to_string(),
+            ..Default::default()
+        });
+        let edge = |src: &str, dst: &str, optional: bool, target: Option<&str>| DependsOn {
+            SRC_ID: src.to_string(),
+            DST_ID: dst.to_string(),
+            optional,
+            target: target.map(String::from),
+            ..Default::default()
+        };
+        context.depends_on = vec![
+            edge("app/1.0.0", "syn/2.0.66", false, None),
+            edge("app/1.0.0", "old/0.1.0", true, None),
+            edge("old/0.1.0", "syn/1.0.109", false, None),
+            edge("app/1.0.0", "rand/0.8.5", false, None),
+            edge("app/1.0.0", "winapi-dep/0.1.0", false, Some("cfg(windows)")),
+            edge("winapi-dep/0.1.0", "rand/0.7.3", false, None),
+            edge("app/1.0.0", "bitflags/1.3.2", false, None),
+            edge("app/1.0.0", "bitflags/2.5.0", false, None),
+        ];
+        let reports = context.find_duplicate_versions();
+        assert_eq!(reports.len(), 1);
+        let conditional: Vec<(&str, bool)> = reports[0]
+            .duplicates
+            .iter()
+            .map(|d| (d.name.as_str(), d.conditional))
+            .collect();
+        assert_eq!(
+            conditional,
+            [("bitflags", false), ("rand", true), ("syn", true)]
+        );
+    }
+}
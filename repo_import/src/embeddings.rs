@@ -0,0 +1,326 @@
+//! Embed the crates into vectors for the semantic search, such as finding the crates like
+//! `reqwest`, and store them in the `import_embeddings` table of pgvector.
+//!
+//! The text of a crate is its name, description, keywords, categories and the beginning of its
+//! readme, up to `IMPORT_EMBEDDING_MAX_CHARS` characters (2000 by default). It's embedded by
+//! the endpoint at `IMPORT_EMBEDDING_URL` in the OpenAI `/v1/embeddings` style, which is
+//! served by OpenAI as well as ollama, vLLM or text-embeddings-inference, with the model
+//! `IMPORT_EMBEDDING_MODEL` (`text-embedding-3-small` by default) and the bearer token
+//! `IMPORT_EMBEDDING_API_KEY` if any. The vectors have `IMPORT_EMBEDDING_DIMENSIONS`
+//! (1536 by default), which is requested from the endpoint only if it's set.
+//!
+//! The sha256 of the text is stored along with the vector, so only the crates whose text
+//! changes are embedded again by the next import.
+
+use crate::db::pg_connect_options_from_env;
+use crate::retry::{is_transient_http, RetryPolicy};
+use crate::ImportContext;
+use model::tugraph_model::Program;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::{Postgres, QueryBuilder, Row};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error::Error;
+
+const DEFAULT_MODEL: &str = "text-embedding-3-small";
+const DEFAULT_DIMENSIONS: usize = 1536;
+const DEFAULT_MAX_CHARS: usize = 2000;
+
+/// The texts embedded by one request.
+const EMBEDDING_BATCH_SIZE: usize = 32;
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest<'a> {
+    input: &'a [String],
+    model: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+/// A client of an embedding endpoint.
+pub(crate) struct EmbeddingClient {
+    client: Client,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    dimensions: usize,
+    /// whether to request the dimensions, which not every endpoint accepts
+    request_dimensions: bool,
+    retry: RetryPolicy,
+}
+
+impl EmbeddingClient {
+    /// The client of the endpoint at `IMPORT_EMBEDDING_URL`, none if it's not set.
+    pub(crate) fn from_env() -> Option<Result<Self, reqwest::Error>> {
+        let url = env::var("IMPORT_EMBEDDING_URL").ok()?;
+        let dimensions = env::var("IMPORT_EMBEDDING_DIMENSIONS")
+            .ok()
+            .and_then(|d| d.parse().ok());
+        Some(Client::builder().build().map(|client| EmbeddingClient {
+            client,
+            url,
+            model: env::var("IMPORT_EMBEDDING_MODEL").unwrap_or_else(|_| DEFAULT_MODEL.into()),
+            api_key: env::var("IMPORT_EMBEDDING_API_KEY").ok(),
+            dimensions: dimensions.unwrap_or(DEFAULT_DIMENSIONS),
+            request_dimensions: dimensions.is_some(),
+            retry: RetryPolicy::from_env(),
+        }))
+    }
+
+    /// The vectors of `texts`, in the same order.
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, reqwest::Error> {
+        let body = EmbeddingRequest {
+            input: texts,
+            model: &self.model,
+            dimensions: self.request_dimensions.then_some(self.dimensions),
+        };
+        let body = &body;
+        let response: EmbeddingResponse = self
+            .retry
+            .retry(&self.url, is_transient_http, || async move {
+                let mut request = self.client.post(&self.url).json(body);
+                if let Some(api_key) = &self.api_key {
+                    request = request.bearer_auth(api_key);
+                }
+                request.send().await?.error_for_status()?.json().await
+            })
+            .await?;
+        let mut data = response.data;
+        data.sort_by_key(|d| d.index);
+        Ok(data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+fn max_chars() -> usize {
+    env::var("IMPORT_EMBEDDING_MAX_CHARS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CHARS)
+}
+
+/// The text describing a crate to embed, truncated to `max_chars` characters.
+fn embedding_text(program: &Program, max_chars: usize) -> String {
+    let mut text = program.name.clone();
+    if let Some(description) = program.description.as_deref().filter(|d| !d.is_empty()) {
+        text.push('\n');
+        text.push_str(description.trim());
+    }
+    if let Some(keywords) = program.keywords.as_deref().filter(|k| !k.is_empty()) {
+        text.push_str("\nkeywords: ");
+        text.push_str(keywords);
+    }
+    if let Some(categories) = program.categories.as_deref().filter(|c| !c.is_empty()) {
+        text.push_str("\ncategories: ");
+        text.push_str(categories);
+    }
+    // the readme is stored in a single line
+    if let Some(readme) = &program.readme_content {
+        text.push('\n');
+        text.push_str(&readme.replace("\\n", "\n"));
+    }
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => text[..end].to_string(),
+        None => text,
+    }
+}
+
+fn text_sha256(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+/// The literal of a vector for pgvector, such as `[0.1,-0.2]`.
+fn vector_literal(vector: &[f32]) -> String {
+    let values: Vec<String> = vector.iter().map(f32::to_string).collect();
+    format!("[{}]", values.join(","))
+}
+
+struct EmbeddingRow {
+    id: String,
+    name: String,
+    text_sha256: String,
+    vector: Vec<f32>,
+}
+
+/// The `import_embeddings` table, whose vectors are indexed by HNSW for the cosine distance.
+struct PgVectorStore {
+    pool: PgPool,
+}
+
+impl PgVectorStore {
+    async fn connect(dimensions: usize) -> Result<Self, sqlx::Error> {
+        let pool = PgPoolOptions::new()
+            .max_connections(2)
+            .connect_with(pg_connect_options_from_env()?)
+            .await?;
+        sqlx::raw_sql(&format!(
+            r#"
+CREATE EXTENSION IF NOT EXISTS vector;
+CREATE TABLE IF NOT EXISTS "import_embeddings" (
+  "id" TEXT PRIMARY KEY,
+  "name" TEXT NOT NULL,
+  "model" TEXT NOT NULL,
+  "text_sha256" TEXT NOT NULL,
+  "embedding" vector({}) NOT NULL,
+  "updated_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS "import_embeddings_name" ON "import_embeddings" ("name");
+CREATE INDEX IF NOT EXISTS "import_embeddings_hnsw" ON "import_embeddings"
+  USING hnsw ("embedding" vector_cosine_ops);
+"#,
+            dimensions
+        ))
+        .execute(&pool)
+        .await?;
+        Ok(Self { pool })
+    }
+
+    /// id -> the sha256 of the text embedded by `model`.
+    async fn text_digests(&self, model: &str) -> Result<HashMap<String, String>, sqlx::Error> {
+        let rows = sqlx::query(
+            r#"SELECT "id", "text_sha256" FROM "import_embeddings" WHERE "model" = $1"#,
+        )
+        .bind(model)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("id"), row.get("text_sha256")))
+            .collect())
+    }
+
+    async fn upsert(&self, model: &str, rows: &[EmbeddingRow]) -> Result<(), sqlx::Error> {
+        let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+            r#"INSERT INTO "import_embeddings" ("id", "name", "model", "text_sha256", "embedding") "#,
+        );
+        builder.push_values(rows, |mut b, row| {
+            b.push_bind(&row.id)
+                .push_bind(&row.name)
+                .push_bind(model)
+                .push_bind(&row.text_sha256)
+                .push_bind(vector_literal(&row.vector))
+                .push_unseparated("::vector");
+        });
+        builder.push(
+            r#" ON CONFLICT ("id") DO UPDATE SET
+            "name" = EXCLUDED."name",
+            "model" = EXCLUDED."model",
+            "text_sha256" = EXCLUDED."text_sha256",
+            "embedding" = EXCLUDED."embedding",
+            "updated_at" = NOW()"#,
+        );
+        builder.build().execute(&self.pool).await?;
+        Ok(())
+    }
+}
+
+impl ImportContext {
+    /// Embed the crates whose text is new or changed, and write them into the vector store.
+    #[tracing::instrument(name = "embeddings", skip_all)]
+    pub(crate) async fn write_embeddings(
+        &self,
+        client: &EmbeddingClient,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let store = PgVectorStore::connect(client.dimensions).await?;
+        let digests = store.text_digests(&client.model).await?;
+        let max_chars = max_chars();
+
+        let mut pending: Vec<(&Program, String, String)> = vec![];
+        let mut seen = HashSet::new();
+        for program in &self.programs {
+            if !seen.insert(program.id.as_str()) {
+                continue;
+            }
+            let text = embedding_text(program, max_chars);
+            let digest = text_sha256(&text);
+            if digests.get(&program.id) != Some(&digest) {
+                pending.push((program, text, digest));
+            }
+        }
+        tracing::info!(
+            "Start to embed {} crates, {} are up to date",
+            pending.len(),
+            seen.len() - pending.len()
+        );
+
+        let mut embedded = 0;
+        for batch in pending.chunks(EMBEDDING_BATCH_SIZE) {
+            let texts: Vec<String> = batch.iter().map(|(_, text, _)| text.clone()).collect();
+            let vectors = client.embed(&texts).await?;
+            if vectors.len() != batch.len() {
+                return Err(format!(
+                    "{} embeddings are returned for {} texts",
+                    vectors.len(),
+                    batch.len()
+                )
+                .into());
+            }
+            let rows: Vec<EmbeddingRow> = batch
+                .iter()
+                .zip(vectors)
+                .filter_map(|((program, _, digest), vector)| {
+                    if vector.len() != client.dimensions {
+                        tracing::warn!(
+                            "Skip the embedding of {} with {} dimensions rather than {}",
+                            program.name,
+                            vector.len(),
+                            client.dimensions
+                        );
+                        return None;
+                    }
+                    Some(EmbeddingRow {
+                        id: program.id.clone(),
+                        name: program.name.clone(),
+                        text_sha256: digest.clone(),
+                        vector,
+                    })
+                })
+                .collect();
+            if !rows.is_empty() {
+                store.upsert(&client.model, &rows).await?;
+            }
+            embedded += rows.len();
+        }
+        tracing::info!("Finish to embed {} crates", embedded);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding_text() {
+        let mut program = Program {
+            name: "reqwest".to_string(),
+            description: Some("higher level HTTP client library".to_string()),
+            keywords: Some("http,request,client".to_string()),
+            readme_content: Some("# reqwest\\n\\nAn ergonomic HTTP Client.".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            embedding_text(&program, 1000),
+            "reqwest\nhigher level HTTP client library\nkeywords: http,request,client\n\
+             # reqwest\n\nAn ergonomic HTTP Client."
+        );
+        program.description = None;
+        assert_eq!(embedding_text(&program, 7), "reqwest");
+
+        assert_eq!(vector_literal(&[0.5, -1.0, 0.25]), "[0.5,-1,0.25]");
+        assert_eq!(text_sha256("reqwest").len(), 64);
+    }
+}
@@ -0,0 +1,141 @@
+//! The errors of importing a repo, with the path or the repo url they come from,
+//! so that a caller can tell a bad url from a network failure or a broken manifest.
+
+use crate::retry::is_transient_git;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("failed to read {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("invalid toml {path:?}: {source}")]
+    Toml {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    /// a manifest which is valid toml, but not a valid package
+    #[error("invalid manifest {path:?}: {message}")]
+    Metadata { path: PathBuf, message: String },
+    #[error("invalid url {url}: {message}")]
+    Url { url: String, message: String },
+    #[error("failed to clone or fetch {url}: {source}")]
+    Git {
+        url: String,
+        #[source]
+        source: git2::Error,
+    },
+    /// failed to parse a cloned repo
+    #[error("failed to import {url}: {message}")]
+    Repo { url: String, message: String },
+    /// failed to write the graph into an output, such as `kafka`
+    #[error("failed to write into {sink}: {message}")]
+    Sink { sink: &'static str, message: String },
+}
+
+impl ImportError {
+    pub(crate) fn io(path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |source| ImportError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub(crate) fn toml(path: &Path) -> impl FnOnce(toml::de::Error) -> Self + '_ {
+        move |source| ImportError::Toml {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub(crate) fn git(url: &str) -> impl FnOnce(git2::Error) -> Self + '_ {
+        move |source| ImportError::Git {
+            url: url.to_string(),
+            source,
+        }
+    }
+
+    pub(crate) fn url(url: &str, message: impl ToString) -> Self {
+        ImportError::Url {
+            url: url.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// The repo url the error comes from, if it's known.
+    pub fn repo_url(&self) -> Option<&str> {
+        match self {
+            ImportError::Url { url, .. }
+            | ImportError::Git { url, .. }
+            | ImportError::Repo { url, .. } => Some(url),
+            _ => None,
+        }
+    }
+
+    /// The file the error comes from, if it's known.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ImportError::Io { path, .. }
+            | ImportError::Toml { path, .. }
+            | ImportError::Metadata { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Whether it may succeed if tried again later, such as a network failure.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ImportError::Git { source, .. } => is_transient_git(source),
+            ImportError::Io { source, .. } => matches!(
+                source.kind(),
+                io::ErrorKind::Interrupted | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+            ),
+            ImportError::Sink { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::extract_namespace;
+
+    #[test]
+    fn test_error_context() {
+        let e = extract_namespace("https://github.com/tokio").unwrap_err();
+        assert!(matches!(e, ImportError::Url { .. }));
+        assert_eq!(e.repo_url(), Some("https://github.com/tokio"));
+        assert!(!e.is_transient());
+
+        let path = Path::new("/nonexistent/Cargo.toml");
+        let e = ImportError::io(path)(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(e.path(), Some(path));
+        assert!(e.to_string().contains("/nonexistent/Cargo.toml"));
+
+        let e = ImportError::git("https://github.com/tokio-rs/tokio")(git2::Error::new(
+            git2::ErrorCode::GenericError,
+            git2::ErrorClass::Net,
+            "connection reset",
+        ));
+        assert!(e.is_transient());
+    }
+
+    #[tokio::test]
+    async fn test_repo_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = "https://github.com/tokio-rs/tokio";
+        let e = crate::ImportContext::default()
+            .parse_a_local_repo_and_return_new_versions(dir.path().to_path_buf(), url.to_string())
+            .await
+            .unwrap_err();
+        assert!(matches!(e, ImportError::Repo { .. }));
+        assert_eq!(e.repo_url(), Some(url));
+    }
+}
@@ -0,0 +1,280 @@
+//! The feature graph of crates,
+//! built from the `[features]` table and the `features = [...]` of dependencies.
+//!
+//! An optional dependency `foo` has an implicit feature `foo = ["dep:foo"]`,
+//! unless any feature refers to it by `dep:foo`, as cargo does.
+
+use crate::utils::{feature_id, name_join_version};
+use crate::version_info::{Dependencies, VersionUpdater};
+use model::general_model::Version;
+use model::tugraph_model::{
+    DependsOnFeature, EnablesDependency, EnablesFeature, Feature, HasFeature,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// What an item in a feature table activates
+#[derive(Debug, PartialEq, Eq)]
+enum Activation<'a> {
+    /// `"foo"`, another feature of the same crate
+    Feature(&'a str),
+    /// `"dep:foo"`, or `"foo"` where foo is an optional dependency
+    Dependency(&'a str),
+    /// `"foo/bar"` or `"foo?/bar"`, a feature of a dependency.
+    /// The dependency itself is only activated without `?`.
+    DependencyFeature {
+        dependency: &'a str,
+        feature: &'a str,
+        weak: bool,
+    },
+}
+
+fn parse_activation<'a>(
+    activation: &'a str,
+    features: &BTreeMap<String, Vec<String>>,
+) -> Activation<'a> {
+    if let Some(dependency) = activation.strip_prefix("dep:") {
+        return Activation::Dependency(dependency);
+    }
+    if let Some((dependency, feature)) = activation.split_once('/') {
+        return match dependency.strip_suffix('?') {
+            Some(dependency) => Activation::DependencyFeature {
+                dependency,
+                feature,
+                weak: true,
+            },
+            None => Activation::DependencyFeature {
+                dependency,
+                feature,
+                weak: false,
+            },
+        };
+    }
+    if features.contains_key(activation) {
+        Activation::Feature(activation)
+    } else {
+        Activation::Dependency(activation)
+    }
+}
+
+/// All the nodes and edges of the feature graph
+#[derive(Debug, Default)]
+pub struct FeatureGraph {
+    pub features: Vec<Feature>,
+    pub has_feature: Vec<HasFeature>,
+    pub enables_feature: Vec<EnablesFeature>,
+    pub enables_dependency: Vec<EnablesDependency>,
+    pub depends_on_feature: Vec<DependsOnFeature>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FeatureUpdater {
+    /// the `[features]` table of each version
+    pub features_map: HashMap<Version, BTreeMap<String, Vec<String>>>,
+
+    /// the features of dependencies enabled unconditionally by each version,
+    /// e.g. `tokio = { version = "1", features = ["full"] }`
+    pub dependency_features_map: HashMap<Version, Vec<(String, Vec<String>)>>,
+
+    /// the implicit features of optional dependencies, also in `features_map`
+    #[serde(default)]
+    pub implicit_features_map: HashMap<Version, Vec<String>>,
+}
+
+/// The implicit features of the optional dependencies of a version.
+fn implicit_features(info: &Dependencies) -> Vec<String> {
+    let explicit = |name: &str| {
+        info.features.contains_key(name)
+            || info
+                .features
+                .values()
+                .flatten()
+                .any(|a| a.strip_prefix("dep:") == Some(name))
+    };
+    let mut implicit: Vec<String> = info
+        .dependencies
+        .iter()
+        .filter(|d| d.optional && !explicit(&d.name))
+        .map(|d| d.name.clone())
+        .collect();
+    implicit.sort();
+    implicit.dedup();
+    implicit
+}
+
+impl FeatureUpdater {
+    pub async fn update_features(&mut self, info: &Dependencies) {
+        let cur_release = Version::new(&info.crate_name, &info.version);
+        // the features of another copy of the version, replaced by this one
+        self.features_map.remove(&cur_release);
+        self.implicit_features_map.remove(&cur_release);
+        self.dependency_features_map.remove(&cur_release);
+        let implicit = implicit_features(info);
+        if !info.features.is_empty() || !implicit.is_empty() {
+            let mut features = info.features.clone();
+            for name in &implicit {
+                features.insert(name.clone(), vec![format!("dep:{}", name)]);
+            }
+            self.features_map.insert(cur_release.clone(), features);
+        }
+        if !implicit.is_empty() {
+            self.implicit_features_map
+                .insert(cur_release.clone(), implicit);
+        }
+
+        let dependency_features: Vec<(String, Vec<String>)> = info
+            .dependencies
+            .iter()
+            .filter(|d| !d.features.is_empty())
+            .map(|d| (d.name.clone(), d.features.clone()))
+            .collect();
+        if !dependency_features.is_empty() {
+            self.dependency_features_map
+                .insert(cur_release, dependency_features);
+        }
+    }
+
+    fn has_feature(&self, version: &Version, feature: &str) -> bool {
+        self.features_map
+            .get(version)
+            .is_some_and(|f| f.contains_key(feature))
+    }
+
+    /// Build the feature graph.
+    /// The dependencies are resolved by `version_updater`,
+    /// so an edge is only emitted if both ends exist.
+    pub async fn to_feature_graph(&self, version_updater: &VersionUpdater) -> FeatureGraph {
+        let mut graph = FeatureGraph::default();
+
+        for (version, features) in &self.features_map {
+            let name_and_version = name_join_version(&version.name, &version.version);
+            let implicit = self.implicit_features_map.get(version);
+            for (feature, activations) in features {
+                let src_id = feature_id(&version.name, &version.version, feature);
+                graph.features.push(Feature {
+                    implicit: implicit.is_some_and(|i| i.contains(feature)),
+                    ..Feature::new(&version.name, &version.version, feature)
+                });
+                graph.has_feature.push(HasFeature {
+                    SRC_ID: name_and_version.clone(),
+                    DST_ID: src_id.clone(),
+                });
+
+                for activation in activations {
+                    match parse_activation(activation, features) {
+                        Activation::Feature(dst) => graph.enables_feature.push(EnablesFeature {
+                            SRC_ID: src_id.clone(),
+                            DST_ID: feature_id(&version.name, &version.version, dst),
+                        }),
+                        Activation::Dependency(dependency) => {
+                            if let Some(dst) =
+                                version_updater.resolve_dependency(version, dependency)
+                            {
+                                graph.enables_dependency.push(EnablesDependency {
+                                    SRC_ID: src_id.clone(),
+                                    DST_ID: name_join_version(&dst.name, &dst.version),
+                                });
+                            }
+                        }
+                        Activation::DependencyFeature {
+                            dependency,
+                            feature: dst_feature,
+                            weak,
+                        } => {
+                            let dst = match version_updater.resolve_dependency(version, dependency)
+                            {
+                                Some(dst) => dst,
+                                None => continue,
+                            };
+                            if !weak {
+                                graph.enables_dependency.push(EnablesDependency {
+                                    SRC_ID: src_id.clone(),
+                                    DST_ID: name_join_version(&dst.name, &dst.version),
+                                });
+                            }
+                            if self.has_feature(dst, dst_feature) {
+                                graph.enables_feature.push(EnablesFeature {
+                                    SRC_ID: src_id.clone(),
+                                    DST_ID: feature_id(&dst.name, &dst.version, dst_feature),
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for (version, dependency_features) in &self.dependency_features_map {
+            for (dependency, features) in dependency_features {
+                let dst = match version_updater.resolve_dependency(version, dependency) {
+                    Some(dst) => dst,
+                    None => continue,
+                };
+                for feature in features.iter().filter(|f| self.has_feature(dst, f)) {
+                    graph.depends_on_feature.push(DependsOnFeature {
+                        SRC_ID: name_join_version(&version.name, &version.version),
+                        DST_ID: feature_id(&dst.name, &dst.version, feature),
+                    });
+                }
+            }
+        }
+
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{implicit_features, parse_activation, Activation};
+    use crate::version_info::Dependencies;
+    use model::general_model::{Dependency, DependencyKind};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse_activation() {
+        let mut features = BTreeMap::new();
+        features.insert("rt".to_string(), vec![]);
+
+        assert_eq!(parse_activation("rt", &features), Activation::Feature("rt"));
+        assert_eq!(
+            parse_activation("mio", &features),
+            Activation::Dependency("mio")
+        );
+        assert_eq!(
+            parse_activation("dep:mio", &features),
+            Activation::Dependency("mio")
+        );
+        assert_eq!(
+            parse_activation("bytes/std", &features),
+            Activation::DependencyFeature {
+                dependency: "bytes",
+                feature: "std",
+                weak: false
+            }
+        );
+        assert_eq!(
+            parse_activation("bytes?/std", &features),
+            Activation::DependencyFeature {
+                dependency: "bytes",
+                feature: "std",
+                weak: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_implicit_features() {
+        let dep = |name: &str| Dependency::new(name, "1", vec![], true, DependencyKind::Normal);
+        let mut features = BTreeMap::new();
+        features.insert(
+            "full".to_string(),
+            vec!["dep:mio".to_string(), "bytes".to_string()],
+        );
+        let info = Dependencies {
+            dependencies: vec![dep("mio"), dep("bytes"), dep("bytes")],
+            features,
+            ..Default::default()
+        };
+        assert_eq!(implicit_features(&info), ["bytes"]);
+    }
+}
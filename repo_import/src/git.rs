@@ -1,3 +1,4 @@
+use crate::retry::{is_transient_git, RetryPolicy};
 use crate::{utils::extract_namespace, utils::insert_namespace_by_repo_path, ImportContext};
 use git2::{ObjectType, Oid, Repository};
 use std::path::PathBuf;
@@ -46,7 +47,16 @@ impl ImportContext {
 async fn clone(path: &PathBuf, url: &str) -> Result<(), git2::Error> {
     if !path.is_dir() {
         //tracing::info!("Start cloning repo into {:?} from URL {}", path, url);
-        Repository::clone(url, path)?;
+        let (path, url) = (path.clone(), url.to_string());
+        tokio::task::spawn_blocking(move || {
+            RetryPolicy::from_env().retry_blocking(
+                &format!("Cloning {}", url),
+                is_transient_git,
+                || Repository::clone(&url, &path).map(|_| ()),
+            )
+        })
+        .await
+        .map_err(|e| git2::Error::from_str(&e.to_string()))??;
         //tracing::info!("Finish cloning repo into {:?}", path);
     } else {
         tracing::info!("Directory {:?} is not empty, skipping Clone", path);
@@ -89,10 +99,43 @@ async fn clone(path: &PathBuf, url: &str) -> Result<(), git2::Error> {
     Ok(())
 }*/
 
-/// return value: (tag_name, tree_id, commit_time)
-pub(crate) async fn get_all_git_tags_with_time_sorted(
-    repo_path: &PathBuf,
-) -> Vec<(String, Oid, i64)> {
+/// A git tag of a release
+#[derive(Debug, Clone)]
+pub(crate) struct GitTag {
+    pub(crate) name: String,
+    pub(crate) tree: Oid,
+    pub(crate) commit: Oid,
+    /// the commit time in seconds since the epoch
+    pub(crate) time: i64,
+}
+
+/// Whether a tag names a release, such as `v1.2.3`, `tokio-1.38.0`,
+/// `serde_json@1.0.1` or `release/0.3`.
+pub(crate) fn is_version_tag(tag: &str) -> bool {
+    let bytes = tag.as_bytes();
+    (0..bytes.len()).any(|i| {
+        if !bytes[i].is_ascii_digit() {
+            return false;
+        }
+        // the version starts after a separator or a leading `v`
+        let starts = match i.checked_sub(1).map(|j| bytes[j]) {
+            None => true,
+            Some(b'v' | b'V') => i == 1 || !bytes[i - 2].is_ascii_alphanumeric(),
+            Some(b) => !b.is_ascii_alphanumeric() && b != b'.',
+        };
+        if !starts {
+            return false;
+        }
+        let version = &tag[i..];
+        // `0.3` is the same as `0.3.0`
+        semver::Version::parse(version).is_ok()
+            || (version.matches('.').count() == 1
+                && semver::Version::parse(&format!("{}.0", version)).is_ok())
+    })
+}
+
+/// Get the version tags of a repo, sorted by the commit time.
+pub(crate) async fn get_all_git_tags_with_time_sorted(repo_path: &PathBuf) -> Vec<GitTag> {
     let mut tags_with_dates = Vec::new();
 
     let repo = Repository::open(repo_path).unwrap();
@@ -103,6 +146,7 @@ pub(crate) async fn get_all_git_tags_with_time_sorted(
         .expect("Could not retrieve tags")
         .iter()
         .flatten()
+        .filter(|tag_name| is_version_tag(tag_name))
         .map(|tag_name| tag_name.to_string())
         .collect();
 
@@ -140,9 +184,14 @@ pub(crate) async fn get_all_git_tags_with_time_sorted(
             _ => continue,
         };
 
-        tags_with_dates.push((tag_name, tree_id, commit_time));
+        tags_with_dates.push(GitTag {
+            name: tag_name,
+            tree: tree_id,
+            commit: commit.id(),
+            time: commit_time,
+        });
     }
-    tags_with_dates.sort_by(|a, b| a.2.cmp(&b.2));
+    tags_with_dates.sort_by_key(|t| t.time);
     tags_with_dates
 }
 
@@ -179,3 +228,25 @@ pub(crate) async fn _print_all_tags(repo: &Repository, v: bool) {
 
     tracing::debug!("TAGS {:?} tags: {}", repo.path(), s);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_version_tag;
+
+    #[test]
+    fn test_is_version_tag() {
+        for tag in [
+            "v1.2.3",
+            "1.2.3",
+            "tokio-1.38.0",
+            "serde_json@1.0.1",
+            "release/0.3",
+            "v0.1.0-alpha.1",
+        ] {
+            assert!(is_version_tag(tag), "{}", tag);
+        }
+        for tag in ["nightly", "2019-01-01", "docs", "mdbook-v2", "x86.64"] {
+            assert!(!is_version_tag(tag), "{}", tag);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Walk the git history of a repo, to find who contributes to it and when,
+//! and how active it is.
+
+use crate::git::get_all_git_tags_with_time_sorted;
+use crate::ImportContext;
+use git2::Repository;
+use model::tugraph_model::{ContributedTo, Contributor, HasRepoMetrics, RepoMetrics};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAY: i64 = 24 * 60 * 60;
+/// the window of `commits_per_month`
+const YEAR: i64 = 365 * DAY;
+/// the window of `active_contributors`
+const ACTIVE_WINDOW: i64 = 90 * DAY;
+
+/// The author of a commit
+#[derive(Debug, Clone)]
+pub(crate) struct CommitInfo {
+    /// the normalized email
+    pub(crate) email: String,
+    pub(crate) name: String,
+    /// seconds since the epoch
+    pub(crate) time: i64,
+}
+
+/// Lowercase an email, and drop the user id of a GitHub noreply email,
+/// i.e. `123+Octocat@users.noreply.github.com` -> `octocat@users.noreply.github.com`.
+pub(crate) fn normalize_email(email: &str) -> String {
+    let email = email.trim().to_lowercase();
+    match email.split_once('+') {
+        Some((id, rest))
+            if rest.ends_with("@users.noreply.github.com")
+                && id.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            rest.to_string()
+        }
+        _ => email,
+    }
+}
+
+/// Walk the commits reachable from HEAD, with the authors mapped by `.mailmap`.
+pub(crate) fn walk_history(repo_path: &Path) -> Result<Vec<CommitInfo>, git2::Error> {
+    let repo = Repository::open(repo_path)?;
+    let mailmap = repo.mailmap().ok();
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+
+    let mut commits = vec![];
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        let author = match &mailmap {
+            Some(mailmap) => commit.author_with_mailmap(mailmap)?,
+            None => commit.author().to_owned(),
+        };
+        let email = match author.email() {
+            Some(email) if !email.trim().is_empty() => normalize_email(email),
+            _ => continue,
+        };
+        commits.push(CommitInfo {
+            email,
+            name: author.name().unwrap_or_default().to_string(),
+            time: commit.time().seconds(),
+        });
+    }
+    Ok(commits)
+}
+
+/// The contributions of an author
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Contribution {
+    /// the name in the latest commit
+    pub(crate) name: String,
+    pub(crate) commits: i64,
+    pub(crate) first_commit: i64,
+    pub(crate) last_commit: i64,
+}
+
+/// Group the commits by the author email.
+pub(crate) fn contributions(commits: &[CommitInfo]) -> BTreeMap<&str, Contribution> {
+    let mut res: BTreeMap<&str, Contribution> = BTreeMap::new();
+    for commit in commits {
+        let contribution = res
+            .entry(commit.email.as_str())
+            .or_insert_with(|| Contribution {
+                name: commit.name.clone(),
+                commits: 0,
+                first_commit: commit.time,
+                last_commit: commit.time,
+            });
+        contribution.commits += 1;
+        contribution.first_commit = contribution.first_commit.min(commit.time);
+        if commit.time > contribution.last_commit {
+            contribution.last_commit = commit.time;
+            contribution.name.clone_from(&commit.name);
+        }
+    }
+    res
+}
+
+/// Compute the activity metrics of a repo at `now`,
+/// `release_times` are the commit times of its release tags.
+pub(crate) fn repo_metrics(
+    id: &str,
+    commits: &[CommitInfo],
+    release_times: &[i64],
+    now: i64,
+) -> RepoMetrics {
+    let recent_commits = commits.iter().filter(|c| c.time > now - YEAR).count();
+    let active_contributors: HashSet<&str> = commits
+        .iter()
+        .filter(|c| c.time > now - ACTIVE_WINDOW)
+        .map(|c| c.email.as_str())
+        .collect();
+
+    let mut release_times = release_times.to_vec();
+    release_times.sort_unstable();
+    let release_cadence_days = match (release_times.first(), release_times.last()) {
+        (Some(first), Some(last)) if release_times.len() > 1 => {
+            Some((last - first) as f64 / DAY as f64 / (release_times.len() - 1) as f64)
+        }
+        _ => None,
+    };
+
+    RepoMetrics {
+        id: id.to_string(),
+        commits: commits.len() as i64,
+        commits_per_month: recent_commits as f64 / 12.0,
+        first_commit: commits.iter().map(|c| c.time).min().unwrap_or_default(),
+        last_commit: commits.iter().map(|c| c.time).max().unwrap_or_default(),
+        releases: release_times.len() as i64,
+        release_cadence_days,
+        active_contributors: active_contributors.len() as i64,
+    }
+}
+
+impl ImportContext {
+    /// Import the history of a repo for the programs in it, including
+    /// the contributors if `IMPORT_CONTRIBUTORS` is set,
+    /// and the activity metrics if `IMPORT_REPO_ACTIVITY` is set.
+    pub(crate) async fn import_git_history(
+        &mut self,
+        repo_path: &PathBuf,
+        namespace: Option<&str>,
+        program_ids: &[String],
+    ) {
+        let import_contributors = env::var("IMPORT_CONTRIBUTORS").is_ok_and(|v| v == "1");
+        let import_activity = env::var("IMPORT_REPO_ACTIVITY").is_ok_and(|v| v == "1");
+        if !import_contributors && !import_activity {
+            return;
+        }
+
+        let commits = match walk_history(repo_path) {
+            Ok(commits) => commits,
+            Err(e) => {
+                tracing::warn!("Failed to walk the history of {:?}: {}", repo_path, e);
+                return;
+            }
+        };
+        if import_contributors {
+            self.import_contributors(&commits, program_ids);
+        }
+        if let (true, Some(namespace)) = (import_activity, namespace) {
+            let release_times: Vec<i64> = get_all_git_tags_with_time_sorted(repo_path)
+                .await
+                .iter()
+                .map(|tag| tag.time)
+                .collect();
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64);
+            self.repo_metrics
+                .push(repo_metrics(namespace, &commits, &release_times, now));
+            for program_id in program_ids {
+                self.has_repo_metrics.push(HasRepoMetrics {
+                    SRC_ID: program_id.clone(),
+                    DST_ID: namespace.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Add the contributors of a repo, who contribute to all the programs in it.
+    fn import_contributors(&mut self, commits: &[CommitInfo], program_ids: &[String]) {
+        for (email, contribution) in contributions(commits) {
+            self.contributors.push(Contributor {
+                id: email.to_string(),
+                name: contribution.name.clone(),
+            });
+            for program_id in program_ids {
+                self.contributed_to.push(ContributedTo {
+                    SRC_ID: email.to_string(),
+                    DST_ID: program_id.clone(),
+                    commits: contribution.commits,
+                    first_commit: contribution.first_commit,
+                    last_commit: contribution.last_commit,
+                });
+            }
+        }
+    }
+
+    /// Keep the contributor added last if it contributes to many repos,
+    /// and the metrics added last if a repo is imported again.
+    pub(crate) fn dedup_git_history(&mut self) {
+        let mut seen = HashSet::new();
+        let mut contributors: Vec<Contributor> = self
+            .contributors
+            .drain(..)
+            .rev()
+            .filter(|c| seen.insert(c.id.clone()))
+            .collect();
+        contributors.reverse();
+        self.contributors = contributors;
+
+        let mut seen = HashSet::new();
+        let mut repo_metrics: Vec<RepoMetrics> = self
+            .repo_metrics
+            .drain(..)
+            .rev()
+            .filter(|m| seen.insert(m.id.clone()))
+            .collect();
+        repo_metrics.reverse();
+        self.repo_metrics = repo_metrics;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contributions() {
+        let commit = |email: &str, name: &str, time| CommitInfo {
+            email: normalize_email(email),
+            name: name.to_string(),
+            time,
+        };
+        let commits = vec![
+            commit("123+Octocat@users.noreply.github.com", "Octo Cat", 300),
+            commit("a@example.com", "A", 200),
+            commit("octocat@users.noreply.github.com", "octocat", 100),
+        ];
+        let contributions = contributions(&commits);
+        assert_eq!(contributions.len(), 2);
+        assert_eq!(
+            contributions["octocat@users.noreply.github.com"],
+            Contribution {
+                name: "Octo Cat".to_string(),
+                commits: 2,
+                first_commit: 100,
+                last_commit: 300,
+            }
+        );
+
+        let metrics = repo_metrics("octocat/hello", &commits, &[0, 10 * DAY, 30 * DAY], 300);
+        assert_eq!(metrics.commits, 3);
+        assert_eq!(metrics.first_commit, 100);
+        assert_eq!(metrics.last_commit, 300);
+        assert_eq!(metrics.commits_per_month, 0.25);
+        assert_eq!(metrics.release_cadence_days, Some(15.0));
+        assert_eq!(metrics.active_contributors, 2);
+    }
+}
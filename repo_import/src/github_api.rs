@@ -0,0 +1,265 @@
+//! A client of the GitHub REST API, used to enrich the crates hosted on GitHub
+//! with the stars of their repos, and how soon their issues and pull requests are closed,
+//! the open ones counted by their age so far.
+//!
+//! The token is the one for cloning from `github.com`, see [`crate::Credentials`], without
+//! which GitHub allows only 60 requests per hour. The requests are throttled by
+//! `GITHUB_API_INTERVAL_MS`, 750 by default, within the 5000 per hour of a token, and the
+//! stars are kept for a day, so a service importing every few hours doesn't fetch them again.
+
+use crate::credentials::{host_of, Credentials};
+use crate::retry::{is_transient_http, RateLimiter, RetryPolicy};
+use crate::ImportContext;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use url::Url;
+
+const GITHUB_API_URL: &str = "https://api.github.com/";
+
+/// The issues and pull requests closed lately, and the ones opened lately and still open,
+/// of which the median time to close is taken.
+const ISSUES_PER_REPO: usize = 100;
+
+/// how long the stars of a repo are kept, so the runs of a service don't fetch them again
+const STARS_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+lazy_static! {
+    static ref STARS_CACHE: Mutex<HashMap<String, (Instant, Option<i64>)>> =
+        Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Deserialize)]
+struct RepoResponse {
+    stargazers_count: i64,
+}
+
+/// An issue or a pull request.
+#[derive(Debug, Deserialize)]
+struct IssueResponse {
+    created_at: String,
+    closed_at: Option<String>,
+}
+
+pub struct GithubClient {
+    client: Client,
+    token: Option<String>,
+    limiter: RateLimiter,
+    retry: RetryPolicy,
+}
+
+/// The `owner/repo` of a GitHub url, such as `https://github.com/tokio-rs/tokio.git`.
+fn github_repo(url: &str) -> Option<String> {
+    if host_of(url)? != "github.com" {
+        return None;
+    }
+    let url = Url::parse(url).ok()?;
+    let mut segments = url.path_segments()?.filter(|s| !s.is_empty());
+    let owner = segments.next()?;
+    let repo = segments.next()?.trim_end_matches(".git");
+    Some(format!("{}/{}", owner, repo))
+}
+
+impl GithubClient {
+    pub fn new() -> Result<Self, reqwest::Error> {
+        let client = Client::builder()
+            .user_agent(concat!("crates-pro/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self {
+            client,
+            token: Credentials::from_env().for_host("github.com").token(),
+            limiter: RateLimiter::from_env("GITHUB_API_INTERVAL_MS", 750),
+            retry: RetryPolicy::from_env(),
+        })
+    }
+
+    /// The stars of the repo `owner/repo`, `None` if not found.
+    pub(crate) async fn repo_stars(&mut self, repo: &str) -> Result<Option<i64>, reqwest::Error> {
+        if let Some((time, stars)) = STARS_CACHE.lock().unwrap().get(repo) {
+            if time.elapsed() < STARS_CACHE_TTL {
+                return Ok(*stars);
+            }
+        }
+        let resp: Option<RepoResponse> = self.get(&format!("repos/{}", repo)).await?;
+        let stars = resp.map(|r| r.stargazers_count);
+        STARS_CACHE
+            .lock()
+            .unwrap()
+            .insert(repo.to_string(), (Instant::now(), stars));
+        Ok(stars)
+    }
+
+    /// The median hours to close the issues and pull requests of the repo `owner/repo`,
+    /// the ones still open counted by their age so far, so that a large backlog isn't hidden
+    /// by a few quick closes. `None` if not found or it has no issue.
+    pub(crate) async fn issue_close_hours(
+        &mut self,
+        repo: &str,
+    ) -> Result<Option<f64>, reqwest::Error> {
+        let mut issues = vec![];
+        for (state, sort) in [("closed", "updated"), ("open", "created")] {
+            let path = format!(
+                "repos/{}/issues?state={}&sort={}&direction=desc&per_page={}",
+                repo, state, sort, ISSUES_PER_REPO
+            );
+            match self.get::<Vec<IssueResponse>>(&path).await? {
+                Some(page) => issues.extend(page),
+                None => return Ok(None),
+            }
+        }
+        Ok(median_close_hours(&issues, Utc::now()))
+    }
+
+    /// GET `path` of the API, `None` if not found.
+    async fn get<T: DeserializeOwned>(&mut self, path: &str) -> Result<Option<T>, reqwest::Error> {
+        self.limiter.wait().await;
+        let url = format!("{}{}", GITHUB_API_URL, path);
+        let (client, token, url) = (&self.client, &self.token, &url);
+        self.retry
+            .retry(url, is_transient_http, || async move {
+                let mut request = client
+                    .get(url)
+                    .header("Accept", "application/vnd.github+json");
+                if let Some(token) = token {
+                    request = request.bearer_auth(token);
+                }
+                let resp = request.send().await?;
+                if resp.status() == StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+                Ok(Some(resp.error_for_status()?.json().await?))
+            })
+            .await
+    }
+}
+
+/// The median hours from the creation to the close of `issues`, or to `now` if still open.
+fn median_close_hours(issues: &[IssueResponse], now: DateTime<Utc>) -> Option<f64> {
+    let mut hours: Vec<f64> = issues
+        .iter()
+        .filter_map(|issue| {
+            let created = DateTime::parse_from_rfc3339(&issue.created_at).ok()?;
+            let closed = match issue.closed_at.as_deref() {
+                Some(closed_at) => DateTime::parse_from_rfc3339(closed_at).ok()?.to_utc(),
+                None => now,
+            };
+            Some((closed - created.to_utc()).num_seconds().max(0) as f64 / 3600.0)
+        })
+        .collect();
+    if hours.is_empty() {
+        return None;
+    }
+    hours.sort_by(f64::total_cmp);
+    let mid = hours.len() / 2;
+    Some(if hours.len().is_multiple_of(2) {
+        (hours[mid - 1] + hours[mid]) / 2.0
+    } else {
+        hours[mid]
+    })
+}
+
+impl ImportContext {
+    /// Fill the stars of the programs whose repos are on GitHub.
+    pub async fn enrich_stars(&mut self, client: &mut GithubClient) {
+        tracing::info!("Start to enrich stars");
+        let mut stars: HashMap<String, Option<i64>> = HashMap::new();
+        for program in &mut self.programs {
+            let Some(repo) = program.github_url.as_deref().and_then(github_repo) else {
+                continue;
+            };
+            if !stars.contains_key(&repo) {
+                let repo_stars = match client.repo_stars(&repo).await {
+                    Ok(Some(repo_stars)) => Some(repo_stars),
+                    Ok(None) => {
+                        tracing::debug!("{} is not found on GitHub", repo);
+                        None
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to get the stars of {}: {}", repo, e);
+                        None
+                    }
+                };
+                stars.insert(repo.clone(), repo_stars);
+            }
+            program.stars = stars[&repo];
+        }
+        tracing::info!(
+            "Finish to enrich the stars of {} repos",
+            stars.values().filter(|s| s.is_some()).count()
+        );
+    }
+
+    /// Fill how soon the issues and pull requests are closed for the programs on GitHub.
+    pub async fn enrich_responsiveness(&mut self, client: &mut GithubClient) {
+        tracing::info!("Start to enrich responsiveness");
+        let mut close_hours: HashMap<String, Option<f64>> = HashMap::new();
+        for program in &mut self.programs {
+            let Some(repo) = program.github_url.as_deref().and_then(github_repo) else {
+                continue;
+            };
+            if !close_hours.contains_key(&repo) {
+                let hours = match client.issue_close_hours(&repo).await {
+                    Ok(hours) => hours,
+                    Err(e) => {
+                        tracing::warn!("Failed to get the issues of {}: {}", repo, e);
+                        None
+                    }
+                };
+                close_hours.insert(repo.clone(), hours);
+            }
+            program.issue_close_hours = close_hours[&repo];
+        }
+        tracing::info!(
+            "Finish to enrich the responsiveness of {} repos",
+            close_hours.values().filter(|h| h.is_some()).count()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_repo() {
+        assert_eq!(
+            github_repo("https://github.com/tokio-rs/tokio.git").as_deref(),
+            Some("tokio-rs/tokio")
+        );
+        assert_eq!(
+            github_repo("https://github.com/serde-rs/serde/tree/master/serde_json").as_deref(),
+            Some("serde-rs/serde")
+        );
+        assert_eq!(github_repo("https://gitlab.com/a/b"), None);
+        assert_eq!(github_repo("https://github.com/tokio-rs"), None);
+    }
+
+    #[test]
+    fn test_median_close_hours() {
+        let issue = |created_at: &str, closed_at: Option<&str>| IssueResponse {
+            created_at: created_at.to_string(),
+            closed_at: closed_at.map(String::from),
+        };
+        let now = DateTime::parse_from_rfc3339("2024-01-11T00:00:00Z")
+            .unwrap()
+            .to_utc();
+        let issues = [
+            issue("2024-01-01T00:00:00Z", Some("2024-01-01T02:00:00Z")),
+            issue("2024-01-01T00:00:00Z", Some("2024-01-02T00:00:00Z")),
+            issue("2024-01-01T00:00:00Z", Some("2024-01-01T06:00:00Z")),
+            issue("2024-01-10T00:00:00Z", None),
+            issue("2024-01-01T00:00:00Z", None),
+        ];
+        assert_eq!(median_close_hours(&issues[..2], now), Some(13.0));
+        assert_eq!(median_close_hours(&issues[..3], now), Some(6.0));
+        // the open issues count by their age, 24 and 240 hours
+        assert_eq!(median_close_hours(&issues, now), Some(24.0));
+        assert_eq!(median_close_hours(&issues[4..], now), Some(240.0));
+        assert_eq!(median_close_hours(&[], now), None);
+    }
+}
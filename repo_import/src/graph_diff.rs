@@ -0,0 +1,426 @@
+//! Compare two outputs of the import, such as the tugraph import files of two nightly syncs,
+//! and report the vertices and edges added, removed or changed between them.
+//!
+//! An output is a directory of the csv files described by its `import.config`. The vertices
+//! are matched by their primary keys, and the edges by their `SRC_ID -> DST_ID`, so a record
+//! is changed if any of its properties is. Several edges of the same pair, such as one per
+//! `target`, are matched by all their properties first, and the rest are paired as changed.
+//! The csv files are looked up by their names in the directory rather than the paths in
+//! `import.config`, so an output can be moved or copied.
+//!
+//! A checkpoint or a snapshot of the graph is compared too, by exporting it as the tugraph
+//! import files first.
+
+use crate::output::{GraphExporter, OutputFormat};
+use crate::snapshot::SnapshotStore;
+use crate::tugraph_export::TuGraphExporter;
+use crate::ImportContext;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+const IMPORT_CONFIG_FILE: &str = "import.config";
+
+#[derive(Debug, Deserialize)]
+struct ImportConfig {
+    schema: Vec<LabelSchema>,
+    files: Vec<ImportFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LabelSchema {
+    label: String,
+    primary: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportFile {
+    path: String,
+    label: String,
+    /// the label of the source vertices, only for the edges
+    #[serde(rename = "SRC_ID")]
+    src_label: Option<String>,
+}
+
+/// A property whose value is changed, none if the property is missing.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordChange {
+    /// the primary key of a vertex, or `SRC_ID -> DST_ID` of an edge
+    pub key: String,
+    pub fields: BTreeMap<String, FieldChange>,
+}
+
+/// The changes of the records of a label, sorted by their keys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LabelDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<RecordChange>,
+    pub unchanged: usize,
+}
+
+impl LabelDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GraphDiff {
+    pub vertices: BTreeMap<String, LabelDiff>,
+    pub edges: BTreeMap<String, LabelDiff>,
+}
+
+impl GraphDiff {
+    /// A line of the numbers of the changes per label, such as `program: +3 -1 ~10`.
+    pub fn summary(&self) -> Vec<String> {
+        self.vertices
+            .iter()
+            .chain(self.edges.iter())
+            .filter(|(_, diff)| !diff.is_empty())
+            .map(|(label, diff)| {
+                format!(
+                    "{}: +{} -{} ~{}",
+                    label,
+                    diff.added.len(),
+                    diff.removed.len(),
+                    diff.changed.len()
+                )
+            })
+            .collect()
+    }
+}
+
+type Row = BTreeMap<String, String>;
+
+/// The records of a label by their keys, with the values of their properties.
+#[derive(Debug, Default)]
+struct Records {
+    is_edge: bool,
+    /// several rows of a key for the edges of the same pair
+    rows: HashMap<String, Vec<Row>>,
+}
+
+/// Read the records of the labels in `labels`, or all if it's empty.
+fn read_output(dir: &Path, labels: &[String]) -> Result<BTreeMap<String, Records>, Box<dyn Error>> {
+    let config_path = dir.join(IMPORT_CONFIG_FILE);
+    let config: ImportConfig = serde_json::from_str(
+        &fs::read_to_string(&config_path)
+            .map_err(|e| format!("Failed to read {:?}: {}", config_path, e))?,
+    )?;
+    let primaries: HashMap<&str, &str> = config
+        .schema
+        .iter()
+        .filter_map(|s| Some((s.label.as_str(), s.primary.as_deref()?)))
+        .collect();
+
+    let mut res: BTreeMap<String, Records> = BTreeMap::new();
+    for file in &config.files {
+        if !labels.is_empty() && !labels.contains(&file.label) {
+            continue;
+        }
+        let file_name = Path::new(&file.path)
+            .file_name()
+            .ok_or_else(|| format!("Bad path {} in {:?}", file.path, config_path))?;
+        let is_edge = file.src_label.is_some();
+        let primary = match primaries.get(file.label.as_str()) {
+            Some(primary) => *primary,
+            None if is_edge => "",
+            None => return Err(format!("No primary key of {}", file.label).into()),
+        };
+
+        let records = res.entry(file.label.clone()).or_default();
+        records.is_edge = is_edge;
+        let mut reader = csv::Reader::from_path(dir.join(file_name))?;
+        let headers = reader.headers()?.clone();
+        for row in reader.records() {
+            let row: Row = headers
+                .iter()
+                .map(String::from)
+                .zip(row?.iter().map(String::from))
+                .collect();
+            let key = if is_edge {
+                format!(
+                    "{} -> {}",
+                    row.get("SRC_ID").map_or("", |s| s.as_str()),
+                    row.get("DST_ID").map_or("", |s| s.as_str())
+                )
+            } else {
+                row.get(primary).cloned().unwrap_or_default()
+            };
+            records.rows.entry(key).or_default().push(row);
+        }
+    }
+    Ok(res)
+}
+
+fn changed_fields(old_row: &Row, new_row: &Row) -> BTreeMap<String, FieldChange> {
+    let mut fields = BTreeMap::new();
+    for name in old_row.keys().chain(new_row.keys()) {
+        let (old, new) = (old_row.get(name), new_row.get(name));
+        if old != new && !fields.contains_key(name) {
+            fields.insert(
+                name.clone(),
+                FieldChange {
+                    old: old.cloned(),
+                    new: new.cloned(),
+                },
+            );
+        }
+    }
+    fields
+}
+
+fn diff_records(old: Records, new: Records) -> LabelDiff {
+    let mut diff = LabelDiff::default();
+    let mut old_rows = old.rows;
+    for (key, mut new_rows) in new.rows {
+        let mut old_rows = old_rows.remove(&key).unwrap_or_default();
+        // the rows in both are unchanged, whatever their order
+        new_rows.retain(|row| match old_rows.iter().position(|old| old == row) {
+            Some(i) => {
+                old_rows.swap_remove(i);
+                diff.unchanged += 1;
+                false
+            }
+            None => true,
+        });
+        old_rows.sort_unstable();
+        new_rows.sort_unstable();
+        for new_row in new_rows {
+            // paired with the most similar row left
+            let closest = old_rows
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, old_row)| changed_fields(old_row, &new_row).len())
+                .map(|(i, _)| i);
+            match closest {
+                Some(i) => {
+                    let old_row = old_rows.swap_remove(i);
+                    diff.changed.push(RecordChange {
+                        key: key.clone(),
+                        fields: changed_fields(&old_row, &new_row),
+                    });
+                }
+                None => diff.added.push(key.clone()),
+            }
+        }
+        diff.removed.extend(old_rows.iter().map(|_| key.clone()));
+    }
+    for (key, rows) in old_rows {
+        diff.removed.extend(rows.iter().map(|_| key.clone()));
+    }
+    diff.added.sort_unstable();
+    diff.removed.sort_unstable();
+    diff.changed.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+    diff
+}
+
+/// Compare the import outputs in the directories `old` and `new`,
+/// only the labels in `labels` if it's not empty.
+pub fn diff_import_files(
+    old: &Path,
+    new: &Path,
+    labels: &[String],
+) -> Result<GraphDiff, Box<dyn Error>> {
+    Ok(diff_outputs(
+        read_output(old, labels)?,
+        read_output(new, labels)?,
+    ))
+}
+
+/// Read a graph to compare: the import files in a directory, a checkpoint file, or
+/// the snapshot of the id or the name in [`SnapshotStore::from_env`] otherwise.
+async fn read_graph(
+    input: &Path,
+    labels: &[String],
+) -> Result<BTreeMap<String, Records>, Box<dyn Error>> {
+    if input.is_dir() {
+        return read_output(input, labels);
+    }
+    let context = if input.is_file() {
+        ImportContext::load_from_file(&input.to_string_lossy()).await?
+    } else {
+        SnapshotStore::from_env().load(&input.to_string_lossy())?
+    };
+    let dir = tempfile::tempdir()?;
+    let formats = [OutputFormat::Csv];
+    let mut exporter = TuGraphExporter::new(dir.path(), &formats);
+    context.export_graph(&mut exporter)?;
+    exporter.finish()?;
+    read_output(dir.path(), labels)
+}
+
+/// Compare two graphs, each of the import files, a checkpoint or a snapshot,
+/// only the labels in `labels` if it's not empty.
+pub async fn diff_graphs(
+    old: &Path,
+    new: &Path,
+    labels: &[String],
+) -> Result<GraphDiff, Box<dyn Error>> {
+    let old = read_graph(old, labels).await?;
+    let new = read_graph(new, labels).await?;
+    Ok(diff_outputs(old, new))
+}
+
+fn diff_outputs(mut old: BTreeMap<String, Records>, new: BTreeMap<String, Records>) -> GraphDiff {
+    let mut diff = GraphDiff::default();
+    let mut add = |label: String, records: LabelDiff, is_edge: bool| {
+        if is_edge {
+            diff.edges.insert(label, records);
+        } else {
+            diff.vertices.insert(label, records);
+        }
+    };
+    for (label, new_records) in new {
+        let old_records = old.remove(&label).unwrap_or_default();
+        let is_edge = new_records.is_edge;
+        add(label, diff_records(old_records, new_records), is_edge);
+    }
+    for (label, old_records) in old {
+        let is_edge = old_records.is_edge;
+        add(
+            label,
+            diff_records(old_records, Records::default()),
+            is_edge,
+        );
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_output(dir: &Path, programs: &str, depends_on: &str) {
+        let config = serde_json::json!({
+            "schema": [
+                { "label": "program", "type": "VERTEX", "primary": "id" },
+                { "label": "depends_on", "type": "EDGE" },
+            ],
+            "files": [
+                { "path": "/elsewhere/program.csv", "label": "program" },
+                {
+                    "path": "/elsewhere/depends_on.csv",
+                    "label": "depends_on",
+                    "SRC_ID": "library_version",
+                    "DST_ID": "library_version",
+                },
+            ],
+        });
+        fs::write(dir.join(IMPORT_CONFIG_FILE), config.to_string()).unwrap();
+        fs::write(dir.join("program.csv"), programs).unwrap();
+        fs::write(dir.join("depends_on.csv"), depends_on).unwrap();
+    }
+
+    #[test]
+    fn test_diff_import_files() {
+        let old = tempfile::tempdir().unwrap();
+        let new = tempfile::tempdir().unwrap();
+        write_output(
+            old.path(),
+            "id,name,max_version\nx/a,a,1.0.0\nx/b,b,0.1.0\nx/c,c,2.0.0\n",
+            "SRC_ID,DST_ID\na/1.0.0,b/0.1.0\n",
+        );
+        write_output(
+            new.path(),
+            "id,name,max_version\nx/a,a,1.1.0\nx/c,c,2.0.0\nx/d,d,0.1.0\n",
+            "SRC_ID,DST_ID\na/1.1.0,d/0.1.0\n",
+        );
+
+        let diff = diff_import_files(old.path(), new.path(), &[]).unwrap();
+        let programs = &diff.vertices["program"];
+        assert_eq!(programs.added, ["x/d"]);
+        assert_eq!(programs.removed, ["x/b"]);
+        assert_eq!(programs.unchanged, 1);
+        assert_eq!(programs.changed.len(), 1);
+        assert_eq!(programs.changed[0].key, "x/a");
+        assert_eq!(
+            programs.changed[0].fields["max_version"],
+            FieldChange {
+                old: Some("1.0.0".to_string()),
+                new: Some("1.1.0".to_string()),
+            }
+        );
+        let depends_on = &diff.edges["depends_on"];
+        assert_eq!(depends_on.added, ["a/1.1.0 -> d/0.1.0"]);
+        assert_eq!(depends_on.removed, ["a/1.0.0 -> b/0.1.0"]);
+        assert_eq!(
+            diff.summary(),
+            ["program: +1 -1 ~1", "depends_on: +1 -1 ~0"]
+        );
+
+        let diff = diff_import_files(old.path(), new.path(), &["depends_on".to_string()]).unwrap();
+        assert!(diff.vertices.is_empty());
+        assert!(diff_import_files(old.path(), old.path(), &[])
+            .unwrap()
+            .summary()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_diff_edges_of_the_same_pair() {
+        let old = tempfile::tempdir().unwrap();
+        let new = tempfile::tempdir().unwrap();
+        write_output(
+            old.path(),
+            "id\n",
+            "SRC_ID,DST_ID,req,target\na/1.0.0,b/0.1.0,^0.1,cfg(unix)\na/1.0.0,b/0.1.0,^0.1,cfg(windows)\n",
+        );
+        write_output(
+            new.path(),
+            "id\n",
+            "SRC_ID,DST_ID,req,target\na/1.0.0,b/0.1.0,^0.1,cfg(windows)\na/1.0.0,b/0.1.0,^0.1.1,cfg(unix)\n\
+             a/1.0.0,b/0.1.0,^0.2,cfg(wasm)\n",
+        );
+
+        let diff = diff_import_files(old.path(), new.path(), &[]).unwrap();
+        let depends_on = &diff.edges["depends_on"];
+        assert_eq!(depends_on.unchanged, 1);
+        assert_eq!(depends_on.changed.len(), 1);
+        let fields = &depends_on.changed[0].fields;
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["req"]);
+        assert_eq!(depends_on.added, ["a/1.0.0 -> b/0.1.0"]);
+        assert!(depends_on.removed.is_empty());
+
+        let diff = diff_import_files(new.path(), old.path(), &[]).unwrap();
+        assert_eq!(diff.summary(), ["depends_on: +0 -1 ~1"]);
+    }
+
+    #[tokio::test]
+    async fn test_diff_checkpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut context = ImportContext::default();
+        context.programs.push(model::tugraph_model::Program {
+            id: "x/a".to_string(),
+            name: "a".to_string(),
+            ..Default::default()
+        });
+        let old = dir.path().join("old.bin");
+        context.save_to_file(&old.to_string_lossy()).await.unwrap();
+        context.programs[0].name = "b".to_string();
+        let new = dir.path().join("new.bin");
+        context.save_to_file(&new.to_string_lossy()).await.unwrap();
+
+        let diff = diff_graphs(&old, &new, &["program".to_string()])
+            .await
+            .unwrap();
+        let programs = &diff.vertices["program"];
+        assert_eq!(programs.changed.len(), 1);
+        assert_eq!(programs.changed[0].key, "x/a");
+        assert!(programs.changed[0].fields.contains_key("name"));
+
+        // an unknown snapshot
+        assert!(diff_graphs(&old, &dir.path().join("missing"), &[])
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Export the whole program/dependency graph as a GraphML file,
+//! which can be opened by Gephi or yEd for visualization.
+
+use crate::output::{fields_of, GraphExporter, Record};
+use arrow::datatypes::DataType;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const GRAPHML_FILE: &str = "graph.graphml";
+
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+fn graphml_type(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::Boolean => "boolean",
+        DataType::Int8 | DataType::Int16 | DataType::Int32 => "int",
+        DataType::Int64 | DataType::UInt32 | DataType::UInt64 => "long",
+        DataType::Float32 | DataType::Float64 => "double",
+        _ => "string",
+    }
+}
+
+/// The id of a node is unique in the whole graph,
+/// e.g. `version/tokio/1.0.0`, since vertices of different labels may share the primary.
+fn node_id(label: &str, primary: &str) -> String {
+    format!("{}/{}", label, primary)
+}
+
+pub(crate) struct GraphMLExporter {
+    path: PathBuf,
+    /// `(domain, name)` -> type, the `<key>`s declared before the graph
+    keys: BTreeMap<(&'static str, String), &'static str>,
+    node_ids: HashSet<String>,
+    nodes: String,
+    edges: String,
+}
+
+impl GraphMLExporter {
+    pub(crate) fn new(dir: &Path) -> Self {
+        Self {
+            path: dir.join(GRAPHML_FILE),
+            keys: BTreeMap::new(),
+            node_ids: HashSet::new(),
+            nodes: String::new(),
+            edges: String::new(),
+        }
+    }
+
+    /// Declare the keys of `T`, and return the names of its properties.
+    fn declare_keys<T: Record>(
+        &mut self,
+        domain: &'static str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut names = vec![];
+        for field in fields_of::<T>()? {
+            if field.name() == "SRC_ID" || field.name() == "DST_ID" {
+                continue;
+            }
+            self.keys
+                .entry((domain, field.name().clone()))
+                .or_insert_with(|| graphml_type(field.data_type()));
+            names.push(field.name().clone());
+        }
+        Ok(names)
+    }
+
+    fn write_data(out: &mut String, domain: &str, names: &[String], value: &Value) {
+        for name in names {
+            let text = match value.get(name) {
+                None | Some(Value::Null) => continue,
+                Some(Value::String(s)) => escape(s),
+                Some(v) => v.to_string(),
+            };
+            let _ = write!(
+                out,
+                "<data key=\"{}_{}\">{}</data>",
+                domain,
+                escape(name),
+                text
+            );
+        }
+    }
+}
+
+impl GraphExporter for GraphMLExporter {
+    fn vertex<T: Record>(
+        &mut self,
+        label: &str,
+        primary: &str,
+        _file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>> {
+        let names = self.declare_keys::<T>("node")?;
+
+        for item in items {
+            let value = serde_json::to_value(item)?;
+            let id = match value.get(primary).and_then(|v| v.as_str()) {
+                Some(primary) => node_id(label, primary),
+                None => continue,
+            };
+            if !self.node_ids.insert(id.clone()) {
+                continue;
+            }
+            let _ = write!(
+                self.nodes,
+                "    <node id=\"{}\"><data key=\"label_node\">{}</data>",
+                escape(&id),
+                escape(label)
+            );
+            Self::write_data(&mut self.nodes, "node", &names, &value);
+            self.nodes.push_str("</node>\n");
+        }
+        Ok(())
+    }
+
+    /// The edges whose ends are not exported are skipped.
+    fn edge<T: Record>(
+        &mut self,
+        label: &str,
+        src_label: &str,
+        dst_label: &str,
+        _file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>> {
+        let names = self.declare_keys::<T>("edge")?;
+
+        for item in items {
+            let value = serde_json::to_value(item)?;
+            let (src, dst) = match (
+                value.get("SRC_ID").and_then(|v| v.as_str()),
+                value.get("DST_ID").and_then(|v| v.as_str()),
+            ) {
+                (Some(src), Some(dst)) => (node_id(src_label, src), node_id(dst_label, dst)),
+                _ => continue,
+            };
+            if !self.node_ids.contains(&src) || !self.node_ids.contains(&dst) {
+                continue;
+            }
+            let _ = write!(
+                self.edges,
+                "    <edge source=\"{}\" target=\"{}\"><data key=\"label_edge\">{}</data>",
+                escape(&src),
+                escape(&dst),
+                escape(label)
+            );
+            Self::write_data(&mut self.edges, "edge", &names, &value);
+            self.edges.push_str("</edge>\n");
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        out.push_str(
+            "  <key id=\"label_node\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        );
+        out.push_str(
+            "  <key id=\"label_edge\" for=\"edge\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        );
+        for ((domain, name), ty) in &self.keys {
+            let _ = writeln!(
+                out,
+                "  <key id=\"{}_{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>",
+                domain,
+                escape(name),
+                domain,
+                escape(name),
+                ty
+            );
+        }
+        out.push_str("  <graph id=\"crates\" edgedefault=\"directed\">\n");
+        out.push_str(&self.nodes);
+        out.push_str(&self.edges);
+        out.push_str("  </graph>\n</graphml>\n");
+        fs::write(&self.path, out)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! Remember the commit each repo is imported at, so that a re-import skips
+//! the repos whose HEAD has not changed, and only the changed ones are parsed again.
+//! A repo is only skipped if its crates are in the context, i.e. restored from a checkpoint,
+//! so a run from scratch with the cache of another run still outputs every repo.
+//!
+//! The cache is kept in the checkpoint, and in the json file at `IMPORT_CACHE`
+//! for the runs without a checkpoint. Set `IMPORT_FULL=1` to import every repo.
+
+use crate::ImportContext;
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ImportCache {
+    /// repo url -> the last imported commit
+    commits: HashMap<String, String>,
+}
+
+impl ImportCache {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.commits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commits.is_empty()
+    }
+
+    /// Whether the repo is imported at `commit` already.
+    pub(crate) fn is_unchanged(&self, url: &str, commit: &str) -> bool {
+        !full_import() && self.commits.get(url).is_some_and(|c| c == commit)
+    }
+
+    pub(crate) fn record(&mut self, url: String, commit: String) {
+        self.commits.insert(url, commit);
+    }
+}
+
+fn full_import() -> bool {
+    env::var("IMPORT_FULL").is_ok_and(|v| v == "1")
+}
+
+/// The commit of HEAD, `None` for an empty repo.
+pub(crate) fn head_commit(repo: &Repository) -> Option<String> {
+    let commit = repo.head().ok()?.peel_to_commit().ok()?;
+    Some(commit.id().to_string())
+}
+
+impl ImportContext {
+    /// Whether the crates of the repo are in the context.
+    pub(crate) fn has_programs_of(&self, url: &str) -> bool {
+        self.programs
+            .iter()
+            .any(|p| p.mega_url.as_deref() == Some(url))
+    }
+
+    /// Merge the cache at `IMPORT_CACHE` into the one of the checkpoint.
+    pub fn load_import_cache(&mut self) {
+        let path = match env::var("IMPORT_CACHE") {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+        match ImportCache::load(Path::new(&path)) {
+            Ok(cache) => {
+                tracing::info!("Loaded the commits of {} repos from {}", cache.len(), path);
+                self.import_cache.commits.extend(cache.commits);
+            }
+            Err(e) => tracing::warn!("Failed to load the import cache {}: {}", path, e),
+        }
+    }
+
+    /// Save the cache into `IMPORT_CACHE` if it's set.
+    pub fn save_import_cache(&self) {
+        if let Ok(path) = env::var("IMPORT_CACHE") {
+            if let Err(e) = self.import_cache.save(Path::new(&path)) {
+                tracing::error!("Failed to save the import cache {}: {}", path, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local::init_crate_repo;
+
+    #[test]
+    fn test_import_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+        let url = "https://github.com/tokio-rs/tokio";
+
+        let mut cache = ImportCache::default();
+        assert!(!cache.is_unchanged(url, "a1"));
+        cache.record(url.to_string(), "a1".to_string());
+        cache.save(&path).unwrap();
+
+        let cache = ImportCache::load(&path).unwrap();
+        assert!(cache.is_unchanged(url, "a1"));
+        assert!(!cache.is_unchanged(url, "b2"));
+    }
+
+    #[tokio::test]
+    async fn test_import_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("twice");
+        init_crate_repo(&repo, "import_twice");
+        let url = "https://github.com/x/import_twice";
+
+        let mut first = ImportContext::default();
+        first
+            .parse_a_local_repo_and_return_new_versions(repo.clone(), url.to_string())
+            .await
+            .unwrap();
+        assert!(first.has_programs_of(url));
+
+        // a new run with the cache of the first one, but not its checkpoint
+        let mut second = ImportContext {
+            import_cache: first.import_cache.clone(),
+            ..Default::default()
+        };
+        second
+            .parse_a_local_repo_and_return_new_versions(repo.clone(), url.to_string())
+            .await
+            .unwrap();
+        assert!(second.programs.iter().any(|p| p.name == "import_twice"));
+
+        // the crates are in the context now, so the repo is skipped
+        let programs = second.programs.len();
+        second
+            .parse_a_local_repo_and_return_new_versions(repo, url.to_string())
+            .await
+            .unwrap();
+        assert_eq!(second.programs.len(), programs);
+    }
+}
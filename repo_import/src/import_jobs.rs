@@ -0,0 +1,172 @@
+//! The import jobs submitted by the other services, such as the gRPC API.
+//!
+//! A job is a list of repo urls, sent as [`RepoImportRequest`]s to `KAFKA_REPO_REQUEST_TOPIC`
+//! for the [`crate::ImportWorker`]s. The [`RepoImportResult`]s of the workers in
+//! `KAFKA_REPO_RESULT_TOPIC` are collected into the status of the jobs by their job id.
+//!
+//! Every service submitting jobs, such as the gRPC API and the GitHub webhook, consumes all
+//! the results in its own group `<KAFKA_IMPORT_JOBS_GROUP_ID>_<service>`, where
+//! `KAFKA_IMPORT_JOBS_GROUP_ID` is `crates_pro_import_jobs` by default, and skips the results
+//! of the jobs of the others.
+//!
+//! The jobs are kept in memory, so they are lost when the service restarts.
+
+use crate::kafka_handler::KafkaHandler;
+use model::general_model::{RepoImportRequest, RepoImportResult};
+use model::message::decode;
+use rdkafka::error::KafkaError;
+use rdkafka::Message;
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct ImportJob {
+    pub id: String,
+    pub urls: Vec<String>,
+    /// in seconds since the epoch
+    pub submitted_at: u64,
+}
+
+/// The status of a job, with the result of each repo if it's imported.
+#[derive(Debug, Clone)]
+pub struct ImportJobStatus {
+    pub job: ImportJob,
+    pub results: Vec<(String, Option<RepoImportResult>)>,
+}
+
+pub struct ImportJobs {
+    producer: KafkaHandler,
+    topic: String,
+    /// the consumer of the results, if `KAFKA_REPO_RESULT_TOPIC` is set
+    results_consumer: Option<KafkaHandler>,
+    jobs: Mutex<HashMap<String, ImportJob>>,
+    /// job id -> url -> the result
+    results: Mutex<HashMap<String, HashMap<String, RepoImportResult>>>,
+}
+
+impl ImportJobs {
+    /// Connect to kafka, and collect the results of the jobs submitted by `service`
+    /// in the background.
+    pub fn from_env(service: &str) -> Result<Arc<Self>, Box<dyn Error>> {
+        let brokers = env::var("KAFKA_BROKER")?;
+        let topic = env::var("KAFKA_REPO_REQUEST_TOPIC")?;
+        let results_consumer = match env::var("KAFKA_REPO_RESULT_TOPIC") {
+            Ok(result_topic) => {
+                let group_id = format!(
+                    "{}_{}",
+                    env::var("KAFKA_IMPORT_JOBS_GROUP_ID")
+                        .unwrap_or_else(|_| "crates_pro_import_jobs".to_string()),
+                    service
+                );
+                Some(KafkaHandler::new_consumer(
+                    &brokers,
+                    &group_id,
+                    &result_topic,
+                )?)
+            }
+            Err(_) => {
+                tracing::warn!("KAFKA_REPO_RESULT_TOPIC is not set, the jobs stay pending");
+                None
+            }
+        };
+        let jobs = Arc::new(ImportJobs {
+            producer: KafkaHandler::new_producer(&brokers)?,
+            topic,
+            results_consumer,
+            jobs: Mutex::new(HashMap::new()),
+            results: Mutex::new(HashMap::new()),
+        });
+        if jobs.results_consumer.is_some() {
+            tokio::spawn(Arc::clone(&jobs).collect_results());
+        }
+        Ok(jobs)
+    }
+
+    /// Request the workers to import the repos.
+    pub async fn submit(&self, urls: Vec<String>) -> ImportJob {
+        let job = ImportJob {
+            id: Uuid::new_v4().to_string(),
+            urls,
+            submitted_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        };
+        // before the requests, not to skip the results of the workers done first
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(job.id.clone(), job.clone());
+        for url in &job.urls {
+            let request = RepoImportRequest {
+                url: url.clone(),
+                job_id: Some(job.id.clone()),
+            };
+            self.producer.send_encoded(&self.topic, url, &request).await;
+        }
+        tracing::info!(
+            "Submitted the import job {} of {} repos",
+            job.id,
+            job.urls.len()
+        );
+        job
+    }
+
+    pub fn status(&self, id: &str) -> Option<ImportJobStatus> {
+        let job = self.jobs.lock().unwrap().get(id)?.clone();
+        let results = self.results.lock().unwrap();
+        let results = job
+            .urls
+            .iter()
+            .map(|url| {
+                let result = results.get(id).and_then(|r| r.get(url));
+                (url.clone(), result.cloned())
+            })
+            .collect();
+        Some(ImportJobStatus { job, results })
+    }
+
+    /// Record the result of a job of this service, the others are skipped.
+    fn record(&self, result: RepoImportResult) {
+        let Some(job_id) = result.job_id.clone() else {
+            return;
+        };
+        if !self.jobs.lock().unwrap().contains_key(&job_id) {
+            return;
+        }
+        self.results
+            .lock()
+            .unwrap()
+            .entry(job_id)
+            .or_default()
+            .insert(result.url.clone(), result);
+    }
+
+    async fn collect_results(self: Arc<Self>) {
+        let Some(consumer) = &self.results_consumer else {
+            return;
+        };
+        loop {
+            // the message is dropped before sleeping, since it's not Send
+            let result = consumer
+                .consume_once()
+                .await
+                .map(|message| message.payload().and_then(|p| decode(p).ok()));
+            match result {
+                Ok(Some(result)) => self.record(result),
+                Ok(None) => tracing::warn!("Skip an invalid import result"),
+                Err(KafkaError::NoMessageReceived) => {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                Err(e) => {
+                    tracing::error!("Failed to consume an import result: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+}
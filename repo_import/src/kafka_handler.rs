@@ -1,12 +1,15 @@
+use model::message::{self, MessageFormat};
 use rdkafka::config::{ClientConfig, RDKafkaLogLevel};
 use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer, ConsumerContext, Rebalance};
 use rdkafka::error::{KafkaError, KafkaResult};
 use rdkafka::message::{BorrowedMessage, Headers};
 use rdkafka::producer::{BaseProducer, BaseRecord, ProducerContext};
 use rdkafka::util::Timeout;
-use rdkafka::{ClientContext, Message, TopicPartitionList};
+use rdkafka::{ClientContext, Message, Offset, TopicPartitionList};
+use std::collections::HashMap;
 use std::env;
 use std::process::Command;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Clone)]
@@ -43,8 +46,24 @@ impl ProducerContext for CustomContext {
     }
 }
 
+fn consumer_lag(consumer: &BaseConsumer<CustomContext>) -> KafkaResult<HashMap<String, i64>> {
+    let mut lag = HashMap::new();
+    for elem in consumer.position()?.elements() {
+        let (_, high) =
+            consumer.fetch_watermarks(elem.topic(), elem.partition(), Duration::from_secs(5))?;
+        let behind = match elem.offset() {
+            Offset::Offset(offset) => (high - offset).max(0),
+            // nothing is consumed from the partition yet
+            _ => continue,
+        };
+        *lag.entry(elem.topic().to_string()).or_insert(0) += behind;
+    }
+    Ok(lag)
+}
+
 pub enum KafkaHandler {
-    Consumer(BaseConsumer<CustomContext>),
+    /// shared with the blocking tasks querying the brokers
+    Consumer(Arc<BaseConsumer<CustomContext>>),
     Producer(BaseProducer<CustomContext>),
 }
 impl KafkaHandler {
@@ -65,7 +84,7 @@ impl KafkaHandler {
 
         consumer.subscribe(&[topic])?;
 
-        Ok(KafkaHandler::Consumer(consumer))
+        Ok(KafkaHandler::Consumer(Arc::new(consumer)))
     }
 
     pub fn new_producer(brokers: &str) -> Result<Self, KafkaError> {
@@ -104,7 +123,47 @@ impl KafkaHandler {
         }
     }
 
+    /// The topics subscribed by a consumer.
+    pub(crate) fn subscribed_topics(&self) -> Vec<String> {
+        match self {
+            KafkaHandler::Consumer(consumer) => consumer
+                .subscription()
+                .map(|list| {
+                    list.elements()
+                        .iter()
+                        .map(|e| e.topic().to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            KafkaHandler::Producer(_) => vec![],
+        }
+    }
+
+    /// The messages not consumed yet in each topic, over the partitions assigned to the consumer.
+    pub(crate) async fn lag(&self) -> Result<HashMap<String, i64>, String> {
+        let KafkaHandler::Consumer(consumer) = self else {
+            return Ok(HashMap::new());
+        };
+        let consumer = Arc::clone(consumer);
+        // the watermarks are fetched from the brokers, blocking up to the timeout
+        tokio::task::spawn_blocking(move || consumer_lag(&consumer).map_err(|e| e.to_string()))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
     pub async fn send_message(&self, topic: &str, key: &str, payload: &str) {
+        self.send_bytes(topic, key, payload.as_bytes());
+    }
+
+    /// Send a message in the format of `KAFKA_MESSAGE_FORMAT`.
+    pub async fn send_encoded<T: message::Message>(&self, topic: &str, key: &str, message: &T) {
+        match message::encode(message, MessageFormat::from_env()) {
+            Ok(payload) => self.send_bytes(topic, key, &payload),
+            Err(e) => tracing::error!("Failed to encode {}: {}", T::NAME, e),
+        }
+    }
+
+    fn send_bytes(&self, topic: &str, key: &str, payload: &[u8]) {
         if let KafkaHandler::Producer(producer) = self {
             let record = BaseRecord::to(topic).key(key).payload(payload);
 
@@ -117,7 +176,7 @@ impl KafkaHandler {
 
             producer.poll(Timeout::Never);
         } else {
-            tracing::error!("Called send_message on a consumer");
+            tracing::error!("Called send_bytes on a consumer");
         }
     }
 
@@ -0,0 +1,309 @@
+//! Publish the graph to Kafka, each vertex and edge as a json message,
+//! into a topic per label such as `crates_pro.program` and `crates_pro.depends_on`.
+//!
+//! A vertex message is keyed by its id, and an edge message by `<src>-><dst>`:
+//!
+//! ```json
+//! {"kind":"vertex","label":"program","id":"...","properties":{...}}
+//! {"kind":"edge","label":"depends_on","src_label":"library_version","dst_label":"library_version",
+//!  "src":"tokio/1.38.0","dst":"bytes/1.6.0","properties":{...}}
+//! ```
+//!
+//! It's configured by
+//! - `KAFKA_SINK_BROKERS`, `KAFKA_BROKER` by default,
+//! - `KAFKA_SINK_TOPIC_PREFIX`, `crates_pro` by default,
+//! - `KAFKA_SINK_BATCH_SIZE` and `KAFKA_SINK_LINGER_MS`, the batching of the producer,
+//! - `KAFKA_SINK_DELIVERY`, `at_most_once`, `at_least_once` (by default) or `exactly_once`,
+//!   where `exactly_once` is the idempotent producer, without duplicates in a partition.
+//!
+//! A graph published again, such as the one of the import worker, can skip the messages
+//! published before, unless they failed to deliver.
+
+use crate::error::ImportError;
+use crate::metrics::SINK_DURATION;
+use crate::output::{GraphExporter, Record};
+use crate::ImportContext;
+use rdkafka::config::ClientConfig;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext};
+use rdkafka::ClientContext;
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::env;
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// the time to wait for the messages in flight at the end
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Count the messages failed to deliver.
+struct SinkContext {
+    failed: Arc<AtomicUsize>,
+}
+
+impl ClientContext for SinkContext {}
+
+impl ProducerContext for SinkContext {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult, _delivery_opaque: Self::DeliveryOpaque) {
+        if let Err((e, _)) = result {
+            tracing::error!("Failed to deliver message: {}", e);
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+fn delivery_config(delivery: &str) -> Result<[(&'static str, &'static str); 2], String> {
+    match delivery {
+        "at_most_once" => Ok([("acks", "0"), ("enable.idempotence", "false")]),
+        "at_least_once" => Ok([("acks", "all"), ("enable.idempotence", "false")]),
+        "exactly_once" => Ok([("acks", "all"), ("enable.idempotence", "true")]),
+        _ => Err(format!("Unknown delivery guarantee: {}", delivery)),
+    }
+}
+
+fn properties_of<T: Record>(item: &T) -> Result<serde_json::Map<String, Value>, Box<dyn Error>> {
+    match serde_json::to_value(item)? {
+        Value::Object(map) => Ok(map),
+        _ => Err("the model is not a struct".into()),
+    }
+}
+
+/// The key and the payload of a vertex.
+fn vertex_message<T: Record>(
+    label: &str,
+    primary: &str,
+    item: &T,
+) -> Result<(String, String), Box<dyn Error>> {
+    let properties = properties_of(item)?;
+    let id = match properties.get(primary) {
+        Some(Value::String(id)) => id.clone(),
+        Some(id) => id.to_string(),
+        None => return Err(format!("{} has no {}", label, primary).into()),
+    };
+    let payload = json!({
+        "kind": "vertex",
+        "label": label,
+        "id": id,
+        "properties": properties,
+    });
+    Ok((id, payload.to_string()))
+}
+
+/// The key and the payload of an edge.
+fn edge_message<T: Record>(
+    label: &str,
+    src_label: &str,
+    dst_label: &str,
+    item: &T,
+) -> Result<(String, String), Box<dyn Error>> {
+    let mut properties = properties_of(item)?;
+    let src = properties.remove("SRC_ID").unwrap_or_default();
+    let dst = properties.remove("DST_ID").unwrap_or_default();
+    let (src, dst) = match (src, dst) {
+        (Value::String(src), Value::String(dst)) => (src, dst),
+        _ => return Err(format!("{} has no SRC_ID or DST_ID", label).into()),
+    };
+    let key = format!("{}->{}", src, dst);
+    let payload = json!({
+        "kind": "edge",
+        "label": label,
+        "src_label": src_label,
+        "dst_label": dst_label,
+        "src": src,
+        "dst": dst,
+        "properties": properties,
+    });
+    Ok((key, payload.to_string()))
+}
+
+/// The hash of a message, to skip the ones published before.
+fn message_hash(label: &str, key: &str, payload: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (label, key, payload).hash(&mut hasher);
+    hasher.finish()
+}
+
+pub(crate) struct KafkaSink {
+    producer: BaseProducer<SinkContext>,
+    topic_prefix: String,
+    sent: usize,
+    failed: Arc<AtomicUsize>,
+    /// the hashes of the messages published before, all are sent if none
+    published: Option<HashSet<u64>>,
+}
+
+impl KafkaSink {
+    pub(crate) fn from_env() -> Result<Self, Box<dyn Error>> {
+        let brokers = env::var("KAFKA_SINK_BROKERS").or_else(|_| env::var("KAFKA_BROKER"))?;
+        let delivery =
+            env::var("KAFKA_SINK_DELIVERY").unwrap_or_else(|_| "at_least_once".to_string());
+        let batch_size = env::var("KAFKA_SINK_BATCH_SIZE").unwrap_or_else(|_| "10000".to_string());
+        let linger_ms = env::var("KAFKA_SINK_LINGER_MS").unwrap_or_else(|_| "100".to_string());
+
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", &brokers)
+            .set("batch.num.messages", &batch_size)
+            .set("linger.ms", &linger_ms)
+            .set("compression.type", "lz4");
+        for (key, value) in delivery_config(&delivery)? {
+            config.set(key, value);
+        }
+        let failed = Arc::new(AtomicUsize::new(0));
+        let producer = config.create_with_context(SinkContext {
+            failed: Arc::clone(&failed),
+        })?;
+
+        Ok(KafkaSink {
+            producer,
+            topic_prefix: env::var("KAFKA_SINK_TOPIC_PREFIX")
+                .unwrap_or_else(|_| "crates_pro".to_string()),
+            sent: 0,
+            failed,
+            published: None,
+        })
+    }
+
+    /// Send a message, waiting for the deliveries if the queue of the producer is full.
+    fn send(&mut self, label: &str, key: &str, payload: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(published) = &mut self.published {
+            if !published.insert(message_hash(label, key, payload)) {
+                return Ok(());
+            }
+        }
+        let topic = format!("{}.{}", self.topic_prefix, label);
+        loop {
+            let record = BaseRecord::to(&topic).key(key).payload(payload);
+            match self.producer.send(record) {
+                Ok(()) => break,
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                    self.producer.poll(Duration::from_millis(100));
+                }
+                Err((e, _)) => return Err(e.into()),
+            }
+        }
+        self.sent += 1;
+        self.producer.poll(Duration::ZERO);
+        Ok(())
+    }
+}
+
+impl GraphExporter for KafkaSink {
+    fn vertex<T: Record>(
+        &mut self,
+        label: &str,
+        primary: &str,
+        _file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>> {
+        for item in items {
+            let (key, payload) = vertex_message(label, primary, item)?;
+            self.send(label, &key, &payload)?;
+        }
+        Ok(())
+    }
+
+    fn edge<T: Record>(
+        &mut self,
+        label: &str,
+        src_label: &str,
+        dst_label: &str,
+        _file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>> {
+        for item in items {
+            let (key, payload) = edge_message(label, src_label, dst_label, item)?;
+            self.send(label, &key, &payload)?;
+        }
+        Ok(())
+    }
+
+    /// Wait for all the messages to be delivered.
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.producer.flush(FLUSH_TIMEOUT)?;
+        let failed = self.failed.load(Ordering::Relaxed);
+        tracing::info!("Sent {} messages to kafka, {} failed", self.sent, failed);
+        if failed > 0 {
+            return Err(format!("{} messages failed to deliver", failed).into());
+        }
+        Ok(())
+    }
+}
+
+impl ImportContext {
+    /// Publish the graph to kafka, which should be normalized.
+    pub(crate) fn publish_to_kafka(&self) -> Result<(), ImportError> {
+        self.publish(None).map(|_| ())
+    }
+
+    /// Publish the messages of the graph not in `published`, the hashes of the ones published
+    /// before, and add them to it. It's emptied on failure, so that all are sent next time.
+    pub(crate) fn publish_changes_to_kafka(
+        &self,
+        published: &mut HashSet<u64>,
+    ) -> Result<(), ImportError> {
+        *published = self
+            .publish(Some(mem::take(published)))?
+            .unwrap_or_default();
+        Ok(())
+    }
+
+    fn publish(
+        &self,
+        published: Option<HashSet<u64>>,
+    ) -> Result<Option<HashSet<u64>>, ImportError> {
+        let _span = tracing::info_span!("kafka").entered();
+        let _timer = SINK_DURATION.with_label_values(&["kafka"]).start_timer();
+        KafkaSink::from_env()
+            .and_then(|mut sink| {
+                sink.published = published;
+                self.export_graph(&mut sink)?;
+                let published = sink.published.take();
+                sink.finish()?;
+                Ok(published)
+            })
+            .map_err(|e| ImportError::Sink {
+                sink: "kafka",
+                message: e.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::tugraph_model::{DependsOn, Program};
+
+    #[test]
+    fn test_messages() {
+        let program = Program {
+            id: "p1".to_string(),
+            name: "tokio".to_string(),
+            ..Default::default()
+        };
+        let (key, payload) = vertex_message("program", "id", &program).unwrap();
+        assert_eq!(key, "p1");
+        let payload: Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(payload["properties"]["name"], "tokio");
+
+        let depends_on = DependsOn {
+            SRC_ID: "tokio/1.38.0".to_string(),
+            DST_ID: "bytes/1.6.0".to_string(),
+            target: None,
+            optional: false,
+            ..Default::default()
+        };
+        let (key, payload) = edge_message("depends_on", "version", "version", &depends_on).unwrap();
+        assert_eq!(key, "tokio/1.38.0->bytes/1.6.0");
+        let payload: Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(payload["dst"], "bytes/1.6.0");
+        assert!(payload["properties"].get("SRC_ID").is_none());
+    }
+}
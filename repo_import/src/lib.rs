@@ -1,20 +1,108 @@
+mod api_diff;
+mod api_surface;
+mod authors;
+mod build_bench;
+mod ci_summary;
+mod clone;
 mod crate_info;
+mod cratesio_api;
+mod cratesio_dump;
+mod cratesio_index;
+mod credentials;
+mod cycles;
+mod db;
+mod dedup;
+mod dependency_closure;
+mod dependents;
+mod discovery_filter;
+mod docs_url;
+mod duplicate_versions;
+mod embeddings;
+mod error;
+mod feature_info;
 mod git;
+mod git_history;
+mod github_api;
+mod graph_diff;
+mod graphml_export;
+mod import_cache;
+mod import_jobs;
 mod kafka_handler;
+mod kafka_sink;
+mod license_detect;
+mod license_policy;
+mod loc_metrics;
+mod local;
+mod lockfile;
+mod maintenance;
+mod manifest;
+mod metrics;
+mod msrv;
+mod neo4j_export;
+mod organizations;
+mod output;
+mod package_size;
+mod popularity;
+mod progress;
+mod provenance;
+mod quality;
+mod readme;
+mod registry;
+mod report;
+mod retry;
+mod reviews;
+mod rustsec;
+mod sandbox;
+mod scheduler;
+mod scoring;
+mod search_index;
+mod semver_check;
+mod semver_range;
+mod similar;
+mod snapshot;
+mod sparse_index;
+mod stage_progress;
+mod std_support;
+mod targets;
+mod tugraph_export;
+mod typosquat;
+mod unsafe_metrics;
+mod unsafe_score;
 mod utils;
 mod version_info;
+mod wasm_support;
+mod worker;
 
 extern crate lazy_static;
 extern crate pretty_env_logger;
 
 use crate::crate_info::extract_info_local;
+use crate::db::PgSink;
+use crate::dedup::VersionSource;
+use crate::discovery_filter::discovery_filter;
+use crate::embeddings::EmbeddingClient;
+use crate::graphml_export::GraphMLExporter;
+use crate::import_cache::{head_commit, ImportCache};
 use crate::kafka_handler::KafkaHandler;
+use crate::maintenance::MaintenanceWeights;
+use crate::metrics::{update_queue_depth, SINK_DURATION};
+use crate::msrv::Msrv;
+use crate::neo4j_export::Neo4jExporter;
+use crate::output::{output_formats, write_table, GraphExporter};
+use crate::popularity::PopularityWeights;
+use crate::progress::ImportProgress;
+use crate::quality::QualityWeights;
+use crate::report::{
+    record_repo_filtered, record_repo_imported, record_repo_unchanged, record_stage, reset_report,
+};
+use crate::stage_progress::ProgressExporter;
+use crate::tugraph_export::TuGraphExporter;
 use crate::utils::{
     extract_namespace, get_program_by_name, insert_namespace_by_repo_path, name_join_version,
-    write_into_csv,
 };
 
 //use git::hard_reset_to_head;
+use feature_info::FeatureUpdater;
 use git2::{ObjectType, Oid, Repository};
 use model::{repo_sync_model, tugraph_model::*};
 use rdkafka::error::KafkaError;
@@ -31,13 +119,45 @@ use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
+use tracing::Instrument;
 use url::Url;
 use version_info::VersionUpdater;
 
 const CLONE_CRATES_DIR: &str = "/mnt/crates/local_crates_file/";
 // const TUGRAPH_IMPORT_FILES_PG: &str = "./tugraph_import_files_mq/";
 
+/// The checkpoints start with the magic and the version of their format.
+const CHECKPOINT_MAGIC: &[u8; 8] = b"CPRO-CKP";
+/// The checkpoint is the bincode of [`ImportContext`], which is not self-describing,
+/// so it's bumped whenever a field of the context or of its models is changed,
+/// and a checkpoint of another version is refused rather than misread.
+pub(crate) const CHECKPOINT_VERSION: u32 = 2;
+
+pub use api_diff::{classify, diff_api, ApiChange, ApiDiff, ChangeClass, ChangeKind};
+pub use clone::CloneOptions;
+pub use cratesio_api::CratesIoClient;
+pub use credentials::Credentials;
+pub use dependency_closure::{ClosureCache, ClosureEntry, DependencyClosure};
+pub use dependents::Dependents;
+pub use docs_url::DocsRsClient;
+pub use error::ImportError;
+pub use github_api::GithubClient;
+pub use graph_diff::{
+    diff_graphs, diff_import_files, FieldChange, GraphDiff, LabelDiff, RecordChange,
+};
+pub use import_jobs::{ImportJob, ImportJobStatus, ImportJobs};
 pub use kafka_handler::reset_kafka_offset;
+pub use license_policy::LicensePolicy;
+pub use metrics::spawn_metrics_server;
+pub use progress::{checkpoint_dir, latest_checkpoint_path};
+pub use report::ImportReport;
+pub use retry::RetryPolicy;
+pub use scheduler::run_schedule;
+pub use search_index::{SearchDocument, SearchHit, SearchIndex, SearchResults};
+pub use snapshot::{SnapshotManifest, SnapshotStore};
+pub use sparse_index::SparseIndexClient;
+pub use utils::{register_namespace_rule, NamespaceRule};
+pub use worker::ImportWorker;
 
 pub enum MessageKind {
     Mega,
@@ -64,7 +184,11 @@ pub struct Licenses {
 }
 
 impl ImportDriver {
-    pub async fn new(dont_clone: bool) -> Self {
+    /// Connect to kafka, and restore the context from the latest checkpoint.
+    ///
+    /// A checkpoint which can't be loaded is an error rather than a start from scratch,
+    /// which would overwrite it, remove it to start over.
+    pub async fn new(dont_clone: bool) -> Result<Self, String> {
         tracing::info!("Start to setup Kafka client.");
 
         let should_reset_kafka_offset = env::var("SHOULD_RESET_KAFKA_OFFSET").unwrap().eq("1");
@@ -75,9 +199,7 @@ impl ImportDriver {
 
         let context = if !should_reset_kafka_offset {
             // 如果不需要重置offset，则从checkpoint中恢复context
-            let checkpoint_dir =
-                env::var("CHECKPOINT_DIR").unwrap_or_else(|_| "./checkpoints".to_string());
-            let checkpoint_path = format!("{}/latest.json", checkpoint_dir);
+            let checkpoint_path = latest_checkpoint_path().to_string_lossy().to_string();
 
             match ImportContext::load_from_file(&checkpoint_path).await {
                 Ok(mut ctx) => {
@@ -92,6 +214,13 @@ impl ImportDriver {
                     tracing::info!("Restored context from checkpoint");
                     ctx
                 }
+                // not started over, or the checkpoint would be overwritten
+                Err(e) if Path::new(&checkpoint_path).exists() => {
+                    return Err(format!(
+                        "Failed to load checkpoint {}: {}, remove it to start over",
+                        checkpoint_path, e
+                    ))
+                }
                 Err(e) => {
                     tracing::warn!("Failed to load checkpoint: {}", e);
                     ImportContext {
@@ -111,15 +240,17 @@ impl ImportDriver {
 
         tracing::info!("Finish to setup Kafka client.");
 
-        Self {
+        Ok(Self {
             context,
             import_handler,
             user_import_handler,
             sender_handler,
-        }
+        })
     }
 
     async fn consume_message(&self) -> Result<ImportMessage, KafkaError> {
+        update_queue_depth(&self.user_import_handler).await;
+        update_queue_depth(&self.import_handler).await;
         // try to get data from user_import_handler
         if let Ok(message) = self.user_import_handler.consume_once().await {
             tracing::info!("Receive a user upload message!");
@@ -227,10 +358,10 @@ impl ImportDriver {
         if !path.is_dir() {
             //if user_upload, no clone
             tracing::info!("dir {} not exist", path.to_str().unwrap().to_string());
-            let clone_start_time = Instant::now();
             let local_repo_path = match self
                 .context
                 .clone_a_repo_by_url(&clone_crates_dir, &git_url_base, &mega_url_suffix)
+                .instrument(tracing::info_span!("clone", repo = %mega_url_suffix))
                 .await
             {
                 Ok(x) => x,
@@ -239,8 +370,6 @@ impl ImportDriver {
                     return Err(());
                 }
             };
-            let clone_need_time = clone_start_time.elapsed();
-            tracing::trace!("clone need time: {:?}", clone_need_time);
             let parts: Vec<&str> = namespace.split("/").collect();
             let mut crate_name = "".to_string();
             if parts.len() == 2 {
@@ -255,32 +384,28 @@ impl ImportDriver {
                 )
                 .await
                 .unwrap();
-            let new_versions = self
+            let new_versions = match self
                 .context
                 .parse_a_local_repo_and_return_new_versions(local_repo_path, mega_url_suffix)
                 .await
-                .unwrap();
+            {
+                Ok(new_versions) => new_versions,
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    return Err(());
+                }
+            };
 
             if matches!(kind, MessageKind::UserUpload) {
                 for ver in new_versions {
                     self.sender_handler
-                        .send_message(
-                            &kafka_analysis_topic,
-                            "",
-                            &serde_json::to_string(&ver).unwrap(),
-                        )
+                        .send_encoded(&kafka_analysis_topic, "", &ver)
                         .await;
                 }
             }
         } else {
             tracing::info!("dir {} already exist", path.to_str().unwrap().to_string());
-            let insert_time = Instant::now();
             insert_namespace_by_repo_path(path.to_str().unwrap().to_string(), namespace.clone());
-            let insert_need_time = insert_time.elapsed();
-            tracing::trace!(
-                "insert_namespace_by_repo_path need time: {:?}",
-                insert_need_time
-            );
             let parts: Vec<&str> = namespace.split("/").collect();
             let mut crate_name = "".to_string();
             if parts.len() == 2 {
@@ -295,19 +420,21 @@ impl ImportDriver {
                 )
                 .await
                 .unwrap();
-            let new_versions = self
+            let new_versions = match self
                 .context
                 .parse_a_local_repo_and_return_new_versions(path, mega_url_suffix)
                 .await
-                .unwrap();
+            {
+                Ok(new_versions) => new_versions,
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    return Err(());
+                }
+            };
             if matches!(kind, MessageKind::UserUpload) {
                 for ver in new_versions {
                     self.sender_handler
-                        .send_message(
-                            &kafka_analysis_topic,
-                            "",
-                            &serde_json::to_string(&ver).unwrap(),
-                        )
+                        .send_encoded(&kafka_analysis_topic, "", &ver)
                         .await;
                 }
             }
@@ -319,12 +446,11 @@ impl ImportDriver {
 
     pub async fn save_checkpoint(&mut self) -> Result<(), Box<dyn Error>> {
         tracing::info!("Saving checkpoint...");
-        let checkpoint_dir =
-            env::var("CHECKPOINT_DIR").unwrap_or_else(|_| "./checkpoints".to_string());
+        let checkpoint_dir = checkpoint_dir();
         tokio::fs::create_dir_all(&checkpoint_dir).await?;
 
         // 保存二进制checkpoint (如果文件存在会覆盖)
-        let checkpoint_path = format!("{}/latest.json", checkpoint_dir);
+        let checkpoint_path = latest_checkpoint_path().to_string_lossy().to_string();
         if tokio::fs::try_exists(&checkpoint_path).await? {
             tokio::fs::remove_file(&checkpoint_path).await?;
         }
@@ -332,11 +458,11 @@ impl ImportDriver {
         self.context.save_to_file(&checkpoint_path).await?;
 
         // 保存人类可读的摘要 (如果文件存在会覆盖)
-        let summary_path = format!("{}/summary.txt", checkpoint_dir);
+        let summary_path = checkpoint_dir.join("summary.txt");
         if tokio::fs::try_exists(&summary_path).await? {
             tokio::fs::remove_file(&summary_path).await?;
         }
-        tracing::info!("Saving summary to {}", summary_path);
+        tracing::info!("Saving summary to {:?}", summary_path);
         tokio::fs::write(summary_path, self.context.format_status()).await?;
 
         tracing::info!("Checkpoint saved to {}", checkpoint_path);
@@ -501,6 +627,15 @@ pub struct ImportContext {
     app_has_dep_version: Vec<HasDepVersion>,
 
     pub depends_on: Vec<DependsOn>,
+    pub dev_depends_on: Vec<DevDependsOn>,
+    pub build_depends_on: Vec<BuildDependsOn>,
+
+    /// feature graph
+    pub features: Vec<Feature>,
+    has_feature: Vec<HasFeature>,
+    enables_feature: Vec<EnablesFeature>,
+    enables_dependency: Vec<EnablesDependency>,
+    depends_on_feature: Vec<DependsOnFeature>,
 
     /// help is judge whether it is a new program
     program_memory: HashSet<model::general_model::Program>,
@@ -508,10 +643,82 @@ pub struct ImportContext {
     version_memory: HashSet<model::general_model::Version>,
 
     pub version_updater: VersionUpdater,
+    pub feature_updater: FeatureUpdater,
 
     // 新增字段保存 Kafka offset
     #[serde(default)]
     pub kafka_offset: Option<i64>,
+
+    /// the last synced commit of the crates.io index
+    pub cratesio_index_commit: Option<String>,
+
+    /// owners on crates.io
+    pub persons: Vec<Person>,
+    pub teams: Vec<Team>,
+    person_owns: Vec<Owns>,
+    team_owns: Vec<Owns>,
+
+    /// the authors of packages, as persons
+    pub authors: Vec<Person>,
+    author_of: Vec<AuthorOf>,
+    is_contributor: Vec<IsContributor>,
+
+    /// name_and_version -> whether it is yanked in the registry
+    yanked: HashMap<String, bool>,
+    /// yank/unyank events not sent yet
+    #[serde(skip)]
+    pub yank_events: Vec<model::general_model::YankEvent>,
+
+    /// RustSec advisories
+    pub advisories: Vec<Advisory>,
+    affected_by: Vec<AffectedBy>,
+
+    /// public API of library versions
+    pub api_items: Vec<ApiItem>,
+    has_api_item: Vec<HasApiItem>,
+
+    /// git authors
+    pub contributors: Vec<Contributor>,
+    contributed_to: Vec<ContributedTo>,
+
+    /// activity metrics of git repos
+    pub repo_metrics: Vec<RepoMetrics>,
+    has_repo_metrics: Vec<HasRepoMetrics>,
+
+    /// the CI of git repos
+    pub ci_summaries: Vec<CiSummary>,
+    has_ci: Vec<HasCi>,
+
+    /// the owners of git repos
+    pub organizations: Vec<Organization>,
+    pub repositories: Vec<Repo>,
+    has_repository: Vec<HasRepository>,
+    has_program: Vec<HasProgram>,
+
+    /// the binary, example, test and bench targets of packages
+    pub targets: Vec<Target>,
+    has_target: Vec<HasTarget>,
+
+    /// the last imported commit of each repo
+    pub import_cache: ImportCache,
+
+    /// the progress of a long import run
+    pub progress: ImportProgress,
+
+    /// name_and_version -> the repos it is found in
+    version_sources: HashMap<String, Vec<VersionSource>>,
+
+    /// the `[patch]` and `[replace]` of the workspaces
+    overrides: Vec<Overrides>,
+
+    /// the dependencies resolved by the lockfiles
+    resolves_to: Vec<ResolvesTo>,
+
+    /// the libraries named like a typo of a popular one
+    typosquats: Vec<Typosquats>,
+
+    /// the alternatives of the libraries
+    similar_to: Vec<SimilarTo>,
 }
 
 impl ImportContext {
@@ -623,19 +830,44 @@ impl ImportContext {
         }
         v1.to_string()
     }
+    /// The repos are traced in a `repo` span, with a span for each stage of them.
+    #[tracing::instrument(name = "repo", skip_all, fields(repo = %git_url))]
     async fn parse_a_local_repo_and_return_new_versions(
         &mut self,
         repo_path: PathBuf,
         git_url: String,
-    ) -> Result<Vec<model::general_model::VersionWithTag>, String> {
+    ) -> Result<Vec<model::general_model::VersionWithTag>, ImportError> {
         let mut new_versions = vec![];
 
+        if !discovery_filter().is_repo_included(&git_url) {
+            tracing::info!("Skip repo {}, it's filtered out", git_url);
+            record_repo_filtered();
+            return Ok(new_versions);
+        }
+
         if repo_path.is_dir() && Path::new(&repo_path).join(".git").is_dir() {
             match Repository::open(&repo_path) {
                 Err(e) => {
-                    tracing::error!("Not a git repo: {:?}, Err: {}", repo_path, e);
+                    return Err(ImportError::Repo {
+                        url: git_url,
+                        message: format!("failed to open {}: {}", repo_path.display(), e),
+                    })
                 }
-                Ok(_) => {
+                Ok(repo) => {
+                    let head = head_commit(&repo);
+                    if let Some(head) = &head {
+                        if self.import_cache.is_unchanged(&git_url, head)
+                            && self.has_programs_of(&git_url)
+                        {
+                            tracing::info!(
+                                "Skip repo {}, HEAD is unchanged: {}",
+                                repo_path.display(),
+                                head
+                            );
+                            record_repo_unchanged();
+                            return Ok(new_versions);
+                        }
+                    }
                     // It'a a valid git repository. Start to parse it.
                     tracing::info!("Processing repo: {}", repo_path.display());
 
@@ -646,12 +878,13 @@ impl ImportContext {
                         .map_err(|x| format!("{:?}", x))?;
                     let hard_reset_need_time = hard_reset_time.elapsed();
                     tracing::info!("hard_reset_to_head need time: {:?}", hard_reset_need_time);*/
-                    let mut all_programs =
-                        self.collect_and_filter_programs(&repo_path, &git_url).await;
+                    let mut head_dependencies = vec![];
+                    let mut all_programs = self
+                        .collect_and_filter_programs(&repo_path, &git_url, &mut head_dependencies)
+                        .await;
 
                     let all_dependencies =
                         self.collect_and_filter_versions(&repo_path, &git_url).await;
-                    let proccess_time = Instant::now();
                     //find max_version
                     let tmp_max_versions: Arc<Mutex<HashMap<String, String>>> =
                         Arc::new(Mutex::new(HashMap::new()));
@@ -690,6 +923,14 @@ impl ImportContext {
                             }
                         }
                     }
+                    if let Some((program, _, _)) = all_programs.first() {
+                        let namespace = program.namespace.clone();
+                        let program_ids: Vec<String> =
+                            all_programs.iter().map(|(p, _, _)| p.id.clone()).collect();
+                        self.import_git_history(&repo_path, namespace.as_deref(), &program_ids)
+                            .await;
+                        self.import_ci_summary(&repo_path, namespace.as_deref(), &program_ids);
+                    }
                     //
                     for (program, has_type, uprogram) in all_programs {
                         self.programs.push(program.clone());
@@ -742,6 +983,8 @@ impl ImportContext {
                         };
 
                         self.version_updater.update_depends_on(&dependencies).await;
+                        self.feature_updater.update_features(&dependencies).await;
+                        self.record_version_source(&dependencies);
 
                         let has_version = HasVersion {
                             SRC_ID: program.id.clone(),
@@ -760,22 +1003,47 @@ impl ImportContext {
 
                         let islib = uprogram.index() == 0;
                         if islib {
-                            let version = LibraryVersion::new(
+                            let mut version = LibraryVersion::new(
                                 program.id.clone(),
                                 &name.clone(),
                                 &version.clone(),
                                 "???",
                             );
+                            (version.msrv, version.msrv_source) =
+                                Msrv::fields(dependencies.msrv.as_ref());
+                            // the versions at HEAD are not tagged
+                            if dependencies.commit.is_some() {
+                                version.tag_name = Some(tag_name.clone());
+                            }
+                            version.commit = dependencies.commit.clone();
+                            version.commit_time = dependencies.commit_time;
+                            version.unpacked_size = dependencies.unpacked_size;
 
                             self.library_versions.push(version);
                             self.lib_has_version.push(has_version);
                             self.lib_has_dep_version.push(has_dep_version);
+                            for item in dependencies.api {
+                                self.has_api_item.push(HasApiItem {
+                                    SRC_ID: item.name_and_version.clone(),
+                                    DST_ID: item.id.clone(),
+                                });
+                                self.api_items.push(item);
+                            }
                         } else {
-                            let version = ApplicationVersion::new(
+                            let mut version = ApplicationVersion::new(
                                 program.id.clone(),
                                 name.clone(),
                                 version.clone(),
                             );
+                            (version.msrv, version.msrv_source) =
+                                Msrv::fields(dependencies.msrv.as_ref());
+                            // the versions at HEAD are not tagged
+                            if dependencies.commit.is_some() {
+                                version.tag_name = Some(tag_name.clone());
+                            }
+                            version.commit = dependencies.commit.clone();
+                            version.commit_time = dependencies.commit_time;
+                            version.unpacked_size = dependencies.unpacked_size;
 
                             self.application_versions.push(version.clone());
                             self.app_has_version.push(has_version);
@@ -794,6 +1062,33 @@ impl ImportContext {
                             ));
                     }
 
+                    // the crates at HEAD whose version has not been tagged yet,
+                    // build `depends_on` edges for them as well.
+                    for dependencies in head_dependencies {
+                        let version = model::general_model::Version::new(
+                            &dependencies.crate_name,
+                            &dependencies.version,
+                        );
+                        if self.version_memory.contains(&version)
+                            || self
+                                .version_updater
+                                .version_parser
+                                .exists(&dependencies.crate_name, &dependencies.version)
+                        {
+                            continue;
+                        }
+
+                        self.version_updater.update_depends_on(&dependencies).await;
+                        self.feature_updater.update_features(&dependencies).await;
+                        self.versions.push(Version {
+                            name_and_version: name_join_version(
+                                &dependencies.crate_name,
+                                &dependencies.version,
+                            ),
+                        });
+                        self.version_memory.insert(version);
+                    }
+
                     /*let mut crates: HashMap<String, String> = HashMap::new();
                     for ff in find_max_version {
                         let name = ff.cname;
@@ -826,28 +1121,37 @@ impl ImportContext {
                             }
                         }
                     }*/
-                    let proccess_need_time = proccess_time.elapsed();
+                    if let Some(head) = head {
+                        self.import_cache.record(git_url.clone(), head);
+                    }
+                    record_repo_imported();
                     tracing::info!("Finish processing repo: {}", repo_path.display());
-                    tracing::trace!("processing repo need time: {:?}", proccess_need_time);
                 }
             }
         } else {
-            tracing::error!("{} is not a directory", repo_path.display());
+            return Err(ImportError::Repo {
+                url: git_url,
+                message: format!("{} is not a git repo", repo_path.display()),
+            });
         }
         Ok(new_versions)
     }
 
+    #[tracing::instrument(name = "programs", skip_all)]
     async fn collect_and_filter_programs(
         &mut self,
         repo_path: &Path,
         git_url: &str,
+        head_dependencies: &mut Vec<version_info::Dependencies>,
     ) -> Vec<(Program, HasType, UProgram)> {
         tracing::info!("Start to collect_and_filter_programs {:?}", repo_path);
-        let collect_time = Instant::now();
+        let mut targets = vec![];
         let all_programs: Vec<(Program, HasType, UProgram)> = extract_info_local(
             repo_path.to_path_buf(),
             git_url.to_owned(),
             &mut self.licenses,
+            head_dependencies,
+            &mut targets,
         )
         .await
         .into_iter()
@@ -860,137 +1164,553 @@ impl ImportContext {
                 ))
         })
         .collect();
-        let collect_need_time = collect_time.elapsed();
+        // only the targets of the new programs
+        let program_ids: HashSet<&str> =
+            all_programs.iter().map(|(p, _, _)| p.id.as_str()).collect();
+        for target in targets {
+            if program_ids.contains(target.program_id.as_str()) {
+                self.has_target.push(HasTarget {
+                    SRC_ID: target.program_id.clone(),
+                    DST_ID: target.id.clone(),
+                });
+                self.targets.push(target);
+            }
+        }
         tracing::info!("Finish to collect_and_filter_programs {:?}", repo_path);
-        tracing::trace!(
-            "collect_and_filter_programs need time: {:?}",
-            collect_need_time
-        );
         all_programs
     }
+    #[tracing::instrument(name = "versions", skip_all)]
     async fn collect_and_filter_versions(
-        &self,
+        &mut self,
         repo_path: &PathBuf,
         git_url: &str,
     ) -> Vec<version_info::Dependencies> {
         tracing::info!("Start to collect_and_filter_versions {:?}", repo_path);
-        let collect_time = Instant::now();
         // get all versions and dependencies
         // filter out new versions!!!
-        let all_dependencies: Vec<version_info::Dependencies> = self
+        let (known, all_dependencies): (Vec<_>, Vec<_>) = self
             .parse_all_versions_of_a_repo(repo_path, git_url)
             .await
             .into_iter()
-            .filter(|x| {
-                !self
-                    .version_memory
+            //.filter(|x| semver::Version::parse(&x.version).is_ok())
+            .partition(|x| {
+                self.version_memory
                     .contains(&model::general_model::Version::new(
                         &x.crate_name,
                         &x.version,
                     ))
-            })
-            //.filter(|x| semver::Version::parse(&x.version).is_ok())
-            .collect();
-        let collect_need_time = collect_time.elapsed();
+                    || self
+                        .version_updater
+                        .version_parser
+                        .exists(&x.crate_name, &x.version)
+            });
+        // the versions found in other repos are merged into the same nodes,
+        // with the dependencies and features of the copy kept by the policy
+        for dependencies in &known {
+            if self.record_version_source(dependencies) {
+                self.version_updater.replace_depends_on(dependencies).await;
+                self.feature_updater.update_features(dependencies).await;
+            }
+        }
         tracing::info!("Finish to collect_and_filter_versions {:?}", repo_path);
-        tracing::trace!(
-            "collect_and_filter_versions need time: {:?}",
-            collect_need_time
-        );
         all_dependencies
     }
 
     async fn normalize(&mut self) {
         self.depends_on
             .clone_from(&(self.version_updater.to_depends_on_edges().await));
+        self.dev_depends_on
+            .clone_from(&(self.version_updater.to_dev_depends_on_edges().await));
+        self.build_depends_on
+            .clone_from(&(self.version_updater.to_build_depends_on_edges().await));
+        self.overrides = self.version_updater.to_overrides_edges().await;
+        self.resolves_to = self.version_updater.to_resolves_to_edges().await;
+        self.detect_cycles();
+
+        let feature_graph = self
+            .feature_updater
+            .to_feature_graph(&self.version_updater)
+            .await;
+        self.features = feature_graph.features;
+        self.has_feature = feature_graph.has_feature;
+        self.enables_feature = feature_graph.enables_feature;
+        self.enables_dependency = feature_graph.enables_dependency;
+        self.depends_on_feature = feature_graph.depends_on_feature;
+
+        self.apply_yanked();
+        self.apply_max_versions();
+        self.apply_version_sources();
+        self.dedup_git_history();
+        self.import_authors();
+        self.import_organizations();
+        self.resolve_documentation();
+        self.score_transitive_unsafety();
+    }
+
+    /// export all the vertices and edges by `exporter`
+    fn export_graph<E: GraphExporter>(&self, exporter: &mut E) -> Result<(), Box<dyn Error>> {
+        let _span = tracing::info_span!("export").entered();
+        let mut exporter = ProgressExporter::new(exporter);
+
+        // vertex
+        exporter.vertex("program", "id", "program", &self.programs)?;
+        exporter.vertex("library", "id", "library", &self.libraries)?;
+        exporter.vertex("application", "id", "application", &self.applications)?;
+        exporter.vertex(
+            "library_version",
+            "name_and_version",
+            "library_version",
+            &self.library_versions,
+        )?;
+        exporter.vertex(
+            "application_version",
+            "name_and_version",
+            "application_version",
+            &self.application_versions,
+        )?;
+        exporter.vertex("version", "name_and_version", "version", &self.versions)?;
+        exporter.vertex("feature", "id", "feature", &self.features)?;
+        exporter.vertex("person", "id", "person", &self.persons)?;
+        exporter.vertex("person", "id", "author", &self.authors)?;
+        exporter.vertex("team", "id", "team", &self.teams)?;
+        exporter.vertex("advisory", "id", "advisory", &self.advisories)?;
+        exporter.vertex("api_item", "id", "api_item", &self.api_items)?;
+        exporter.vertex("contributor", "id", "contributor", &self.contributors)?;
+        exporter.vertex("repo_metrics", "id", "repo_metrics", &self.repo_metrics)?;
+        exporter.vertex("ci_summary", "id", "ci_summary", &self.ci_summaries)?;
+        exporter.vertex("organization", "id", "organization", &self.organizations)?;
+        exporter.vertex("repository", "id", "repository", &self.repositories)?;
+        exporter.vertex("target", "id", "target", &self.targets)?;
+
+        // edge
+        exporter.edge(
+            "has_type",
+            "program",
+            "library",
+            "has_lib_type",
+            &self.has_lib_type,
+        )?;
+        exporter.edge(
+            "has_type",
+            "program",
+            "application",
+            "has_app_type",
+            &self.has_app_type,
+        )?;
+        exporter.edge(
+            "has_version",
+            "library",
+            "library_version",
+            "lib_has_version",
+            &self.lib_has_version,
+        )?;
+        exporter.edge(
+            "has_version",
+            "application",
+            "application_version",
+            "app_has_version",
+            &self.app_has_version,
+        )?;
+        exporter.edge(
+            "has_dep_version",
+            "library_version",
+            "version",
+            "lib_has_dep_version",
+            &self.lib_has_dep_version,
+        )?;
+        exporter.edge(
+            "has_dep_version",
+            "application_version",
+            "version",
+            "app_has_dep_version",
+            &self.app_has_dep_version,
+        )?;
+        exporter.edge(
+            "depends_on",
+            "version",
+            "version",
+            "depends_on",
+            &self.depends_on,
+        )?;
+        exporter.edge(
+            "dev_depends_on",
+            "version",
+            "version",
+            "dev_depends_on",
+            &self.dev_depends_on,
+        )?;
+        exporter.edge(
+            "build_depends_on",
+            "version",
+            "version",
+            "build_depends_on",
+            &self.build_depends_on,
+        )?;
+        exporter.edge(
+            "overrides",
+            "version",
+            "version",
+            "overrides",
+            &self.overrides,
+        )?;
+        exporter.edge(
+            "resolves_to",
+            "version",
+            "version",
+            "resolves_to",
+            &self.resolves_to,
+        )?;
+        exporter.edge(
+            "typosquats",
+            "program",
+            "program",
+            "typosquats",
+            &self.typosquats,
+        )?;
+        exporter.edge(
+            "similar_to",
+            "program",
+            "program",
+            "similar_to",
+            &self.similar_to,
+        )?;
+        exporter.edge(
+            "has_feature",
+            "version",
+            "feature",
+            "has_feature",
+            &self.has_feature,
+        )?;
+        exporter.edge(
+            "enables_feature",
+            "feature",
+            "feature",
+            "enables_feature",
+            &self.enables_feature,
+        )?;
+        exporter.edge(
+            "enables_dependency",
+            "feature",
+            "version",
+            "enables_dependency",
+            &self.enables_dependency,
+        )?;
+        exporter.edge(
+            "depends_on_feature",
+            "version",
+            "feature",
+            "depends_on_feature",
+            &self.depends_on_feature,
+        )?;
+        exporter.edge(
+            "owns",
+            "person",
+            "program",
+            "person_owns",
+            &self.person_owns,
+        )?;
+        exporter.edge("owns", "team", "program", "team_owns", &self.team_owns)?;
+        exporter.edge(
+            "author_of",
+            "person",
+            "program",
+            "author_of",
+            &self.author_of,
+        )?;
+        exporter.edge(
+            "is_contributor",
+            "person",
+            "contributor",
+            "is_contributor",
+            &self.is_contributor,
+        )?;
+        exporter.edge(
+            "affected_by",
+            "version",
+            "advisory",
+            "affected_by",
+            &self.affected_by,
+        )?;
+        exporter.edge(
+            "has_api_item",
+            "library_version",
+            "api_item",
+            "has_api_item",
+            &self.has_api_item,
+        )?;
+        exporter.edge(
+            "contributed_to",
+            "contributor",
+            "program",
+            "contributed_to",
+            &self.contributed_to,
+        )?;
+        exporter.edge(
+            "has_repo_metrics",
+            "program",
+            "repo_metrics",
+            "has_repo_metrics",
+            &self.has_repo_metrics,
+        )?;
+        exporter.edge("has_ci", "program", "ci_summary", "has_ci", &self.has_ci)?;
+        exporter.edge(
+            "has_repository",
+            "organization",
+            "repository",
+            "has_repository",
+            &self.has_repository,
+        )?;
+        exporter.edge(
+            "has_program",
+            "organization",
+            "program",
+            "has_program",
+            &self.has_program,
+        )?;
+        exporter.edge(
+            "has_target",
+            "program",
+            "target",
+            "has_target",
+            &self.has_target,
+        )?;
+
+        exporter.finish()
     }
 
     /// write data base into tugraph import files
+    #[tracing::instrument(name = "write", skip_all)]
     pub async fn write_tugraph_import_files(&mut self) {
         tracing::info!("Start to write");
         self.normalize().await;
 
+        if env::var("IMPORT_ENRICH_DOWNLOADS").is_ok_and(|v| v == "1") {
+            match CratesIoClient::new() {
+                Ok(mut client) => self.enrich_downloads(&mut client).await,
+                Err(e) => tracing::error!("Failed to create crates.io client: {}", e),
+            }
+        }
+        if env::var("IMPORT_ENRICH_OWNERS").is_ok_and(|v| v == "1") {
+            match CratesIoClient::new() {
+                Ok(mut client) => self.enrich_owners(&mut client).await,
+                Err(e) => tracing::error!("Failed to create crates.io client: {}", e),
+            }
+        }
+        if env::var("IMPORT_ENRICH_STARS").is_ok_and(|v| v == "1") {
+            match GithubClient::new() {
+                Ok(mut client) => self.enrich_stars(&mut client).await,
+                Err(e) => tracing::error!("Failed to create GitHub client: {}", e),
+            }
+        }
+        if env::var("IMPORT_ENRICH_RESPONSIVENESS").is_ok_and(|v| v == "1") {
+            match GithubClient::new() {
+                Ok(mut client) => self.enrich_responsiveness(&mut client).await,
+                Err(e) => tracing::error!("Failed to create GitHub client: {}", e),
+            }
+        }
+        if env::var("IMPORT_ENRICH_CRATE_SIZES").is_ok_and(|v| v == "1") {
+            match CratesIoClient::new() {
+                Ok(mut client) => self.enrich_crate_sizes(&mut client).await,
+                Err(e) => tracing::error!("Failed to create crates.io client: {}", e),
+            }
+        }
+        self.measure_builds().await;
+        if let Ok(db_path) = env::var("RUSTSEC_ADVISORY_DB") {
+            self.import_rustsec(Path::new(&db_path)).await;
+        }
+        let vet_audits: Vec<PathBuf> = env::var("CARGO_VET_AUDITS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        let crev_dir = env::var("CREV_PROOFS_DIR").ok().map(PathBuf::from);
+        if !vet_audits.is_empty() || crev_dir.is_some() {
+            self.import_reviews(&vet_audits, crev_dir.as_deref());
+        }
+        if !self.api_items.is_empty() {
+            self.check_semver_compliance();
+        }
+        if env::var("IMPORT_TYPOSQUAT_CHECK").is_ok_and(|v| v == "1") {
+            self.detect_typosquats();
+        }
+        if env::var("IMPORT_SIMILAR_CRATES").is_ok_and(|v| v == "1") {
+            self.recommend_similar_crates();
+        }
+        self.score_popularity(&PopularityWeights::from_env());
+        self.score_maintenance(&MaintenanceWeights::from_env());
+        self.score_quality(&QualityWeights::from_env());
+
         let write_time = Instant::now();
         let tugraph_import_files = PathBuf::from(env::var("TUGRAPH_IMPORT_FILES_PG").unwrap());
         fs::create_dir_all(tugraph_import_files.clone())
             .unwrap_or_else(|e| tracing::error!("Error: {}", e));
 
-        // write into csv
-        write_into_csv(
-            tugraph_import_files.join("program.csv"),
-            self.programs.clone(),
-        )
-        .unwrap();
-        write_into_csv(
-            tugraph_import_files.join("library.csv"),
-            self.libraries.clone(),
-        )
-        .unwrap();
-        write_into_csv(
-            tugraph_import_files.join("application.csv"),
-            self.applications.clone(),
-        )
-        .unwrap();
-        write_into_csv(
-            tugraph_import_files.join("library_version.csv"),
-            self.library_versions.clone(),
-        )
-        .unwrap();
-        write_into_csv(
-            tugraph_import_files.join("application_version.csv"),
-            self.application_versions.clone(),
-        )
-        .unwrap();
-        write_into_csv(
-            tugraph_import_files.join("version.csv"),
-            self.versions.clone(),
-        )
-        .unwrap();
-        write_into_csv(
-            tugraph_import_files.join("licenses.csv"),
-            self.licenses.clone(),
-        )
-        .unwrap();
-
-        // edge
-        let _ = write_into_csv(
-            tugraph_import_files.join("has_lib_type.csv"),
-            self.has_lib_type.clone(),
-        );
-        let _ = write_into_csv(
-            tugraph_import_files.join("has_app_type.csv"),
-            self.has_app_type.clone(),
-        );
-        let _ = write_into_csv(
-            tugraph_import_files.join("lib_has_version.csv"),
-            self.lib_has_version.clone(),
-        );
-        let _ = write_into_csv(
-            tugraph_import_files.join("app_has_version.csv"),
-            self.app_has_version.clone(),
-        );
+        // the outputs written before a run is interrupted are skipped
+        if !self.progress.is_flushed("tugraph") {
+            let _timer = SINK_DURATION.with_label_values(&["tugraph"]).start_timer();
+            match self
+                .write_tugraph_outputs(&tugraph_import_files)
+                .map_err(|e| e.to_string())
+            {
+                Ok(()) => self.mark_flushed("tugraph").await,
+                Err(e) => tracing::error!("Failed to write the tugraph import files: {}", e),
+            }
+        }
 
-        let _ = write_into_csv(
-            tugraph_import_files.join("lib_has_dep_version.csv"),
-            self.lib_has_dep_version.clone(),
-        );
-        let _ = write_into_csv(
-            tugraph_import_files.join("app_has_dep_version.csv"),
-            self.app_has_dep_version.clone(),
-        );
-        let _ = write_into_csv(
-            tugraph_import_files.join("depends_on.csv"),
-            self.depends_on.clone(),
-        );
+        if env::var("IMPORT_NEO4J_EXPORT").is_ok_and(|v| v == "1")
+            && !self.progress.is_flushed("neo4j")
+        {
+            let _timer = SINK_DURATION.with_label_values(&["neo4j"]).start_timer();
+            let res =
+                Neo4jExporter::new(&tugraph_import_files.join("neo4j")).and_then(|mut exporter| {
+                    self.export_graph(&mut exporter)?;
+                    exporter.finish()
+                });
+            // the error is not `Send`, so it can't be held over the await
+            match res.map_err(|e| e.to_string()) {
+                Ok(()) => self.mark_flushed("neo4j").await,
+                Err(e) => tracing::error!("Failed to export for neo4j: {}", e),
+            }
+        }
+        if env::var("IMPORT_GRAPHML_EXPORT").is_ok_and(|v| v == "1")
+            && !self.progress.is_flushed("graphml")
+        {
+            let _timer = SINK_DURATION.with_label_values(&["graphml"]).start_timer();
+            let mut exporter = GraphMLExporter::new(&tugraph_import_files);
+            let res = self
+                .export_graph(&mut exporter)
+                .and_then(|_| exporter.finish());
+            match res.map_err(|e| e.to_string()) {
+                Ok(()) => self.mark_flushed("graphml").await,
+                Err(e) => tracing::error!("Failed to export graphml: {}", e),
+            }
+        }
+        if env::var("IMPORT_POSTGRES_SINK").is_ok_and(|v| v == "1")
+            && !self.progress.is_flushed("postgres")
+        {
+            let _timer = SINK_DURATION.with_label_values(&["postgres"]).start_timer();
+            match self.write_into_postgres().await {
+                Ok(()) => self.mark_flushed("postgres").await,
+                Err(e) => tracing::error!("Failed to write into postgres: {}", e),
+            }
+        }
+        if env::var("IMPORT_SEARCH_INDEX").is_ok_and(|v| v == "1")
+            && !self.progress.is_flushed("search_index")
+        {
+            let _timer = SINK_DURATION
+                .with_label_values(&["search_index"])
+                .start_timer();
+            let dir = env::var("SEARCH_INDEX_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| tugraph_import_files.join("search_index"));
+            match self.write_search_index(&dir) {
+                Ok(()) => self.mark_flushed("search_index").await,
+                Err(e) => tracing::error!("Failed to write the search index: {}", e),
+            }
+        }
+        if env::var("IMPORT_EMBEDDINGS").is_ok_and(|v| v == "1")
+            && !self.progress.is_flushed("embeddings")
+        {
+            let _timer = SINK_DURATION
+                .with_label_values(&["embeddings"])
+                .start_timer();
+            match EmbeddingClient::from_env() {
+                Some(Ok(client)) => match self.write_embeddings(&client).await {
+                    Ok(()) => self.mark_flushed("embeddings").await,
+                    Err(e) => tracing::error!("Failed to write the embeddings: {}", e),
+                },
+                Some(Err(e)) => tracing::error!("Failed to create the embedding client: {}", e),
+                None => tracing::error!("IMPORT_EMBEDDING_URL is not set"),
+            }
+        }
+        if env::var("IMPORT_KAFKA_SINK").is_ok_and(|v| v == "1")
+            && !self.progress.is_flushed("kafka")
+        {
+            match self.publish_to_kafka() {
+                Ok(()) => self.mark_flushed("kafka").await,
+                Err(e) => tracing::error!("Failed to publish to kafka: {}", e),
+            }
+        }
         tracing::info!("Finish to write");
-        let write_need_time = write_time.elapsed();
-        tracing::trace!("write need time: {:?}", write_need_time);
+        record_stage("write", write_time.elapsed(), 0, 0, 0);
+        if let Err(e) = self.write_import_report() {
+            tracing::error!("Failed to write the import report: {}", e);
+        }
+        reset_report();
     }
 
-    pub async fn save_to_file(&mut self, path: &str) -> Result<(), String> {
+    /// Write the tugraph import files with the reports along with them into `dir`.
+    fn write_tugraph_outputs(&self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        let formats = output_formats();
+        let mut exporter = TuGraphExporter::new(dir, &formats);
+        self.export_graph(&mut exporter)?;
+        exporter.finish()?;
+        // licenses are not imported into tugraph
+        write_table(dir, "licenses", &self.licenses, &formats)?;
+        if let Ok(policy_path) = env::var("LICENSE_POLICY") {
+            let output = dir.join("license_report.json");
+            if let Err(e) = self.write_license_report(Path::new(&policy_path), &output) {
+                tracing::error!("Failed to check the license policy: {}", e);
+            }
+        }
+        if env::var("IMPORT_DUPLICATE_REPORT").is_ok_and(|v| v == "1") {
+            let output = dir.join("duplicate_versions.json");
+            if let Err(e) = self.write_duplicate_report(&output) {
+                tracing::error!("Failed to report the duplicate versions: {}", e);
+            }
+        }
+        if env::var("IMPORT_PROVENANCE").is_ok_and(|v| v == "1") {
+            let output = dir.join("provenance.jsonl");
+            if let Err(e) = self.write_provenance(&output) {
+                tracing::error!("Failed to write the provenance: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// upsert programs, libraries and applications into postgres
+    #[tracing::instrument(name = "postgres", skip_all)]
+    pub async fn write_into_postgres(&self) -> Result<(), sqlx::Error> {
+        let sink = PgSink::connect().await?;
+        sink.upsert_programs(&self.programs).await?;
+        sink.upsert_libraries(&self.libraries).await?;
+        sink.upsert_applications(&self.applications).await?;
+        Ok(())
+    }
+
+    /// Normalize the context and encode its checkpoint, see [`CHECKPOINT_VERSION`].
+    pub(crate) async fn encode_checkpoint(&mut self) -> Result<Vec<u8>, String> {
         self.normalize().await;
-        let serialized =
-            bincode::serialize(self).map_err(|e| format!("Serialization error: {}", e))?;
+        let mut checkpoint = CHECKPOINT_MAGIC.to_vec();
+        checkpoint.extend(CHECKPOINT_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut checkpoint, self)
+            .map_err(|e| format!("Serialization error: {}", e))?;
+        Ok(checkpoint)
+    }
+
+    /// Load a checkpoint, which fails unless it's of the current [`CHECKPOINT_VERSION`].
+    pub(crate) fn from_checkpoint(checkpoint: &[u8]) -> Result<Self, String> {
+        let content = checkpoint
+            .strip_prefix(CHECKPOINT_MAGIC)
+            .ok_or("Not a checkpoint of a known format")?;
+        if content.len() < 4 {
+            return Err("The checkpoint is truncated".to_string());
+        }
+        let (version, content) = content.split_at(4);
+        let version = u32::from_le_bytes(version.try_into().unwrap());
+        if version != CHECKPOINT_VERSION {
+            return Err(format!(
+                "The checkpoint is of the format {}, but {} is expected",
+                version, CHECKPOINT_VERSION
+            ));
+        }
+        bincode::deserialize(content).map_err(|e| format!("Deserialization error: {}", e))
+    }
+
+    pub async fn save_to_file(&mut self, path: &str) -> Result<(), String> {
+        let serialized = self.encode_checkpoint().await?;
 
         let mut file = File::create(path)
             .await
@@ -1010,8 +1730,7 @@ impl ImportContext {
             .await
             .map_err(|e| format!("Failed to read file: {}", e))?;
 
-        let context: ImportContext =
-            bincode::deserialize(&content).map_err(|e| format!("Deserialization error: {}", e))?;
+        let context = ImportContext::from_checkpoint(&content)?;
         tracing::info!(
             "Context loaded successfully, there are {} programs",
             context.programs.len()
@@ -1024,6 +1743,8 @@ impl ImportContext {
             "Checkpoint Summary:\n\
              Time: {}\n\
              Kafka Offset: {}\n\
+             Cached Repos: {}\n\
+             Repos Done In Run: {}\n\
              \n\
              Collection Sizes:\n\
              - Programs: {}\n\
@@ -1033,6 +1754,18 @@ impl ImportContext {
              - Application Versions: {}\n\
              - Versions: {}\n\
              - Licenses: {}\n\
+             - Features: {}\n\
+             - Persons: {}\n\
+             - Teams: {}\n\
+             - Authors: {}\n\
+             - Advisories: {}\n\
+             - API Items: {}\n\
+             - Contributors: {}\n\
+             - Repo Metrics: {}\n\
+             - CI Summaries: {}\n\
+             - Organizations: {}\n\
+             - Repositories: {}\n\
+             - Targets: {}\n\
              \n\
              Memory Sets:\n\
              - Program Memory: {}\n\
@@ -1045,12 +1778,36 @@ impl ImportContext {
              - App Has Version: {}\n\
              - Lib Has Dep Version: {}\n\
              - App Has Dep Version: {}\n\
-             - Depends On: {}\n",
+             - Depends On: {}\n\
+             - Dev Depends On: {}\n\
+             - Build Depends On: {}\n\
+             - Overrides: {}\n\
+             - Resolves To: {}\n\
+             - Typosquats: {}\n\
+             - Similar To: {}\n\
+             - Has Feature: {}\n\
+             - Enables Feature: {}\n\
+             - Enables Dependency: {}\n\
+             - Depends On Feature: {}\n\
+             - Person Owns: {}\n\
+             - Team Owns: {}\n\
+             - Author Of: {}\n\
+             - Is Contributor: {}\n\
+             - Affected By: {}\n\
+             - Has API Item: {}\n\
+             - Contributed To: {}\n\
+             - Has Repo Metrics: {}\n\
+             - Has CI: {}\n\
+             - Has Repository: {}\n\
+             - Has Program: {}\n\
+             - Has Target: {}\n",
             SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
             self.kafka_offset.unwrap_or(-1),
+            self.import_cache.len(),
+            self.progress.repos_done(),
             self.programs.len(),
             self.libraries.len(),
             self.applications.len(),
@@ -1058,6 +1815,18 @@ impl ImportContext {
             self.application_versions.len(),
             self.versions.len(),
             self.licenses.len(),
+            self.features.len(),
+            self.persons.len(),
+            self.teams.len(),
+            self.authors.len(),
+            self.advisories.len(),
+            self.api_items.len(),
+            self.contributors.len(),
+            self.repo_metrics.len(),
+            self.ci_summaries.len(),
+            self.organizations.len(),
+            self.repositories.len(),
+            self.targets.len(),
             self.program_memory.len(),
             self.version_memory.len(),
             self.has_lib_type.len(),
@@ -1067,6 +1836,28 @@ impl ImportContext {
             self.lib_has_dep_version.len(),
             self.app_has_dep_version.len(),
             self.depends_on.len(),
+            self.dev_depends_on.len(),
+            self.build_depends_on.len(),
+            self.overrides.len(),
+            self.resolves_to.len(),
+            self.typosquats.len(),
+            self.similar_to.len(),
+            self.has_feature.len(),
+            self.enables_feature.len(),
+            self.enables_dependency.len(),
+            self.depends_on_feature.len(),
+            self.person_owns.len(),
+            self.team_owns.len(),
+            self.author_of.len(),
+            self.is_contributor.len(),
+            self.affected_by.len(),
+            self.has_api_item.len(),
+            self.contributed_to.len(),
+            self.has_repo_metrics.len(),
+            self.has_ci.len(),
+            self.has_repository.len(),
+            self.has_program.len(),
+            self.has_target.len(),
         )
     }
 
@@ -1150,6 +1941,126 @@ impl ImportContext {
                 self.depends_on.capacity(),
                 mem::size_of::<DependsOn>(),
             ),
+            (
+                "DevDependsOn",
+                self.dev_depends_on.capacity(),
+                mem::size_of::<DevDependsOn>(),
+            ),
+            (
+                "BuildDependsOn",
+                self.build_depends_on.capacity(),
+                mem::size_of::<BuildDependsOn>(),
+            ),
+            (
+                "ResolvesTo",
+                self.resolves_to.capacity(),
+                mem::size_of::<ResolvesTo>(),
+            ),
+            (
+                "Typosquats",
+                self.typosquats.capacity(),
+                mem::size_of::<Typosquats>(),
+            ),
+            (
+                "SimilarTo",
+                self.similar_to.capacity(),
+                mem::size_of::<SimilarTo>(),
+            ),
+            ("Persons", self.persons.capacity(), mem::size_of::<Person>()),
+            ("Teams", self.teams.capacity(), mem::size_of::<Team>()),
+            (
+                "PersonOwns",
+                self.person_owns.capacity(),
+                mem::size_of::<Owns>(),
+            ),
+            (
+                "TeamOwns",
+                self.team_owns.capacity(),
+                mem::size_of::<Owns>(),
+            ),
+            ("Authors", self.authors.capacity(), mem::size_of::<Person>()),
+            (
+                "AuthorOf",
+                self.author_of.capacity(),
+                mem::size_of::<AuthorOf>(),
+            ),
+            (
+                "IsContributor",
+                self.is_contributor.capacity(),
+                mem::size_of::<IsContributor>(),
+            ),
+            (
+                "Advisories",
+                self.advisories.capacity(),
+                mem::size_of::<Advisory>(),
+            ),
+            (
+                "AffectedBy",
+                self.affected_by.capacity(),
+                mem::size_of::<AffectedBy>(),
+            ),
+            (
+                "ApiItems",
+                self.api_items.capacity(),
+                mem::size_of::<ApiItem>(),
+            ),
+            (
+                "HasApiItem",
+                self.has_api_item.capacity(),
+                mem::size_of::<HasApiItem>(),
+            ),
+            (
+                "Contributors",
+                self.contributors.capacity(),
+                mem::size_of::<Contributor>(),
+            ),
+            (
+                "ContributedTo",
+                self.contributed_to.capacity(),
+                mem::size_of::<ContributedTo>(),
+            ),
+            (
+                "RepoMetrics",
+                self.repo_metrics.capacity(),
+                mem::size_of::<RepoMetrics>(),
+            ),
+            (
+                "HasRepoMetrics",
+                self.has_repo_metrics.capacity(),
+                mem::size_of::<HasRepoMetrics>(),
+            ),
+            (
+                "CiSummaries",
+                self.ci_summaries.capacity(),
+                mem::size_of::<CiSummary>(),
+            ),
+            ("HasCi", self.has_ci.capacity(), mem::size_of::<HasCi>()),
+            (
+                "Organizations",
+                self.organizations.capacity(),
+                mem::size_of::<Organization>(),
+            ),
+            (
+                "Repositories",
+                self.repositories.capacity(),
+                mem::size_of::<Repo>(),
+            ),
+            (
+                "HasRepository",
+                self.has_repository.capacity(),
+                mem::size_of::<HasRepository>(),
+            ),
+            (
+                "HasProgram",
+                self.has_program.capacity(),
+                mem::size_of::<HasProgram>(),
+            ),
+            ("Targets", self.targets.capacity(), mem::size_of::<Target>()),
+            (
+                "HasTarget",
+                self.has_target.capacity(),
+                mem::size_of::<HasTarget>(),
+            ),
             (
                 "ProgramMemory",
                 self.program_memory.capacity(),
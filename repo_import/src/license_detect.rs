@@ -0,0 +1,165 @@
+//! Detect the licenses of a crate from its LICENSE/COPYING files,
+//! since many manifests omit or misstate `license`.
+//!
+//! The texts are normalized (lowercase, letters and digits only) and matched
+//! against the distinctive phrases of the SPDX license templates.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The file names (lowercase) of license files, such as `LICENSE-MIT` or `COPYING.txt`.
+const LICENSE_FILE_PREFIXES: [&str; 4] = ["license", "licence", "copying", "unlicense"];
+
+/// `(SPDX id, phrases which must all appear)`, the more specific ones first.
+///
+/// The GPL family is matched by the title with its date,
+/// since each of them mentions the others in its text.
+const LICENSE_TEMPLATES: [(&str, &[&str]); 15] = [
+    (
+        "AGPL-3.0-only",
+        &["gnu affero general public license version 3 19 november 2007"],
+    ),
+    (
+        "LGPL-3.0-only",
+        &["gnu lesser general public license version 3 29 june 2007"],
+    ),
+    (
+        "LGPL-2.1-only",
+        &["gnu lesser general public license version 2 1 february 1999"],
+    ),
+    (
+        "GPL-3.0-only",
+        &["gnu general public license version 3 29 june 2007"],
+    ),
+    (
+        "GPL-2.0-only",
+        &["gnu general public license version 2 june 1991"],
+    ),
+    (
+        "Apache-2.0",
+        &["apache license version 2 0 january 2004"],
+    ),
+    ("MPL-2.0", &["mozilla public license version 2 0"]),
+    ("BSL-1.0", &["boost software license version 1 0"]),
+    (
+        "Unlicense",
+        &["this is free and unencumbered software released into the public domain"],
+    ),
+    ("CC0-1.0", &["cc0 1 0 universal"]),
+    (
+        "Zlib",
+        &["altered source versions must be plainly marked as such and must not be misrepresented as being the original software"],
+    ),
+    (
+        "BSD-3-Clause",
+        &[
+            "redistribution and use in source and binary forms",
+            "neither the name of",
+        ],
+    ),
+    (
+        "BSD-2-Clause",
+        &["redistribution and use in source and binary forms"],
+    ),
+    (
+        "ISC",
+        &["permission to use copy modify and or distribute this software for any purpose with or without fee is hereby granted"],
+    ),
+    (
+        "MIT",
+        &["permission is hereby granted free of charge to any person obtaining a copy"],
+    ),
+];
+
+/// Keep lowercase letters and digits, separated by single spaces.
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The SPDX id of a license text, `None` if unknown.
+pub(crate) fn match_license(text: &str) -> Option<&'static str> {
+    let text = normalize(text);
+    LICENSE_TEMPLATES
+        .iter()
+        .find(|(_, phrases)| phrases.iter().all(|p| text.contains(p)))
+        .map(|(id, _)| *id)
+}
+
+fn is_license_file(path: &Path) -> bool {
+    path.is_file()
+        && path.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+            let n = n.to_lowercase();
+            LICENSE_FILE_PREFIXES.iter().any(|p| n.starts_with(p))
+        })
+}
+
+fn license_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| is_license_file(p))
+            .collect(),
+        Err(_) => vec![],
+    };
+    files.sort();
+    files
+}
+
+/// Detect the licenses of a crate, as an SPDX expression such as `Apache-2.0 OR MIT`.
+///
+/// The `license-file` in the manifest and the license files in the crate directory
+/// are read, falling back to the repo root for the crates of a workspace.
+/// Several licenses found are assumed to be alternatives, as the dual licensing is common.
+pub(crate) fn detect_license(
+    crate_dir: &Path,
+    repo_root: &Path,
+    license_file: Option<&str>,
+) -> Option<String> {
+    let mut files = license_files(crate_dir);
+    if let Some(license_file) = license_file {
+        let path = crate_dir.join(license_file);
+        if path.is_file() && !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    if files.is_empty() && crate_dir != repo_root {
+        files = license_files(repo_root);
+    }
+
+    let mut ids: Vec<&str> = files
+        .iter()
+        .filter_map(|f| fs::read_to_string(f).ok())
+        .filter_map(|text| match_license(&text))
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.is_empty() {
+        None
+    } else {
+        Some(ids.join(" OR "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::match_license;
+
+    #[test]
+    fn test_match_license() {
+        let mit = "MIT License\n\nPermission is hereby granted, free of charge, to any person \
+                   obtaining a copy of this software and associated documentation files";
+        assert_eq!(match_license(mit), Some("MIT"));
+        let bsd3 = "Redistribution and use in source and binary forms, with or without \
+                    modification, are permitted provided that the following conditions are met:\n\
+                    3. Neither the name of the copyright holder nor the names of its contributors";
+        assert_eq!(match_license(bsd3), Some("BSD-3-Clause"));
+        let lgpl = "GNU LESSER GENERAL PUBLIC LICENSE\nVersion 2.1, February 1999";
+        assert_eq!(match_license(lgpl), Some("LGPL-2.1-only"));
+        assert_eq!(match_license("All rights reserved."), None);
+    }
+}
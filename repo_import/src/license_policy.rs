@@ -0,0 +1,333 @@
+//! A license policy checker in the style of cargo-deny,
+//! which reports the dependencies violating the allow/deny lists of each repo.
+//!
+//! The policy is a toml file, such as
+//!
+//! ```toml
+//! allow = ["MIT", "Apache-2.0", "BSD-3-Clause"]
+//! deny = ["GPL-3.0-only"]
+//! allow_unlicensed = false
+//!
+//! [[exceptions]]
+//! name = "ring"
+//! allow = ["OpenSSL"]
+//! ```
+
+use crate::utils::name_join_version;
+use crate::ImportContext;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct LicensePolicy {
+    /// the licenses allowed, all the licenses not denied are allowed if empty
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// whether a crate without any license is allowed
+    #[serde(default)]
+    pub allow_unlicensed: bool,
+    /// more licenses allowed for some crates
+    #[serde(default)]
+    pub exceptions: Vec<LicenseException>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct LicenseException {
+    pub name: String,
+    pub allow: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LicenseViolation {
+    /// the crate of the repo whose dependency tree contains the violation
+    pub crate_name: String,
+    /// the name and version of the violating dependency
+    pub dependency: String,
+    pub license: Option<String>,
+    pub reason: String,
+}
+
+/// The violations of a repo, i.e. a namespace
+#[derive(Debug, Clone, Serialize)]
+pub struct LicenseReport {
+    pub namespace: String,
+    pub violations: Vec<LicenseViolation>,
+}
+
+/// A parsed SPDX license expression
+#[derive(Debug)]
+enum LicenseExpr {
+    License(String),
+    And(Box<LicenseExpr>, Box<LicenseExpr>),
+    Or(Box<LicenseExpr>, Box<LicenseExpr>),
+}
+
+fn tokenize(expr: &str) -> Vec<String> {
+    expr.replace('(', " ( ")
+        .replace(')', " ) ")
+        // the legacy `MIT/Apache-2.0` means `MIT OR Apache-2.0`
+        .replace('/', " OR ")
+        .split_whitespace()
+        .map(String::from)
+        .collect()
+}
+
+/// Parse by `or := and (OR and)*`, `and := atom (AND atom)*`,
+/// `atom := ( or ) | id [WITH exception]`.
+struct ExprParser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.as_str())
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<LicenseExpr, String> {
+        let mut expr = self.parse_and()?;
+        while self.peek().is_some_and(|t| t.eq_ignore_ascii_case("OR")) {
+            self.next();
+            expr = LicenseExpr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<LicenseExpr, String> {
+        let mut expr = self.parse_atom()?;
+        while self.peek().is_some_and(|t| t.eq_ignore_ascii_case("AND")) {
+            self.next();
+            expr = LicenseExpr::And(Box::new(expr), Box::new(self.parse_atom()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_atom(&mut self) -> Result<LicenseExpr, String> {
+        match self.next().as_deref() {
+            Some("(") => {
+                let expr = self.parse_or()?;
+                match self.next().as_deref() {
+                    Some(")") => Ok(expr),
+                    _ => Err("unclosed parenthesis".to_string()),
+                }
+            }
+            Some(")") | None => Err("missing license".to_string()),
+            Some(id) => {
+                let mut id = id.to_string();
+                if self.peek().is_some_and(|t| t.eq_ignore_ascii_case("WITH")) {
+                    self.next();
+                    let exception = self.next().ok_or("missing exception")?;
+                    id = format!("{} WITH {}", id, exception);
+                }
+                Ok(LicenseExpr::License(id))
+            }
+        }
+    }
+}
+
+fn parse_license_expr(expr: &str) -> Result<LicenseExpr, String> {
+    let mut parser = ExprParser {
+        tokens: tokenize(expr),
+        pos: 0,
+    };
+    let res = parser.parse_or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(format!("unexpected {}", parser.tokens[parser.pos]));
+    }
+    Ok(res)
+}
+
+impl LicenseExpr {
+    fn collect_ids(&self, ids: &mut BTreeSet<String>) {
+        match self {
+            LicenseExpr::License(id) => {
+                ids.insert(id.split(" WITH ").next().unwrap_or(id).to_string());
+            }
+            LicenseExpr::And(a, b) | LicenseExpr::Or(a, b) => {
+                a.collect_ids(ids);
+                b.collect_ids(ids);
+            }
+        }
+    }
+}
+
+/// The licenses in an SPDX expression without the exceptions,
+/// such as `MIT` and `Apache-2.0` in `MIT OR Apache-2.0`.
+pub(crate) fn license_ids(expr: &str) -> Result<BTreeSet<String>, String> {
+    let mut ids = BTreeSet::new();
+    parse_license_expr(expr)?.collect_ids(&mut ids);
+    Ok(ids)
+}
+
+impl LicensePolicy {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn allows_license(&self, crate_name: &str, license: &str) -> bool {
+        // `GPL-2.0-or-later WITH Classpath-exception-2.0` is judged by `GPL-2.0-or-later`
+        let id = license.split(" WITH ").next().unwrap_or(license);
+        let excepted = self
+            .exceptions
+            .iter()
+            .any(|e| e.name == crate_name && e.allow.iter().any(|l| l == id));
+        if excepted {
+            return true;
+        }
+        if self.deny.iter().any(|l| l == id) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|l| l == id)
+    }
+
+    fn allows_expr(&self, crate_name: &str, expr: &LicenseExpr) -> bool {
+        match expr {
+            LicenseExpr::License(id) => self.allows_license(crate_name, id),
+            LicenseExpr::And(a, b) => {
+                self.allows_expr(crate_name, a) && self.allows_expr(crate_name, b)
+            }
+            LicenseExpr::Or(a, b) => {
+                self.allows_expr(crate_name, a) || self.allows_expr(crate_name, b)
+            }
+        }
+    }
+
+    /// Check the license of a crate, return the reason if it's not allowed.
+    pub fn check(&self, crate_name: &str, license: Option<&str>) -> Option<String> {
+        let license = match license.map(str::trim) {
+            Some(license) if !license.is_empty() && license != "None" => license,
+            _ if self.allow_unlicensed => return None,
+            _ => return Some("no license".to_string()),
+        };
+        match parse_license_expr(license) {
+            Ok(expr) if self.allows_expr(crate_name, &expr) => None,
+            Ok(_) => Some("license not allowed".to_string()),
+            Err(e) => Some(format!("bad license expression: {}", e)),
+        }
+    }
+}
+
+impl ImportContext {
+    /// Check the normal dependency trees of the latest version of every crate,
+    /// and group the violations by repo.
+    ///
+    /// The declared license is checked, or the detected one if not declared.
+    /// The dependencies whose crate is not imported are skipped.
+    pub fn check_license_policy(&self, policy: &LicensePolicy) -> Vec<LicenseReport> {
+        let licenses: HashMap<&str, Option<&str>> = self
+            .programs
+            .iter()
+            .map(|p| {
+                let license = p
+                    .license
+                    .as_deref()
+                    .filter(|l| *l != "None")
+                    .or(p.detected_license.as_deref());
+                (p.name.as_str(), license)
+            })
+            .collect();
+        let mut dependencies: HashMap<&str, Vec<&str>> = HashMap::new();
+        for edge in &self.depends_on {
+            dependencies
+                .entry(edge.SRC_ID.as_str())
+                .or_default()
+                .push(edge.DST_ID.as_str());
+        }
+
+        let mut reports: BTreeMap<String, Vec<LicenseViolation>> = BTreeMap::new();
+        for program in &self.programs {
+            let (namespace, max_version) = match (&program.namespace, &program.max_version) {
+                (Some(namespace), Some(max_version)) => (namespace, max_version),
+                _ => continue,
+            };
+            let root = name_join_version(&program.name, max_version);
+
+            let mut visited: HashSet<&str> = HashSet::new();
+            let mut queue: VecDeque<&str> = VecDeque::new();
+            queue.push_back(&root);
+            while let Some(name_and_version) = queue.pop_front() {
+                if !visited.insert(name_and_version) {
+                    continue;
+                }
+                let name = name_and_version
+                    .split_once('/')
+                    .map_or(name_and_version, |(n, _)| n);
+                if let Some(license) = licenses.get(name) {
+                    if let Some(reason) = policy.check(name, *license) {
+                        reports
+                            .entry(namespace.clone())
+                            .or_default()
+                            .push(LicenseViolation {
+                                crate_name: program.name.clone(),
+                                dependency: name_and_version.to_string(),
+                                license: license.map(String::from),
+                                reason,
+                            });
+                    }
+                }
+                if let Some(deps) = dependencies.get(name_and_version) {
+                    queue.extend(deps);
+                }
+            }
+        }
+
+        reports
+            .into_iter()
+            .map(|(namespace, violations)| LicenseReport {
+                namespace,
+                violations,
+            })
+            .collect()
+    }
+
+    /// Check the policy at `policy_path`, and write the reports into `output` as json.
+    pub fn write_license_report(
+        &self,
+        policy_path: &Path,
+        output: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        let policy = LicensePolicy::from_file(policy_path)?;
+        let reports = self.check_license_policy(&policy);
+        tracing::info!("{} repos violate the license policy", reports.len());
+        fs::write(output, serde_json::to_string_pretty(&reports)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_license() {
+        let policy = LicensePolicy {
+            allow: vec!["MIT".to_string(), "Apache-2.0".to_string()],
+            deny: vec!["GPL-3.0-only".to_string()],
+            allow_unlicensed: false,
+            exceptions: vec![LicenseException {
+                name: "ring".to_string(),
+                allow: vec!["OpenSSL".to_string()],
+            }],
+        };
+        assert_eq!(policy.check("a", Some("MIT OR Apache-2.0")), None);
+        assert_eq!(policy.check("a", Some("MIT/GPL-3.0-only")), None);
+        assert!(policy.check("a", Some("MIT AND GPL-3.0-only")).is_some());
+        assert!(policy
+            .check("a", Some("(MIT OR ISC) AND OpenSSL"))
+            .is_some());
+        assert_eq!(policy.check("ring", Some("MIT AND ISC OR OpenSSL")), None);
+        assert_eq!(policy.check("a", None).as_deref(), Some("no license"));
+        assert!(policy.check("a", Some("MIT OR")).is_some());
+    }
+}
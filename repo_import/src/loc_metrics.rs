@@ -0,0 +1,208 @@
+//! Count the lines of code of a crate in the style of tokei:
+//! the code, comment and test lines of Rust, and the code lines of the other languages.
+
+use crate::crate_info::crate_files;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CommentStyle {
+    /// `//` and `/* */`
+    CLike,
+    /// `#`
+    Hash,
+}
+
+/// The languages counted by the file extension
+const LANGUAGES: &[(&str, &str, CommentStyle)] = &[
+    ("rs", "Rust", CommentStyle::CLike),
+    ("c", "C", CommentStyle::CLike),
+    ("h", "C", CommentStyle::CLike),
+    ("cc", "C++", CommentStyle::CLike),
+    ("cpp", "C++", CommentStyle::CLike),
+    ("cxx", "C++", CommentStyle::CLike),
+    ("hpp", "C++", CommentStyle::CLike),
+    ("go", "Go", CommentStyle::CLike),
+    ("java", "Java", CommentStyle::CLike),
+    ("js", "JavaScript", CommentStyle::CLike),
+    ("ts", "TypeScript", CommentStyle::CLike),
+    ("swift", "Swift", CommentStyle::CLike),
+    ("kt", "Kotlin", CommentStyle::CLike),
+    ("cu", "CUDA", CommentStyle::CLike),
+    ("py", "Python", CommentStyle::Hash),
+    ("sh", "Shell", CommentStyle::Hash),
+    ("rb", "Ruby", CommentStyle::Hash),
+    ("pl", "Perl", CommentStyle::Hash),
+    ("s", "Assembly", CommentStyle::Hash),
+    ("asm", "Assembly", CommentStyle::Hash),
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct LineCounts {
+    pub(crate) code: i64,
+    pub(crate) comments: i64,
+    pub(crate) blanks: i64,
+    /// the code lines of tests, i.e. in `#[cfg(test)]` modules or the `tests` and `benches` directories
+    pub(crate) tests: i64,
+}
+
+impl LineCounts {
+    fn add(&mut self, other: &LineCounts) {
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+        self.tests += other.tests;
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct LocMetrics {
+    pub(crate) rust: LineCounts,
+    /// the code lines of the other languages
+    pub(crate) languages: BTreeMap<&'static str, i64>,
+}
+
+impl LocMetrics {
+    /// e.g. `C:1200,Python:30`
+    pub(crate) fn languages_summary(&self) -> Option<String> {
+        if self.languages.is_empty() {
+            return None;
+        }
+        let languages: Vec<String> = self
+            .languages
+            .iter()
+            .map(|(l, n)| format!("{}:{}", l, n))
+            .collect();
+        Some(languages.join(","))
+    }
+}
+
+/// Count the lines of a source file.
+///
+/// The code lines of a `#[cfg(test)]` item are counted as tests,
+/// whose end is found by matching the braces.
+fn count_lines(source: &str, style: CommentStyle, is_test_file: bool) -> LineCounts {
+    let mut counts = LineCounts::default();
+    let mut in_block_comment = false;
+    let mut cfg_test = false;
+    // the brace depth inside a test item
+    let mut test_depth: Option<i64> = None;
+
+    for line in source.lines() {
+        let line = line.trim();
+        if in_block_comment {
+            counts.comments += 1;
+            if line.contains("*/") {
+                in_block_comment = false;
+            }
+            continue;
+        }
+        if line.is_empty() {
+            counts.blanks += 1;
+            continue;
+        }
+        let is_comment = match style {
+            CommentStyle::CLike => line.starts_with("//") || line.starts_with("/*"),
+            CommentStyle::Hash => line.starts_with('#') && !line.starts_with("#!"),
+        };
+        if style == CommentStyle::CLike {
+            if let Some(start) = line.rfind("/*") {
+                in_block_comment = !line[start..].contains("*/");
+            }
+        }
+        if is_comment {
+            counts.comments += 1;
+            continue;
+        }
+
+        counts.code += 1;
+        if line.starts_with("#[cfg(test)]") {
+            cfg_test = true;
+        }
+        let braces = line.matches('{').count() as i64 - line.matches('}').count() as i64;
+        match test_depth {
+            Some(depth) => {
+                counts.tests += 1;
+                test_depth = Some(depth + braces).filter(|d| *d > 0);
+            }
+            None if cfg_test => {
+                counts.tests += 1;
+                if line.contains('{') {
+                    cfg_test = false;
+                    test_depth = Some(braces).filter(|d| *d > 0);
+                } else if line.ends_with(';') {
+                    // such as `#[cfg(test)] mod tests;`
+                    cfg_test = false;
+                }
+            }
+            None if is_test_file => counts.tests += 1,
+            None => {}
+        }
+    }
+    counts
+}
+
+/// Count the lines of the files of a crate, by their languages.
+pub(crate) fn count_loc(crate_dir: &Path) -> LocMetrics {
+    let mut metrics = LocMetrics::default();
+    for file in crate_files(crate_dir) {
+        let ext = match file.extension().and_then(|e| e.to_str()) {
+            Some(ext) => ext.to_lowercase(),
+            None => continue,
+        };
+        let (language, style) = match LANGUAGES.iter().find(|(e, _, _)| *e == ext) {
+            Some((_, language, style)) => (*language, *style),
+            None => continue,
+        };
+        let source = match fs::read_to_string(&file) {
+            Ok(source) => source,
+            Err(_) => continue,
+        };
+        let is_test_file = file
+            .strip_prefix(crate_dir)
+            .ok()
+            .and_then(|p| p.components().next())
+            .is_some_and(|c| c.as_os_str() == "tests" || c.as_os_str() == "benches");
+        let counts = count_lines(&source, style, is_test_file);
+        if language == "Rust" {
+            metrics.rust.add(&counts);
+        } else {
+            *metrics.languages.entry(language).or_default() += counts.code;
+        }
+    }
+    metrics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_lines() {
+        let source = r#"
+//! docs
+fn main() {
+    /* a
+       block */
+    println!("hi");
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn it_works() {}
+}
+"#;
+        let counts = count_lines(source, CommentStyle::CLike, false);
+        assert_eq!(
+            counts,
+            LineCounts {
+                code: 8,
+                comments: 3,
+                blanks: 2,
+                tests: 5,
+            }
+        );
+    }
+}
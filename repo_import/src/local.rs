@@ -0,0 +1,200 @@
+//! Import the repos on the local disk, given by a plain path or a `file://` url,
+//! for the offline and air-gapped machines.
+//!
+//! The namespace is not parsed from an url, but the directory of the repo,
+//! relative to the directory of repos in the batch mode, such as `tokio-rs/tokio`.
+
+use crate::error::ImportError;
+use crate::report::record_repo_failed;
+use crate::stage_progress::StageProgress;
+use crate::utils::insert_namespace_by_repo_path;
+use crate::ImportContext;
+use std::path::{Path, PathBuf};
+use url::Url;
+use walkdir::WalkDir;
+
+/// the depth of the repos in a directory of repos, such as `<dir>/<owner>/<repo>`
+const MAX_REPO_DEPTH: usize = 3;
+
+/// The path of a plain path or a `file://` url, `None` for the remote urls.
+pub(crate) fn local_path(source: &str) -> Option<PathBuf> {
+    if source.starts_with("file://") {
+        return Url::parse(source).ok()?.to_file_path().ok();
+    }
+    let path = Path::new(source);
+    if source.contains("://") || !path.exists() {
+        return None;
+    }
+    Some(path.to_path_buf())
+}
+
+fn is_git_repo(path: &Path) -> bool {
+    path.join(".git").is_dir()
+}
+
+/// The git repos in a directory of repos, without descending into a repo.
+pub(crate) fn find_repos(dir: &Path) -> Vec<PathBuf> {
+    let mut repos = vec![];
+    let mut walker = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(MAX_REPO_DEPTH)
+        .sort_by_file_name()
+        .into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!("Failed to read {:?}: {}", dir, e);
+                continue;
+            }
+        };
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            walker.skip_current_dir();
+            continue;
+        }
+        if is_git_repo(entry.path()) {
+            repos.push(entry.into_path());
+            walker.skip_current_dir();
+        }
+    }
+    repos
+}
+
+/// The namespace of a repo, the path relative to `root`, or its directory name.
+fn local_namespace(repo: &Path, root: &Path) -> String {
+    let relative = repo.strip_prefix(root).unwrap_or(repo);
+    let name = if relative.as_os_str().is_empty() {
+        repo.file_name().map(Path::new).unwrap_or(repo)
+    } else {
+        relative
+    };
+    name.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl ImportContext {
+    /// Import a local repo, or all the repos in a directory of repos,
+    /// given by a plain path or a `file://` url.
+    #[tracing::instrument(name = "import_local", skip(self))]
+    pub async fn import_local(&mut self, source: &str) -> Result<(), ImportError> {
+        let path =
+            local_path(source).ok_or_else(|| ImportError::url(source, "not a local path"))?;
+        let root = path.canonicalize().map_err(ImportError::io(&path))?;
+        let repos = if is_git_repo(&root) {
+            vec![root.clone()]
+        } else {
+            find_repos(&root)
+        };
+        tracing::info!("Found {} local repos in {:?}", repos.len(), root);
+
+        let progress =
+            StageProgress::new("import", "repos", Some(repos.len())).with_items("crates");
+        for repo in repos {
+            let source = repo.to_string_lossy().to_string();
+            if self.progress.is_done(&source) {
+                progress.inc();
+                continue;
+            }
+            let namespace = local_namespace(&repo, &root);
+            insert_namespace_by_repo_path(repo.to_string_lossy().to_string(), namespace);
+            let url = Url::from_file_path(&repo)
+                .map(String::from)
+                .unwrap_or_else(|_| repo.to_string_lossy().to_string());
+            let programs = self.programs.len();
+            // a bad repo is skipped, and the others are still imported
+            match self
+                .parse_a_local_repo_and_return_new_versions(repo, url.clone())
+                .await
+            {
+                Ok(_) => {
+                    self.mark_repo_done(&source).await;
+                    progress.add_items(self.programs.len().saturating_sub(programs));
+                    progress.inc();
+                }
+                Err(e) => {
+                    tracing::error!("Failed to import {}: {}", source, e);
+                    record_repo_failed(&url, &e);
+                    progress.fail();
+                }
+            }
+        }
+        progress.finish();
+        Ok(())
+    }
+}
+
+/// Init a git repo at `path` with a commit of the crate `name`.
+#[cfg(test)]
+pub(crate) fn init_crate_repo(path: &Path, name: &str) {
+    use std::fs;
+
+    fs::create_dir_all(path.join("src")).unwrap();
+    fs::write(
+        path.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+            name
+        ),
+    )
+    .unwrap();
+    fs::write(path.join("src/lib.rs"), "").unwrap();
+
+    let repo = git2::Repository::init(path).unwrap();
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_find_repos() {
+        let dir = tempfile::tempdir().unwrap();
+        for repo in ["tokio-rs/tokio", "serde", "serde/vendored", ".cache/x"] {
+            fs::create_dir_all(dir.path().join(repo).join(".git")).unwrap();
+        }
+        let repos: Vec<String> = find_repos(dir.path())
+            .iter()
+            .map(|r| local_namespace(r, dir.path()))
+            .collect();
+        assert_eq!(repos, vec!["serde", "tokio-rs/tokio"]);
+
+        let url = Url::from_file_path(dir.path()).unwrap();
+        assert_eq!(local_path(url.as_str()), Some(dir.path().to_path_buf()));
+        assert_eq!(local_path("https://github.com/tokio-rs/tokio"), None);
+    }
+
+    #[tokio::test]
+    async fn test_import_local_with_a_bad_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        init_crate_repo(&dir.path().join("a/good"), "local_good");
+        // a `.git` which is not a repo
+        fs::create_dir_all(dir.path().join("a/bad/.git")).unwrap();
+
+        let mut context = ImportContext::default();
+        context
+            .import_local(&dir.path().to_string_lossy())
+            .await
+            .unwrap();
+        assert!(context.programs.iter().any(|p| p.name == "local_good"));
+        assert!(context
+            .import_report()
+            .failed_repos
+            .iter()
+            .any(|f| f.url.ends_with("a/bad")));
+    }
+}
@@ -0,0 +1,396 @@
+//! The versions resolved by the `Cargo.lock` of a repo, with their checksums, for the
+//! `resolves_to` edges beside the declared requirements.
+//!
+//! The lockfile is the one of the workspace, i.e. in the directory of the crate or any
+//! ancestor of it. All the formats are supported, where the checksums of the version 1
+//! are in the `[metadata]` table.
+//!
+//! With `IMPORT_GENERATE_LOCKFILE=1`, the lockfiles missing at HEAD are generated by
+//! `cargo generate-lockfile` in a copy of the manifests, so the working tree is never written,
+//! with `--offline` if `IMPORT_GENERATE_LOCKFILE_OFFLINE=1`, so only the crates in the local
+//! registry cache are resolved. Each run is killed after `IMPORT_GENERATE_LOCKFILE_TIMEOUT`
+//! seconds, 120 by default. The tags are read from the git objects, so they're not affected.
+
+use git2::{Repository, Tree};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::process::Command;
+use walkdir::WalkDir;
+
+/// The lockfiles generated for a repo, by the directories of the workspaces in the repo.
+pub(crate) type GeneratedLockfiles = HashMap<PathBuf, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedDependency {
+    pub name: String,
+    pub version: String,
+    /// such as `registry+https://github.com/rust-lang/crates.io-index`,
+    /// none for a path dependency
+    pub source: Option<String>,
+    pub checksum: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+    checksum: Option<String>,
+    /// `name`, `name version` or `name version (source)`
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+impl Lockfile {
+    /// The package of a dependency entry, the name is enough if only one version is locked.
+    fn find(&self, entry: &str) -> Option<&LockedPackage> {
+        let mut parts = entry.splitn(3, ' ');
+        let name = parts.next()?;
+        let version = parts.next();
+        let source = parts
+            .next()
+            .map(|s| s.trim_start_matches('(').trim_end_matches(')'));
+        let mut candidates = self.package.iter().filter(|p| {
+            p.name == name
+                && version.is_none_or(|v| p.version == v)
+                && source.is_none_or(|s| p.source.as_deref() == Some(s))
+        });
+        let package = candidates.next()?;
+        candidates.next().is_none().then_some(package)
+    }
+
+    fn checksum(&self, package: &LockedPackage) -> Option<String> {
+        if package.checksum.is_some() {
+            return package.checksum.clone();
+        }
+        let key = format!(
+            "checksum {} {} ({})",
+            package.name,
+            package.version,
+            package.source.as_deref()?
+        );
+        self.metadata
+            .get(&key)
+            .filter(|c| c.as_str() != "<none>")
+            .cloned()
+    }
+}
+
+/// The direct dependencies of a local package as resolved by a lockfile.
+pub(crate) fn locked_dependencies(
+    lockfile: &str,
+    name: &str,
+    version: &str,
+) -> Vec<LockedDependency> {
+    let lockfile: Lockfile = match toml::from_str(lockfile) {
+        Ok(lockfile) => lockfile,
+        Err(e) => {
+            tracing::debug!("Invalid Cargo.lock for {} {}: {}", name, version, e);
+            return vec![];
+        }
+    };
+    let Some(package) = lockfile
+        .package
+        .iter()
+        .find(|p| p.name == name && p.version == version && p.source.is_none())
+    else {
+        return vec![];
+    };
+    package
+        .dependencies
+        .iter()
+        .filter_map(|entry| lockfile.find(entry))
+        .map(|dep| LockedDependency {
+            name: dep.name.clone(),
+            version: dep.version.clone(),
+            source: dep.source.clone(),
+            checksum: lockfile.checksum(dep),
+        })
+        .collect()
+}
+
+/// The lockfile of the crate in `crate_dir` of the git tree.
+pub(crate) fn read_lockfile_in_tree(
+    repo: &Repository,
+    tree: &Tree,
+    crate_dir: &str,
+) -> Option<String> {
+    Path::new(crate_dir).ancestors().find_map(|dir| {
+        let obj = tree
+            .get_path(&dir.join("Cargo.lock"))
+            .ok()?
+            .to_object(repo)
+            .ok()?;
+        String::from_utf8(obj.as_blob()?.content().to_vec()).ok()
+    })
+}
+
+fn find_lockfile(crate_dir: &Path, repo_path: &Path) -> Option<PathBuf> {
+    crate_dir
+        .ancestors()
+        .take_while(|dir| dir.starts_with(repo_path))
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.is_file())
+}
+
+/// The lockfile of the crate in `crate_dir` of the working tree of the repo,
+/// or the one generated for it.
+pub(crate) fn read_lockfile(
+    crate_dir: &Path,
+    repo_path: &Path,
+    generated: &GeneratedLockfiles,
+) -> Option<String> {
+    crate_dir
+        .ancestors()
+        .take_while(|dir| dir.starts_with(repo_path))
+        .find_map(|dir| {
+            let path = dir.join("Cargo.lock");
+            if path.is_file() {
+                fs::read_to_string(path).ok()
+            } else {
+                generated.get(dir).cloned()
+            }
+        })
+}
+
+fn generate_timeout() -> Duration {
+    let secs = env::var("IMPORT_GENERATE_LOCKFILE_TIMEOUT")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(120);
+    Duration::from_secs(secs)
+}
+
+/// Copy what cargo reads to resolve the dependencies of the repo: the manifests, the cargo
+/// configs, and the rust files, which are only looked up to find the targets, so they're empty.
+fn copy_manifests(repo_path: &Path) -> io::Result<TempDir> {
+    let copy = tempfile::tempdir()?;
+    let walker = WalkDir::new(repo_path)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git" && e.file_name() != "target");
+    for entry in walker {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(repo_path) else {
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy();
+        let is_config = relative.parent().is_some_and(|dir| dir.ends_with(".cargo"));
+        let target = copy.path().join(relative);
+        if name == "Cargo.toml" || is_config {
+            fs::create_dir_all(target.parent().unwrap_or(copy.path()))?;
+            fs::copy(entry.path(), &target)?;
+        } else if name.ends_with(".rs") {
+            fs::create_dir_all(target.parent().unwrap_or(copy.path()))?;
+            fs::File::create(&target)?;
+        }
+    }
+    Ok(copy)
+}
+
+async fn generate_lockfile(manifest: &Path, offline: bool) -> Result<(), String> {
+    let mut command = Command::new("cargo");
+    command
+        .args(["generate-lockfile", "--quiet", "--manifest-path"])
+        .arg(manifest)
+        .kill_on_drop(true);
+    if offline {
+        command.arg("--offline");
+    }
+    let output = tokio::time::timeout(generate_timeout(), command.output())
+        .await
+        .map_err(|_| "timed out".to_string())?
+        .map_err(|e| format!("failed to run cargo: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
+
+/// Generate the lockfiles of the manifests without one, if `IMPORT_GENERATE_LOCKFILE=1`.
+pub(crate) async fn generate_lockfiles(
+    manifests: &[PathBuf],
+    repo_path: &Path,
+) -> GeneratedLockfiles {
+    if !env::var("IMPORT_GENERATE_LOCKFILE").is_ok_and(|v| v == "1") {
+        return GeneratedLockfiles::new();
+    }
+    let offline = env::var("IMPORT_GENERATE_LOCKFILE_OFFLINE").is_ok_and(|v| v == "1");
+    generate_missing_lockfiles(manifests, repo_path, offline).await
+}
+
+async fn generate_missing_lockfiles(
+    manifests: &[PathBuf],
+    repo_path: &Path,
+    offline: bool,
+) -> GeneratedLockfiles {
+    let mut generated = GeneratedLockfiles::new();
+    // the workspace roots first, since the members share their lockfiles
+    let mut missing: Vec<&Path> = manifests
+        .iter()
+        .filter(|m| find_lockfile(m.parent().unwrap_or(repo_path), repo_path).is_none())
+        .filter_map(|m| m.strip_prefix(repo_path).ok())
+        .collect();
+    if missing.is_empty() {
+        return generated;
+    }
+    missing.sort_by_key(|m| m.components().count());
+
+    let copy = match copy_manifests(repo_path) {
+        Ok(copy) => copy,
+        Err(e) => {
+            tracing::warn!("Failed to copy the manifests of {:?}: {}", repo_path, e);
+            return generated;
+        }
+    };
+    for manifest in missing {
+        let dir = copy.path().join(manifest.parent().unwrap_or(Path::new("")));
+        if find_lockfile(&dir, copy.path()).is_some() {
+            continue;
+        }
+        if let Err(e) = generate_lockfile(&copy.path().join(manifest), offline).await {
+            tracing::warn!("Failed to generate the lockfile of {:?}: {}", manifest, e);
+            continue;
+        }
+        // the lockfile of a member is the one of its workspace
+        let Some(lockfile) = find_lockfile(&dir, copy.path()) else {
+            continue;
+        };
+        let Ok(workspace) = lockfile
+            .parent()
+            .unwrap_or(copy.path())
+            .strip_prefix(copy.path())
+        else {
+            continue;
+        };
+        match fs::read_to_string(&lockfile) {
+            Ok(content) => {
+                tracing::info!("Generated the lockfile of {:?}", manifest);
+                generated.insert(repo_path.join(workspace), content);
+            }
+            Err(e) => tracing::warn!("Failed to read {:?}: {}", lockfile, e),
+        }
+    }
+    generated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locked_dependencies() {
+        let lockfile = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["bitflags 2.5.0", "serde", "util"]
+
+[[package]]
+name = "bitflags"
+version = "1.3.2"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "bef38d45163c2f1dde094a7dfd33ccf595c92905c8f8f4fdc18d06fb1037718a"
+
+[[package]]
+name = "bitflags"
+version = "2.5.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "cf4b9d6a944f767f8e5e0db018570623c85f3d925ac718db4e06d0187adb21c1"
+
+[[package]]
+name = "serde"
+version = "1.0.203"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "7253ab4de971e72fb7be983802300c30b5a7f0c2e56fab8abfc6a214307c0094"
+
+[[package]]
+name = "util"
+version = "0.1.0"
+"#;
+        let locked = locked_dependencies(lockfile, "app", "0.1.0");
+        let versions: Vec<_> = locked
+            .iter()
+            .map(|d| (d.name.as_str(), d.version.as_str()))
+            .collect();
+        assert_eq!(
+            versions,
+            [
+                ("bitflags", "2.5.0"),
+                ("serde", "1.0.203"),
+                ("util", "0.1.0")
+            ]
+        );
+        assert!(locked[0].checksum.as_deref().unwrap().starts_with("cf4b"));
+        assert_eq!(locked[2].source, None);
+        assert!(locked_dependencies(lockfile, "app", "0.2.0").is_empty());
+
+        let v1 = r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["libc 0.2.155 (registry+https://github.com/rust-lang/crates.io-index)"]
+
+[[package]]
+name = "libc"
+version = "0.2.155"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[metadata]
+"checksum libc 0.2.155 (registry+https://github.com/rust-lang/crates.io-index)" = "97b3888a4aecf77e811145cadf6eef5901f4782c53886191b2f693f24761847c"
+"#;
+        let locked = locked_dependencies(v1, "app", "0.1.0");
+        assert_eq!(locked.len(), 1);
+        assert!(locked[0].checksum.as_deref().unwrap().starts_with("97b3"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_lockfiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        fs::create_dir_all(repo.join("member/src")).unwrap();
+        fs::write(
+            repo.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"member\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            repo.join("member/Cargo.toml"),
+            "[package]\nname = \"member\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        fs::write(repo.join("member/src/lib.rs"), "pub fn f() {}").unwrap();
+
+        let manifests = [repo.join("Cargo.toml"), repo.join("member/Cargo.toml")];
+        let generated = generate_missing_lockfiles(&manifests, repo, true).await;
+        // the member shares the lockfile of the workspace
+        assert_eq!(generated.len(), 1);
+        let lockfile = read_lockfile(&repo.join("member"), repo, &generated).unwrap();
+        assert!(lockfile.contains("name = \"member\""));
+
+        // the working tree is untouched
+        assert!(!repo.join("Cargo.lock").exists());
+        assert_eq!(
+            fs::read_to_string(repo.join("member/src/lib.rs")).unwrap(),
+            "pub fn f() {}"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Score how well the crates are maintained from 0 to 1, to rank them, by the weighted sum of:
+//! - the spread of the commits among the contributors, `1 - HHI` of their shares,
+//!   so that a crate of a single author is scored 0,
+//! - the responsiveness, halved every `response_half_life_hours` of the median time to
+//!   close its issues and pull requests, the open ones counted by their age so far,
+//! - the release recency, halved every `release_half_life_days` since its last release.
+//!
+//! Each crate is scored by the signals known for it: the contributors with
+//! `IMPORT_CONTRIBUTORS=1`, and the responsiveness of the repos on GitHub with
+//! `IMPORT_ENRICH_RESPONSIVENESS=1`. The bus factor, the fewest contributors who make half of
+//! the commits, is stored along with the score. The weights are in the `[import.maintenance]`
+//! section of the config, i.e. `IMPORT_MAINTENANCE_CONCENTRATION` and so on.
+
+use crate::scoring::{halved, last_releases, unix_now, weights_from_env, SECONDS_PER_DAY};
+use crate::ImportContext;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct MaintenanceWeights {
+    pub(crate) concentration: f64,
+    pub(crate) responsiveness: f64,
+    pub(crate) recency: f64,
+    pub(crate) response_half_life_hours: f64,
+    pub(crate) release_half_life_days: f64,
+}
+
+impl Default for MaintenanceWeights {
+    fn default() -> Self {
+        MaintenanceWeights {
+            concentration: 0.4,
+            responsiveness: 0.3,
+            recency: 0.3,
+            response_half_life_hours: 168.0,
+            release_half_life_days: 180.0,
+        }
+    }
+}
+
+impl MaintenanceWeights {
+    pub(crate) fn from_env() -> Self {
+        let default = MaintenanceWeights::default();
+        let weight = weights_from_env("IMPORT_MAINTENANCE");
+        MaintenanceWeights {
+            concentration: weight("CONCENTRATION", default.concentration),
+            responsiveness: weight("RESPONSIVENESS", default.responsiveness),
+            recency: weight("RECENCY", default.recency),
+            response_half_life_hours: weight(
+                "RESPONSE_HALF_LIFE_HOURS",
+                default.response_half_life_hours,
+            )
+            .max(1.0),
+            release_half_life_days: weight(
+                "RELEASE_HALF_LIFE_DAYS",
+                default.release_half_life_days,
+            )
+            .max(1.0),
+        }
+    }
+}
+
+/// The fewest contributors who make half of `commits`, and the concentration of the commits
+/// among them, the Herfindahl-Hirschman index of their shares from 0 to 1.
+fn contributor_concentration(commits: &[i64]) -> Option<(i64, f64)> {
+    let total: i64 = commits.iter().filter(|c| **c > 0).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut commits: Vec<i64> = commits.iter().copied().filter(|c| *c > 0).collect();
+    commits.sort_unstable_by(|a, b| b.cmp(a));
+    let mut covered = 0;
+    let mut bus_factor = 0;
+    for c in &commits {
+        covered += c;
+        bus_factor += 1;
+        if covered * 2 >= total {
+            break;
+        }
+    }
+    let hhi = commits
+        .iter()
+        .map(|c| (*c as f64 / total as f64).powi(2))
+        .sum();
+    Some((bus_factor, hhi))
+}
+
+/// The signals of a crate, none if unknown.
+#[derive(Debug, Clone, Copy, Default)]
+struct Signals {
+    /// the Herfindahl-Hirschman index of the commits of the contributors
+    concentration: Option<f64>,
+    issue_close_hours: Option<f64>,
+    /// the last release, in seconds since the epoch
+    last_release: Option<i64>,
+}
+
+/// The maintenance score of a crate from 0 to 1, none if no signal is known.
+fn maintenance(signals: &Signals, weights: &MaintenanceWeights, now: i64) -> Option<f64> {
+    let mut score = 0.0;
+    let mut total = 0.0;
+    if let Some(hhi) = signals.concentration {
+        score += weights.concentration * (1.0 - hhi);
+        total += weights.concentration;
+    }
+    if let Some(hours) = signals.issue_close_hours {
+        score += weights.responsiveness * halved(hours, weights.response_half_life_hours);
+        total += weights.responsiveness;
+    }
+    if let Some(last_release) = signals.last_release {
+        let age_days = (now - last_release) as f64 / SECONDS_PER_DAY;
+        score += weights.recency * halved(age_days, weights.release_half_life_days);
+        total += weights.recency;
+    }
+    (total > 0.0).then(|| score / total)
+}
+
+impl ImportContext {
+    /// Fill the bus factor and the maintenance score of the programs.
+    pub(crate) fn score_maintenance(&mut self, weights: &MaintenanceWeights) {
+        // program id -> contributor -> the commits, the last if a repo is imported again
+        let mut commits: HashMap<&str, HashMap<&str, i64>> = HashMap::new();
+        for edge in &self.contributed_to {
+            commits
+                .entry(edge.DST_ID.as_str())
+                .or_default()
+                .insert(edge.SRC_ID.as_str(), edge.commits);
+        }
+        let last_releases = last_releases(&self.library_versions, &self.application_versions);
+        let now = unix_now();
+        let mut scored = 0;
+        for program in &mut self.programs {
+            let concentration = commits.get(program.id.as_str()).and_then(|c| {
+                let commits: Vec<i64> = c.values().copied().collect();
+                contributor_concentration(&commits)
+            });
+            program.bus_factor = concentration.map(|(bus_factor, _)| bus_factor);
+            let signals = Signals {
+                concentration: concentration.map(|(_, hhi)| hhi),
+                issue_close_hours: program.issue_close_hours,
+                last_release: last_releases.get(program.id.as_str()).copied(),
+            };
+            program.maintenance = maintenance(&signals, weights, now);
+            scored += program.maintenance.is_some() as usize;
+        }
+        tracing::info!("Scored the maintenance of {} programs", scored);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maintenance() {
+        assert_eq!(contributor_concentration(&[10]), Some((1, 1.0)));
+        assert_eq!(contributor_concentration(&[5, 5, 5, 5]), Some((2, 0.25)));
+        let (bus_factor, _) = contributor_concentration(&[1, 90, 3, 6]).unwrap();
+        assert_eq!(bus_factor, 1);
+        assert_eq!(contributor_concentration(&[]), None);
+
+        let now = 1_700_000_000;
+        let weights = MaintenanceWeights::default();
+        let signals = Signals {
+            concentration: Some(0.25),
+            issue_close_hours: Some(168.0),
+            last_release: Some(now - 180 * 24 * 60 * 60),
+        };
+        let expected = 0.4 * 0.75 + 0.3 * 0.5 + 0.3 * 0.5;
+        assert!((maintenance(&signals, &weights, now).unwrap() - expected).abs() < 1e-9);
+
+        // a single author releasing today, without the issues known
+        let signals = Signals {
+            concentration: Some(1.0),
+            issue_close_hours: None,
+            last_release: Some(now),
+        };
+        let expected = 0.3 / 0.7;
+        assert!((maintenance(&signals, &weights, now).unwrap() - expected).abs() < 1e-9);
+        assert_eq!(maintenance(&Signals::default(), &weights, now), None);
+    }
+}
@@ -0,0 +1,790 @@
+//! Helpers for reading a `Cargo.toml`,
+//! resolving the fields inherited from the workspace root manifest,
+//! e.g. `version.workspace = true` or `tokio = { workspace = true }`,
+//! and deserializing it into a [`Manifest`].
+
+use crate::discovery_filter::PatternSet;
+use crate::error::ImportError;
+use git2::{Repository, Tree};
+use model::general_model::DependencySource;
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::Value;
+use walkdir::WalkDir;
+
+/// The parts of a `Cargo.toml` used by the import.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct Manifest {
+    /// none for a virtual workspace manifest
+    #[serde(default, deserialize_with = "package_with_unresolved")]
+    pub(crate) package: Option<Package>,
+    pub(crate) workspace: Option<Workspace>,
+    pub(crate) lib: Option<LibTarget>,
+    /// the `[[bin]]` targets
+    #[serde(default)]
+    pub(crate) bin: Vec<TargetSpec>,
+    #[serde(default)]
+    pub(crate) example: Vec<TargetSpec>,
+    #[serde(default)]
+    pub(crate) test: Vec<TargetSpec>,
+    #[serde(default)]
+    pub(crate) bench: Vec<TargetSpec>,
+    #[serde(default)]
+    pub(crate) dependencies: BTreeMap<String, DependencySpec>,
+    #[serde(default, rename = "dev-dependencies", alias = "dev_dependencies")]
+    pub(crate) dev_dependencies: BTreeMap<String, DependencySpec>,
+    #[serde(default, rename = "build-dependencies", alias = "build_dependencies")]
+    pub(crate) build_dependencies: BTreeMap<String, DependencySpec>,
+    /// by the platform, such as `[target.'cfg(windows)'.dependencies]`
+    #[serde(default)]
+    pub(crate) target: BTreeMap<String, TargetDependencies>,
+    /// e.g. `full = ["rt", "dep:mio", "bytes?/std"]`
+    #[serde(default)]
+    pub(crate) features: BTreeMap<String, Vec<String>>,
+    /// by the overridden source, such as `[patch.crates-io]`
+    #[serde(default)]
+    pub(crate) patch: BTreeMap<String, BTreeMap<String, DependencySpec>>,
+    /// by the overridden package, such as `"foo:1.0.0" = { git = "..." }`
+    #[serde(default)]
+    pub(crate) replace: BTreeMap<String, DependencySpec>,
+}
+
+/// The dependencies only for a platform, a `cfg(...)` expression or a target triple.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct TargetDependencies {
+    #[serde(default)]
+    pub(crate) dependencies: BTreeMap<String, DependencySpec>,
+    #[serde(default, rename = "dev-dependencies", alias = "dev_dependencies")]
+    pub(crate) dev_dependencies: BTreeMap<String, DependencySpec>,
+    #[serde(default, rename = "build-dependencies", alias = "build_dependencies")]
+    pub(crate) build_dependencies: BTreeMap<String, DependencySpec>,
+}
+
+/// The version of a package which omits it, as cargo does
+const DEFAULT_VERSION: &str = "0.0.0";
+
+/// The `[package]` table. The fields inherited from a workspace which does not define them
+/// are none, and listed in `unresolved` however the manifest is deserialized.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Package {
+    pub(crate) name: String,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) version: Option<String>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) description: Option<String>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) repository: Option<String>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) documentation: Option<String>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) homepage: Option<String>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) license: Option<String>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) license_file: Option<String>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) keywords: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) categories: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) authors: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) edition: Option<String>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) rust_version: Option<String>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) readme: Option<Readme>,
+    pub(crate) build: Option<BuildScript>,
+    pub(crate) links: Option<String>,
+    /// whether the binaries in `src/main.rs` and `src/bin/` are discovered, true by default
+    pub(crate) autobins: Option<bool>,
+    /// the same for `examples/`, `tests/` and `benches/`
+    pub(crate) autoexamples: Option<bool>,
+    pub(crate) autotests: Option<bool>,
+    pub(crate) autobenches: Option<bool>,
+    /// the files to package, `exclude` is ignored if it's given
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) include: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "inheritable")]
+    pub(crate) exclude: Option<Vec<String>>,
+    /// the fields still `{ workspace = true }`, such as `version`
+    #[serde(skip)]
+    pub(crate) unresolved: Vec<String>,
+}
+
+impl Package {
+    /// The version, `0.0.0` if it's omitted, or none if it's inherited but unknown.
+    pub(crate) fn resolved_version(&self) -> Option<&str> {
+        match &self.version {
+            Some(version) => Some(version),
+            None if self.unresolved.iter().any(|f| f == "version") => None,
+            None => Some(DEFAULT_VERSION),
+        }
+    }
+}
+
+/// The `[workspace]` table, whose `members` and `exclude` are globs such as `crates/*`.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct Workspace {
+    #[serde(default)]
+    pub(crate) members: Vec<String>,
+    #[serde(default)]
+    pub(crate) exclude: Vec<String>,
+}
+
+/// `readme = "README.md"`, or `readme = false` to disable it
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Readme {
+    Path(String),
+    Enabled(bool),
+}
+
+/// `build = "build/main.rs"`, or `build = false` to disable `build.rs`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum BuildScript {
+    Path(String),
+    Enabled(bool),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct LibTarget {
+    pub(crate) path: Option<String>,
+    #[serde(alias = "proc_macro")]
+    pub(crate) proc_macro: Option<bool>,
+    #[serde(alias = "crate_type")]
+    pub(crate) crate_type: Option<Vec<String>>,
+}
+
+/// A target declared in `[[bin]]`, `[[example]]`, `[[test]]` or `[[bench]]`,
+/// whose name and path are inferred from each other if omitted.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct TargetSpec {
+    pub(crate) name: Option<String>,
+    pub(crate) path: Option<String>,
+}
+
+/// `foo = "1.0"` or `foo = { version = "1.0", features = [...], optional = true }`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum DependencySpec {
+    Version(String),
+    Detailed(DependencyDetail),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub(crate) struct DependencyDetail {
+    pub(crate) version: Option<String>,
+    /// the real name of a renamed dependency
+    pub(crate) package: Option<String>,
+    #[serde(default)]
+    pub(crate) features: Vec<String>,
+    #[serde(default)]
+    pub(crate) optional: bool,
+    /// `default-features = false`, none if it's unset and the default features are enabled
+    #[serde(rename = "default-features", alias = "default_features")]
+    pub(crate) default_features: Option<bool>,
+    pub(crate) path: Option<String>,
+    pub(crate) git: Option<String>,
+    pub(crate) tag: Option<String>,
+}
+
+impl DependencyDetail {
+    /// A path takes precedence over the git repo and the registry, as cargo does.
+    pub(crate) fn source(&self) -> DependencySource {
+        match (&self.path, &self.git) {
+            (Some(path), _) => DependencySource::Path(path.clone()),
+            (None, Some(git)) => DependencySource::Git(git.clone()),
+            (None, None) => DependencySource::Registry,
+        }
+    }
+
+    /// The version of a git dependency pinned to a tag such as `v1.2.3`.
+    pub(crate) fn tag_version(&self) -> Option<String> {
+        let tag = self.tag.as_deref()?;
+        let version = tag.strip_prefix('v').unwrap_or(tag);
+        semver::Version::parse(version).ok()?;
+        Some(version.to_string())
+    }
+}
+
+/// A field which is still `{ workspace = true }`, if the workspace does not define it.
+fn inheritable<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let value = Value::deserialize(deserializer)?;
+    if is_inherited(&value) {
+        return Ok(None);
+    }
+    T::deserialize(value).map(Some).map_err(D::Error::custom)
+}
+
+/// The `[package]` table with its fields still inherited listed in `unresolved`.
+fn package_with_unresolved<'de, D>(deserializer: D) -> Result<Option<Package>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Value::deserialize(deserializer)?;
+    let unresolved = value
+        .as_table()
+        .map(|table| {
+            table
+                .iter()
+                .filter(|(_, val)| is_inherited(val))
+                .map(|(key, _)| key.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut package = Package::deserialize(value).map_err(D::Error::custom)?;
+    package.unresolved = unresolved;
+    Ok(Some(package))
+}
+
+impl Manifest {
+    /// Deserialize a manifest whose inherited fields are resolved.
+    pub(crate) fn from_value(value: &Value) -> Result<Self, toml::de::Error> {
+        value.clone().try_into()
+    }
+
+    /// A manifest with `[workspace]` but without `[package]`.
+    pub(crate) fn is_virtual(&self) -> bool {
+        self.package.is_none() && self.workspace.is_some()
+    }
+
+    /// Read and deserialize a Cargo.toml in the working tree,
+    /// with the fields it inherits from the workspace.
+    pub(crate) fn read(cargo_toml_path: &Path, repo_root: &Path) -> Result<Self, ImportError> {
+        let value = read_manifest(cargo_toml_path, repo_root)?;
+        Manifest::from_value(&value).map_err(ImportError::toml(cargo_toml_path))
+    }
+}
+
+/// Read a Cargo.toml in the working tree,
+/// and resolve the fields it inherits from the workspace.
+pub(crate) fn read_manifest(
+    cargo_toml_path: &Path,
+    repo_root: &Path,
+) -> Result<Value, ImportError> {
+    let content = fs::read_to_string(cargo_toml_path).map_err(ImportError::io(cargo_toml_path))?;
+    let mut manifest = content
+        .parse::<Value>()
+        .map_err(ImportError::toml(cargo_toml_path))?;
+
+    if let Some(workspace_root) = find_workspace_root(&manifest, cargo_toml_path, repo_root) {
+        inherit_from_workspace(&mut manifest, &workspace_root);
+    }
+
+    Ok(manifest)
+}
+
+fn to_strs(v: &[String]) -> Vec<&str> {
+    v.iter().map(String::as_str).collect()
+}
+
+/// The manifests of the members of a workspace whose root manifest is `cargo_toml_path`,
+/// with the globs expanded, and without the excluded ones. The members out of the root
+/// directory, such as `../foo`, are skipped.
+pub(crate) fn workspace_members(cargo_toml_path: &Path, workspace: &Workspace) -> Vec<PathBuf> {
+    let Some(root_dir) = cargo_toml_path.parent() else {
+        return vec![];
+    };
+    let trim = |patterns: &[String]| -> Vec<String> {
+        patterns
+            .iter()
+            .map(|p| p.trim_start_matches("./").trim_end_matches('/').to_string())
+            .collect()
+    };
+    let members = trim(&workspace.members);
+    let exclude = trim(&workspace.exclude);
+    let patterns = match PatternSet::new(&to_strs(&members), &to_strs(&exclude)) {
+        Ok(patterns) if !members.is_empty() => patterns,
+        Ok(_) => return vec![],
+        Err(e) => {
+            tracing::warn!("Invalid workspace members in {:?}: {}", cargo_toml_path, e);
+            return vec![];
+        }
+    };
+    // a member is at most as deep as its pattern, unless the pattern has `**`
+    let max_depth = members
+        .iter()
+        .map(|m| {
+            if m.contains("**") {
+                usize::MAX
+            } else {
+                m.split('/').count()
+            }
+        })
+        .max()
+        .unwrap_or(0);
+    WalkDir::new(root_dir)
+        .min_depth(1)
+        .max_depth(max_depth)
+        .into_iter()
+        .filter_entry(|e| {
+            e.file_type().is_dir() && !e.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let relative = e
+                .path()
+                .strip_prefix(root_dir)
+                .unwrap_or(e.path())
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            patterns.matches(&relative)
+        })
+        .map(|e| e.path().join("Cargo.toml"))
+        .filter(|p| p.is_file())
+        .collect()
+}
+
+/// Search upwards from the crate directory, but not beyond the repo,
+/// for the first manifest containing a `[workspace]` table,
+/// unless the manifest points to its workspace root by `package.workspace`.
+fn find_workspace_root(
+    manifest: &Value,
+    cargo_toml_path: &Path,
+    repo_root: &Path,
+) -> Option<Value> {
+    let crate_dir = cargo_toml_path.parent()?;
+    if let Some(path) = manifest
+        .get("package")
+        .and_then(|p| p.get("workspace"))
+        .and_then(|w| w.as_str())
+    {
+        // not beyond the repo either
+        let dir = crate_dir.join(path).canonicalize().ok()?;
+        if !dir.starts_with(repo_root.canonicalize().ok()?) {
+            return None;
+        }
+        let content = fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+        return content
+            .parse::<Value>()
+            .ok()
+            .filter(|v| v.get("workspace").is_some());
+    }
+    for dir in crate_dir.ancestors() {
+        if !dir.starts_with(repo_root) {
+            break;
+        }
+        let content = match fs::read_to_string(dir.join("Cargo.toml")) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        if let Ok(value) = content.parse::<Value>() {
+            if value.get("workspace").is_some() {
+                return Some(value);
+            }
+        }
+    }
+    None
+}
+
+/// Same as `find_workspace_root`, but searches the git tree of a given version.
+///
+/// `crate_dir` is the path of the crate relative to the tree, such as `crates/foo/`.
+pub(crate) fn find_workspace_root_in_tree(
+    repo: &Repository,
+    tree: &Tree,
+    crate_dir: &str,
+) -> Option<Value> {
+    Path::new(crate_dir)
+        .ancestors()
+        .filter_map(|dir| read_toml_in_tree(repo, tree, &dir.join("Cargo.toml")))
+        .find(|value| value.get("workspace").is_some())
+}
+
+fn read_toml_in_tree(repo: &Repository, tree: &Tree, path: &Path) -> Option<Value> {
+    let obj = tree.get_path(path).ok()?.to_object(repo).ok()?;
+    let content = std::str::from_utf8(obj.as_blob()?.content()).ok()?;
+    content.parse().ok()
+}
+
+/// Read the manifest of the crate in `crate_dir` of the git tree,
+/// with the fields inherited from its workspace.
+pub(crate) fn read_manifest_in_tree(
+    repo: &Repository,
+    tree: &Tree,
+    crate_dir: &str,
+) -> Option<Manifest> {
+    let mut toml = read_toml_in_tree(repo, tree, &Path::new(crate_dir).join("Cargo.toml"))?;
+    if let Some(workspace_root) = find_workspace_root_in_tree(repo, tree, crate_dir) {
+        inherit_from_workspace(&mut toml, &workspace_root);
+    }
+    Manifest::from_value(&toml).ok()
+}
+
+/// Join the `path` of a path dependency to the directory of the dependent crate,
+/// both relative to the repo, such as `crates/foo/` and `../bar` into `crates/bar`.
+///
+/// Returns `None` if it's out of the repo.
+pub(crate) fn join_relative(crate_dir: &str, path: &str) -> Option<String> {
+    if Path::new(path).has_root() {
+        return None;
+    }
+    let mut parts: Vec<&str> = crate_dir.split('/').filter(|s| !s.is_empty()).collect();
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Replace the inherited fields of `manifest` with the values
+/// in `[workspace.package]` and `[workspace.dependencies]` of `workspace_root`.
+pub(crate) fn inherit_from_workspace(manifest: &mut Value, workspace_root: &Value) {
+    let workspace = match workspace_root.get("workspace") {
+        Some(workspace) => workspace,
+        None => return,
+    };
+
+    let ws_package = workspace.get("package").and_then(|p| p.as_table());
+    if let (Some(package), Some(ws_package)) = (
+        manifest.get_mut("package").and_then(|p| p.as_table_mut()),
+        ws_package,
+    ) {
+        for (key, val) in package.iter_mut() {
+            if is_inherited(val) {
+                if let Some(ws_val) = ws_package.get(key) {
+                    *val = ws_val.clone();
+                }
+            }
+        }
+    }
+
+    // the overrides of the workspace root apply to all the members
+    if let Some(manifest) = manifest.as_table_mut() {
+        for table_name in ["patch", "replace"] {
+            if let Some(table) = workspace_root.get(table_name) {
+                manifest.entry(table_name).or_insert_with(|| table.clone());
+            }
+        }
+    }
+
+    let ws_deps = match workspace.get("dependencies").and_then(|d| d.as_table()) {
+        Some(ws_deps) => ws_deps,
+        None => return,
+    };
+    inherit_dependencies(manifest, ws_deps);
+    // such as `[target.'cfg(unix)'.dependencies]`
+    if let Some(targets) = manifest.get_mut("target").and_then(|t| t.as_table_mut()) {
+        for (_, target) in targets.iter_mut() {
+            inherit_dependencies(target, ws_deps);
+        }
+    }
+}
+
+/// Resolve the inherited dependencies in the dependency tables of `tables`.
+fn inherit_dependencies(tables: &mut Value, ws_deps: &toml::Table) {
+    for table_name in ["dependencies", "dev-dependencies", "build-dependencies"] {
+        let deps = match tables.get_mut(table_name).and_then(|d| d.as_table_mut()) {
+            Some(deps) => deps,
+            None => continue,
+        };
+        for (name, dep) in deps.iter_mut() {
+            if is_inherited(dep) {
+                if let Some(ws_dep) = ws_deps.get(name) {
+                    *dep = inherit_dependency(dep, ws_dep);
+                }
+            }
+        }
+    }
+}
+
+/// `xxx.workspace = true`
+fn is_inherited(val: &Value) -> bool {
+    val.get("workspace").and_then(|w| w.as_bool()) == Some(true)
+}
+
+/// Merge a member dependency `{ workspace = true, features = [...], optional = true }`
+/// into the workspace one. Features are additive, as cargo does.
+fn inherit_dependency(dep: &Value, ws_dep: &Value) -> Value {
+    let mut merged = match ws_dep {
+        Value::String(version) => {
+            let mut table = toml::Table::new();
+            table.insert("version".to_string(), Value::String(version.clone()));
+            table
+        }
+        Value::Table(table) => table.clone(),
+        _ => return dep.clone(),
+    };
+
+    if let Some(dep) = dep.as_table() {
+        for (key, val) in dep {
+            match key.as_str() {
+                "workspace" => {}
+                "features" => {
+                    let mut features = merged
+                        .get("features")
+                        .and_then(|f| f.as_array())
+                        .cloned()
+                        .unwrap_or_default();
+                    for feature in val.as_array().into_iter().flatten() {
+                        if !features.contains(feature) {
+                            features.push(feature.clone());
+                        }
+                    }
+                    merged.insert(key.clone(), Value::Array(features));
+                }
+                _ => {
+                    merged.insert(key.clone(), val.clone());
+                }
+            }
+        }
+    }
+
+    Value::Table(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_manifest() {
+        let mut manifest: Value = r#"
+            [package]
+            name = "foo"
+            version.workspace = true
+            license.workspace = true
+            description = "a crate"
+            readme = false
+            keywords = ["async", "io"]
+            build = "build/main.rs"
+            links = "z"
+
+            [lib]
+            proc_macro = true
+
+            [dependencies]
+            serde = "1.0"
+            tokio = { workspace = true, features = ["rt"] }
+            bar = { package = "real-bar", version = "0.2", optional = true }
+        "#
+        .parse()
+        .unwrap();
+        let workspace_root: Value = r#"
+            [workspace.package]
+            version = "0.3.0"
+
+            [workspace.dependencies]
+            tokio = { version = "1", features = ["net"] }
+
+            [patch.crates-io]
+            serde = { path = "serde" }
+        "#
+        .parse()
+        .unwrap();
+        inherit_from_workspace(&mut manifest, &workspace_root);
+
+        let manifest = Manifest::from_value(&manifest).unwrap();
+        let package = manifest.package.unwrap();
+        assert_eq!(package.name, "foo");
+        assert_eq!(package.version.as_deref(), Some("0.3.0"));
+        assert_eq!(package.resolved_version(), Some("0.3.0"));
+        // not defined by the workspace
+        assert_eq!(package.license, None);
+        assert_eq!(package.unresolved, ["license"]);
+        assert_eq!(package.readme, Some(Readme::Enabled(false)));
+        assert_eq!(
+            package.build,
+            Some(BuildScript::Path("build/main.rs".into()))
+        );
+        assert_eq!(package.links.as_deref(), Some("z"));
+        assert_eq!(package.keywords, Some(vec!["async".into(), "io".into()]));
+        assert_eq!(manifest.lib.unwrap().proc_macro, Some(true));
+        assert!(matches!(
+            &manifest.patch["crates-io"]["serde"],
+            DependencySpec::Detailed(d) if d.source() == DependencySource::Path("serde".into())
+        ));
+        assert_eq!(
+            manifest.dependencies["serde"],
+            DependencySpec::Version("1.0".to_string())
+        );
+        assert_eq!(
+            manifest.dependencies["tokio"],
+            DependencySpec::Detailed(DependencyDetail {
+                version: Some("1".to_string()),
+                features: vec!["net".to_string(), "rt".to_string()],
+                ..Default::default()
+            })
+        );
+        assert!(matches!(
+            &manifest.dependencies["bar"],
+            DependencySpec::Detailed(d) if d.package.as_deref() == Some("real-bar") && d.optional
+        ));
+
+        let unknown: Value = "[package]\nname = \"foo\"\nversion.workspace = true"
+            .parse()
+            .unwrap();
+        let unknown = Manifest::from_value(&unknown).unwrap().package.unwrap();
+        assert_eq!(unknown.resolved_version(), None);
+        let omitted: Manifest = toml::from_str("[package]\nname = \"foo\"").unwrap();
+        assert_eq!(omitted.package.unwrap().resolved_version(), Some("0.0.0"));
+
+        let virtual_manifest: Manifest = toml::from_str("[workspace]\nmembers = []").unwrap();
+        assert!(virtual_manifest.package.is_none());
+
+        let e =
+            toml::from_str::<Manifest>("[package]\nname = \"foo\"\nedition = 2021").unwrap_err();
+        assert!(e.to_string().contains("invalid type"), "{}", e);
+    }
+
+    #[test]
+    fn test_default_features() {
+        let manifest: Manifest = toml::from_str(
+            "[dependencies]\nserde = { version = \"1\", default-features = false }\n\
+             tokio = { version = \"1\", default_features = false }\nlog = { version = \"0.4\" }",
+        )
+        .unwrap();
+        let default_features = |name: &str| match &manifest.dependencies[name] {
+            DependencySpec::Detailed(detail) => detail.default_features,
+            DependencySpec::Version(_) => None,
+        };
+        assert_eq!(default_features("serde"), Some(false));
+        assert_eq!(default_features("tokio"), Some(false));
+        assert_eq!(default_features("log"), None);
+    }
+
+    #[test]
+    fn test_git_and_path_dependencies() {
+        let git: DependencyDetail =
+            toml::from_str("git = \"https://github.com/foo/bar\"\ntag = \"v1.2.0\"").unwrap();
+        assert_eq!(
+            git.source(),
+            DependencySource::Git("https://github.com/foo/bar".to_string())
+        );
+        assert_eq!(git.tag_version().as_deref(), Some("1.2.0"));
+        assert_eq!(
+            join_relative("crates/foo/", "../bar"),
+            Some("crates/bar".into())
+        );
+        assert_eq!(join_relative("", "./bar"), Some("bar".into()));
+        assert_eq!(join_relative("foo", "../../bar"), None);
+    }
+
+    #[test]
+    fn test_inherit_from_workspace() {
+        let workspace_root: Value = r#"
+            [workspace.package]
+            version = "1.2.0"
+            edition = "2021"
+
+            [workspace.dependencies]
+            serde = "1.0"
+            tokio = { version = "1", features = ["net"] }
+
+            [replace]
+            "foo:0.1.0" = { path = "foo" }
+        "#
+        .parse()
+        .unwrap();
+        let mut manifest: Value = r#"
+            [package]
+            name = "bar"
+            version.workspace = true
+            edition = "2018"
+            license.workspace = true
+
+            [dependencies]
+            serde = { workspace = true, optional = true }
+            tokio = { workspace = true, features = ["rt", "net"] }
+            log.workspace = true
+
+            [target.'cfg(unix)'.dev-dependencies]
+            serde.workspace = true
+        "#
+        .parse()
+        .unwrap();
+        inherit_from_workspace(&mut manifest, &workspace_root);
+
+        let package = &manifest["package"];
+        assert_eq!(package["version"].as_str(), Some("1.2.0"));
+        // the member's own value is kept, and the one not in the workspace stays inherited
+        assert_eq!(package["edition"].as_str(), Some("2018"));
+        assert!(is_inherited(&package["license"]));
+
+        let deps = &manifest["dependencies"];
+        assert_eq!(deps["serde"]["version"].as_str(), Some("1.0"));
+        assert_eq!(deps["serde"]["optional"].as_bool(), Some(true));
+        assert!(deps["serde"].get("workspace").is_none());
+        let features: Vec<&str> = deps["tokio"]["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|f| f.as_str())
+            .collect();
+        assert_eq!(features, ["net", "rt"]);
+        assert!(is_inherited(&deps["log"]));
+        let unix = &manifest["target"]["cfg(unix)"]["dev-dependencies"];
+        assert_eq!(unix["serde"]["version"].as_str(), Some("1.0"));
+        assert!(manifest["replace"].get("foo:0.1.0").is_some());
+
+        // a root without `[workspace]` changes nothing
+        let before = manifest.clone();
+        inherit_from_workspace(&mut manifest, &"[package]\nname = \"x\"".parse().unwrap());
+        assert_eq!(manifest, before);
+    }
+
+    #[test]
+    fn test_unresolved_fields() {
+        let toml = "[package]\nname = \"foo\"\nversion.workspace = true\nlicense = \"MIT\"\n\
+                    edition = { workspace = true }";
+        // the same however the manifest is deserialized
+        let parsed: Manifest = toml::from_str(toml).unwrap();
+        let value: Value = toml.parse().unwrap();
+        for manifest in [parsed, Manifest::from_value(&value).unwrap()] {
+            let package = manifest.package.unwrap();
+            assert_eq!(package.unresolved, ["edition", "version"]);
+            assert_eq!(package.resolved_version(), None);
+            assert_eq!(package.license.as_deref(), Some("MIT"));
+        }
+    }
+
+    #[test]
+    fn test_workspace_members() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let write = |path: &str, content: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\", \"tools/gen/\"]\nexclude = [\"crates/old\"]",
+        );
+        write("crates/a/Cargo.toml", "[package]\nname = \"a\"");
+        write("crates/old/Cargo.toml", "[package]\nname = \"old\"");
+        write("crates/notes/README.md", "");
+        write("tools/gen/Cargo.toml", "[package]\nname = \"gen\"");
+
+        let manifest: Manifest =
+            toml::from_str(&fs::read_to_string(root.join("Cargo.toml")).unwrap()).unwrap();
+        assert!(manifest.is_virtual());
+        let mut members = workspace_members(&root.join("Cargo.toml"), &manifest.workspace.unwrap());
+        members.sort();
+        assert_eq!(
+            members,
+            [
+                root.join("crates/a/Cargo.toml"),
+                root.join("tools/gen/Cargo.toml")
+            ]
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! The prometheus metrics of the import service and the workers, served on
+//! `http://<METRICS_ADDR>/metrics` if `METRICS_ADDR` is set, such as `0.0.0.0:9898`.
+//!
+//! - `crates_pro_repos_processed_total{outcome}`, where the outcome is `imported`,
+//!   `unchanged`, `filtered` or `failed`,
+//! - `crates_pro_parse_failures_total`, the manifests failed to parse,
+//! - `crates_pro_sink_duration_seconds{sink}`, the time to write the graph into an output,
+//! - `crates_pro_queue_depth{topic}`, the messages not consumed yet by the group.
+
+use crate::kafka_handler::KafkaHandler;
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge_vec,
+    Encoder, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, TextEncoder,
+};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// the interval to query the watermarks of the topics
+const QUEUE_DEPTH_INTERVAL: Duration = Duration::from_secs(15);
+
+lazy_static! {
+    pub(crate) static ref REPOS_PROCESSED: IntCounterVec = register_int_counter_vec!(
+        "crates_pro_repos_processed_total",
+        "The repos processed by the import",
+        &["outcome"]
+    )
+    .unwrap();
+    pub(crate) static ref PARSE_FAILURES: IntCounter = register_int_counter!(
+        "crates_pro_parse_failures_total",
+        "The manifests failed to parse"
+    )
+    .unwrap();
+    pub(crate) static ref SINK_DURATION: HistogramVec = register_histogram_vec!(
+        "crates_pro_sink_duration_seconds",
+        "The time to write the graph into an output",
+        &["sink"],
+        vec![0.1, 0.5, 1.0, 5.0, 15.0, 60.0, 300.0, 1200.0, 3600.0]
+    )
+    .unwrap();
+    pub(crate) static ref QUEUE_DEPTH: IntGaugeVec = register_int_gauge_vec!(
+        "crates_pro_queue_depth",
+        "The messages not consumed yet in a kafka topic",
+        &["topic"]
+    )
+    .unwrap();
+    /// the last time the depth of the topics of a consumer was updated
+    static ref QUEUE_DEPTH_UPDATED: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
+}
+
+/// Update the depth of the topics consumed by a handler, at most every 15 seconds.
+pub(crate) async fn update_queue_depth(handler: &KafkaHandler) {
+    let topics = handler.subscribed_topics().join(",");
+    {
+        let mut updated = QUEUE_DEPTH_UPDATED.lock().unwrap();
+        if updated
+            .get(&topics)
+            .is_some_and(|t| t.elapsed() < QUEUE_DEPTH_INTERVAL)
+        {
+            return;
+        }
+        updated.insert(topics, Instant::now());
+    }
+    match handler.lag().await {
+        Ok(lag) => {
+            for (topic, depth) in lag {
+                QUEUE_DEPTH.with_label_values(&[&topic]).set(depth);
+            }
+        }
+        Err(e) => tracing::warn!("Failed to get the lag of the consumer: {}", e),
+    }
+}
+
+fn render() -> Vec<u8> {
+    let mut buffer = vec![];
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        tracing::error!("Failed to encode the metrics: {}", e);
+    }
+    buffer
+}
+
+async fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut request = [0; 1024];
+    let n = stream.read(&mut request).await?;
+    let request = String::from_utf8_lossy(&request[..n]);
+    let response = if request.starts_with("GET /metrics ") {
+        let body = render();
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        response.extend(body);
+        response
+    } else {
+        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+    };
+    stream.write_all(&response).await?;
+    stream.shutdown().await
+}
+
+/// Serve the metrics on `METRICS_ADDR` in the background, if it's set.
+pub fn spawn_metrics_server() {
+    let Ok(addr) = env::var("METRICS_ADDR") else {
+        return;
+    };
+    tokio::spawn(async move {
+        let listener = match TcpListener::bind(&addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::error!("Failed to serve the metrics on {}: {}", addr, e);
+                return;
+            }
+        };
+        tracing::info!("Serve the metrics on http://{}/metrics", addr);
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(async move {
+                        if let Err(e) = handle(stream).await {
+                            tracing::debug!("Failed to serve the metrics: {}", e);
+                        }
+                    });
+                }
+                Err(e) => tracing::warn!("Failed to accept a connection: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        REPOS_PROCESSED.with_label_values(&["imported"]).inc();
+        SINK_DURATION.with_label_values(&["tugraph"]).observe(0.3);
+        let text = String::from_utf8(render()).unwrap();
+        assert!(text.contains("crates_pro_repos_processed_total{outcome=\"imported\"}"));
+        assert!(
+            text.contains("crates_pro_sink_duration_seconds_bucket{sink=\"tugraph\",le=\"0.5\"}")
+        );
+    }
+}
@@ -0,0 +1,331 @@
+//! Detect the minimum supported Rust version (MSRV) of a crate version, from
+//! 1. the `rust-version` field of its `Cargo.toml`,
+//! 2. the `msrv` of its `clippy.toml`,
+//! 3. the oldest toolchain pinned in the CI configs of the repo,
+//! 4. or bisecting the toolchains which can build it in a [`crate::sandbox`], if
+//!    `MSRV_BISECT_TOOLCHAINS` is set, such as `1.56.0,1.60.0,1.65.0,1.70.0`.
+
+use crate::manifest::Manifest;
+use crate::sandbox::{fetch_command, Sandbox};
+use crate::ImportContext;
+use git2::build::CheckoutBuilder;
+use git2::{Repository, Tree};
+use semver::Version;
+use std::env;
+use std::path::Path;
+use std::time::Duration;
+use toml::Value;
+
+/// The time to check a crate with a toolchain.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// The CI configs in the repo root, besides `.github/workflows/*`
+pub(crate) const CI_CONFIGS: &[&str] = &[
+    ".travis.yml",
+    ".gitlab-ci.yml",
+    "appveyor.yml",
+    ".appveyor.yml",
+    "azure-pipelines.yml",
+    ".circleci/config.yml",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Msrv {
+    pub(crate) version: String,
+    /// `rust-version`, `clippy`, `ci` or `bisect`
+    pub(crate) source: &'static str,
+}
+
+impl Msrv {
+    fn new(version: &str, source: &'static str) -> Self {
+        Msrv {
+            version: version.to_string(),
+            source,
+        }
+    }
+
+    /// The MSRV declared by `rust-version`, if it's valid.
+    pub(crate) fn declared(version: &str) -> Option<Self> {
+        parse_rust_version(version).map(|_| Msrv::new(version, "rust-version"))
+    }
+
+    /// The `msrv` and `msrv_source` of a version node.
+    pub(crate) fn fields(msrv: Option<&Msrv>) -> (Option<String>, Option<String>) {
+        match msrv {
+            Some(msrv) => (Some(msrv.version.clone()), Some(msrv.source.to_string())),
+            None => (None, None),
+        }
+    }
+}
+
+/// Parse a rust version, such as `1.56` or `1.56.1`.
+pub(crate) fn parse_rust_version(version: &str) -> Option<Version> {
+    let mut parts = version.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    let patch = match parts.next() {
+        Some(patch) => patch.parse().ok()?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Version::new(major, minor, patch))
+}
+
+/// The `rust-version` declared in a manifest, whose workspace fields are inherited.
+pub(crate) fn msrv_from_manifest(manifest: &Manifest) -> Option<Msrv> {
+    manifest
+        .package
+        .as_ref()
+        .and_then(|p| p.rust_version.as_deref())
+        .and_then(Msrv::declared)
+}
+
+/// Find the rust versions in a line, such as `1.56` in `toolchain: [1.56, stable]`.
+///
+/// The numbers following a letter are skipped, e.g. `actions/cache@v1.0`.
+pub(crate) fn rust_versions_in(line: &str) -> Vec<Version> {
+    let mut res = vec![];
+    let mut token = String::new();
+    let mut standalone = false;
+    let mut prev: Option<char> = None;
+    for c in line.chars().chain(std::iter::once(' ')) {
+        if c.is_ascii_digit() || c == '.' {
+            if token.is_empty() {
+                standalone = !prev.is_some_and(|p| p.is_alphanumeric() || p == '_' || p == '-');
+            }
+            token.push(c);
+        } else {
+            if standalone && token.starts_with("1.") {
+                res.extend(parse_rust_version(token.trim_end_matches('.')));
+            }
+            token.clear();
+        }
+        prev = Some(c);
+    }
+    res
+}
+
+/// The lines of a CI config mentioning rust, toolchain or msrv, or in the lists under such keys.
+pub(crate) fn toolchain_lines(config: &str) -> Vec<&str> {
+    let mut lines = vec![];
+    let mut in_list = false;
+    for line in config.lines() {
+        let line = line.trim();
+        let lower = line.to_lowercase();
+        if ["rust", "toolchain", "msrv"]
+            .iter()
+            .any(|k| lower.contains(k))
+        {
+            lines.push(line);
+            in_list = line.ends_with(':');
+        } else if in_list && line.starts_with('-') {
+            lines.push(line);
+        } else {
+            in_list = false;
+        }
+    }
+    lines
+}
+
+/// The oldest rust version pinned in a CI config.
+pub(crate) fn msrv_from_ci(config: &str) -> Option<Version> {
+    toolchain_lines(config)
+        .into_iter()
+        .flat_map(rust_versions_in)
+        .min()
+}
+
+fn read_blob(repo: &Repository, tree: &Tree, path: &str) -> Option<String> {
+    let entry = tree.get_path(Path::new(path)).ok()?;
+    let object = entry.to_object(repo).ok()?;
+    let blob = object.as_blob()?;
+    Some(String::from_utf8_lossy(blob.content()).into_owned())
+}
+
+fn ci_configs(repo: &Repository, tree: &Tree) -> Vec<String> {
+    let mut configs: Vec<String> = CI_CONFIGS
+        .iter()
+        .filter_map(|path| read_blob(repo, tree, path))
+        .collect();
+    let workflows = tree
+        .get_path(Path::new(".github/workflows"))
+        .ok()
+        .and_then(|entry| entry.to_object(repo).ok())
+        .and_then(|object| object.into_tree().ok());
+    if let Some(workflows) = workflows {
+        for entry in workflows.iter() {
+            let name = entry.name().unwrap_or_default();
+            if !(name.ends_with(".yml") || name.ends_with(".yaml")) {
+                continue;
+            }
+            if let Some(blob) = entry.to_object(repo).ok().and_then(|o| o.into_blob().ok()) {
+                configs.push(String::from_utf8_lossy(blob.content()).into_owned());
+            }
+        }
+    }
+    configs
+}
+
+/// Detect the MSRV of the crate at `root` of a git tree, such as `crates/foo/`,
+/// by the configs of clippy and CI, if it's not declared by `rust-version`.
+pub(crate) fn detect_msrv_in_tree(repo: &Repository, tree: &Tree, root: &str) -> Option<Msrv> {
+    // the nearest clippy.toml
+    for dir in Path::new(root).ancestors() {
+        for file in ["clippy.toml", ".clippy.toml"] {
+            let path = dir.join(file);
+            let msrv = read_blob(repo, tree, &path.to_string_lossy())
+                .and_then(|content| content.parse::<Value>().ok())
+                .and_then(|clippy| {
+                    clippy
+                        .get("msrv")
+                        .and_then(|v| v.as_str())
+                        .map(String::from)
+                })
+                .filter(|v| parse_rust_version(v).is_some());
+            if let Some(msrv) = msrv {
+                return Some(Msrv::new(&msrv, "clippy"));
+            }
+        }
+    }
+
+    ci_configs(repo, tree)
+        .iter()
+        .filter_map(|config| msrv_from_ci(config))
+        .min()
+        .map(|v| Msrv::new(&v.to_string(), "ci"))
+}
+
+/// The toolchains to bisect, empty if `MSRV_BISECT_TOOLCHAINS` is not set.
+pub(crate) fn bisect_toolchains() -> Vec<String> {
+    let mut toolchains: Vec<String> = env::var("MSRV_BISECT_TOOLCHAINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| parse_rust_version(t).is_some())
+        .collect();
+    toolchains.sort_by_key(|t| parse_rust_version(t));
+    toolchains
+}
+
+/// Fetch the dependencies by `toolchain`, and `cargo check` the crate in the sandbox.
+fn builds_with(sandbox: &Sandbox, dir: &Path, toolchain: &str, manifest_path: &Path) -> bool {
+    let fetched = fetch_command(dir, Some(toolchain), manifest_path).status();
+    if !matches!(fetched, Ok(status) if status.success()) {
+        return false;
+    }
+    let status = sandbox
+        .command(dir, CHECK_TIMEOUT)
+        .arg("cargo")
+        .arg(format!("+{}", toolchain))
+        .args(["check", "--quiet", "--offline", "--target-dir"])
+        .arg(dir.join("target").join(toolchain))
+        .arg("--manifest-path")
+        .arg(manifest_path)
+        .status();
+    matches!(status, Ok(status) if status.success())
+}
+
+/// Check out the tree into a temporary directory, and bisect the oldest of the
+/// sorted `toolchains` which can `cargo check` the crate at `root` in the sandbox.
+///
+/// It assumes that a crate building with a toolchain builds with the newer ones,
+/// and the toolchains are installed by rustup.
+pub(crate) fn bisect_msrv(
+    sandbox: &Sandbox,
+    repo: &Repository,
+    tree: &Tree,
+    root: &str,
+    toolchains: &[String],
+) -> Option<Msrv> {
+    let newest = toolchains.len().checked_sub(1)?;
+    let dir = match tempfile::tempdir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            tracing::error!("Failed to create a temporary directory: {}", e);
+            return None;
+        }
+    };
+    let mut checkout = CheckoutBuilder::new();
+    checkout
+        .target_dir(&dir.path().join("src"))
+        .update_index(false)
+        .force();
+    if let Err(e) = repo.checkout_tree(tree.as_object(), Some(&mut checkout)) {
+        tracing::error!("Failed to check out the tree: {}", e);
+        return None;
+    }
+    let manifest_path = dir.path().join("src").join(root).join("Cargo.toml");
+    let builds_with = |toolchain: &str| builds_with(sandbox, dir.path(), toolchain, &manifest_path);
+
+    // the oldest buildable one is in toolchains[lo..=hi]
+    let (mut lo, mut hi) = (0, newest);
+    if !builds_with(&toolchains[hi]) {
+        return None;
+    }
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if builds_with(&toolchains[mid]) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    Some(Msrv::new(&toolchains[lo], "bisect"))
+}
+
+impl ImportContext {
+    /// The versions of a crate which can be used with a given rustc, such as `1.70`.
+    ///
+    /// The versions whose MSRV is unknown are compatible, as cargo treats them.
+    pub fn versions_compatible_with(&self, crate_name: &str, rustc: &str) -> Vec<String> {
+        let rustc = match parse_rust_version(rustc) {
+            Some(rustc) => rustc,
+            None => return vec![],
+        };
+        let compatible = |msrv: &Option<String>| {
+            msrv.as_deref()
+                .and_then(parse_rust_version)
+                .is_none_or(|msrv| msrv <= rustc)
+        };
+        let lib_versions = self
+            .library_versions
+            .iter()
+            .filter(|v| v.name == crate_name && compatible(&v.msrv))
+            .map(|v| v.version.clone());
+        let app_versions = self
+            .application_versions
+            .iter()
+            .filter(|v| v.name == crate_name && compatible(&v.msrv))
+            .map(|v| v.version.clone());
+        lib_versions.chain(app_versions).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msrv_from_ci() {
+        let workflow = r#"
+jobs:
+  test:
+    runs-on: ubuntu-20.04
+    strategy:
+      matrix:
+        rust:
+          - 1.63.0
+          - stable
+    steps:
+      - uses: actions/checkout@v4.1
+      - uses: dtolnay/rust-toolchain@1.70
+      - uses: Swatinem/rust-cache@v2.7.0
+"#;
+        assert_eq!(msrv_from_ci(workflow), Some(Version::new(1, 63, 0)));
+        assert_eq!(msrv_from_ci("runs-on: ubuntu-latest"), None);
+        assert_eq!(parse_rust_version("1.56"), Some(Version::new(1, 56, 0)));
+        assert_eq!(parse_rust_version("stable"), None);
+    }
+}
@@ -0,0 +1,174 @@
+//! Export the graph as the node and relationship csv files of `neo4j-admin import`,
+//! so Neo4j can be used as an alternative graph backend.
+//!
+//! Along with the csv files, an argument file `import.args` is written, and the files
+//! can be imported by `neo4j-admin database import full @import.args <database>`.
+
+use crate::output::{columns_of, fields_of, GraphExporter, Record};
+use arrow::datatypes::DataType;
+use csv::WriterBuilder;
+use serde_json::Value;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+const IMPORT_ARGS_FILE: &str = "import.args";
+
+/// `library_version` -> `LibraryVersion`
+fn to_node_label(label: &str) -> String {
+    label
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// `depends_on` -> `DEPENDS_ON`
+fn to_relationship_type(label: &str) -> String {
+    label.to_uppercase()
+}
+
+/// The header of a property, with the type suffix of neo4j-admin, e.g. `downloads:long`
+fn property_headers<T: Record>() -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let fields = fields_of::<T>()?;
+    let mut res = vec![];
+    for column in columns_of::<T>()? {
+        let suffix = match fields
+            .iter()
+            .find(|f| *f.name() == column)
+            .map(|f| f.data_type())
+        {
+            Some(DataType::Boolean) => ":boolean",
+            Some(DataType::Int8 | DataType::Int16 | DataType::Int32) => ":int",
+            Some(DataType::Int64 | DataType::UInt32 | DataType::UInt64) => ":long",
+            Some(DataType::Float32 | DataType::Float64) => ":double",
+            _ => "",
+        };
+        let header = format!("{}{}", column, suffix);
+        res.push((column, header));
+    }
+    Ok(res)
+}
+
+fn row_of<T: Record>(item: &T) -> Result<serde_json::Map<String, Value>, Box<dyn Error>> {
+    match serde_json::to_value(item)? {
+        Value::Object(map) => Ok(map),
+        _ => Err("the model is not a struct".into()),
+    }
+}
+
+/// `null` is written as an empty field, which neo4j-admin imports as no property.
+fn to_field(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+pub(crate) struct Neo4jExporter {
+    dir: PathBuf,
+    args: Vec<String>,
+}
+
+impl Neo4jExporter {
+    pub(crate) fn new(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: fs::canonicalize(dir)?,
+            args: vec![],
+        })
+    }
+
+    fn write_csv<T: Record>(
+        &self,
+        file_name: &str,
+        header: Vec<String>,
+        columns: &[String],
+        items: &[T],
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        let path = self.dir.join(format!("{}.csv", file_name));
+        let mut writer = WriterBuilder::new()
+            .quote_style(csv::QuoteStyle::Necessary)
+            .from_writer(BufWriter::new(File::create(&path)?));
+        writer.write_record(&header)?;
+        for item in items {
+            let row = row_of(item)?;
+            writer.write_record(columns.iter().map(|c| to_field(row.get(c))))?;
+        }
+        writer.flush()?;
+        Ok(path)
+    }
+}
+
+impl GraphExporter for Neo4jExporter {
+    fn vertex<T: Record>(
+        &mut self,
+        label: &str,
+        primary: &str,
+        file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>> {
+        let node_label = to_node_label(label);
+        let (columns, header): (Vec<String>, Vec<String>) = property_headers::<T>()?
+            .into_iter()
+            .map(|(column, header)| {
+                let header = if column == primary {
+                    format!("{}:ID({})", column, node_label)
+                } else {
+                    header
+                };
+                (column, header)
+            })
+            .unzip();
+
+        let path = self.write_csv(file_name, header, &columns, items)?;
+        self.args
+            .push(format!("--nodes={}={}", node_label, path.display()));
+        Ok(())
+    }
+
+    fn edge<T: Record>(
+        &mut self,
+        label: &str,
+        src_label: &str,
+        dst_label: &str,
+        file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut columns = vec!["SRC_ID".to_string(), "DST_ID".to_string()];
+        let mut header = vec![
+            format!(":START_ID({})", to_node_label(src_label)),
+            format!(":END_ID({})", to_node_label(dst_label)),
+        ];
+        for (column, column_header) in property_headers::<T>()? {
+            if column != "SRC_ID" && column != "DST_ID" {
+                columns.push(column);
+                header.push(column_header);
+            }
+        }
+
+        let path = self.write_csv(file_name, header, &columns, items)?;
+        self.args.push(format!(
+            "--relationships={}={}",
+            to_relationship_type(label),
+            path.display()
+        ));
+        Ok(())
+    }
+
+    /// Write the `import.args`. The vertices are collected from many repos,
+    /// so the duplicated nodes and the dangling relationships are skipped.
+    fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.args.push("--skip-duplicate-nodes=true".to_string());
+        self.args.push("--skip-bad-relationships=true".to_string());
+        fs::write(self.dir.join(IMPORT_ARGS_FILE), self.args.join("\n") + "\n")?;
+        Ok(())
+    }
+}
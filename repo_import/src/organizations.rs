@@ -0,0 +1,75 @@
+//! The owners of the repos as organization vertices, such as `tokio-rs` of `tokio-rs/tokio`,
+//! linked to their repos and programs, for the statistics of an organization.
+//!
+//! The owner is the namespace without the repo name, so a GitLab subgroup such as
+//! `group/subgroup` of `group/subgroup/project` is an organization of its own.
+
+use crate::ImportContext;
+use model::tugraph_model::{HasProgram, HasRepository, Organization, Repo};
+use std::collections::BTreeMap;
+
+/// The owner of a namespace, such as `tokio-rs` of `tokio-rs/tokio`.
+fn owner_of(namespace: &str) -> Option<&str> {
+    let (owner, _) = namespace.rsplit_once('/')?;
+    (!owner.is_empty()).then_some(owner)
+}
+
+impl ImportContext {
+    /// Rebuild the organizations and the repos by the namespaces of the programs.
+    pub(crate) fn import_organizations(&mut self) {
+        let mut repositories: BTreeMap<&str, Repo> = BTreeMap::new();
+        let mut organizations: BTreeMap<&str, Organization> = BTreeMap::new();
+        let mut has_program = vec![];
+        for program in &self.programs {
+            let Some(namespace) = program.namespace.as_deref() else {
+                continue;
+            };
+            let Some(owner) = owner_of(namespace) else {
+                continue;
+            };
+            let organization = organizations.entry(owner).or_insert_with(|| Organization {
+                id: owner.to_string(),
+                ..Default::default()
+            });
+            organization.programs += 1;
+            if !repositories.contains_key(namespace) {
+                organization.repositories += 1;
+                repositories.insert(
+                    namespace,
+                    Repo {
+                        id: namespace.to_string(),
+                        organization: owner.to_string(),
+                        url: program.mega_url.clone(),
+                    },
+                );
+            }
+            has_program.push(HasProgram {
+                SRC_ID: owner.to_string(),
+                DST_ID: program.id.clone(),
+            });
+        }
+
+        self.has_repository = repositories
+            .values()
+            .map(|r| HasRepository {
+                SRC_ID: r.organization.clone(),
+                DST_ID: r.id.clone(),
+            })
+            .collect();
+        self.repositories = repositories.into_values().collect();
+        self.organizations = organizations.into_values().collect();
+        self.has_program = has_program;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_of() {
+        assert_eq!(owner_of("tokio-rs/tokio"), Some("tokio-rs"));
+        assert_eq!(owner_of("group/subgroup/project"), Some("group/subgroup"));
+        assert_eq!(owner_of("tokio"), None);
+    }
+}
@@ -0,0 +1,129 @@
+//! The output formats of the import files.
+//!
+//! The formats are selected by `IMPORT_OUTPUT_FORMATS`, a comma-separated list
+//! such as `csv,jsonl,parquet,arrow`. CSV is always what TuGraph imports, the others are
+//! written alongside for other consumers.
+
+use crate::utils::{write_into_arrow_ipc, write_into_csv, write_into_jsonl, write_into_parquet};
+use arrow::datatypes::FieldRef;
+use serde::{Deserialize, Serialize};
+use serde_arrow::schema::{SchemaLike, TracingOptions};
+use serde_json::Value;
+use std::env;
+use std::error::Error;
+use std::fmt::Debug;
+use std::path::Path;
+
+/// A vertex or an edge of the graph
+pub(crate) trait Record: Serialize + for<'de> Deserialize<'de> + Default + Debug {}
+
+impl<T: Serialize + for<'de> Deserialize<'de> + Default + Debug> Record for T {}
+
+/// A graph database which the vertices and edges are exported to,
+/// such as TuGraph or Neo4j.
+pub(crate) trait GraphExporter {
+    /// Export the vertices of `label`, identified by the property `primary`.
+    fn vertex<T: Record>(
+        &mut self,
+        label: &str,
+        primary: &str,
+        file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Export the edges of `label` from `src_label` to `dst_label`.
+    /// An edge label can connect several pairs of vertex labels.
+    fn edge<T: Record>(
+        &mut self,
+        label: &str,
+        src_label: &str,
+        dst_label: &str,
+        file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Write what describes the exported files, such as the import config.
+    fn finish(self) -> Result<(), Box<dyn Error>>;
+}
+
+/// The fields of `T`, traced from its type
+pub(crate) fn fields_of<T: Record>() -> Result<Vec<FieldRef>, Box<dyn Error>> {
+    Ok(Vec::<FieldRef>::from_type::<T>(
+        TracingOptions::default().allow_null_fields(true),
+    )?)
+}
+
+/// The columns of the csv file, in the order written by `write_into_csv`
+pub(crate) fn columns_of<T: Record>() -> Result<Vec<String>, Box<dyn Error>> {
+    match serde_json::to_value(T::default())? {
+        Value::Object(map) => Ok(map.keys().cloned().collect()),
+        _ => Err("the model is not a struct".into()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Jsonl,
+    Parquet,
+    /// Arrow IPC streaming format
+    ArrowIpc,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "csv" => Some(OutputFormat::Csv),
+            "jsonl" | "ndjson" => Some(OutputFormat::Jsonl),
+            "parquet" => Some(OutputFormat::Parquet),
+            "arrow" | "ipc" | "arrows" => Some(OutputFormat::ArrowIpc),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::ArrowIpc => "arrows",
+        }
+    }
+}
+
+/// Read `IMPORT_OUTPUT_FORMATS`, csv only by default.
+pub(crate) fn output_formats() -> Vec<OutputFormat> {
+    let names = env::var("IMPORT_OUTPUT_FORMATS").unwrap_or_else(|_| "csv".to_string());
+    let mut formats = vec![];
+    for name in names.split(',').filter(|n| !n.trim().is_empty()) {
+        match OutputFormat::from_name(name) {
+            Some(format) if !formats.contains(&format) => formats.push(format),
+            Some(_) => {}
+            None => tracing::warn!("Unknown output format: {}", name),
+        }
+    }
+    if formats.is_empty() {
+        formats.push(OutputFormat::Csv);
+    }
+    formats
+}
+
+/// Write the records of a vertex or an edge, e.g. `program`,
+/// into `<dir>/<name>.<ext>` for each format.
+pub(crate) fn write_table<T: Record>(
+    dir: &Path,
+    name: &str,
+    items: &[T],
+    formats: &[OutputFormat],
+) -> Result<(), Box<dyn Error>> {
+    for format in formats {
+        let path = dir.join(format!("{}.{}", name, format.extension()));
+        match format {
+            OutputFormat::Csv => write_into_csv(path, items)?,
+            OutputFormat::Jsonl => write_into_jsonl(path, items)?,
+            OutputFormat::Parquet => write_into_parquet(path, items)?,
+            OutputFormat::ArrowIpc => write_into_arrow_ipc(path, items)?,
+        }
+    }
+    Ok(())
+}
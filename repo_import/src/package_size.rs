@@ -0,0 +1,257 @@
+//! The sizes of the `.crate` of each version, for the bloat analysis: the tarball size and
+//! the unpacked size, i.e. the total size of the packaged files.
+//!
+//! The versions from the crates.io dump have the tarball size, and with
+//! `IMPORT_ENRICH_CRATE_SIZES=1` the tarballs of the registry versions are downloaded to
+//! sum up the unpacked sizes.
+//!
+//! The tagged versions in git repos are measured if `IMPORT_PACKAGE_SIZE=1`, by the files
+//! `cargo package --list` would list: the ones in the directory of the crate without the
+//! nested packages and the hidden and `target` directories, filtered by the `include` and
+//! `exclude` of `[package]`. Neither the `.gitignore` nor the files generated by cargo,
+//! such as `Cargo.toml.orig`, are taken into account.
+
+use crate::crate_info::is_excluded_dir_name;
+use crate::cratesio_api::CratesIoClient;
+use crate::discovery_filter::glob_to_regex;
+use crate::manifest::{inherit_from_workspace, Manifest, Package};
+use crate::utils::name_join_version;
+use crate::ImportContext;
+use flate2::read::GzDecoder;
+use git2::{ObjectType, Repository, Tree, TreeWalkMode, TreeWalkResult};
+use regex::Regex;
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::path::Path;
+use toml::Value;
+
+/// Whether to measure the packaged size of the crates in the git repos.
+pub(crate) fn package_size_enabled() -> bool {
+    env::var("IMPORT_PACKAGE_SIZE").is_ok_and(|v| v == "1")
+}
+
+/// A pattern of `include` or `exclude` in the style of gitignore.
+#[derive(Debug)]
+struct PackagePattern {
+    regex: Regex,
+    /// a pattern with a `/` is matched with the path from the crate directory,
+    /// otherwise with the name of the file or of any directory in the path
+    anchored: bool,
+}
+
+impl PackagePattern {
+    fn new(pattern: &str) -> Option<Self> {
+        let pattern = pattern.trim_end_matches('/');
+        let anchored = pattern.contains('/');
+        match Regex::new(&glob_to_regex(pattern.trim_start_matches('/'))) {
+            Ok(regex) => Some(PackagePattern { regex, anchored }),
+            Err(e) => {
+                tracing::debug!("Skip the invalid package pattern {}: {}", pattern, e);
+                None
+            }
+        }
+    }
+
+    /// Whether it matches the file or a directory containing it.
+    fn matches(&self, path: &str) -> bool {
+        if self.anchored {
+            path.match_indices('/')
+                .map(|(i, _)| &path[..i])
+                .chain(std::iter::once(path))
+                .any(|p| self.regex.is_match(p))
+        } else {
+            path.split('/').any(|name| self.regex.is_match(name))
+        }
+    }
+}
+
+/// The `include` and `exclude` of a package, where the `exclude` is ignored if there is
+/// an `include`, as cargo does.
+#[derive(Debug, Default)]
+struct PackageFilter {
+    include: Vec<PackagePattern>,
+    exclude: Vec<PackagePattern>,
+}
+
+impl PackageFilter {
+    fn new(package: Option<&Package>) -> Self {
+        let compile = |patterns: Option<&Vec<String>>| -> Vec<PackagePattern> {
+            patterns
+                .into_iter()
+                .flatten()
+                .filter_map(|p| PackagePattern::new(p))
+                .collect()
+        };
+        PackageFilter {
+            include: compile(package.and_then(|p| p.include.as_ref())),
+            exclude: compile(package.and_then(|p| p.exclude.as_ref())),
+        }
+    }
+
+    /// Whether a file is packaged, given by its path from the crate directory.
+    fn is_packaged(&self, path: &str) -> bool {
+        if path == "Cargo.toml" {
+            return true;
+        }
+        if !self.include.is_empty() {
+            return self.include.iter().any(|p| p.matches(path));
+        }
+        !self.exclude.iter().any(|p| p.matches(path))
+    }
+}
+
+/// The packaged size of the crate in `crate_dir` of the git tree, whose `Cargo.toml` is
+/// `manifest`, none if the tree can't be read.
+pub(crate) fn package_size_in_tree(
+    repo: &Repository,
+    tree: &Tree,
+    crate_dir: &str,
+    manifest: &str,
+    workspace_root: Option<&Value>,
+) -> Option<i64> {
+    let mut value = manifest.parse::<Value>().ok()?;
+    if let Some(workspace_root) = workspace_root {
+        inherit_from_workspace(&mut value, workspace_root);
+    }
+    let manifest = Manifest::from_value(&value).ok()?;
+    let filter = PackageFilter::new(manifest.package.as_ref());
+
+    let crate_dir = crate_dir.trim_end_matches('/');
+    let crate_tree = if crate_dir.is_empty() {
+        tree.clone()
+    } else {
+        repo.find_tree(tree.get_path(Path::new(crate_dir)).ok()?.id())
+            .ok()?
+    };
+    let odb = repo.odb().ok()?;
+    let mut size = 0;
+    crate_tree
+        .walk(TreeWalkMode::PreOrder, |root, entry| {
+            let name = entry.name().unwrap_or_default();
+            match entry.kind() {
+                Some(ObjectType::Tree) => {
+                    let nested = repo
+                        .find_tree(entry.id())
+                        .is_ok_and(|t| t.get_name("Cargo.toml").is_some());
+                    if nested || is_excluded_dir_name(name) {
+                        return TreeWalkResult::Skip;
+                    }
+                }
+                Some(ObjectType::Blob) if filter.is_packaged(&format!("{}{}", root, name)) => {
+                    if let Ok((len, _)) = odb.read_header(entry.id()) {
+                        size += len as i64;
+                    }
+                }
+                _ => {}
+            }
+            TreeWalkResult::Ok
+        })
+        .ok()?;
+    Some(size)
+}
+
+/// The total size of the files in a `.crate` tarball.
+fn unpacked_size(tarball: &[u8]) -> io::Result<i64> {
+    let mut archive = tar::Archive::new(GzDecoder::new(tarball));
+    let mut size = 0;
+    for entry in archive.entries()? {
+        let header = entry?.header().clone();
+        if header.entry_type().is_file() {
+            size += header.size()? as i64;
+        }
+    }
+    Ok(size)
+}
+
+impl ImportContext {
+    /// Fill the sizes of the registry versions by downloading their `.crate` from crates.io.
+    pub async fn enrich_crate_sizes(&mut self, client: &mut CratesIoClient) {
+        tracing::info!("Start to enrich crate sizes");
+        let releases: Vec<(String, String)> = self
+            .library_versions
+            .iter()
+            .filter(|v| v.published_at.is_some() && v.unpacked_size.is_none())
+            .map(|v| (v.name.clone(), v.version.clone()))
+            .chain(
+                self.application_versions
+                    .iter()
+                    .filter(|v| v.published_at.is_some() && v.unpacked_size.is_none())
+                    .map(|v| (v.name.clone(), v.version.clone())),
+            )
+            .collect();
+
+        // name_and_version -> (crate size, unpacked size)
+        let mut sizes: HashMap<String, (i64, i64)> = HashMap::new();
+        for (name, version) in releases {
+            let tarball = match client.download_crate(&name, &version).await {
+                Ok(Some(tarball)) => tarball,
+                Ok(None) => {
+                    tracing::debug!("{}/{} is not on crates.io", name, version);
+                    continue;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to download {}/{}: {}", name, version, e);
+                    continue;
+                }
+            };
+            match unpacked_size(&tarball) {
+                Ok(unpacked) => {
+                    sizes.insert(
+                        name_join_version(&name, &version),
+                        (tarball.len() as i64, unpacked),
+                    );
+                }
+                Err(e) => tracing::warn!("Invalid .crate of {}/{}: {}", name, version, e),
+            }
+        }
+
+        for version in &mut self.library_versions {
+            if let Some((crate_size, unpacked)) = sizes.get(&version.name_and_version) {
+                version.crate_size = Some(*crate_size);
+                version.unpacked_size = Some(*unpacked);
+            }
+        }
+        for version in &mut self.application_versions {
+            if let Some((crate_size, unpacked)) = sizes.get(&version.name_and_version) {
+                version.crate_size = Some(*crate_size);
+                version.unpacked_size = Some(*unpacked);
+            }
+        }
+        tracing::info!("Finish to enrich sizes of {} versions", sizes.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_filter() {
+        let package = Package {
+            exclude: Some(vec![
+                "/ci".to_string(),
+                "*.png".to_string(),
+                "benches/**/*.json".to_string(),
+            ]),
+            ..Default::default()
+        };
+        let filter = PackageFilter::new(Some(&package));
+        assert!(filter.is_packaged("src/lib.rs"));
+        assert!(!filter.is_packaged("ci/run.sh"));
+        assert!(filter.is_packaged("src/ci/mod.rs"));
+        assert!(!filter.is_packaged("docs/images/logo.png"));
+        assert!(!filter.is_packaged("benches/data/large.json"));
+
+        let package = Package {
+            include: Some(vec!["src/".to_string(), "LICENSE-*".to_string()]),
+            exclude: Some(vec!["src/tests".to_string()]),
+            ..Default::default()
+        };
+        let filter = PackageFilter::new(Some(&package));
+        assert!(filter.is_packaged("Cargo.toml"));
+        assert!(filter.is_packaged("src/tests/mod.rs"));
+        assert!(filter.is_packaged("LICENSE-MIT"));
+        assert!(!filter.is_packaged("README.md"));
+    }
+}
@@ -0,0 +1,214 @@
+//! Score the popularity of the crates from 0 to 1, by the weighted sum of:
+//! - the downloads from crates.io,
+//! - the crates depending on it,
+//! - the stars of its GitHub repo,
+//! - the recency, halved every `recency_half_life_days` since its last release or commit.
+//!
+//! The counts are scaled by their logarithms relative to the most popular crate, so that a
+//! crate with a tenth of the downloads of the top one isn't scored a tenth. The weights are
+//! in the `[import.popularity]` section of the config, i.e. `IMPORT_POPULARITY_DOWNLOADS`
+//! and so on, and the signals not imported at all, such as the stars without
+//! `IMPORT_ENRICH_STARS=1`, are left out of the sum.
+
+use crate::scoring::{halved, last_releases, unix_now, weights_from_env, SECONDS_PER_DAY};
+use crate::ImportContext;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PopularityWeights {
+    pub(crate) downloads: f64,
+    pub(crate) dependents: f64,
+    pub(crate) stars: f64,
+    pub(crate) recency: f64,
+    pub(crate) recency_half_life_days: f64,
+}
+
+impl Default for PopularityWeights {
+    fn default() -> Self {
+        PopularityWeights {
+            downloads: 0.4,
+            dependents: 0.3,
+            stars: 0.2,
+            recency: 0.1,
+            recency_half_life_days: 365.0,
+        }
+    }
+}
+
+impl PopularityWeights {
+    pub(crate) fn from_env() -> Self {
+        let default = PopularityWeights::default();
+        let weight = weights_from_env("IMPORT_POPULARITY");
+        PopularityWeights {
+            downloads: weight("DOWNLOADS", default.downloads),
+            dependents: weight("DEPENDENTS", default.dependents),
+            stars: weight("STARS", default.stars),
+            recency: weight("RECENCY", default.recency),
+            recency_half_life_days: weight(
+                "RECENCY_HALF_LIFE_DAYS",
+                default.recency_half_life_days,
+            )
+            .max(1.0),
+        }
+    }
+}
+
+/// The signals of a crate, none if unknown.
+#[derive(Debug, Clone, Copy, Default)]
+struct Signals {
+    downloads: Option<i64>,
+    dependents: Option<i64>,
+    stars: Option<i64>,
+    /// the last release or commit, in seconds since the epoch
+    last_activity: Option<i64>,
+}
+
+/// `ln(1 + count) / ln(1 + max)`, from 0 to 1.
+fn log_scale(count: i64, max: i64) -> f64 {
+    if max <= 0 {
+        return 0.0;
+    }
+    ((count.max(0) as f64).ln_1p() / (max as f64).ln_1p()).min(1.0)
+}
+
+/// The popularity of each crate, from 0 to 1, in the order of `signals`.
+fn popularity(signals: &[Signals], weights: &PopularityWeights, now: i64) -> Vec<f64> {
+    let max = |signal: fn(&Signals) -> Option<i64>| signals.iter().filter_map(signal).max();
+    let max_downloads = max(|s| s.downloads);
+    let max_dependents = max(|s| s.dependents);
+    let max_stars = max(|s| s.stars);
+    let has_activity = signals.iter().any(|s| s.last_activity.is_some());
+
+    // the weights of the signals imported
+    let total = [
+        (max_downloads.is_some(), weights.downloads),
+        (max_dependents.is_some(), weights.dependents),
+        (max_stars.is_some(), weights.stars),
+        (has_activity, weights.recency),
+    ]
+    .iter()
+    .filter(|(imported, _)| *imported)
+    .map(|(_, weight)| weight)
+    .sum::<f64>();
+    if total <= 0.0 {
+        return vec![0.0; signals.len()];
+    }
+
+    signals
+        .iter()
+        .map(|s| {
+            let mut score = 0.0;
+            if let Some(max) = max_downloads {
+                score += weights.downloads * log_scale(s.downloads.unwrap_or(0), max);
+            }
+            if let Some(max) = max_dependents {
+                score += weights.dependents * log_scale(s.dependents.unwrap_or(0), max);
+            }
+            if let Some(max) = max_stars {
+                score += weights.stars * log_scale(s.stars.unwrap_or(0), max);
+            }
+            if let Some(last_activity) = s.last_activity {
+                let age_days = (now - last_activity) as f64 / SECONDS_PER_DAY;
+                score += weights.recency * halved(age_days, weights.recency_half_life_days);
+            }
+            score / total
+        })
+        .collect()
+}
+
+impl ImportContext {
+    /// Fill the popularity of the programs.
+    pub(crate) fn score_popularity(&mut self, weights: &PopularityWeights) {
+        let downloads: HashMap<&str, i64> = self
+            .libraries
+            .iter()
+            .filter(|l| l.downloads > 0)
+            .map(|l| (l.id.as_str(), l.downloads))
+            .collect();
+        // crate name -> the crates depending on it
+        let mut dependents: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for edge in &self.depends_on {
+            let (Some((dependent, _)), Some((dependency, _))) =
+                (edge.SRC_ID.split_once('/'), edge.DST_ID.split_once('/'))
+            else {
+                continue;
+            };
+            if dependent != dependency {
+                dependents.entry(dependency).or_default().insert(dependent);
+            }
+        }
+        let last_releases = last_releases(&self.library_versions, &self.application_versions);
+        let repo_activity: HashMap<&str, i64> = self
+            .repo_metrics
+            .iter()
+            .map(|m| (m.id.as_str(), m.last_commit))
+            .collect();
+
+        let has_dependencies = !self.depends_on.is_empty();
+        let signals: Vec<Signals> = self
+            .programs
+            .iter()
+            .map(|p| Signals {
+                downloads: downloads.get(p.id.as_str()).copied(),
+                dependents: has_dependencies.then(|| {
+                    dependents
+                        .get(p.name.as_str())
+                        .map_or(0, |d| d.len() as i64)
+                }),
+                stars: p.stars,
+                last_activity: last_releases.get(p.id.as_str()).copied().max(
+                    p.namespace
+                        .as_deref()
+                        .and_then(|n| repo_activity.get(n))
+                        .copied(),
+                ),
+            })
+            .collect();
+        let scores = popularity(&signals, weights, unix_now());
+        for (program, score) in self.programs.iter_mut().zip(scores) {
+            program.popularity = Some(score);
+        }
+        tracing::info!("Scored the popularity of {} programs", self.programs.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_popularity() {
+        let now = 1_700_000_000;
+        let signals = [
+            Signals {
+                downloads: Some(1_000_000),
+                dependents: Some(100),
+                stars: None,
+                last_activity: Some(now),
+            },
+            Signals {
+                downloads: Some(1000),
+                dependents: Some(0),
+                stars: None,
+                last_activity: Some(now - 365 * 24 * 60 * 60),
+            },
+            Signals::default(),
+        ];
+        let weights = PopularityWeights::default();
+        let scores = popularity(&signals, &weights, now);
+        // no stars are imported, so the top crate is scored 1
+        assert!((scores[0] - 1.0).abs() < 1e-9);
+        let expected = (0.4 * 0.5 + 0.1 * 0.5) / 0.8;
+        assert!((scores[1] - expected).abs() < 1e-3);
+        assert_eq!(scores[2], 0.0);
+
+        let weights = PopularityWeights {
+            downloads: 0.0,
+            dependents: 0.0,
+            stars: 0.0,
+            recency: 0.0,
+            ..weights
+        };
+        assert_eq!(popularity(&signals, &weights, now), [0.0; 3]);
+    }
+}
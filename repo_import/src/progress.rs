@@ -0,0 +1,285 @@
+//! Checkpoint the progress of a long import run, i.e. which repos are imported
+//! and which outputs are written, so that an interrupted run resumes where it stopped.
+//!
+//! The run is saved into `CHECKPOINT_DIR/run.bin` every `CHECKPOINT_EVERY` repos (10 by default)
+//! and after each output, and removed when the run finishes.
+//! Set `IMPORT_RESTART=1` to discard the checkpoint of an interrupted run.
+//!
+//! A finished run is saved into `CHECKPOINT_DIR/latest.json`, and the next run starts from it,
+//! so that the repos skipped as unchanged keep their crates in the outputs.
+
+use crate::ImportContext;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ImportProgress {
+    /// whether a run is in progress, the outputs are written every time otherwise
+    running: bool,
+    /// the urls or paths imported in the run
+    done: HashSet<String>,
+    /// the outputs written after the last repo is imported, such as `tugraph`
+    flushed: HashSet<String>,
+    /// the repos imported since the last checkpoint
+    #[serde(skip)]
+    unsaved: usize,
+    /// where and how often the run is checkpointed, by the env if none
+    #[serde(skip)]
+    checkpoints: Option<Checkpoints>,
+}
+
+impl ImportProgress {
+    pub fn repos_done(&self) -> usize {
+        self.done.len()
+    }
+
+    pub(crate) fn is_done(&self, source: &str) -> bool {
+        self.done.contains(source)
+    }
+
+    pub(crate) fn is_flushed(&self, output: &str) -> bool {
+        self.running && self.flushed.contains(output)
+    }
+
+    fn checkpoints(&self) -> Checkpoints {
+        self.checkpoints
+            .clone()
+            .unwrap_or_else(Checkpoints::from_env)
+    }
+}
+
+/// Where and how often the runs are checkpointed.
+#[derive(Debug, Clone)]
+struct Checkpoints {
+    dir: PathBuf,
+    /// the repos imported between two checkpoints
+    every: usize,
+}
+
+impl Checkpoints {
+    fn from_env() -> Self {
+        Checkpoints {
+            dir: checkpoint_dir(),
+            every: checkpoint_every(),
+        }
+    }
+
+    fn run_path(&self) -> PathBuf {
+        self.dir.join("run.bin")
+    }
+
+    fn latest_path(&self) -> PathBuf {
+        self.dir.join("latest.json")
+    }
+}
+
+const DEFAULT_CHECKPOINT_DIR: &str = "./checkpoints";
+
+/// The directory of the checkpoints, `CHECKPOINT_DIR` or `./checkpoints`.
+pub fn checkpoint_dir() -> PathBuf {
+    env::var_os("CHECKPOINT_DIR")
+        .map_or_else(|| PathBuf::from(DEFAULT_CHECKPOINT_DIR), PathBuf::from)
+}
+
+/// The checkpoint of the last finished run, `latest.json` in [`checkpoint_dir`].
+pub fn latest_checkpoint_path() -> PathBuf {
+    Checkpoints::from_env().latest_path()
+}
+
+pub(crate) async fn load_checkpoint(path: &Path) -> Result<ImportContext, String> {
+    ImportContext::load_from_file(&path.to_string_lossy())
+        .await
+        .map_err(|e| format!("Failed to load the checkpoint {:?}: {}", path, e))
+}
+
+fn checkpoint_every() -> usize {
+    env::var("CHECKPOINT_EVERY")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(10)
+}
+
+impl ImportContext {
+    /// Resume the interrupted run, or start a new one from the last finished run.
+    ///
+    /// A checkpoint which can't be loaded, such as one of an older format, is an error
+    /// rather than a run from scratch, remove it to start over.
+    pub async fn resume_run() -> Result<Self, String> {
+        Self::resume_run_with(Checkpoints::from_env()).await
+    }
+
+    async fn resume_run_with(checkpoints: Checkpoints) -> Result<Self, String> {
+        let path = checkpoints.run_path();
+        let latest = checkpoints.latest_path();
+        let restart = env::var("IMPORT_RESTART").is_ok_and(|v| v == "1");
+        let mut context = if path.is_file() && !restart {
+            let context = load_checkpoint(&path).await?;
+            tracing::info!(
+                "Resume the run with {} repos imported",
+                context.progress.repos_done()
+            );
+            context
+        } else if latest.is_file() {
+            let context = load_checkpoint(&latest).await?;
+            tracing::info!(
+                "Start the run from {:?} with {} programs",
+                latest,
+                context.programs.len()
+            );
+            context
+        } else {
+            ImportContext::default()
+        };
+        context.progress.checkpoints = Some(checkpoints);
+        context.start_run();
+        Ok(context)
+    }
+
+    /// Start a new run, finished by [`ImportContext::finish_run`].
+    pub(crate) fn start_run(&mut self) {
+        self.progress.running = true;
+    }
+
+    async fn save_run_checkpoint(&mut self) {
+        if !self.progress.running {
+            return;
+        }
+        let path = self.progress.checkpoints().run_path();
+        if let Some(dir) = path.parent() {
+            if let Err(e) = tokio::fs::create_dir_all(dir).await {
+                tracing::error!("Failed to create {:?}: {}", dir, e);
+                return;
+            }
+        }
+        match self.save_to_file(&path.to_string_lossy()).await {
+            Ok(()) => self.progress.unsaved = 0,
+            Err(e) => tracing::error!("Failed to save the run checkpoint: {}", e),
+        }
+    }
+
+    /// Mark a repo imported, and save the run every `CHECKPOINT_EVERY` repos.
+    pub(crate) async fn mark_repo_done(&mut self, source: &str) {
+        if !self.progress.running {
+            return;
+        }
+        self.progress.done.insert(source.to_string());
+        // the outputs are stale now
+        self.progress.flushed.clear();
+        self.progress.unsaved += 1;
+        if self.progress.unsaved >= self.progress.checkpoints().every {
+            self.save_run_checkpoint().await;
+        }
+    }
+
+    /// Mark an output written, and save the run.
+    pub(crate) async fn mark_flushed(&mut self, output: &str) {
+        if !self.progress.running {
+            return;
+        }
+        self.progress.flushed.insert(output.to_string());
+        self.save_run_checkpoint().await;
+    }
+
+    /// Finish the run, save it as the latest checkpoint, and remove the one of the run.
+    pub async fn finish_run(&mut self) {
+        let checkpoints = self.progress.checkpoints();
+        self.progress = ImportProgress {
+            checkpoints: Some(checkpoints.clone()),
+            ..Default::default()
+        };
+        let latest = checkpoints.latest_path();
+        if let Err(e) = tokio::fs::create_dir_all(&checkpoints.dir).await {
+            tracing::error!("Failed to create {:?}: {}", checkpoints.dir, e);
+        } else if let Err(e) = self.save_to_file(&latest.to_string_lossy()).await {
+            tracing::error!("Failed to save the checkpoint {:?}: {}", latest, e);
+        }
+        let path = checkpoints.run_path();
+        if path.is_file() {
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                tracing::error!("Failed to remove the run checkpoint {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::tugraph_model::Program;
+
+    #[tokio::test]
+    async fn test_checkpoint_format() {
+        let mut context = ImportContext::default();
+        context.programs.push(Program {
+            id: "x/a".to_string(),
+            ..Default::default()
+        });
+        let checkpoint = context.encode_checkpoint().await.unwrap();
+        let loaded = ImportContext::from_checkpoint(&checkpoint).unwrap();
+        assert_eq!(loaded.programs[0].id, "x/a");
+
+        // the bincode without the header, as the checkpoints of the older versions
+        let unversioned = bincode::serialize(&context).unwrap();
+        assert!(ImportContext::from_checkpoint(&unversioned).is_err());
+
+        let mut other_version = checkpoint.clone();
+        other_version[8] += 1;
+        let e = ImportContext::from_checkpoint(&other_version)
+            .err()
+            .unwrap();
+        assert!(e.contains("format"));
+    }
+
+    #[tokio::test]
+    async fn test_resume_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoints = Checkpoints {
+            dir: dir.path().to_path_buf(),
+            every: 2,
+        };
+        let run_checkpoint_path = || checkpoints.run_path();
+
+        // nothing is recorded out of a run
+        let mut context = ImportContext::default();
+        context.mark_repo_done("https://github.com/foo/a").await;
+        assert!(!context.progress.is_done("https://github.com/foo/a"));
+
+        let mut context = ImportContext::resume_run_with(checkpoints.clone())
+            .await
+            .unwrap();
+        context.mark_repo_done("https://github.com/foo/a").await;
+        assert!(!run_checkpoint_path().is_file());
+        context.mark_repo_done("https://github.com/foo/b").await;
+        assert!(run_checkpoint_path().is_file());
+        context.mark_flushed("tugraph").await;
+        assert!(context.progress.is_flushed("tugraph"));
+
+        // interrupted here, and resumed
+        let mut resumed = ImportContext::resume_run_with(checkpoints.clone())
+            .await
+            .unwrap();
+        assert_eq!(resumed.progress.repos_done(), 2);
+        assert!(resumed.progress.is_done("https://github.com/foo/b"));
+        assert!(resumed.progress.is_flushed("tugraph"));
+        resumed.mark_repo_done("https://github.com/foo/c").await;
+        assert!(!resumed.progress.is_flushed("tugraph"));
+
+        resumed.programs.push(Program {
+            id: "foo/c".to_string(),
+            ..Default::default()
+        });
+        resumed.finish_run().await;
+        assert!(!run_checkpoint_path().is_file());
+        assert!(checkpoints.latest_path().is_file());
+
+        // the next run starts from the finished one, with nothing done yet
+        let next = ImportContext::resume_run_with(checkpoints.clone())
+            .await
+            .unwrap();
+        assert_eq!(next.progress.repos_done(), 0);
+        assert_eq!(next.programs[0].id, "foo/c");
+    }
+}
@@ -0,0 +1,296 @@
+//! SLSA-style provenance of the imported versions, for the supply-chain verification
+//! downstream: an in-toto statement per version, one json per line of `provenance.jsonl`.
+//!
+//! The subject is the `.crate` by its sha256 in the registry, or by the one resolved by the
+//! lockfiles of the dependents, otherwise the tagged commit of a version only found in a git
+//! repo. The predicate records the source it's imported from: the registry, and the repo
+//! with the commit of its tag if known. The versions without any digest are skipped.
+
+use crate::ImportContext;
+use chrono::{DateTime, SecondsFormat};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+const PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+/// The `.crate` is built by `cargo package`.
+const BUILD_TYPE: &str = "https://doc.rust-lang.org/cargo/commands/cargo-package.html";
+const CRATESIO_REGISTRY: &str = "https://crates.io";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Statement {
+    #[serde(rename = "_type")]
+    statement_type: &'static str,
+    subject: Vec<ResourceDescriptor>,
+    predicate_type: &'static str,
+    predicate: Provenance,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct ResourceDescriptor {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri: Option<String>,
+    /// such as `sha256` or `gitCommit`
+    digest: BTreeMap<&'static str, String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Provenance {
+    build_definition: BuildDefinition,
+    run_details: RunDetails,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BuildDefinition {
+    build_type: &'static str,
+    external_parameters: ExternalParameters,
+    resolved_dependencies: Vec<ResourceDescriptor>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExternalParameters {
+    package: String,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    registry: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repository: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunDetails {
+    builder: Builder,
+    metadata: RunMetadata,
+}
+
+#[derive(Debug, Serialize)]
+struct Builder {
+    id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunMetadata {
+    /// when it's published, or committed for a version only in a git repo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_on: Option<String>,
+}
+
+/// Where a version comes from, as known by the import.
+#[derive(Debug, Default)]
+struct VersionOrigin<'a> {
+    name: &'a str,
+    version: &'a str,
+    checksum: Option<&'a str>,
+    /// whether it's published to crates.io
+    published: bool,
+    published_at: Option<i64>,
+    repository: Option<&'a str>,
+    tag_name: Option<&'a str>,
+    commit: Option<&'a str>,
+    commit_time: Option<i64>,
+}
+
+fn rfc3339(timestamp: i64) -> Option<String> {
+    DateTime::from_timestamp(timestamp, 0).map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+fn statement(origin: &VersionOrigin) -> Option<Statement> {
+    let source = match (origin.repository, origin.commit) {
+        (Some(repository), Some(commit)) => Some(ResourceDescriptor {
+            name: None,
+            uri: Some(match origin.tag_name {
+                Some(tag) => format!("git+{}@refs/tags/{}", repository, tag),
+                None => format!("git+{}", repository),
+            }),
+            digest: BTreeMap::from([("gitCommit", commit.to_string())]),
+        }),
+        _ => None,
+    };
+    let digest = match (origin.checksum, &source) {
+        (Some(checksum), _) => BTreeMap::from([("sha256", checksum.to_string())]),
+        (None, Some(source)) => source.digest.clone(),
+        (None, None) => return None,
+    };
+    let registry = origin.published.then_some(CRATESIO_REGISTRY);
+    let builder = match (registry, origin.repository) {
+        (Some(registry), _) => registry.to_string(),
+        (None, Some(repository)) => repository.to_string(),
+        (None, None) => return None,
+    };
+    let finished_on = origin.published_at.or(origin.commit_time).and_then(rfc3339);
+    Some(Statement {
+        statement_type: STATEMENT_TYPE,
+        subject: vec![ResourceDescriptor {
+            name: Some(format!("pkg:cargo/{}@{}", origin.name, origin.version)),
+            uri: None,
+            digest,
+        }],
+        predicate_type: PREDICATE_TYPE,
+        predicate: Provenance {
+            build_definition: BuildDefinition {
+                build_type: BUILD_TYPE,
+                external_parameters: ExternalParameters {
+                    package: origin.name.to_string(),
+                    version: origin.version.to_string(),
+                    registry,
+                    repository: origin.repository.map(String::from),
+                    tag: origin.tag_name.map(String::from),
+                },
+                resolved_dependencies: source.into_iter().collect(),
+            },
+            run_details: RunDetails {
+                builder: Builder { id: builder },
+                metadata: RunMetadata { finished_on },
+            },
+        },
+    })
+}
+
+impl ImportContext {
+    /// Write the provenance statements of the library and application versions into `output`.
+    pub(crate) fn write_provenance(&self, output: &Path) -> Result<(), Box<dyn Error>> {
+        // the checksums of the registry versions resolved by the lockfiles
+        let locked: HashMap<&str, &str> = self
+            .resolves_to
+            .iter()
+            .filter_map(|e| Some((e.DST_ID.as_str(), e.checksum.as_deref()?)))
+            .collect();
+        let repositories: HashMap<&str, &str> = self
+            .programs
+            .iter()
+            .filter_map(|p| {
+                Some((
+                    p.id.as_str(),
+                    p.github_url.as_deref().or(p.mega_url.as_deref())?,
+                ))
+            })
+            .collect();
+        let repository = |name_and_version: &str, program_id: &str| {
+            self.version_sources
+                .get(name_and_version)
+                .and_then(|sources| sources.first())
+                .map(|s| s.git_url.as_str())
+                .or_else(|| repositories.get(program_id).copied())
+                .filter(|url| !url.is_empty())
+        };
+
+        let origins = self
+            .library_versions
+            .iter()
+            .map(|v| VersionOrigin {
+                name: &v.name,
+                version: &v.version,
+                checksum: v
+                    .checksum
+                    .as_deref()
+                    .or_else(|| locked.get(v.name_and_version.as_str()).copied()),
+                published: v.published_at.is_some() || v.checksum.is_some(),
+                published_at: v.published_at,
+                repository: repository(&v.name_and_version, &v.id),
+                tag_name: v.tag_name.as_deref(),
+                commit: v.commit.as_deref(),
+                commit_time: v.commit_time,
+            })
+            .chain(self.application_versions.iter().map(|v| {
+                VersionOrigin {
+                    name: &v.name,
+                    version: &v.version,
+                    checksum: v
+                        .checksum
+                        .as_deref()
+                        .or_else(|| locked.get(v.name_and_version.as_str()).copied()),
+                    published: v.published_at.is_some() || v.checksum.is_some(),
+                    published_at: v.published_at,
+                    repository: repository(&v.name_and_version, &v.id),
+                    tag_name: v.tag_name.as_deref(),
+                    commit: v.commit.as_deref(),
+                    commit_time: v.commit_time,
+                }
+            }));
+
+        let mut writer = BufWriter::new(File::create(output)?);
+        let mut count = 0;
+        for statement in origins.filter_map(|origin| statement(&origin)) {
+            serde_json::to_writer(&mut writer, &statement)?;
+            writer.write_all(b"\n")?;
+            count += 1;
+        }
+        writer.flush()?;
+        tracing::info!("Wrote the provenance of {} versions", count);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statement() {
+        let origin = VersionOrigin {
+            name: "serde",
+            version: "1.0.203",
+            checksum: Some("7253ab4de971e72fb7be983802300c30b5a7f0c2e56fab8abfc6a214307c0094"),
+            published: true,
+            published_at: Some(1716940800),
+            repository: Some("https://github.com/serde-rs/serde"),
+            tag_name: Some("v1.0.203"),
+            commit: Some("c4b2bc8f5e24a3c4b3c4e2a8d6b5cd0e0b1f4a2e"),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(statement(&origin).unwrap()).unwrap();
+        assert_eq!(json["_type"], STATEMENT_TYPE);
+        assert_eq!(json["subject"][0]["name"], "pkg:cargo/serde@1.0.203");
+        assert!(json["subject"][0]["digest"]["sha256"]
+            .as_str()
+            .unwrap()
+            .starts_with("7253"));
+        let definition = &json["predicate"]["buildDefinition"];
+        assert_eq!(
+            definition["externalParameters"]["registry"],
+            CRATESIO_REGISTRY
+        );
+        assert_eq!(
+            definition["resolvedDependencies"][0]["uri"],
+            "git+https://github.com/serde-rs/serde@refs/tags/v1.0.203"
+        );
+        assert_eq!(
+            json["predicate"]["runDetails"]["metadata"]["finishedOn"],
+            "2024-05-29T00:00:00Z"
+        );
+
+        // a version only in a git repo is identified by its commit
+        let origin = VersionOrigin {
+            checksum: None,
+            published: false,
+            published_at: None,
+            ..origin
+        };
+        let json = serde_json::to_value(statement(&origin).unwrap()).unwrap();
+        assert!(json["subject"][0]["digest"]["gitCommit"].is_string());
+        assert_eq!(
+            json["predicate"]["runDetails"]["builder"]["id"],
+            "https://github.com/serde-rs/serde"
+        );
+
+        let origin = VersionOrigin {
+            name: "local",
+            version: "0.1.0",
+            ..Default::default()
+        };
+        assert!(statement(&origin).is_none());
+    }
+}
@@ -0,0 +1,239 @@
+//! Score the quality of the crates from 0 to 1 by the weighted sum of its factors, each from
+//! 0 to 1 as well:
+//! - `tests`, whether it has any test code, with `IMPORT_LOC_METRICS=1`,
+//! - `docs`, the share of the public API of its latest version with doc comments, the trait
+//!   impls and re-exports aside, with the API surface imported,
+//! - `ci`, whether its repo has a CI config,
+//! - `license`, how clear its license is, from 1 for an SPDX expression backed by the
+//!   license files, to 0 for none at all,
+//! - `unsafe`, halved by every `unsafe_half_density` unsafe blocks, functions, impls and
+//!   traits per 1000 lines of non-test code, with `IMPORT_UNSAFE_METRICS=1` and
+//!   `IMPORT_LOC_METRICS=1`.
+//!
+//! The factors unknown for a crate are left out of its sum, and the ones known are stored
+//! along with the score, such as `ci:1.00,docs:0.82,license:1.00,tests:1.00`, so the score
+//! can be explained. The weights are in the `[import.quality]` section of the config,
+//! i.e. `IMPORT_QUALITY_TESTS` and so on.
+
+use crate::license_policy::license_ids;
+use crate::scoring::{halved, weights_from_env};
+use crate::utils::name_join_version;
+use crate::ImportContext;
+use model::tugraph_model::Program;
+use std::collections::{HashMap, HashSet};
+
+/// The API items which need no doc comments of their own.
+const UNDOCUMENTED_KINDS: [&str; 2] = ["impl", "use"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct QualityWeights {
+    pub(crate) tests: f64,
+    pub(crate) docs: f64,
+    pub(crate) ci: f64,
+    pub(crate) license: f64,
+    pub(crate) unsafe_code: f64,
+    pub(crate) unsafe_half_density: f64,
+}
+
+impl Default for QualityWeights {
+    fn default() -> Self {
+        QualityWeights {
+            tests: 0.2,
+            docs: 0.25,
+            ci: 0.2,
+            license: 0.15,
+            unsafe_code: 0.2,
+            unsafe_half_density: 5.0,
+        }
+    }
+}
+
+impl QualityWeights {
+    pub(crate) fn from_env() -> Self {
+        let default = QualityWeights::default();
+        let weight = weights_from_env("IMPORT_QUALITY");
+        QualityWeights {
+            tests: weight("TESTS", default.tests),
+            docs: weight("DOCS", default.docs),
+            ci: weight("CI", default.ci),
+            license: weight("LICENSE", default.license),
+            unsafe_code: weight("UNSAFE", default.unsafe_code),
+            unsafe_half_density: weight("UNSAFE_HALF_DENSITY", default.unsafe_half_density)
+                .max(0.1),
+        }
+    }
+}
+
+/// The factors of a crate from 0 to 1, none if unknown.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct QualityFactors {
+    tests: Option<f64>,
+    docs: Option<f64>,
+    ci: Option<f64>,
+    license: Option<f64>,
+    unsafe_code: Option<f64>,
+}
+
+impl QualityFactors {
+    /// The factors known with their weights, by the name.
+    fn weighted(&self, weights: &QualityWeights) -> Vec<(&'static str, f64, f64)> {
+        [
+            ("ci", self.ci, weights.ci),
+            ("docs", self.docs, weights.docs),
+            ("license", self.license, weights.license),
+            ("tests", self.tests, weights.tests),
+            ("unsafe", self.unsafe_code, weights.unsafe_code),
+        ]
+        .into_iter()
+        .filter_map(|(name, factor, weight)| Some((name, factor?, weight)))
+        .collect()
+    }
+
+    /// The score and the factors known, such as `ci:1.00,docs:0.82`, none if no factor is known.
+    fn score(&self, weights: &QualityWeights) -> Option<(f64, String)> {
+        let weighted = self.weighted(weights);
+        let total: f64 = weighted.iter().map(|(_, _, weight)| weight).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let score = weighted
+            .iter()
+            .map(|(_, factor, weight)| factor * weight)
+            .sum::<f64>()
+            / total;
+        let factors: Vec<String> = weighted
+            .iter()
+            .map(|(name, factor, _)| format!("{}:{:.2}", name, factor))
+            .collect();
+        Some((score, factors.join(",")))
+    }
+}
+
+/// How clear the license of a crate is, by the declared `license` and the one detected
+/// from its license files.
+fn license_clarity(declared: Option<&str>, detected: Option<&str>) -> f64 {
+    let declared = declared
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && *l != "None");
+    match (declared.map(license_ids), detected) {
+        (Some(Ok(declared)), Some(detected)) => match license_ids(detected) {
+            Ok(detected) if detected.is_subset(&declared) => 1.0,
+            _ => 0.5,
+        },
+        (Some(Ok(_)), None) => 0.75,
+        (None, Some(_)) => 0.5,
+        (Some(Err(_)), _) => 0.25,
+        (None, None) => 0.0,
+    }
+}
+
+/// The unsafe score by the unsafe items per 1000 lines of non-test code.
+fn unsafe_score(program: &Program, half_density: f64) -> Option<f64> {
+    let unsafe_items = program.unsafe_blocks?
+        + program.unsafe_fns?
+        + program.unsafe_impls?
+        + program.unsafe_traits?;
+    let code_lines = program.rust_code_lines? - program.rust_test_lines.unwrap_or(0);
+    if code_lines <= 0 {
+        return None;
+    }
+    let density = unsafe_items as f64 * 1000.0 / code_lines as f64;
+    Some(halved(density, half_density))
+}
+
+impl ImportContext {
+    /// Fill the quality score and its factors of the programs.
+    pub(crate) fn score_quality(&mut self, weights: &QualityWeights) {
+        // name and version -> the API items documented and all
+        let mut api_docs: HashMap<&str, (usize, usize)> = HashMap::new();
+        for item in &self.api_items {
+            if UNDOCUMENTED_KINDS.contains(&item.kind.as_str()) {
+                continue;
+            }
+            let docs = api_docs.entry(item.name_and_version.as_str()).or_default();
+            docs.0 += item.documented as usize;
+            docs.1 += 1;
+        }
+        let has_ci: HashSet<&str> = self.has_ci.iter().map(|e| e.SRC_ID.as_str()).collect();
+
+        let mut scored = 0;
+        for program in &mut self.programs {
+            let docs = program.max_version.as_deref().and_then(|version| {
+                let (documented, all) =
+                    api_docs.get(&*name_join_version(&program.name, version))?;
+                Some(*documented as f64 / *all as f64)
+            });
+            let factors = QualityFactors {
+                tests: program
+                    .rust_test_lines
+                    .map(|lines| if lines > 0 { 1.0 } else { 0.0 }),
+                docs,
+                ci: program.namespace.as_ref().map(|_| {
+                    if has_ci.contains(&*program.id) {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                }),
+                license: Some(license_clarity(
+                    program.license.as_deref(),
+                    program.detected_license.as_deref(),
+                )),
+                unsafe_code: unsafe_score(program, weights.unsafe_half_density),
+            };
+            match factors.score(weights) {
+                Some((score, summary)) => {
+                    program.quality = Some(score);
+                    program.quality_factors = Some(summary);
+                    scored += 1;
+                }
+                None => {
+                    program.quality = None;
+                    program.quality_factors = None;
+                }
+            }
+        }
+        tracing::info!("Scored the quality of {} programs", scored);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality() {
+        assert_eq!(license_clarity(Some("MIT OR Apache-2.0"), Some("MIT")), 1.0);
+        assert_eq!(license_clarity(Some("MIT"), Some("GPL-3.0-only")), 0.5);
+        assert_eq!(license_clarity(Some("MIT"), None), 0.75);
+        assert_eq!(license_clarity(None, Some("MIT")), 0.5);
+        assert_eq!(license_clarity(Some("MIT OR"), None), 0.25);
+        assert_eq!(license_clarity(Some("None"), None), 0.0);
+
+        let program = Program {
+            unsafe_blocks: Some(4),
+            unsafe_fns: Some(1),
+            unsafe_impls: Some(0),
+            unsafe_traits: Some(0),
+            rust_code_lines: Some(1200),
+            rust_test_lines: Some(200),
+            ..Default::default()
+        };
+        assert_eq!(unsafe_score(&program, 5.0), Some(0.5));
+        assert_eq!(unsafe_score(&Program::default(), 5.0), None);
+
+        let weights = QualityWeights::default();
+        let factors = QualityFactors {
+            tests: Some(1.0),
+            docs: Some(0.5),
+            ci: None,
+            license: Some(1.0),
+            unsafe_code: None,
+        };
+        let (score, summary) = factors.score(&weights).unwrap();
+        let expected = (0.2 + 0.25 * 0.5 + 0.15) / 0.6;
+        assert!((score - expected).abs() < 1e-9);
+        assert_eq!(summary, "docs:0.50,license:1.00,tests:1.00");
+        assert_eq!(QualityFactors::default().score(&weights), None);
+    }
+}
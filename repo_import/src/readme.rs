@@ -0,0 +1,63 @@
+//! The content of the readme of a crate, for the display and the search.
+//!
+//! It's stored in a single line as the description, with `\n` for the line breaks, and
+//! truncated to `IMPORT_README_MAX_CHARS` characters, 16384 by default.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const DEFAULT_MAX_CHARS: usize = 16384;
+
+fn max_chars() -> usize {
+    env::var("IMPORT_README_MAX_CHARS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CHARS)
+}
+
+/// Trim the content and truncate it to `max_chars` characters, in a single line.
+fn normalize(content: &str, max_chars: usize) -> Option<String> {
+    let content = content.trim_start_matches('\u{feff}').trim();
+    if content.is_empty() {
+        return None;
+    }
+    let truncated = match content.char_indices().nth(max_chars) {
+        Some((end, _)) => &content[..end],
+        None => content,
+    };
+    Some(truncated.replace('\r', "").replace('\n', "\\n"))
+}
+
+/// The content of the readme at `readme` relative to `crate_dir`, none if it's missing,
+/// not valid UTF-8, or out of the repo.
+pub(crate) fn read_readme(crate_dir: &Path, repo_path: &Path, readme: &str) -> Option<String> {
+    let path = fs::canonicalize(crate_dir.join(readme)).ok()?;
+    // `readme = "../README.md"` is allowed, but not a file out of the repo
+    if !fs::canonicalize(repo_path).is_ok_and(|repo| path.starts_with(repo)) {
+        tracing::warn!("Skip the readme {:?} out of the repo", path);
+        return None;
+    }
+    match fs::read_to_string(&path) {
+        Ok(content) => normalize(&content, max_chars()),
+        Err(e) => {
+            tracing::debug!("Failed to read the readme {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize("\u{feff}# foo\r\n\r\nA crate.\r\n", 100).as_deref(),
+            Some("# foo\\n\\nA crate.")
+        );
+        assert_eq!(normalize("# 文档说明", 4).as_deref(), Some("# 文档"));
+        assert_eq!(normalize(" \n", 100), None);
+    }
+}
@@ -0,0 +1,244 @@
+//! Adding the crates and versions known from the crates.io registry into the model,
+//! shared by the importers of the database dump and of the index.
+
+use crate::kafka_handler::KafkaHandler;
+use crate::msrv::Msrv;
+use crate::utils::{
+    extract_namespace, generate_program_id, get_program_by_name, insert_program_by_name,
+    name_join_version,
+};
+use crate::version_info::Dependencies;
+use crate::ImportContext;
+use model::general_model::YankEvent;
+use model::tugraph_model::{
+    ApplicationVersion, CrateType2Idx, HasDepVersion, HasType, HasVersion, Library, LibraryVersion,
+    Program, UProgram, Version,
+};
+use std::collections::HashMap;
+
+/// The namespace of the crates whose repository is unknown
+const CRATESIO_NAMESPACE: &str = "crates.io";
+
+/// What the registry knows about a crate
+#[derive(Debug, Default, Clone)]
+pub(crate) struct RegistryCrate {
+    pub(crate) name: String,
+    pub(crate) description: Option<String>,
+    pub(crate) documentation: Option<String>,
+    pub(crate) homepage: Option<String>,
+    pub(crate) repository: Option<String>,
+    pub(crate) downloads: Option<i64>,
+    pub(crate) max_version: Option<String>,
+    pub(crate) license: Option<String>,
+    pub(crate) rust_version: Option<String>,
+    pub(crate) edition: Option<String>,
+}
+
+impl ImportContext {
+    /// Get the program of a registry crate, as `(program id, is library)`.
+    ///
+    /// A crate already imported from its repo is kept as it is,
+    /// otherwise a library is created for it.
+    pub(crate) fn registry_program(&mut self, krate: &RegistryCrate) -> (String, bool) {
+        if let Some((program, uprogram)) = get_program_by_name(&krate.name) {
+            return (program.id, uprogram.index() == 0);
+        }
+
+        let namespace = krate
+            .repository
+            .as_deref()
+            .and_then(|r| extract_namespace(r).ok())
+            .unwrap_or_else(|| CRATESIO_NAMESPACE.to_string());
+        let id = generate_program_id(&namespace, &krate.name);
+        let mut program = Program::new(
+            id.clone(),
+            krate.name.clone(),
+            krate
+                .description
+                .as_ref()
+                .map(|d| d.replace('\n', "\\n").replace('\r', "")),
+            Some(namespace),
+            krate.max_version.clone(),
+            krate.repository.clone(),
+            None,
+            krate.documentation.clone(),
+        );
+        program.homepage = krate.homepage.clone();
+        program.license = krate.license.clone();
+        program.rust_version = krate.rust_version.clone();
+        program.edition = krate.edition.clone();
+
+        let library = Library::new(
+            &id,
+            &krate.name,
+            krate.downloads.unwrap_or(-1),
+            Some(&format!("https://crates.io/crates/{}", krate.name)),
+        );
+        insert_program_by_name(
+            krate.name.clone(),
+            (program.clone(), UProgram::Library(library.clone())),
+        );
+        self.programs.push(program);
+        self.libraries.push(library);
+        self.has_lib_type.push(HasType {
+            SRC_ID: id.clone(),
+            DST_ID: id.clone(),
+        });
+        (id, true)
+    }
+
+    /// Add a version of a registry crate, with its dependencies and features.
+    /// Return false if the version is already known.
+    pub(crate) async fn add_registry_version(
+        &mut self,
+        program_id: &str,
+        islib: bool,
+        info: &Dependencies,
+    ) -> bool {
+        let release = model::general_model::Version::new(&info.crate_name, &info.version);
+        if self.version_memory.contains(&release)
+            || self
+                .version_updater
+                .version_parser
+                .exists(&info.crate_name, &info.version)
+        {
+            return false;
+        }
+
+        self.version_updater.update_depends_on(info).await;
+        self.feature_updater.update_features(info).await;
+
+        let name_and_version = name_join_version(&info.crate_name, &info.version);
+        let has_version = HasVersion {
+            SRC_ID: program_id.to_string(),
+            DST_ID: name_and_version.clone(),
+        };
+        let has_dep_version = HasDepVersion {
+            SRC_ID: name_and_version.clone(),
+            DST_ID: name_and_version.clone(),
+        };
+        if islib {
+            let mut version = LibraryVersion::new(
+                program_id.to_string(),
+                &info.crate_name,
+                &info.version,
+                "???",
+            );
+            (version.msrv, version.msrv_source) = Msrv::fields(info.msrv.as_ref());
+            version.published_at = info.published_at;
+            version.crate_size = info.crate_size;
+            version.unpacked_size = info.unpacked_size;
+            version.checksum.clone_from(&info.checksum);
+            self.library_versions.push(version);
+            self.lib_has_version.push(has_version);
+            self.lib_has_dep_version.push(has_dep_version);
+        } else {
+            let mut version = ApplicationVersion::new(
+                program_id.to_string(),
+                info.crate_name.clone(),
+                info.version.clone(),
+            );
+            (version.msrv, version.msrv_source) = Msrv::fields(info.msrv.as_ref());
+            version.published_at = info.published_at;
+            version.crate_size = info.crate_size;
+            version.unpacked_size = info.unpacked_size;
+            version.checksum.clone_from(&info.checksum);
+            self.application_versions.push(version);
+            self.app_has_version.push(has_version);
+            self.app_has_dep_version.push(has_dep_version);
+        }
+        self.versions.push(Version { name_and_version });
+        self.version_memory.insert(release);
+        true
+    }
+
+    /// Record whether a version is yanked in the registry,
+    /// and queue a yank/unyank event if it changes.
+    ///
+    /// A version seen for the first time has no state before, so a version yanked long ago
+    /// isn't reported as yanked now.
+    pub(crate) fn set_yanked(&mut self, name: &str, version: &str, yanked: bool) {
+        let name_and_version = name_join_version(name, version);
+        let before = self.yanked.insert(name_and_version, yanked);
+        if before.is_some_and(|before| before != yanked) {
+            self.yank_events.push(YankEvent::new(name, version, yanked));
+        }
+    }
+
+    /// Fill the `yanked` flag of the library and application versions.
+    pub(crate) fn apply_yanked(&mut self) {
+        for version in &mut self.library_versions {
+            version.yanked = self.yanked.get(&version.name_and_version).copied();
+        }
+        for version in &mut self.application_versions {
+            version.yanked = self.yanked.get(&version.name_and_version).copied();
+        }
+    }
+
+    /// Raise the max version of the programs to their latest version not yanked,
+    /// such as the ones synced from the index after the programs are created.
+    pub(crate) fn apply_max_versions(&mut self) {
+        let mut latest: HashMap<&str, semver::Version> = HashMap::new();
+        let versions = self
+            .library_versions
+            .iter()
+            .map(|v| (&v.id, &v.version, v.yanked))
+            .chain(
+                self.application_versions
+                    .iter()
+                    .map(|v| (&v.id, &v.version, v.yanked)),
+            );
+        for (id, version, yanked) in versions {
+            let Ok(version) = semver::Version::parse(version) else {
+                continue;
+            };
+            if yanked == Some(true) || latest.get(id.as_str()).is_some_and(|v| *v >= version) {
+                continue;
+            }
+            latest.insert(id, version);
+        }
+        for program in &mut self.programs {
+            let Some(version) = latest.get(program.id.as_str()) else {
+                continue;
+            };
+            let current = program
+                .max_version
+                .as_deref()
+                .and_then(|v| semver::Version::parse(v).ok());
+            if current.is_none_or(|current| current < *version) {
+                program.max_version = Some(version.to_string());
+            }
+        }
+    }
+
+    /// Send the queued yank/unyank events to kafka.
+    pub async fn send_yank_events(&mut self, sender: &KafkaHandler, topic: &str) {
+        for event in self.yank_events.drain(..) {
+            sender.send_encoded(topic, &event.name, &event).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_yanked() {
+        let mut context = ImportContext::default();
+        // the versions seen for the first time
+        context.set_yanked("foo", "1.0.0", true);
+        context.set_yanked("foo", "1.1.0", false);
+        assert!(context.yank_events.is_empty());
+
+        context.set_yanked("foo", "1.1.0", true);
+        context.set_yanked("foo", "1.0.0", false);
+        context.set_yanked("foo", "1.0.0", false);
+        let events: Vec<(&str, bool)> = context
+            .yank_events
+            .iter()
+            .map(|e| (e.version.as_str(), e.yanked))
+            .collect();
+        assert_eq!(events, [("1.1.0", true), ("1.0.0", false)]);
+    }
+}
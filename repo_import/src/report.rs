@@ -0,0 +1,242 @@
+//! A json report of an import, for monitoring the pipelines, such as
+//!
+//! ```json
+//! {"started_at": 1718000000, "finished_at": 1718003600,
+//!  "repos": {"imported": 1180, "unchanged": 12, "filtered": 5, "failed": 3},
+//!  "failed_repos": [{"url": "https://github.com/...", "reason": "..."}],
+//!  "crates": 5321, "versions": 61023,
+//!  "records": {"program": 5321, "depends_on": 402118, ...},
+//!  "skipped_manifests": [{"path": ".../Cargo.toml", "reason": "..."}],
+//!  "cycles": [{"versions": ["serde/1.0.203", "serde_derive/1.0.203"], "labels": ["depends_on", "dev_depends_on"]}],
+//!  "stages": [{"stage": "clone", "seconds": 1242.5, "processed": 1200, "failed": 3, "items": 0}, ...]}
+//! ```
+//!
+//! It's written with the import files into `IMPORT_REPORT`,
+//! `TUGRAPH_IMPORT_FILES_PG/import_report.json` by default, and started over after that,
+//! so each report covers a run.
+
+use crate::metrics::REPOS_PROCESSED;
+use crate::ImportContext;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// the skipped manifests kept in the report, the others are only counted
+const MAX_SKIPPED_MANIFESTS: usize = 10_000;
+/// the failed repos kept in the report, the others are only counted
+const MAX_FAILED_REPOS: usize = 10_000;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RepoCounts {
+    pub imported: usize,
+    /// skipped since their HEAD is unchanged
+    pub unchanged: usize,
+    /// skipped by the discovery filters
+    pub filtered: usize,
+    /// failed to clone or import
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedRepo {
+    pub url: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedManifest {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// the versions depending on each other, by the edges of the labels
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyCycle {
+    pub versions: Vec<String>,
+    pub labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageReport {
+    pub stage: String,
+    pub seconds: f64,
+    pub processed: usize,
+    pub failed: usize,
+    pub items: usize,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ImportReport {
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub repos: RepoCounts,
+    pub failed_repos: Vec<FailedRepo>,
+    pub crates: usize,
+    pub versions: usize,
+    /// the records exported of each table
+    pub records: BTreeMap<String, usize>,
+    pub skipped_manifests: Vec<SkippedManifest>,
+    pub skipped_manifests_total: usize,
+    pub cycles: Vec<DependencyCycle>,
+    pub stages: Vec<StageReport>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+lazy_static! {
+    static ref REPORT: Mutex<ImportReport> = Mutex::new(ImportReport {
+        started_at: unix_now(),
+        ..Default::default()
+    });
+}
+
+pub(crate) fn record_repo_imported() {
+    REPORT.lock().unwrap().repos.imported += 1;
+    REPOS_PROCESSED.with_label_values(&["imported"]).inc();
+}
+
+/// Start the report of a new run, once the last one is written.
+pub(crate) fn reset_report() {
+    *REPORT.lock().unwrap() = ImportReport {
+        started_at: unix_now(),
+        ..Default::default()
+    };
+}
+
+/// The repos imported so far, to tell whether a run imports anything.
+pub(crate) fn repos_imported() -> usize {
+    REPORT.lock().unwrap().repos.imported
+}
+
+pub(crate) fn record_repo_unchanged() {
+    REPORT.lock().unwrap().repos.unchanged += 1;
+    REPOS_PROCESSED.with_label_values(&["unchanged"]).inc();
+}
+
+pub(crate) fn record_repo_filtered() {
+    REPORT.lock().unwrap().repos.filtered += 1;
+    REPOS_PROCESSED.with_label_values(&["filtered"]).inc();
+}
+
+pub(crate) fn record_repo_failed(url: &str, reason: impl ToString) {
+    let mut report = REPORT.lock().unwrap();
+    report.repos.failed += 1;
+    if report.failed_repos.len() < MAX_FAILED_REPOS {
+        report.failed_repos.push(FailedRepo {
+            url: url.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+    REPOS_PROCESSED.with_label_values(&["failed"]).inc();
+}
+
+pub(crate) fn record_skipped_manifest(path: &Path, reason: impl ToString) {
+    let mut report = REPORT.lock().unwrap();
+    report.skipped_manifests_total += 1;
+    if report.skipped_manifests.len() < MAX_SKIPPED_MANIFESTS {
+        report.skipped_manifests.push(SkippedManifest {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+/// The records of a table, the last export wins since every output exports the same graph.
+pub(crate) fn record_records(table: &str, count: usize) {
+    REPORT
+        .lock()
+        .unwrap()
+        .records
+        .insert(table.to_string(), count);
+}
+
+/// The cycles of the graph, found again by every export.
+pub(crate) fn record_cycles(cycles: Vec<DependencyCycle>) {
+    REPORT.lock().unwrap().cycles = cycles;
+}
+
+pub(crate) fn record_stage(
+    stage: &str,
+    elapsed: Duration,
+    processed: usize,
+    failed: usize,
+    items: usize,
+) {
+    REPORT.lock().unwrap().stages.push(StageReport {
+        stage: stage.to_string(),
+        seconds: elapsed.as_secs_f64(),
+        processed,
+        failed,
+        items,
+    });
+}
+
+fn report_path() -> Option<PathBuf> {
+    env::var("IMPORT_REPORT")
+        .or_else(|_| {
+            env::var("TUGRAPH_IMPORT_FILES_PG").map(|dir| format!("{}/import_report.json", dir))
+        })
+        .ok()
+        .map(PathBuf::from)
+}
+
+impl ImportContext {
+    /// The report of the import so far.
+    pub fn import_report(&self) -> ImportReport {
+        let mut report = REPORT.lock().unwrap().clone();
+        report.finished_at = unix_now();
+        report.crates = self.programs.len();
+        report.versions = self.library_versions.len() + self.application_versions.len();
+        report
+    }
+
+    /// Write the report into `IMPORT_REPORT`.
+    pub(crate) fn write_import_report(&self) -> Result<(), Box<dyn Error>> {
+        let Some(path) = report_path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(&self.import_report())?)?;
+        tracing::info!("Wrote the import report into {:?}", path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_report() {
+        let path = Path::new("/repos/tokio/fuzz/Cargo.toml");
+        record_skipped_manifest(path, "invalid toml");
+        record_stage("import", Duration::from_millis(1500), 2, 1, 7);
+
+        let report = ImportContext::default().import_report();
+        assert!(report
+            .skipped_manifests
+            .iter()
+            .any(|s| s.path == path && s.reason == "invalid toml"));
+        assert!(report
+            .stages
+            .iter()
+            .any(|s| s.stage == "import" && s.seconds == 1.5 && s.processed == 2 && s.failed == 1));
+        assert!(report.finished_at >= report.started_at);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert!(json["repos"]["imported"].is_number());
+    }
+}
@@ -0,0 +1,251 @@
+//! Retry the network and git operations on the transient errors,
+//! with an exponential backoff and a jitter, so one timeout does not fail a whole repo.
+//!
+//! The policy is configured by `RETRY_MAX_ATTEMPTS` (4 by default),
+//! `RETRY_BASE_DELAY_MS` (500 by default) and `RETRY_MAX_DELAY_MS` (30000 by default).
+//!
+//! The clients of the web APIs are throttled by a [`RateLimiter`] each.
+
+use git2::{ErrorClass, ErrorCode};
+use reqwest::StatusCode;
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fmt::Display;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// the attempts including the first one
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// A random number in `0..n`, good enough for a jitter.
+fn random_below(n: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+    RandomState::new().build_hasher().finish() % n
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> Self {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            max_attempts: env_or("RETRY_MAX_ATTEMPTS", default.max_attempts).max(1),
+            base_delay: Duration::from_millis(env_or(
+                "RETRY_BASE_DELAY_MS",
+                default.base_delay.as_millis() as u64,
+            )),
+            max_delay: Duration::from_millis(env_or(
+                "RETRY_MAX_DELAY_MS",
+                default.max_delay.as_millis() as u64,
+            )),
+        }
+    }
+
+    /// The delay before the retry after the `attempt`-th failure (from 1),
+    /// a random one in the upper half of the exponential delay.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let half = exp.as_millis() as u64 / 2;
+        Duration::from_millis(half + random_below(half + 1))
+    }
+
+    /// Run `f` until it succeeds, fails with a permanent error, or runs out of attempts.
+    pub(crate) async fn retry<T, E, F, Fut>(
+        &self,
+        what: &str,
+        is_transient: fn(&E) -> bool,
+        mut f: F,
+    ) -> Result<T, E>
+    where
+        E: Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match f().await {
+                Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                    let delay = self.delay(attempt);
+                    tracing::warn!(
+                        "{} failed (attempt {}/{}), retry in {:?}: {}",
+                        what,
+                        attempt,
+                        self.max_attempts,
+                        delay,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// The blocking version of [`RetryPolicy::retry`], for the git operations,
+    /// which sleeps the thread, so it's called in `spawn_blocking` from the async code.
+    pub(crate) fn retry_blocking<T, E, F>(
+        &self,
+        what: &str,
+        is_transient: fn(&E) -> bool,
+        mut f: F,
+    ) -> Result<T, E>
+    where
+        E: Display,
+        F: FnMut() -> Result<T, E>,
+    {
+        let mut attempt = 1;
+        loop {
+            match f() {
+                Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                    let delay = self.delay(attempt);
+                    tracing::warn!(
+                        "{} failed (attempt {}/{}), retry in {:?}: {}",
+                        what,
+                        attempt,
+                        self.max_attempts,
+                        delay,
+                        e
+                    );
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+/// The minimum interval between two requests of a client.
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    last_request: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(interval: Duration) -> Self {
+        RateLimiter {
+            interval,
+            last_request: None,
+        }
+    }
+
+    /// The interval in milliseconds is read from `key`, `default_ms` by default.
+    pub(crate) fn from_env(key: &str, default_ms: u64) -> Self {
+        RateLimiter::new(Duration::from_millis(env_or(key, default_ms)))
+    }
+
+    /// Wait until the interval since the last request passes.
+    pub(crate) async fn wait(&mut self) {
+        if let Some(last) = self.last_request {
+            let elapsed = last.elapsed();
+            if elapsed < self.interval {
+                tokio::time::sleep(self.interval - elapsed).await;
+            }
+        }
+        self.last_request = Some(Instant::now());
+    }
+}
+
+/// Timeouts, connection errors, 429 and 5xx are transient.
+pub(crate) fn is_transient_http(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+        None => e.is_timeout() || e.is_connect() || e.is_request(),
+    }
+}
+
+/// The network errors of git are transient, but not the failed authentication.
+pub(crate) fn is_transient_git(e: &git2::Error) -> bool {
+    e.code() != ErrorCode::Auth
+        && e.code() != ErrorCode::Certificate
+        && matches!(
+            e.class(),
+            ErrorClass::Net | ErrorClass::Http | ErrorClass::Ssl | ErrorClass::Os
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_blocking() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+        };
+        let net_error = || git2::Error::new(ErrorCode::GenericError, ErrorClass::Net, "reset");
+
+        let mut calls = 0;
+        let res: Result<(), _> = policy.retry_blocking("clone", is_transient_git, || {
+            calls += 1;
+            Err(net_error())
+        });
+        assert!(res.is_err());
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let res = policy.retry_blocking("clone", is_transient_git, || {
+            calls += 1;
+            if calls < 2 {
+                Err(net_error())
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(res.unwrap(), 2);
+
+        let mut calls = 0;
+        let res: Result<(), _> = policy.retry_blocking("clone", is_transient_git, || {
+            calls += 1;
+            Err(git2::Error::new(
+                ErrorCode::Auth,
+                ErrorClass::Http,
+                "denied",
+            ))
+        });
+        assert!(res.is_err());
+        assert_eq!(calls, 1);
+
+        assert!(policy.delay(10) <= Duration::from_millis(2));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter() {
+        let mut limiter = RateLimiter::new(Duration::from_millis(20));
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.wait().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
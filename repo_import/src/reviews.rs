@@ -0,0 +1,360 @@
+//! Attach the community reviews to the imported versions: the audits of cargo-vet and the
+//! package reviews of cargo-crev, so the trust in a version is next to its dependencies.
+//!
+//! - `CARGO_VET_AUDITS` is a comma separated list of the `audits.toml` of cargo-vet, or of
+//!   the `supply-chain` directories containing them. A version is certified for the criteria
+//!   of its full audits, and of the delta audits `a -> b` from a version certified for them.
+//!   A `violation` audit flags the versions matching its requirement.
+//! - `CREV_PROOFS_DIR` is a directory of the cloned crev proof repos, whose `*.crev` files
+//!   hold the signed proofs. Only the package reviews of crates.io are counted, and the
+//!   lowest rating of a version is kept, to be conservative.
+
+use crate::ImportContext;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const CREV_REVIEW_BEGIN: &str = "----- BEGIN CREV PACKAGE REVIEW -----";
+const CREV_SIGNATURE_BEGIN: &str = "----- BEGIN CREV PACKAGE REVIEW SIGNATURE -----";
+
+/// The ratings of crev from the lowest.
+const CREV_RATINGS: [&str; 5] = ["dangerous", "negative", "neutral", "positive", "strong"];
+
+#[derive(Debug, Default, Deserialize)]
+struct VetAudits {
+    /// crate name -> the audits of it
+    #[serde(default)]
+    audits: BTreeMap<String, Vec<VetAudit>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VetAudit {
+    /// a criteria or a list of them
+    criteria: StringOrList,
+    version: Option<String>,
+    /// `1.0.0 -> 1.1.0`
+    delta: Option<String>,
+    /// a version requirement, such as `<1.2.0`
+    violation: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StringOrList {
+    String(String),
+    List(Vec<String>),
+}
+
+impl StringOrList {
+    fn to_vec(&self) -> Vec<&str> {
+        match self {
+            StringOrList::String(s) => vec![s.as_str()],
+            StringOrList::List(list) => list.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// What the audits of cargo-vet say about the versions of a crate.
+#[derive(Debug, Default)]
+struct CrateAudits {
+    /// version -> the criteria it's certified for
+    certified: HashMap<String, BTreeSet<String>>,
+    violations: Vec<VersionReq>,
+}
+
+impl CrateAudits {
+    fn is_violation(&self, version: &str) -> bool {
+        Version::parse(version).is_ok_and(|v| self.violations.iter().any(|req| req.matches(&v)))
+    }
+}
+
+/// Resolve the full, delta and violation audits of each crate.
+fn resolve_audits(files: &[VetAudits]) -> HashMap<String, CrateAudits> {
+    let mut resolved: HashMap<String, CrateAudits> = HashMap::new();
+    // (from, to, criteria) of each crate
+    let mut deltas: HashMap<&str, Vec<(&str, &str, &str)>> = HashMap::new();
+    for (name, audits) in files.iter().flat_map(|f| &f.audits) {
+        let crate_audits = resolved.entry(name.clone()).or_default();
+        for audit in audits {
+            let criteria = audit.criteria.to_vec();
+            if let Some(version) = &audit.version {
+                crate_audits
+                    .certified
+                    .entry(version.trim().to_string())
+                    .or_default()
+                    .extend(criteria.iter().map(|c| c.to_string()));
+            }
+            if let Some((from, to)) = audit.delta.as_deref().and_then(|d| d.split_once("->")) {
+                for criteria in &criteria {
+                    deltas.entry(name.as_str()).or_default().push((
+                        from.trim(),
+                        to.trim(),
+                        *criteria,
+                    ));
+                }
+            }
+            if let Some(violation) = &audit.violation {
+                match VersionReq::parse(violation) {
+                    Ok(req) => crate_audits.violations.push(req),
+                    Err(e) => tracing::debug!("Bad violation {} of {}: {}", violation, name, e),
+                }
+            }
+        }
+    }
+    // the chains of deltas, until nothing is certified any more
+    for (name, deltas) in deltas {
+        let certified = &mut resolved.get_mut(name).unwrap().certified;
+        loop {
+            let mut changed = false;
+            for (from, to, criteria) in &deltas {
+                if certified.get(*from).is_some_and(|c| c.contains(*criteria)) {
+                    changed |= certified
+                        .entry(to.to_string())
+                        .or_default()
+                        .insert(criteria.to_string());
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+    resolved
+}
+
+/// The `audits.toml` files of `CARGO_VET_AUDITS`.
+fn read_vet_audits(paths: &[PathBuf]) -> Vec<VetAudits> {
+    paths
+        .iter()
+        .map(|path| {
+            if path.is_dir() {
+                path.join("audits.toml")
+            } else {
+                path.clone()
+            }
+        })
+        .filter_map(|path| {
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    tracing::warn!("Failed to read {:?}: {}", path, e);
+                    return None;
+                }
+            };
+            match toml::from_str(&content) {
+                Ok(audits) => Some(audits),
+                Err(e) => {
+                    tracing::warn!("Bad cargo-vet audits {:?}: {}", path, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CrevReview {
+    name: String,
+    version: String,
+    rating: String,
+}
+
+/// The package reviews of crates.io in the content of a proof file, whose bodies are yaml.
+/// Only the fields needed are read, by the indentation, rather than a yaml parser.
+fn parse_crev_reviews(content: &str) -> Vec<CrevReview> {
+    let mut reviews = vec![];
+    for proof in content.split(CREV_REVIEW_BEGIN).skip(1) {
+        let body = proof.split(CREV_SIGNATURE_BEGIN).next().unwrap_or_default();
+        let mut section = "";
+        let mut fields: HashMap<(&str, &str), &str> = HashMap::new();
+        for line in body.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim().trim_matches('"');
+            if !line.starts_with(' ') {
+                section = key.trim();
+            } else {
+                fields.insert((section, key.trim()), value);
+            }
+        }
+        let field = |section: &'static str, key: &'static str| fields.get(&(section, key)).copied();
+        if field("package", "source") != Some("https://crates.io") {
+            continue;
+        }
+        if let (Some(name), Some(version), Some(rating)) = (
+            field("package", "name"),
+            field("package", "version"),
+            field("review", "rating"),
+        ) {
+            reviews.push(CrevReview {
+                name: name.to_string(),
+                version: version.to_string(),
+                rating: rating.to_string(),
+            });
+        }
+    }
+    reviews
+}
+
+fn read_crev_reviews(dir: &Path) -> Vec<CrevReview> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "crev"))
+        .filter_map(|e| fs::read_to_string(e.path()).ok())
+        .flat_map(|content| parse_crev_reviews(&content))
+        .collect()
+}
+
+fn rating_rank(rating: &str) -> Option<usize> {
+    CREV_RATINGS.iter().position(|r| *r == rating)
+}
+
+/// The review status of a version.
+#[derive(Debug, Default)]
+struct ReviewStatus {
+    audit_criteria: Option<String>,
+    audit_violation: Option<bool>,
+    crev_reviews: Option<i64>,
+    crev_rating: Option<String>,
+}
+
+impl ImportContext {
+    /// Attach the audits of cargo-vet in `vet_audits` and the reviews of cargo-crev in
+    /// `crev_dir` to the library and application versions.
+    pub fn import_reviews(&mut self, vet_audits: &[PathBuf], crev_dir: Option<&Path>) {
+        tracing::info!("Start to import the reviews");
+        let audits = resolve_audits(&read_vet_audits(vet_audits));
+        // (name, version) -> (the number of reviews, the lowest rating)
+        let mut crev: HashMap<(String, String), (i64, Option<usize>)> = HashMap::new();
+        for review in crev_dir.map(read_crev_reviews).unwrap_or_default() {
+            let entry = crev.entry((review.name, review.version)).or_default();
+            entry.0 += 1;
+            if let Some(rank) = rating_rank(&review.rating) {
+                entry.1 = Some(entry.1.map_or(rank, |r| r.min(rank)));
+            }
+        }
+
+        let status = |name: &str, version: &str| {
+            let mut status = ReviewStatus::default();
+            if !vet_audits.is_empty() {
+                let crate_audits = audits.get(name);
+                status.audit_violation =
+                    Some(crate_audits.is_some_and(|a| a.is_violation(version)));
+                status.audit_criteria = crate_audits
+                    .and_then(|a| a.certified.get(version))
+                    .map(|c| c.iter().cloned().collect::<Vec<_>>().join(","));
+            }
+            if crev_dir.is_some() {
+                let (count, rank) = crev
+                    .get(&(name.to_string(), version.to_string()))
+                    .copied()
+                    .unwrap_or_default();
+                status.crev_reviews = Some(count);
+                status.crev_rating = rank.map(|r| CREV_RATINGS[r].to_string());
+            }
+            status
+        };
+        let mut reviewed = 0;
+        for version in &mut self.library_versions {
+            let status = status(&version.name, &version.version);
+            reviewed +=
+                usize::from(status.audit_criteria.is_some() || status.crev_rating.is_some());
+            version.audit_criteria = status.audit_criteria;
+            version.audit_violation = status.audit_violation;
+            version.crev_reviews = status.crev_reviews;
+            version.crev_rating = status.crev_rating;
+        }
+        for version in &mut self.application_versions {
+            let status = status(&version.name, &version.version);
+            reviewed +=
+                usize::from(status.audit_criteria.is_some() || status.crev_rating.is_some());
+            version.audit_criteria = status.audit_criteria;
+            version.audit_violation = status.audit_violation;
+            version.crev_reviews = status.crev_reviews;
+            version.crev_rating = status.crev_rating;
+        }
+        tracing::info!(
+            "Finish to import the reviews of {} crates and {} crev reviews, {} versions reviewed",
+            audits.len(),
+            crev.len(),
+            reviewed
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_audits() {
+        let audits: VetAudits = toml::from_str(
+            r#"
+[[audits.serde]]
+who = "Alice <alice@example.com>"
+criteria = "safe-to-deploy"
+version = "1.0.130"
+
+[[audits.serde]]
+who = "Bob <bob@example.com>"
+criteria = ["safe-to-deploy", "safe-to-run"]
+delta = "1.0.136 -> 1.0.140"
+
+[[audits.serde]]
+who = "Alice <alice@example.com>"
+criteria = "safe-to-deploy"
+delta = "1.0.130 -> 1.0.136"
+
+[[audits.evil]]
+criteria = "safe-to-run"
+violation = "<0.2"
+"#,
+        )
+        .unwrap();
+        let resolved = resolve_audits(&[audits]);
+        let serde = &resolved["serde"];
+        let criteria = |v: &str| serde.certified.get(v).map(|c| c.len());
+        assert_eq!(criteria("1.0.136"), Some(1));
+        // safe-to-run is not certified for 1.0.136
+        assert_eq!(criteria("1.0.140"), Some(1));
+        assert!(resolved["evil"].is_violation("0.1.9"));
+        assert!(!resolved["evil"].is_violation("0.2.0"));
+    }
+
+    #[test]
+    fn test_parse_crev_reviews() {
+        let proof = r#"
+----- BEGIN CREV PACKAGE REVIEW -----
+version: -1
+date: "2021-01-01T00:00:00+00:00"
+from:
+  id-type: crev
+  id: FYlr8YoYGVvDwHQxqEIs89reKKDy-oWisoO0qXXEfHE
+package:
+  source: "https://crates.io"
+  name: log
+  version: 0.4.8
+  digest: 5ZUrB1mm8Pd2i7wNp7x0r5NWkDFO2RMufPDeaTxjCHs
+review:
+  thoroughness: low
+  understanding: medium
+  rating: positive
+----- BEGIN CREV PACKAGE REVIEW SIGNATURE -----
+4R2WjtU-avpBznmJYAl44H1lOYgETu3RSNhCDQ2tsC0
+----- END CREV PACKAGE REVIEW -----
+"#;
+        assert_eq!(
+            parse_crev_reviews(proof),
+            [CrevReview {
+                name: "log".to_string(),
+                version: "0.4.8".to_string(),
+                rating: "positive".to_string(),
+            }]
+        );
+    }
+}
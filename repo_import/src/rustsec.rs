@@ -0,0 +1,387 @@
+//! Cross-reference the imported versions with the RustSec advisory database
+//! (<https://github.com/rustsec/advisory-db>).
+//!
+//! Each advisory is a markdown file `crates/<crate>/<id>.md`, which starts with
+//! its metadata as toml in a ```` ```toml ```` block, followed by the title and description.
+//!
+//! The db is fetched at most once every `RUSTSEC_ADVISORY_DB_TTL_SECS`, a day by default,
+//! rather than on every write.
+
+use crate::retry::{is_transient_git, RetryPolicy};
+use crate::ImportContext;
+use git2::build::RepoBuilder;
+use git2::{Repository, ResetType};
+use model::tugraph_model::{Advisory, AffectedBy};
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+const ADVISORY_DB_URL: &str = "https://github.com/rustsec/advisory-db";
+
+const ADVISORY_DB_REFSPEC: &str = "+refs/heads/main:refs/remotes/origin/main";
+const ADVISORY_DB_REF: &str = "refs/remotes/origin/main";
+
+/// touched whenever the db is cloned or fetched
+const ADVISORY_DB_STAMP: &str = ".git/crates_pro_updated";
+const DEFAULT_ADVISORY_DB_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Deserialize)]
+struct AdvisoryFile {
+    advisory: AdvisoryMetadata,
+    #[serde(default)]
+    versions: AdvisoryVersions,
+}
+
+#[derive(Debug, Deserialize)]
+struct AdvisoryMetadata {
+    id: String,
+    package: String,
+    date: String,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    cvss: Option<String>,
+    #[serde(default)]
+    informational: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    /// the date withdrawn, the advisory should be ignored
+    #[serde(default)]
+    withdrawn: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AdvisoryVersions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+
+/// An advisory with its version requirements parsed
+struct ParsedAdvisory {
+    advisory: Advisory,
+    /// the patched and unaffected versions
+    safe: Vec<VersionReq>,
+}
+
+impl ParsedAdvisory {
+    fn affects(&self, version: &str) -> bool {
+        match Version::parse(version) {
+            Ok(version) => !self.safe.iter().any(|req| req.matches(&version)),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Split the toml metadata and the title of an advisory file.
+fn split_advisory(content: &str) -> Option<(&str, Option<&str>)> {
+    let rest = content.trim_start().strip_prefix("```toml")?;
+    let (toml, body) = rest.split_once("\n```")?;
+    let title = body
+        .lines()
+        .find_map(|l| l.strip_prefix("# "))
+        .map(str::trim);
+    Some((toml, title))
+}
+
+fn parse_advisory(content: &str) -> Result<Option<ParsedAdvisory>, Box<dyn Error>> {
+    let (metadata, title) = split_advisory(content).ok_or("no toml metadata")?;
+    let file: AdvisoryFile = toml::from_str(metadata)?;
+    if file.advisory.withdrawn.is_some() {
+        return Ok(None);
+    }
+
+    let safe = file
+        .versions
+        .patched
+        .iter()
+        .chain(&file.versions.unaffected)
+        .filter_map(|req| match VersionReq::parse(req) {
+            Ok(req) => Some(req),
+            Err(e) => {
+                tracing::warn!(
+                    "Bad version requirement {} of {}: {}",
+                    req,
+                    file.advisory.id,
+                    e
+                );
+                None
+            }
+        })
+        .collect();
+    let meta = file.advisory;
+    let advisory = Advisory {
+        severity: meta.cvss.as_deref().and_then(cvss_severity),
+        title: title.map(String::from),
+        patched: (!file.versions.patched.is_empty()).then(|| file.versions.patched.join(";")),
+        aliases: (!meta.aliases.is_empty()).then(|| meta.aliases.join(",")),
+        id: meta.id,
+        package: meta.package,
+        date: meta.date,
+        cvss: meta.cvss,
+        informational: meta.informational,
+        url: meta.url,
+    };
+    Ok(Some(ParsedAdvisory { advisory, safe }))
+}
+
+/// The CVSS v3 base score of a vector, such as `CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`.
+fn cvss_score(vector: &str) -> Option<f64> {
+    let mut parts = vector.split('/');
+    if !parts.next()?.starts_with("CVSS:3") {
+        return None;
+    }
+    let metrics: HashMap<&str, &str> = parts.filter_map(|p| p.split_once(':')).collect();
+    let changed = match *metrics.get("S")? {
+        "U" => false,
+        "C" => true,
+        _ => return None,
+    };
+    let av = match *metrics.get("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        "P" => 0.2,
+        _ => return None,
+    };
+    let ac = match *metrics.get("AC")? {
+        "L" => 0.77,
+        "H" => 0.44,
+        _ => return None,
+    };
+    let pr = match (*metrics.get("PR")?, changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None,
+    };
+    let ui = match *metrics.get("UI")? {
+        "N" => 0.85,
+        "R" => 0.62,
+        _ => return None,
+    };
+    let cia = |m: &str| match *metrics.get(m)? {
+        "H" => Some(0.56),
+        "L" => Some(0.22),
+        "N" => Some(0.0),
+        _ => None,
+    };
+    let iss = 1.0 - (1.0 - cia("C")?) * (1.0 - cia("I")?) * (1.0 - cia("A")?);
+    let impact = if changed {
+        7.52 * (iss - 0.029) - 3.25 * (iss - 0.02f64).powi(15)
+    } else {
+        6.42 * iss
+    };
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+    let exploitability = 8.22 * av * ac * pr * ui;
+    let score = if changed {
+        1.08 * (impact + exploitability)
+    } else {
+        impact + exploitability
+    };
+    // round up to one decimal
+    Some((score.min(10.0) * 10.0 - 1e-9).ceil() / 10.0)
+}
+
+fn cvss_severity(vector: &str) -> Option<String> {
+    let severity = match cvss_score(vector)? {
+        0.0 => "none",
+        s if s < 4.0 => "low",
+        s if s < 7.0 => "medium",
+        s if s < 9.0 => "high",
+        _ => "critical",
+    };
+    Some(severity.to_string())
+}
+
+fn advisory_db_ttl() -> Duration {
+    env::var("RUSTSEC_ADVISORY_DB_TTL_SECS")
+        .ok()
+        .and_then(|t| t.parse().ok())
+        .map_or(DEFAULT_ADVISORY_DB_TTL, Duration::from_secs)
+}
+
+fn stamp_path(db_path: &Path) -> PathBuf {
+    db_path.join(ADVISORY_DB_STAMP)
+}
+
+/// Whether the db at `db_path` was cloned or fetched within `ttl`.
+fn is_fresh(db_path: &Path, ttl: Duration) -> bool {
+    fs::metadata(stamp_path(db_path))
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|updated| SystemTime::now().duration_since(updated).ok())
+        .is_some_and(|age| age < ttl)
+}
+
+/// Clone the advisory db into `db_path`, or update it if it exists and is older than `ttl`.
+fn update_advisory_db(db_path: &Path, ttl: Duration) -> Result<(), git2::Error> {
+    let retry = RetryPolicy::from_env();
+    if !db_path.is_dir() {
+        tracing::info!("Cloning RustSec advisory db into {:?}", db_path);
+        retry.retry_blocking("Cloning RustSec advisory db", is_transient_git, || {
+            RepoBuilder::new()
+                .clone(ADVISORY_DB_URL, db_path)
+                .map(|_| ())
+        })?;
+    } else if is_fresh(db_path, ttl) {
+        tracing::info!("RustSec advisory db is fetched within {:?}", ttl);
+        return Ok(());
+    } else {
+        let repo = Repository::open(db_path)?;
+        retry.retry_blocking("Fetching RustSec advisory db", is_transient_git, || {
+            repo.find_remote("origin")?
+                .fetch(&[ADVISORY_DB_REFSPEC], None, None)
+        })?;
+        let head = repo.find_reference(ADVISORY_DB_REF)?.peel_to_commit()?;
+        repo.reset(head.as_object(), ResetType::Hard, None)?;
+    }
+    if let Err(e) = fs::write(stamp_path(db_path), b"") {
+        tracing::warn!("Failed to stamp the advisory db: {}", e);
+    }
+    Ok(())
+}
+
+fn read_advisories(db_path: &Path) -> Vec<ParsedAdvisory> {
+    WalkDir::new(db_path.join("crates"))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "md"))
+        .filter_map(|e| {
+            let content = fs::read_to_string(e.path()).ok()?;
+            match parse_advisory(&content) {
+                Ok(advisory) => advisory,
+                Err(err) => {
+                    tracing::warn!("Bad advisory {:?}: {}", e.path(), err);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+impl ImportContext {
+    /// Read the advisories in the RustSec advisory db at `db_path` (cloned if missing),
+    /// and link every imported version to the advisories affecting it.
+    pub async fn import_rustsec(&mut self, db_path: &Path) {
+        tracing::info!("Start to cross-reference RustSec advisories");
+        let db_path = db_path.to_path_buf();
+        let advisories = match tokio::task::spawn_blocking(move || {
+            if let Err(e) = update_advisory_db(&db_path, advisory_db_ttl()) {
+                tracing::warn!("Failed to update advisory db, use it as it is: {}", e);
+            }
+            read_advisories(&db_path)
+        })
+        .await
+        {
+            Ok(advisories) => advisories,
+            Err(e) => {
+                tracing::error!("Failed to read the advisory db: {}", e);
+                return;
+            }
+        };
+
+        let mut by_package: HashMap<&str, Vec<&ParsedAdvisory>> = HashMap::new();
+        for advisory in &advisories {
+            by_package
+                .entry(&advisory.advisory.package)
+                .or_default()
+                .push(advisory);
+        }
+
+        let versions = self
+            .library_versions
+            .iter()
+            .map(|v| (&v.name, &v.version, &v.name_and_version))
+            .chain(
+                self.application_versions
+                    .iter()
+                    .map(|v| (&v.name, &v.version, &v.name_and_version)),
+            );
+        let mut affected_by = vec![];
+        for (name, version, name_and_version) in versions {
+            for advisory in by_package.get(name.as_str()).into_iter().flatten() {
+                if advisory.affects(version) {
+                    affected_by.push(AffectedBy {
+                        SRC_ID: name_and_version.clone(),
+                        DST_ID: advisory.advisory.id.clone(),
+                    });
+                }
+            }
+        }
+
+        self.affected_by = affected_by;
+        self.advisories = advisories.into_iter().map(|a| a.advisory).collect();
+        tracing::info!(
+            "Finish to cross-reference {} advisories, {} affected versions",
+            self.advisories.len(),
+            self.affected_by.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cvss_severity() {
+        let critical = "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H";
+        assert_eq!(cvss_score(critical), Some(9.8));
+        assert_eq!(cvss_severity(critical).as_deref(), Some("critical"));
+        let medium = "CVSS:3.1/AV:N/AC:L/PR:N/UI:R/S:C/C:L/I:L/A:N";
+        assert_eq!(cvss_score(medium), Some(6.1));
+        assert_eq!(cvss_severity("CVSS:2.0/AV:N"), None);
+    }
+
+    #[test]
+    fn test_advisory_db_ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        let ttl = Duration::from_secs(60);
+        assert!(!is_fresh(dir.path(), ttl));
+
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(stamp_path(dir.path()), b"").unwrap();
+        assert!(is_fresh(dir.path(), ttl));
+        assert!(!is_fresh(dir.path(), Duration::ZERO));
+        // a fresh db is not fetched, so a db which is not a repo is kept as it is
+        assert!(update_advisory_db(dir.path(), ttl).is_ok());
+    }
+
+    #[test]
+    fn test_parse_advisory() {
+        let content = r#"```toml
+[advisory]
+id = "RUSTSEC-2021-0001"
+package = "foo"
+date = "2021-01-01"
+aliases = ["CVE-2021-0001"]
+
+[versions]
+patched = [">= 1.2.3"]
+unaffected = ["< 1.0.0"]
+```
+
+# Something is wrong
+
+Details.
+"#;
+        let parsed = parse_advisory(content).unwrap().unwrap();
+        assert_eq!(parsed.advisory.title.as_deref(), Some("Something is wrong"));
+        assert_eq!(parsed.advisory.patched.as_deref(), Some(">= 1.2.3"));
+        assert!(parsed.affects("1.2.0"));
+        assert!(!parsed.affects("1.2.3"));
+        assert!(!parsed.affects("0.9.0"));
+    }
+}
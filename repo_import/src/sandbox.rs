@@ -0,0 +1,181 @@
+//! Run the builds of untrusted crates, whose build scripts and proc-macros are arbitrary code,
+//! in a sandbox of [bubblewrap](https://github.com/containers/bubblewrap):
+//! - without the network, the sources are fetched into its cargo home before,
+//!   which runs no code of the crates,
+//! - as `nobody` in a user namespace, which sees the system and the toolchains read-only,
+//!   and only its working directory writable,
+//! - with the memory of a process limited to `IMPORT_SANDBOX_MEMORY_MB` (4096 by default),
+//!   and the parallel jobs of cargo to `IMPORT_SANDBOX_JOBS` (2 by default),
+//! - killed after a timeout, which also limits the CPU time of a process.
+//!
+//! The toolchains are the ones in `RUSTUP_HOME` and `CARGO_HOME/bin`, `~/.rustup` and
+//! `~/.cargo/bin` by default. Without `bwrap`, `prlimit` or `timeout` installed, the builds
+//! are skipped rather than run on the host.
+
+use once_cell::sync::Lazy;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+const DEFAULT_MEMORY_MB: u64 = 4096;
+const DEFAULT_JOBS: u64 = 2;
+
+/// The uid and gid of `nobody`.
+const NOBODY: &str = "65534";
+
+/// The system directories seen read-only in the sandbox, if they exist.
+const SYSTEM_DIRS: &[&str] = &["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc"];
+
+/// The cargo home of a sandbox, in its working directory.
+const CARGO_HOME: &str = "cargo-home";
+
+static SANDBOX: Lazy<Option<Sandbox>> = Lazy::new(|| match Sandbox::from_env() {
+    Ok(sandbox) => Some(sandbox),
+    Err(e) => {
+        tracing::warn!("Skip the builds of the crates without a sandbox: {}", e);
+        None
+    }
+});
+
+/// The sandbox to build the crates in, none if it can't be set up.
+pub(crate) fn sandbox() -> Option<&'static Sandbox> {
+    SANDBOX.as_ref()
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Sandbox {
+    memory_mb: u64,
+    jobs: u64,
+    rustup_home: PathBuf,
+    cargo_bin: PathBuf,
+}
+
+fn home() -> PathBuf {
+    env::var_os("HOME").map_or_else(|| PathBuf::from("/root"), PathBuf::from)
+}
+
+fn is_installed(program: &str) -> bool {
+    Command::new(program)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+impl Sandbox {
+    fn from_env() -> Result<Self, String> {
+        if let Some(program) = ["bwrap", "prlimit", "timeout"]
+            .into_iter()
+            .find(|p| !is_installed(p))
+        {
+            return Err(format!("{} is not installed", program));
+        }
+        let number = |key: &str, default: u64| {
+            env::var(key)
+                .ok()
+                .and_then(|n| n.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(default)
+        };
+        let cargo_home =
+            env::var_os("CARGO_HOME").map_or_else(|| home().join(".cargo"), PathBuf::from);
+        Ok(Sandbox {
+            memory_mb: number("IMPORT_SANDBOX_MEMORY_MB", DEFAULT_MEMORY_MB),
+            jobs: number("IMPORT_SANDBOX_JOBS", DEFAULT_JOBS),
+            rustup_home: env::var_os("RUSTUP_HOME")
+                .map_or_else(|| home().join(".rustup"), PathBuf::from),
+            cargo_bin: cargo_home.join("bin"),
+        })
+    }
+
+    /// The command running in the sandbox the program and the args appended to it,
+    /// in `dir`, the only directory writable, offline and killed after `timeout`.
+    /// The environment of the host is not passed to it.
+    pub(crate) fn command(&self, dir: &Path, timeout: Duration) -> Command {
+        let secs = timeout.as_secs().max(1).to_string();
+        let path = format!("{}:/usr/local/bin:/usr/bin:/bin", self.cargo_bin.display());
+        let mut command = Command::new("timeout");
+        command
+            .args(["--signal=KILL", &secs, "prlimit"])
+            .arg(format!("--as={}", self.memory_mb * 1024 * 1024))
+            .arg(format!("--cpu={}", secs))
+            .args([
+                "--",
+                "bwrap",
+                "--unshare-all",
+                "--die-with-parent",
+                "--new-session",
+            ])
+            .args(["--uid", NOBODY, "--gid", NOBODY]);
+        for system_dir in SYSTEM_DIRS {
+            command.args(["--ro-bind-try", system_dir, system_dir]);
+        }
+        for toolchains in [&self.rustup_home, &self.cargo_bin] {
+            command.arg("--ro-bind-try").arg(toolchains).arg(toolchains);
+        }
+        command
+            .args(["--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"])
+            .arg("--bind")
+            .arg(dir)
+            .arg(dir)
+            .arg("--chdir")
+            .arg(dir)
+            .args(["--clearenv", "--setenv", "HOME", "/tmp", "--setenv", "PATH"])
+            .arg(path)
+            .args(["--setenv", "RUSTUP_HOME"])
+            .arg(&self.rustup_home)
+            .args(["--setenv", "CARGO_HOME"])
+            .arg(dir.join(CARGO_HOME))
+            .args(["--setenv", "CARGO_BUILD_JOBS"])
+            .arg(self.jobs.to_string())
+            .args(["--setenv", "CARGO_NET_OFFLINE", "true", "--"]);
+        command
+    }
+}
+
+/// `cargo fetch` of `manifest_path` into the cargo home of the sandbox in `dir`,
+/// by `toolchain` or the default one. It's run on the host, since it needs the network.
+pub(crate) fn fetch_command(dir: &Path, toolchain: Option<&str>, manifest_path: &Path) -> Command {
+    let mut command = Command::new("cargo");
+    if let Some(toolchain) = toolchain {
+        command.arg(format!("+{}", toolchain));
+    }
+    command
+        .args(["fetch", "--quiet", "--manifest-path"])
+        .arg(manifest_path)
+        // not to read the cargo configs of the repo
+        .current_dir(dir)
+        .env("CARGO_HOME", dir.join(CARGO_HOME));
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_command() {
+        let sandbox = Sandbox {
+            memory_mb: 1024,
+            jobs: 2,
+            rustup_home: PathBuf::from("/home/a/.rustup"),
+            cargo_bin: PathBuf::from("/home/a/.cargo/bin"),
+        };
+        let mut command = sandbox.command(Path::new("/tmp/build"), Duration::from_secs(60));
+        command.args(["cargo", "build"]);
+        let args: Vec<String> = command
+            .get_args()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect();
+        assert_eq!(command.get_program(), "timeout");
+        assert_eq!(args[..3], ["--signal=KILL", "60", "prlimit"]);
+        assert!(args.contains(&format!("--as={}", 1024 * 1024 * 1024)));
+        assert!(args.contains(&"--unshare-all".to_string()));
+        // only the working directory is writable
+        let bind = args.iter().position(|a| a == "--bind").unwrap();
+        assert_eq!(args[bind + 1..bind + 3], ["/tmp/build", "/tmp/build"]);
+        assert_eq!(args[args.len() - 3..], ["--", "cargo", "build"]);
+    }
+}
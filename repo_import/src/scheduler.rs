@@ -0,0 +1,338 @@
+//! A long-running mode re-importing some sources on cron schedules, such as
+//!
+//! ```toml
+//! [[source]]
+//! name = "rust-lang"
+//! kind = "repos"              # a url list, cloned into `dir` or `NEW_CRATES_DIR`
+//! path = "lists/rust-lang.txt"
+//! cron = "0 */6 * * *"
+//!
+//! [[source]]
+//! name = "index"
+//! kind = "cratesio-index"     # the new versions in the crates.io index
+//! path = "/data/crates.io-index"
+//! cron = "*/10 * * * *"
+//! ```
+//!
+//! where `kind` is `repos`, `local` or `cratesio-index`, and `cron` is a cron expression of
+//! `minute hour day-of-month month day-of-week` in UTC, or `@hourly`, `@daily` or `@weekly`.
+//!
+//! The sources share a context, so the import files written after each run cover all of them.
+//! The context starts from the last checkpoint, see [`ImportContext::resume_run`].
+//! A run is skipped if the previous run of the source is still in progress.
+
+use crate::clone::CloneOptions;
+use crate::report::repos_imported;
+use crate::ImportContext;
+use chrono::{DateTime, Datelike, TimeDelta, Timelike, Utc};
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// The fields of a cron expression, a bit for each value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// whether the day of month or the day of week is `*`, see [`CronSchedule::matches_day`]
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// A field such as `*`, `*/15`, `1-5`, `0,30` or `8-18/2`.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .map_err(|_| format!("invalid step {}", part))?,
+            ),
+            None => (part, 1),
+        };
+        let parse = |s: &str| {
+            s.parse::<u32>()
+                .map_err(|_| format!("invalid value {}", part))
+        };
+        let (lo, hi) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((lo, hi)) => (parse(lo)?, parse(hi)?),
+            // `5/10` is from 5 to the max
+            None if step > 1 => (parse(range)?, max),
+            None => (parse(range)?, parse(range)?),
+        };
+        if step == 0 || lo < min || hi > max || lo > hi {
+            return Err(format!("{} is out of {}-{}", part, min, max));
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+impl CronSchedule {
+    pub(crate) fn parse(expr: &str) -> Result<Self, String> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            expr => expr,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("expected 5 fields in the cron expression {}", expr));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        // 7 is sunday too
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(CronSchedule {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    /// If both the day of month and the day of week are restricted, either of them matches,
+    /// as the classic cron.
+    fn matches_day(&self, t: &DateTime<Utc>) -> bool {
+        let day = self.days & (1 << t.day()) != 0;
+        let weekday = self.weekdays & (1 << t.weekday().num_days_from_sunday()) != 0;
+        let day = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            (false, true) => day,
+            (true, false) => weekday,
+            (true, true) => true,
+        };
+        day && self.months & (1 << t.month()) != 0
+    }
+
+    /// The first time after `after` matching the schedule, within 4 years for `0 0 29 2 *`.
+    pub(crate) fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = after.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let end = t + TimeDelta::days(4 * 366);
+        while t < end {
+            if !self.matches_day(&t) {
+                t = t.date_naive().succ_opt()?.and_hms_opt(0, 0, 0)?.and_utc();
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + TimeDelta::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += TimeDelta::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SourceKind {
+    Repos,
+    Local,
+    CratesioIndex,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduledSource {
+    pub name: String,
+    pub kind: SourceKind,
+    pub path: PathBuf,
+    pub cron: String,
+    /// the directory to clone the repos into, `NEW_CRATES_DIR` by default
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleFile {
+    #[serde(default, rename = "source")]
+    sources: Vec<ScheduledSource>,
+}
+
+/// Run a source once, return whether anything is imported.
+async fn run_source(context: &mut ImportContext, source: &ScheduledSource) -> Result<bool, String> {
+    let imported = repos_imported();
+    match source.kind {
+        SourceKind::Repos => {
+            let dir = source.dir.clone().unwrap_or_else(|| {
+                std::env::var("NEW_CRATES_DIR")
+                    .unwrap_or_else(|_| "/mnt/crates/local_crates_file/".to_string())
+                    .into()
+            });
+            context
+                .import_from_url_list(&source.path, &dir, CloneOptions::from_env())
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(repos_imported() > imported)
+        }
+        SourceKind::Local => {
+            context
+                .import_local(&source.path.to_string_lossy())
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(repos_imported() > imported)
+        }
+        SourceKind::CratesioIndex => context
+            .sync_cratesio_index(&source.path)
+            .await
+            .map(|count| count > 0)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Clears the flag of a running source when its run ends, by a panic too.
+struct RunningGuard(Arc<AtomicBool>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+async fn schedule_source(
+    context: Arc<Mutex<ImportContext>>,
+    source: ScheduledSource,
+    schedule: CronSchedule,
+) {
+    let source = Arc::new(source);
+    let running = Arc::new(AtomicBool::new(false));
+    loop {
+        let Some(next) = schedule.next_after(Utc::now()) else {
+            tracing::error!("The schedule of {} never fires", source.name);
+            return;
+        };
+        tracing::info!("The next run of {} is at {}", source.name, next);
+        let wait = (next - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        if running.swap(true, Ordering::SeqCst) {
+            tracing::warn!(
+                "Skip a run of {}, the last one is still running",
+                source.name
+            );
+            continue;
+        }
+        let context = Arc::clone(&context);
+        let source = Arc::clone(&source);
+        let running = Arc::clone(&running);
+        tokio::spawn(async move {
+            let _running = RunningGuard(running);
+            let mut context = context.lock().await;
+            tracing::info!("Start the scheduled run of {}", source.name);
+            context.start_run();
+            match run_source(&mut context, &source).await {
+                Ok(true) => {
+                    context.write_tugraph_import_files().await;
+                    context.save_import_cache();
+                    context.finish_run().await;
+                }
+                Ok(false) => {
+                    tracing::info!("Nothing new in {}", source.name);
+                    context.finish_run().await;
+                }
+                // the run is resumed by the next one
+                Err(e) => tracing::error!("Failed to run {}: {}", source.name, e),
+            }
+        });
+    }
+}
+
+/// Read the sources of a schedule file, and check their cron expressions.
+fn load_schedule(path: &Path) -> Result<Vec<(ScheduledSource, CronSchedule)>, Box<dyn Error>> {
+    let file: ScheduleFile = toml::from_str(&fs::read_to_string(path)?)?;
+    if file.sources.is_empty() {
+        return Err(format!("no sources in {:?}", path).into());
+    }
+    file.sources
+        .into_iter()
+        .map(|source| {
+            let schedule = CronSchedule::parse(&source.cron)
+                .map_err(|e| format!("invalid cron of {}: {}", source.name, e))?;
+            Ok((source, schedule))
+        })
+        .collect()
+}
+
+/// Re-import the sources of a schedule file on their schedules, forever.
+pub async fn run_schedule(path: &Path) -> Result<(), Box<dyn Error>> {
+    let sources = load_schedule(path)?;
+    let mut context = ImportContext::resume_run().await?;
+    context.load_import_cache();
+    let context = Arc::new(Mutex::new(context));
+
+    let tasks: Vec<_> = sources
+        .into_iter()
+        .map(|(source, schedule)| {
+            tracing::info!("Schedule {} at {}", source.name, source.cron);
+            tokio::spawn(schedule_source(Arc::clone(&context), source, schedule))
+        })
+        .collect();
+    for task in tasks {
+        task.await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_cron_schedule() {
+        let at = |y, m, d, h, min| Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap();
+        // a wednesday
+        let now = at(2024, 6, 12, 10, 7);
+
+        let every_15 = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(every_15.next_after(now), Some(at(2024, 6, 12, 10, 15)));
+
+        let six_hourly = CronSchedule::parse("0 */6 * * *").unwrap();
+        assert_eq!(six_hourly.next_after(now), Some(at(2024, 6, 12, 12, 0)));
+
+        let weekdays = CronSchedule::parse("30 2 * * 1-5").unwrap();
+        assert_eq!(
+            weekdays.next_after(at(2024, 6, 14, 3, 0)),
+            Some(at(2024, 6, 17, 2, 30))
+        );
+
+        let sunday = CronSchedule::parse("@weekly").unwrap();
+        assert_eq!(sunday, CronSchedule::parse("0 0 * * 7").unwrap());
+        assert_eq!(sunday.next_after(now), Some(at(2024, 6, 16, 0, 0)));
+
+        let leap = CronSchedule::parse("0 0 29 2 *").unwrap();
+        assert_eq!(leap.next_after(now), Some(at(2028, 2, 29, 0, 0)));
+
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_running_guard() {
+        let running = Arc::new(AtomicBool::new(true));
+        let guard = RunningGuard(Arc::clone(&running));
+        let task = tokio::spawn(async move {
+            let _running = guard;
+            panic!("a failed run");
+        });
+        assert!(task.await.is_err());
+        assert!(!running.load(Ordering::SeqCst));
+    }
+}
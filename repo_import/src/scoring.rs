@@ -0,0 +1,71 @@
+//! The parts shared by the scores of the crates, i.e. [`crate::popularity`],
+//! [`crate::maintenance`] and [`crate::quality`].
+
+use model::tugraph_model::{ApplicationVersion, LibraryVersion};
+use std::collections::HashMap;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// Read the weight `<prefix>_<key>` of a score, such as `IMPORT_POPULARITY_DOWNLOADS`,
+/// the default if it's unset, negative or not a number.
+pub(crate) fn weights_from_env(prefix: &str) -> impl Fn(&str, f64) -> f64 + '_ {
+    move |key, default| {
+        env::var(format!("{}_{}", prefix, key))
+            .ok()
+            .and_then(|w| w.parse::<f64>().ok())
+            .filter(|w| w.is_finite() && *w >= 0.0)
+            .unwrap_or(default)
+    }
+}
+
+/// `0.5 ^ (age / half_life)`, from 1 for a fresh signal to 0.
+pub(crate) fn halved(age: f64, half_life: f64) -> f64 {
+    0.5f64.powf(age.max(0.0) / half_life)
+}
+
+pub(crate) fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// The last release of each program by its id, published or committed,
+/// in seconds since the epoch.
+pub(crate) fn last_releases<'a>(
+    library_versions: &'a [LibraryVersion],
+    application_versions: &'a [ApplicationVersion],
+) -> HashMap<&'a str, i64> {
+    let mut last_releases: HashMap<&str, i64> = HashMap::new();
+    for (id, time) in library_versions
+        .iter()
+        .map(|v| (v.id.as_str(), v.published_at.or(v.commit_time)))
+        .chain(
+            application_versions
+                .iter()
+                .map(|v| (v.id.as_str(), v.published_at.or(v.commit_time))),
+        )
+    {
+        if let Some(time) = time {
+            let last = last_releases.entry(id).or_insert(time);
+            *last = (*last).max(time);
+        }
+    }
+    last_releases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scoring() {
+        let weight = weights_from_env("IMPORT_SCORING_UNSET");
+        assert_eq!(weight("RECENCY", 0.1), 0.1);
+
+        assert_eq!(halved(0.0, 7.0), 1.0);
+        assert_eq!(halved(14.0, 7.0), 0.25);
+        assert_eq!(halved(-1.0, 7.0), 1.0);
+    }
+}
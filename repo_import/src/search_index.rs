@@ -0,0 +1,325 @@
+//! A full-text index of the crates built with tantivy, over their names, descriptions,
+//! keywords, categories and readmes, so that the search doesn't need a database.
+//!
+//! The import rebuilds it in `SEARCH_INDEX_DIR` with `IMPORT_SEARCH_INDEX=1`, and the query
+//! API searches it there. A crate matches by any of its fields, weighted from the name to
+//! the readme, and the crate named exactly as the query, `-` and `_` aside, is ranked first.
+
+use crate::ImportContext;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tantivy::collector::{Count, TopDocs};
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, BoostQuery, Occur, Query, QueryParser, TermQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, Value, FAST, INDEXED, STORED, STRING, TEXT,
+};
+use tantivy::{Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+/// The memory of the index writer, split among its threads.
+const WRITER_MEMORY: usize = 100_000_000;
+
+/// The boosts of the fields matching the query.
+const FIELD_BOOSTS: [(&str, f32); 5] = [
+    ("name", 4.0),
+    ("keywords", 2.0),
+    ("categories", 1.5),
+    ("description", 1.5),
+    ("readme", 1.0),
+];
+
+/// The boost of the crate named as the query.
+const EXACT_NAME_BOOST: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Fields {
+    id: Field,
+    name: Field,
+    exact_name: Field,
+    description: Field,
+    keywords: Field,
+    categories: Field,
+    readme: Field,
+    namespace: Field,
+    max_version: Field,
+    downloads: Field,
+}
+
+impl Fields {
+    fn schema() -> (Schema, Fields) {
+        let mut builder = Schema::builder();
+        let fields = Fields {
+            id: builder.add_text_field("id", STRING | STORED),
+            name: builder.add_text_field("name", TEXT | STORED),
+            exact_name: builder.add_text_field("exact_name", STRING),
+            description: builder.add_text_field("description", TEXT | STORED),
+            keywords: builder.add_text_field("keywords", TEXT | STORED),
+            categories: builder.add_text_field("categories", TEXT | STORED),
+            readme: builder.add_text_field("readme", TEXT),
+            namespace: builder.add_text_field("namespace", STORED),
+            max_version: builder.add_text_field("max_version", STORED),
+            downloads: builder.add_i64_field("downloads", INDEXED | FAST | STORED),
+        };
+        (builder.build(), fields)
+    }
+}
+
+/// `-` and `_` are the same in the names of crates.io.
+fn exact_name(name: &str) -> String {
+    name.trim().to_lowercase().replace('-', "_")
+}
+
+/// A crate to index.
+#[derive(Debug, Clone, Default)]
+pub struct SearchDocument {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// separated by commas
+    pub keywords: Option<String>,
+    /// separated by commas
+    pub categories: Option<String>,
+    pub readme: Option<String>,
+    pub namespace: Option<String>,
+    pub max_version: Option<String>,
+    pub downloads: i64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SearchHit {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub keywords: Option<String>,
+    pub categories: Option<String>,
+    pub namespace: Option<String>,
+    pub max_version: Option<String>,
+    pub downloads: i64,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResults {
+    /// the number of the crates matched
+    pub total: usize,
+    pub hits: Vec<SearchHit>,
+}
+
+pub struct SearchIndex {
+    index: Index,
+    reader: IndexReader,
+    fields: Fields,
+}
+
+impl SearchIndex {
+    /// Open the index in `dir`, or create an empty one.
+    pub fn open(dir: &Path) -> tantivy::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let (schema, _) = Fields::schema();
+        let directory = MmapDirectory::open(dir)?;
+        Self::with_index(Index::open_or_create(directory, schema)?)
+    }
+
+    fn with_index(index: Index) -> tantivy::Result<Self> {
+        let (_, fields) = Fields::schema();
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        Ok(SearchIndex {
+            index,
+            reader,
+            fields,
+        })
+    }
+
+    /// Replace all the documents with `documents`, returns the number indexed.
+    pub fn rebuild(
+        &self,
+        documents: impl IntoIterator<Item = SearchDocument>,
+    ) -> tantivy::Result<usize> {
+        let mut writer: IndexWriter = self.index.writer(WRITER_MEMORY)?;
+        writer.delete_all_documents()?;
+        let f = self.fields;
+        let mut count = 0;
+        for d in documents {
+            let mut doc = TantivyDocument::default();
+            doc.add_text(f.id, &d.id);
+            doc.add_text(f.name, &d.name);
+            doc.add_text(f.exact_name, exact_name(&d.name));
+            let optional = [
+                (f.description, &d.description),
+                (f.keywords, &d.keywords),
+                (f.categories, &d.categories),
+                // the readme is stored in a single line
+                (f.readme, &d.readme.as_ref().map(|r| r.replace("\\n", "\n"))),
+                (f.namespace, &d.namespace),
+                (f.max_version, &d.max_version),
+            ];
+            for (field, value) in optional {
+                if let Some(value) = value.as_deref().filter(|v| !v.is_empty()) {
+                    doc.add_text(field, value);
+                }
+            }
+            doc.add_i64(f.downloads, d.downloads);
+            writer.add_document(doc)?;
+            count += 1;
+        }
+        writer.commit()?;
+        self.reader.reload()?;
+        Ok(count)
+    }
+
+    fn query(&self, text: &str) -> Box<dyn Query> {
+        let f = self.fields;
+        let mut parser = QueryParser::for_index(
+            &self.index,
+            vec![f.name, f.keywords, f.categories, f.description, f.readme],
+        );
+        for (name, boost) in FIELD_BOOSTS {
+            if let Ok(field) = self.index.schema().get_field(name) {
+                parser.set_field_boost(field, boost);
+            }
+        }
+        // a malformed query such as `tokio AND` is searched as far as it's valid
+        let (parsed, _) = parser.parse_query_lenient(text);
+        let exact = TermQuery::new(
+            Term::from_field_text(f.exact_name, &exact_name(text)),
+            IndexRecordOption::Basic,
+        );
+        Box::new(BooleanQuery::new(vec![
+            (Occur::Should, parsed),
+            (
+                Occur::Should,
+                Box::new(BoostQuery::new(Box::new(exact), EXACT_NAME_BOOST)),
+            ),
+        ]))
+    }
+
+    /// The crates matching `text`, the most relevant first, skipping `offset` of them.
+    pub fn search(
+        &self,
+        text: &str,
+        limit: usize,
+        offset: usize,
+    ) -> tantivy::Result<SearchResults> {
+        let searcher = self.reader.searcher();
+        let query = self.query(text);
+        let (top, total) = searcher.search(
+            &*query,
+            &(TopDocs::with_limit(limit.max(1)).and_offset(offset), Count),
+        )?;
+        let f = self.fields;
+        let mut hits = Vec::with_capacity(top.len());
+        for (score, address) in top {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let text = |field| {
+                doc.get_first(field)
+                    .and_then(|v| v.as_str())
+                    .map(String::from)
+            };
+            hits.push(SearchHit {
+                id: text(f.id).unwrap_or_default(),
+                name: text(f.name).unwrap_or_default(),
+                description: text(f.description),
+                keywords: text(f.keywords),
+                categories: text(f.categories),
+                namespace: text(f.namespace),
+                max_version: text(f.max_version),
+                downloads: doc
+                    .get_first(f.downloads)
+                    .and_then(|v| v.as_i64())
+                    .unwrap_or(0),
+                score,
+            });
+        }
+        Ok(SearchResults { total, hits })
+    }
+}
+
+impl ImportContext {
+    /// Rebuild the full-text index in `dir` with the imported crates.
+    pub(crate) fn write_search_index(&self, dir: &Path) -> tantivy::Result<()> {
+        let downloads: HashMap<&str, i64> = self
+            .libraries
+            .iter()
+            .map(|l| (l.id.as_str(), l.downloads))
+            .collect();
+        let mut seen = HashSet::new();
+        let documents = self
+            .programs
+            .iter()
+            .filter(|p| seen.insert(p.id.as_str()))
+            .map(|p| SearchDocument {
+                id: p.id.clone(),
+                name: p.name.clone(),
+                description: p.description.clone(),
+                keywords: p.keywords.clone(),
+                categories: p.categories.clone(),
+                readme: p.readme_content.clone(),
+                namespace: p.namespace.clone(),
+                max_version: p.max_version.clone(),
+                downloads: downloads.get(p.id.as_str()).copied().unwrap_or(0),
+            });
+        let count = SearchIndex::open(dir)?.rebuild(documents)?;
+        tracing::info!("Indexed {} crates for the search in {:?}", count, dir);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let (schema, _) = Fields::schema();
+        let index = SearchIndex::with_index(Index::create_in_ram(schema)).unwrap();
+        let documents = [
+            (
+                "serde",
+                "A generic serialization/deserialization framework",
+                "serde,serialization",
+                500,
+            ),
+            (
+                "serde_json",
+                "A JSON serialization file format",
+                "json,serde,serialization",
+                400,
+            ),
+            (
+                "reqwest",
+                "higher level HTTP client library",
+                "http,request,client",
+                300,
+            ),
+        ]
+        .map(|(name, description, keywords, downloads)| SearchDocument {
+            id: name.to_string(),
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            keywords: Some(keywords.to_string()),
+            readme: Some("# Title\\n\\nSome JSON examples.".to_string()),
+            downloads,
+            ..Default::default()
+        });
+        assert_eq!(index.rebuild(documents).unwrap(), 3);
+
+        let names = |text: &str| -> Vec<String> {
+            let results = index.search(text, 10, 0).unwrap();
+            results.hits.into_iter().map(|h| h.name).collect()
+        };
+        assert_eq!(names("serde-json")[0], "serde_json");
+        assert_eq!(names("http client"), ["reqwest"]);
+        assert_eq!(names("json").len(), 3);
+        assert!(names("nothing").is_empty());
+        // the malformed query doesn't fail
+        assert!(!names("serde AND").is_empty());
+
+        let results = index.search("json", 1, 1).unwrap();
+        assert_eq!(results.total, 3);
+        assert_eq!(results.hits.len(), 1);
+    }
+}
@@ -0,0 +1,121 @@
+//! Check whether the version bump of each release matches its API changes,
+//! e.g. a breaking change in a patch release violates semver.
+//!
+//! Following cargo, the left-most non-zero component is the major one,
+//! so `0.3.0 -> 0.4.0` may break and `0.3.0 -> 0.3.1` may add APIs.
+
+use crate::api_diff::{classify, diff_api, ChangeClass};
+use crate::ImportContext;
+use model::tugraph_model::ApiItem;
+use semver::Version;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+/// The bump from `old` to `new`, in the cargo flavor of semver.
+fn bump(old: &Version, new: &Version) -> Bump {
+    if old.major != new.major {
+        Bump::Major
+    } else if old.major == 0 {
+        // `0.x.y -> 0.x.z` is the minor bump, and every `0.0.z` release is a major one
+        if old.minor != new.minor || old.minor == 0 {
+            Bump::Major
+        } else {
+            Bump::Minor
+        }
+    } else if old.minor != new.minor {
+        Bump::Minor
+    } else {
+        Bump::Patch
+    }
+}
+
+/// Whether a bump is too small for the class of the API changes.
+fn is_violation(class: ChangeClass, bump: Bump) -> bool {
+    match class {
+        ChangeClass::Breaking => bump < Bump::Major,
+        ChangeClass::Additive => bump < Bump::Minor,
+        ChangeClass::Internal => false,
+    }
+}
+
+impl ImportContext {
+    /// Compare the API of every library version with its previous release,
+    /// and set the `semver_violation` flag of the versions whose API is extracted.
+    ///
+    /// The pre-releases are skipped, and the first extracted release is never a violation.
+    pub(crate) fn check_semver_compliance(&mut self) {
+        let mut apis: HashMap<&str, Vec<&ApiItem>> = HashMap::new();
+        for item in &self.api_items {
+            apis.entry(item.name_and_version.as_str())
+                .or_default()
+                .push(item);
+        }
+
+        // name -> [(version, name_and_version)]
+        let mut releases: HashMap<&str, Vec<(Version, &str)>> = HashMap::new();
+        for version in &self.library_versions {
+            if !apis.contains_key(version.name_and_version.as_str()) {
+                continue;
+            }
+            match Version::parse(&version.version) {
+                Ok(v) if v.pre.is_empty() => releases
+                    .entry(version.name.as_str())
+                    .or_default()
+                    .push((v, version.name_and_version.as_str())),
+                _ => {}
+            }
+        }
+
+        let mut violations: HashMap<String, bool> = HashMap::new();
+        for versions in releases.values_mut() {
+            versions.sort_by(|a, b| a.0.cmp(&b.0));
+            violations.insert(versions[0].1.to_string(), false);
+            for pair in versions.windows(2) {
+                let (old, new) = (&pair[0], &pair[1]);
+                let changes = diff_api(apis[old.1].iter().copied(), apis[new.1].iter().copied());
+                let violation = is_violation(classify(&changes), bump(&old.0, &new.0));
+                if violation {
+                    tracing::debug!("{} -> {} violates semver", old.1, new.1);
+                }
+                violations.insert(new.1.to_string(), violation);
+            }
+        }
+
+        tracing::info!(
+            "{} library versions violate semver",
+            violations.values().filter(|v| **v).count()
+        );
+        for version in &mut self.library_versions {
+            version.semver_violation = violations.get(&version.name_and_version).copied();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn test_semver_violation() {
+        assert_eq!(bump(&v("1.2.3"), &v("1.2.4")), Bump::Patch);
+        assert_eq!(bump(&v("1.2.3"), &v("1.3.0")), Bump::Minor);
+        assert_eq!(bump(&v("0.3.1"), &v("0.3.2")), Bump::Minor);
+        assert_eq!(bump(&v("0.3.1"), &v("0.4.0")), Bump::Major);
+        assert_eq!(bump(&v("0.0.1"), &v("0.0.2")), Bump::Major);
+
+        assert!(is_violation(ChangeClass::Breaking, Bump::Minor));
+        assert!(is_violation(ChangeClass::Additive, Bump::Patch));
+        assert!(!is_violation(ChangeClass::Additive, Bump::Minor));
+        assert!(!is_violation(ChangeClass::Internal, Bump::Patch));
+    }
+}
@@ -0,0 +1,107 @@
+//! The version requirements of the dependencies as explicit ranges, so a consumer can
+//! match a version without the cargo rules, e.g. `^0.3` is `>=0.3.0, <0.4.0`.
+//!
+//! A partial version of a comparator is filled with zeros, following cargo:
+//! `>1.2` is `>=1.3.0`, `<=1` is `<2.0.0` and `1.*` is `>=1.0.0, <2.0.0`.
+
+use semver::{Comparator, Op, Prerelease, VersionReq};
+
+fn version(major: u64, minor: u64, patch: u64, pre: &Prerelease) -> String {
+    if pre.is_empty() {
+        format!("{}.{}.{}", major, minor, patch)
+    } else {
+        format!("{}.{}.{}-{}", major, minor, patch, pre)
+    }
+}
+
+/// The bounds of a comparator, such as `[">=1.2.0", "<1.3.0"]` of `~1.2`.
+fn bounds(c: &Comparator) -> Option<Vec<String>> {
+    let (major, minor, patch) = (c.major, c.minor, c.patch);
+    let lower = version(major, minor.unwrap_or(0), patch.unwrap_or(0), &c.pre);
+    let none = Prerelease::EMPTY;
+    // the upper bound of the versions matching the given components, as `=1.2` does
+    let exact_upper = match minor {
+        Some(minor) => match patch {
+            Some(_) => None,
+            None => Some(version(major, minor + 1, 0, &none)),
+        },
+        None => Some(version(major + 1, 0, 0, &none)),
+    };
+    let bounds = match c.op {
+        Op::Exact | Op::Wildcard => match exact_upper {
+            Some(upper) => vec![format!(">={}", lower), format!("<{}", upper)],
+            None => vec![format!("={}", lower)],
+        },
+        Op::Greater => match exact_upper {
+            Some(upper) => vec![format!(">={}", upper)],
+            None => vec![format!(">{}", lower)],
+        },
+        Op::GreaterEq => vec![format!(">={}", lower)],
+        Op::Less => vec![format!("<{}", lower)],
+        Op::LessEq => match exact_upper {
+            Some(upper) => vec![format!("<{}", upper)],
+            None => vec![format!("<={}", lower)],
+        },
+        Op::Tilde => {
+            let upper = match minor {
+                Some(minor) => version(major, minor + 1, 0, &none),
+                None => version(major + 1, 0, 0, &none),
+            };
+            vec![format!(">={}", lower), format!("<{}", upper)]
+        }
+        Op::Caret => {
+            // the left-most non-zero component may break, or the last given one
+            let upper = match (major, minor, patch) {
+                (0, Some(0), Some(patch)) => version(0, 0, patch + 1, &none),
+                (0, Some(minor), _) => version(0, minor + 1, 0, &none),
+                _ => version(major + 1, 0, 0, &none),
+            };
+            vec![format!(">={}", lower), format!("<{}", upper)]
+        }
+        _ => return None,
+    };
+    Some(bounds)
+}
+
+/// The range of a version requirement, the bounds joined by `, `,
+/// none if it's not a valid requirement.
+pub(crate) fn semver_range(req: &str) -> Option<String> {
+    let req = VersionReq::parse(req.trim()).ok()?;
+    if req.comparators.is_empty() {
+        // `*`
+        return Some(">=0.0.0".to_string());
+    }
+    let mut range = vec![];
+    for comparator in &req.comparators {
+        range.extend(bounds(comparator)?);
+    }
+    Some(range.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semver_range() {
+        let cases = [
+            ("1.2.3", ">=1.2.3, <2.0.0"),
+            ("^0.3", ">=0.3.0, <0.4.0"),
+            ("0.0.3", ">=0.0.3, <0.0.4"),
+            ("^0.0", ">=0.0.0, <0.1.0"),
+            ("~1.2", ">=1.2.0, <1.3.0"),
+            ("~1", ">=1.0.0, <2.0.0"),
+            ("=1.2.3", "=1.2.3"),
+            ("1.*", ">=1.0.0, <2.0.0"),
+            ("*", ">=0.0.0"),
+            (">1.2", ">=1.3.0"),
+            ("<=1", "<2.0.0"),
+            (">=1.2, <1.5.1", ">=1.2.0, <1.5.1"),
+            ("^1.0.0-beta.1", ">=1.0.0-beta.1, <2.0.0"),
+        ];
+        for (req, range) in cases {
+            assert_eq!(semver_range(req).as_deref(), Some(range), "{}", req);
+        }
+        assert_eq!(semver_range("not a version"), None);
+    }
+}
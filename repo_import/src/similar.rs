@@ -0,0 +1,260 @@
+//! Recommend the alternatives of each library as `similar_to` edges, such as `ureq` for
+//! `reqwest`, with a score from 0 to 1.
+//!
+//! It's opt-in by `IMPORT_SIMILAR_CRATES=1`. The candidates of a library are the ones sharing
+//! a keyword or a category with it, and they're scored by the weighted sum of the Jaccard
+//! indexes of their keywords and of their categories, times `1 - co-occurrence`, the cosine
+//! similarity of the sets of crates depending on them. The crates used together are rather
+//! complements, such as `serde` and `serde_json`, while a crate depends on one alternative
+//! or the other, so the more dependents they share, the lower they're scored.
+//!
+//! The top `IMPORT_SIMILAR_TOP_N` (10 by default) candidates are kept for each library.
+
+use crate::dependents::crate_of;
+use crate::ImportContext;
+use model::tugraph_model::SimilarTo;
+use std::collections::{HashMap, HashSet};
+use std::env;
+
+const DEFAULT_TOP_N: usize = 10;
+
+/// The candidates below it are not recorded.
+const MIN_SCORE: f64 = 0.1;
+
+/// A keyword or a category of more libraries than it, such as `development-tools`,
+/// is too generic to find the candidates by, but it's still counted in their overlap.
+const MAX_LIBRARIES_PER_TERM: usize = 500;
+
+const KEYWORD_WEIGHT: f64 = 0.6;
+const CATEGORY_WEIGHT: f64 = 0.4;
+
+#[derive(Debug)]
+struct Profile<'a> {
+    id: &'a str,
+    name: &'a str,
+    keywords: HashSet<String>,
+    categories: HashSet<String>,
+    /// the crates depending on it
+    dependents: HashSet<&'a str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Similarity {
+    score: f64,
+    cooccurrence: f64,
+    shared_keywords: usize,
+    shared_categories: usize,
+}
+
+/// The comma separated terms, case insensitive.
+fn terms(list: Option<&str>) -> HashSet<String> {
+    list.unwrap_or_default()
+        .split(',')
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// The Jaccard index and the number of the shared terms.
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> (f64, usize) {
+    let shared = a.intersection(b).count();
+    let union = a.len() + b.len() - shared;
+    if union == 0 {
+        return (0.0, 0);
+    }
+    (shared as f64 / union as f64, shared)
+}
+
+fn cosine(a: &HashSet<&str>, b: &HashSet<&str>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let shared = a.intersection(b).count();
+    shared as f64 / ((a.len() * b.len()) as f64).sqrt()
+}
+
+/// How similar `b` is to `a`, none without a keyword or a category in common.
+fn similarity(a: &Profile, b: &Profile) -> Option<Similarity> {
+    let (keywords, shared_keywords) = jaccard(&a.keywords, &b.keywords);
+    let (categories, shared_categories) = jaccard(&a.categories, &b.categories);
+    if shared_keywords + shared_categories == 0 {
+        return None;
+    }
+    let cooccurrence = cosine(&a.dependents, &b.dependents);
+    Some(Similarity {
+        score: (KEYWORD_WEIGHT * keywords + CATEGORY_WEIGHT * categories) * (1.0 - cooccurrence),
+        cooccurrence,
+        shared_keywords,
+        shared_categories,
+    })
+}
+
+/// The top `n` alternatives of each profile, by the index of the profile.
+fn top_similar(profiles: &[Profile], n: usize) -> Vec<Vec<(usize, Similarity)>> {
+    // keywords and categories are in the same postings, told apart by a prefix
+    let mut postings: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, profile) in profiles.iter().enumerate() {
+        for keyword in &profile.keywords {
+            postings
+                .entry(format!("k:{}", keyword))
+                .or_default()
+                .push(i);
+        }
+        for category in &profile.categories {
+            postings
+                .entry(format!("c:{}", category))
+                .or_default()
+                .push(i);
+        }
+    }
+    postings.retain(|_, libraries| libraries.len() <= MAX_LIBRARIES_PER_TERM);
+
+    let mut res = Vec::with_capacity(profiles.len());
+    for (i, profile) in profiles.iter().enumerate() {
+        let candidates: HashSet<usize> = profile
+            .keywords
+            .iter()
+            .map(|k| format!("k:{}", k))
+            .chain(profile.categories.iter().map(|c| format!("c:{}", c)))
+            .filter_map(|term| postings.get(&term))
+            .flatten()
+            .copied()
+            .filter(|&j| j != i)
+            .collect();
+        let mut similar: Vec<(usize, Similarity)> = candidates
+            .into_iter()
+            .filter_map(|j| Some((j, similarity(profile, &profiles[j])?)))
+            .filter(|(_, s)| s.score >= MIN_SCORE)
+            .collect();
+        similar.sort_by(|(a, x), (b, y)| {
+            y.score
+                .total_cmp(&x.score)
+                .then_with(|| profiles[*a].name.cmp(profiles[*b].name))
+        });
+        similar.truncate(n);
+        res.push(similar);
+    }
+    res
+}
+
+impl ImportContext {
+    /// Build the `similar_to` edges from the libraries to their alternatives.
+    pub(crate) fn recommend_similar_crates(&mut self) {
+        let top_n = env::var("IMPORT_SIMILAR_TOP_N")
+            .ok()
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(DEFAULT_TOP_N);
+        let libraries: HashSet<&str> = self.libraries.iter().map(|l| l.id.as_str()).collect();
+        let mut profiles: Vec<Profile> = vec![];
+        let mut by_name: HashMap<&str, usize> = HashMap::new();
+        for program in &self.programs {
+            if !libraries.contains(program.id.as_str()) || by_name.contains_key(&*program.name) {
+                continue;
+            }
+            by_name.insert(&program.name, profiles.len());
+            profiles.push(Profile {
+                id: &program.id,
+                name: &program.name,
+                keywords: terms(program.keywords.as_deref()),
+                categories: terms(program.categories.as_deref()),
+                dependents: HashSet::new(),
+            });
+        }
+        for edge in &self.depends_on {
+            let (dependent, dependency) = (crate_of(&edge.SRC_ID), crate_of(&edge.DST_ID));
+            if dependent == dependency {
+                continue;
+            }
+            if let Some(&i) = by_name.get(dependency) {
+                profiles[i].dependents.insert(dependent);
+            }
+        }
+
+        let mut similar_to = vec![];
+        for (i, similar) in top_similar(&profiles, top_n).into_iter().enumerate() {
+            for (rank, (j, similarity)) in similar.into_iter().enumerate() {
+                similar_to.push(SimilarTo {
+                    SRC_ID: profiles[i].id.to_string(),
+                    DST_ID: profiles[j].id.to_string(),
+                    score: similarity.score,
+                    rank: rank as i64 + 1,
+                    cooccurrence: similarity.cooccurrence,
+                    shared_keywords: similarity.shared_keywords as i64,
+                    shared_categories: similarity.shared_categories as i64,
+                });
+            }
+        }
+        tracing::info!(
+            "Recommended {} alternatives of {} libraries",
+            similar_to.len(),
+            profiles.len()
+        );
+        self.similar_to = similar_to;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile<'a>(
+        name: &'a str,
+        keywords: &str,
+        categories: &str,
+        dependents: &[&'a str],
+    ) -> Profile<'a> {
+        Profile {
+            id: name,
+            name,
+            keywords: terms(Some(keywords)),
+            categories: terms(Some(categories)),
+            dependents: dependents.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn test_top_similar() {
+        let profiles = [
+            profile(
+                "reqwest",
+                "http,client,request",
+                "web-programming::http-client",
+                &["a", "b", "c"],
+            ),
+            profile(
+                "ureq",
+                "http,client,Request",
+                "web-programming::http-client",
+                &["d", "e"],
+            ),
+            profile("isahc", "http,curl", "web-programming::http-client", &["c"]),
+            // used along with reqwest by all its dependents
+            profile(
+                "reqwest-middleware",
+                "http,client,request",
+                "web-programming::http-client",
+                &["a", "b", "c"],
+            ),
+            profile("serde_json", "json,serde", "encoding", &["a", "b", "c"]),
+        ];
+        let similar = top_similar(&profiles, 10);
+        let names =
+            |i: usize| -> Vec<&str> { similar[i].iter().map(|(j, _)| profiles[*j].name).collect() };
+        assert_eq!(names(0), ["ureq", "isahc"]);
+        let (_, ureq) = similar[0][0];
+        assert_eq!(ureq.shared_keywords, 3);
+        assert_eq!(ureq.shared_categories, 1);
+        assert_eq!(ureq.cooccurrence, 0.0);
+        assert!((ureq.score - 1.0).abs() < 1e-9);
+        let (_, isahc) = similar[0][1];
+        let cooccurrence = 1.0 / 3.0f64.sqrt();
+        assert!((isahc.cooccurrence - cooccurrence).abs() < 1e-9);
+        assert!((isahc.score - (0.6 * 0.25 + 0.4) * (1.0 - cooccurrence)).abs() < 1e-9);
+        // the complements sharing all the dependents aren't alternatives
+        assert!(!names(3).contains(&"reqwest"));
+        // the dependents in common alone don't make an alternative
+        assert!(names(4).is_empty());
+
+        assert_eq!(top_similar(&profiles, 1)[0].len(), 1);
+    }
+}
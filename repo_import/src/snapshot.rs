@@ -0,0 +1,302 @@
+//! Named snapshots of the imported graph, so that the analyses can be pinned to a known dataset.
+//!
+//! A snapshot is the graph in `SNAPSHOT_DIR/<id>/graph.bin`, in the format of the checkpoints,
+//! along with its `snapshot.json`: the id, the first 12 hex digits of the sha256 of the graph,
+//! the name such as `nightly-2024-06-01`, when it's taken, and the numbers of the crates and
+//! versions in it, and the version of the format of the checkpoints. `SNAPSHOT_DIR` is
+//! `CHECKPOINT_DIR/snapshots` by default.
+//!
+//! A snapshot is exported as a `.tar.gz` to be imported by another host, and restored as
+//! `CHECKPOINT_DIR/latest.json` to continue importing from it. The graph is checked against
+//! its sha256 and its format whenever it's loaded, so the snapshots of another format are
+//! refused rather than misread.
+
+use crate::progress::checkpoint_dir;
+use crate::{ImportContext, CHECKPOINT_VERSION};
+use chrono::{SecondsFormat, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+const MANIFEST_FILE: &str = "snapshot.json";
+const GRAPH_FILE: &str = "graph.bin";
+
+/// The hex digits of the sha256 of the graph in the id.
+const ID_LEN: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    /// the format of `graph.bin`, 0 for the snapshots taken before it's recorded
+    #[serde(default)]
+    pub format_version: u32,
+    pub id: String,
+    pub name: String,
+    /// RFC 3339, such as `2024-06-01T00:00:00Z`
+    pub created_at: String,
+    /// the sha256 of `graph.bin`
+    pub sha256: String,
+    pub programs: usize,
+    pub library_versions: usize,
+    pub application_versions: usize,
+}
+
+/// The snapshots in a directory, one subdirectory per snapshot named by its id.
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SnapshotStore { dir: dir.into() }
+    }
+
+    /// The store in `SNAPSHOT_DIR`, or `CHECKPOINT_DIR/snapshots`.
+    pub fn from_env() -> Self {
+        let dir = env::var("SNAPSHOT_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| checkpoint_dir().join("snapshots"));
+        SnapshotStore::new(dir)
+    }
+
+    /// Take a snapshot of `context` named `name`, which is unique in the store.
+    pub async fn create(
+        &self,
+        name: &str,
+        context: &mut ImportContext,
+    ) -> Result<SnapshotManifest, Box<dyn Error>> {
+        if self.list()?.iter().any(|s| s.name == name) {
+            return Err(format!("The snapshot {} exists", name).into());
+        }
+        let graph = context.encode_checkpoint().await?;
+        let sha256 = hex::encode(Sha256::digest(&graph));
+        let manifest = SnapshotManifest {
+            format_version: CHECKPOINT_VERSION,
+            id: sha256[..ID_LEN].to_string(),
+            name: name.to_string(),
+            created_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            sha256,
+            programs: context.programs.len(),
+            library_versions: context.library_versions.len(),
+            application_versions: context.application_versions.len(),
+        };
+        let dir = self.dir.join(&manifest.id);
+        if dir.exists() {
+            return Err(format!("The same graph is in the snapshot {}", manifest.id).into());
+        }
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(GRAPH_FILE), graph)?;
+        fs::write(
+            dir.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&manifest)?,
+        )?;
+        tracing::info!("Took the snapshot {} as {}", manifest.name, manifest.id);
+        Ok(manifest)
+    }
+
+    /// The snapshots, the oldest first.
+    pub fn list(&self) -> Result<Vec<SnapshotManifest>, Box<dyn Error>> {
+        if !self.dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut snapshots = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path().join(MANIFEST_FILE);
+            if path.is_file() {
+                snapshots.push(read_manifest(&path)?);
+            }
+        }
+        snapshots.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+        Ok(snapshots)
+    }
+
+    /// The snapshot of the id or the name.
+    pub fn find(&self, snapshot: &str) -> Result<SnapshotManifest, Box<dyn Error>> {
+        self.list()?
+            .into_iter()
+            .find(|s| s.id == snapshot || s.name == snapshot)
+            .ok_or_else(|| format!("No snapshot {}", snapshot).into())
+    }
+
+    /// The graph of a snapshot, checked against its format and sha256.
+    fn read_graph(&self, manifest: &SnapshotManifest) -> Result<Vec<u8>, Box<dyn Error>> {
+        check_format(manifest)?;
+        let graph = fs::read(self.dir.join(&manifest.id).join(GRAPH_FILE))?;
+        if hex::encode(Sha256::digest(&graph)) != manifest.sha256 {
+            return Err(format!("The graph of the snapshot {} is corrupted", manifest.id).into());
+        }
+        Ok(graph)
+    }
+
+    /// Load the graph of the snapshot of the id or the name.
+    pub fn load(&self, snapshot: &str) -> Result<ImportContext, Box<dyn Error>> {
+        let manifest = self.find(snapshot)?;
+        let context = ImportContext::from_checkpoint(&self.read_graph(&manifest)?)?;
+        tracing::info!(
+            "Loaded the snapshot {} ({}) with {} programs",
+            manifest.name,
+            manifest.id,
+            context.programs.len()
+        );
+        Ok(context)
+    }
+
+    /// Write the graph of a snapshot into the checkpoint at `checkpoint`.
+    pub fn restore(
+        &self,
+        snapshot: &str,
+        checkpoint: &Path,
+    ) -> Result<SnapshotManifest, Box<dyn Error>> {
+        let manifest = self.find(snapshot)?;
+        let graph = self.read_graph(&manifest)?;
+        if let Some(dir) = checkpoint.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(checkpoint, graph)?;
+        tracing::info!(
+            "Restored the snapshot {} into {:?}",
+            manifest.id,
+            checkpoint
+        );
+        Ok(manifest)
+    }
+
+    /// Export a snapshot into a `.tar.gz` of its directory.
+    pub fn export(
+        &self,
+        snapshot: &str,
+        archive: &Path,
+    ) -> Result<SnapshotManifest, Box<dyn Error>> {
+        let manifest = self.find(snapshot)?;
+        let encoder = GzEncoder::new(File::create(archive)?, Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        builder.append_dir_all(&manifest.id, self.dir.join(&manifest.id))?;
+        builder.into_inner()?.finish()?;
+        Ok(manifest)
+    }
+
+    /// Import a snapshot exported by [`SnapshotStore::export`].
+    pub fn import(&self, archive: &Path) -> Result<SnapshotManifest, Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let unpacked = tempfile::tempdir_in(&self.dir)?;
+        tar::Archive::new(GzDecoder::new(File::open(archive)?)).unpack(unpacked.path())?;
+        let root = fs::read_dir(unpacked.path())?
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .find(|p| p.join(MANIFEST_FILE).is_file())
+            .ok_or_else(|| format!("No {} in {:?}", MANIFEST_FILE, archive))?;
+        let manifest = read_manifest(&root.join(MANIFEST_FILE))?;
+        check_format(&manifest)?;
+        let graph = fs::read(root.join(GRAPH_FILE))?;
+        if hex::encode(Sha256::digest(&graph)) != manifest.sha256 {
+            return Err(format!("The graph in {:?} is corrupted", archive).into());
+        }
+
+        if let Ok(existing) = self.find(&manifest.name) {
+            if existing.id == manifest.id {
+                return Ok(existing);
+            }
+            return Err(format!("The snapshot {} exists", manifest.name).into());
+        }
+        let dir = self.dir.join(&manifest.id);
+        if dir.exists() {
+            return Err(format!("The same graph is in the snapshot {}", manifest.id).into());
+        }
+        fs::rename(&root, dir)?;
+        tracing::info!("Imported the snapshot {} as {}", manifest.name, manifest.id);
+        Ok(manifest)
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<SnapshotManifest, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+fn check_format(manifest: &SnapshotManifest) -> Result<(), Box<dyn Error>> {
+    if manifest.format_version != CHECKPOINT_VERSION {
+        return Err(format!(
+            "The snapshot {} is of the format {}, but {} is expected",
+            manifest.id, manifest.format_version, CHECKPOINT_VERSION
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::tugraph_model::Program;
+
+    #[tokio::test]
+    async fn test_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().join("snapshots"));
+        assert!(store.list().unwrap().is_empty());
+
+        let mut context = ImportContext::default();
+        context.programs.push(Program {
+            id: "x/a".to_string(),
+            name: "a".to_string(),
+            ..Default::default()
+        });
+        let manifest = store.create("nightly", &mut context).await.unwrap();
+        assert_eq!(manifest.id.len(), ID_LEN);
+        assert_eq!(manifest.programs, 1);
+        assert!(store.create("nightly", &mut context).await.is_err());
+        assert_eq!(store.find(&manifest.id).unwrap(), manifest);
+        assert_eq!(store.load("nightly").unwrap().programs[0].name, "a");
+
+        let checkpoint = dir.path().join("checkpoints/latest.json");
+        store.restore("nightly", &checkpoint).unwrap();
+        let restored = ImportContext::load_from_file(&checkpoint.to_string_lossy())
+            .await
+            .unwrap();
+        assert_eq!(restored.programs.len(), 1);
+
+        let archive = dir.path().join("nightly.tar.gz");
+        store.export("nightly", &archive).unwrap();
+        let other = SnapshotStore::new(dir.path().join("other"));
+        assert_eq!(other.import(&archive).unwrap(), manifest);
+        assert_eq!(other.import(&archive).unwrap(), manifest);
+        assert_eq!(other.list().unwrap(), std::slice::from_ref(&manifest));
+
+        // a corrupted graph is not loaded
+        let graph = dir.path().join("other").join(&manifest.id).join(GRAPH_FILE);
+        fs::write(graph, b"x").unwrap();
+        assert!(other.load("nightly").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path());
+        let manifest = store
+            .create("nightly", &mut ImportContext::default())
+            .await
+            .unwrap();
+        assert_eq!(manifest.format_version, CHECKPOINT_VERSION);
+        let archive = dir.path().join("nightly.tar.gz");
+        store.export("nightly", &archive).unwrap();
+
+        // a snapshot without the format, as the ones taken before it's recorded
+        let path = dir.path().join(&manifest.id).join(MANIFEST_FILE);
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        json.as_object_mut().unwrap().remove("format_version");
+        fs::write(&path, json.to_string()).unwrap();
+        assert_eq!(store.find("nightly").unwrap().format_version, 0);
+        assert!(store.load("nightly").is_err());
+        assert!(store
+            .restore("nightly", &dir.path().join("latest.json"))
+            .is_err());
+
+        let other = SnapshotStore::new(dir.path().join("other"));
+        assert!(other.import(&archive).is_ok());
+    }
+}
@@ -0,0 +1,110 @@
+//! A client of the sparse index protocol (`sparse+https://index.crates.io/`),
+//! which fetches the index file of a single crate on demand,
+//! so enriching a crate doesn't require cloning the whole index.
+
+use crate::cratesio_index::{parse_index_file, IndexEntry};
+use crate::retry::{is_transient_http, RetryPolicy};
+use crate::ImportContext;
+use reqwest::{Client, StatusCode};
+use std::env;
+use std::error::Error;
+
+const SPARSE_INDEX_URL: &str = "sparse+https://index.crates.io/";
+
+/// The path of a crate in the index, e.g. `se/rd/serde`, `3/s/syn`, `1/a`
+pub(crate) fn index_path(name: &str) -> Result<String, String> {
+    if name.is_empty() || !name.is_ascii() {
+        return Err(format!("Invalid crate name {:?}", name));
+    }
+    let name = name.to_lowercase();
+    Ok(match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    })
+}
+
+pub struct SparseIndexClient {
+    client: Client,
+    base_url: String,
+    retry: RetryPolicy,
+}
+
+impl SparseIndexClient {
+    /// The registry is `CRATESIO_SPARSE_INDEX_URL`, or crates.io by default.
+    pub fn new() -> Result<Self, reqwest::Error> {
+        let url =
+            env::var("CRATESIO_SPARSE_INDEX_URL").unwrap_or_else(|_| SPARSE_INDEX_URL.to_string());
+        let mut base_url = url.trim_start_matches("sparse+").to_string();
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        let client = Client::builder()
+            .user_agent(concat!("crates-pro/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self {
+            client,
+            base_url,
+            retry: RetryPolicy::from_env(),
+        })
+    }
+
+    /// Fetch all the versions of a crate, `None` if the crate doesn't exist.
+    pub(crate) async fn fetch_crate(
+        &self,
+        name: &str,
+    ) -> Result<Option<Vec<IndexEntry>>, Box<dyn Error>> {
+        let url = format!("{}{}", self.base_url, index_path(name)?);
+        let client = &self.client;
+        let url = &url;
+        self.retry
+            .retry(url, is_transient_http, || async move {
+                let resp = client.get(url).send().await?;
+                // the registry may answer 403 or 410 for a crate which doesn't exist
+                if matches!(
+                    resp.status(),
+                    StatusCode::NOT_FOUND | StatusCode::FORBIDDEN | StatusCode::GONE
+                ) {
+                    return Ok(None);
+                }
+                let content = resp.error_for_status()?.text().await?;
+                Ok(Some(parse_index_file(&content)))
+            })
+            .await
+            .map_err(Into::into)
+    }
+}
+
+impl ImportContext {
+    /// Add the versions of a single crate fetched from the sparse index,
+    /// return the number of new versions.
+    pub async fn import_crate_from_sparse_index(
+        &mut self,
+        client: &SparseIndexClient,
+        name: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        match client.fetch_crate(name).await? {
+            Some(entries) => Ok(self.add_index_entries(&entries).await),
+            None => {
+                tracing::warn!("Crate {} is not in the sparse index", name);
+                Ok(0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::index_path;
+
+    #[test]
+    fn test_index_path() {
+        assert_eq!(index_path("a").unwrap(), "1/a");
+        assert_eq!(index_path("cc").unwrap(), "2/cc");
+        assert_eq!(index_path("syn").unwrap(), "3/s/syn");
+        assert_eq!(index_path("Serde").unwrap(), "se/rd/serde");
+        assert!(index_path("").is_err());
+        assert!(index_path("sérde").is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! Periodic progress logs of the stages of a large import, such as
+//!
+//! ```text
+//! clone: 1200/50000 repos (2.4%), 3 failed, 4.1 repos/s, elapsed 4m 52s, ETA 3h 18m
+//! import: 800/1197 repos (66.8%), 5321 crates, 2.0 repos/s, elapsed 6m 40s, ETA 3m 18s
+//! export: 12 tables, 1830452 records, elapsed 41s
+//! ```
+//!
+//! so that it's clear whether an import is stuck or progressing.
+//! A stage logs every `PROGRESS_LOG_INTERVAL_SECS` seconds (10 by default), and when it finishes.
+
+use crate::output::{GraphExporter, Record};
+use crate::report::{record_records, record_stage};
+use std::env;
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+fn log_interval() -> Duration {
+    let secs = env::var("PROGRESS_LOG_INTERVAL_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(10);
+    Duration::from_secs(secs)
+}
+
+/// Such as `1h 02m`, `4m 52s` or `41s`.
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, _) => format!("{}h {:02}m", h, m),
+    }
+}
+
+/// The progress of a stage, shared by its concurrent tasks.
+pub(crate) struct StageProgress {
+    stage: &'static str,
+    /// what is processed, such as `repos`
+    unit: &'static str,
+    total: Option<usize>,
+    /// what is produced, such as `crates`
+    items_unit: Option<&'static str>,
+    done: AtomicUsize,
+    failed: AtomicUsize,
+    items: AtomicUsize,
+    start: Instant,
+    interval: Duration,
+    last_log: Mutex<Instant>,
+}
+
+impl StageProgress {
+    pub(crate) fn new(stage: &'static str, unit: &'static str, total: Option<usize>) -> Self {
+        let now = Instant::now();
+        StageProgress {
+            stage,
+            unit,
+            total,
+            items_unit: None,
+            done: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            items: AtomicUsize::new(0),
+            start: now,
+            interval: log_interval(),
+            last_log: Mutex::new(now),
+        }
+    }
+
+    pub(crate) fn with_items(mut self, items_unit: &'static str) -> Self {
+        self.items_unit = Some(items_unit);
+        self
+    }
+
+    pub(crate) fn inc(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+        self.tick();
+    }
+
+    pub(crate) fn fail(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        self.tick();
+    }
+
+    pub(crate) fn add_items(&self, n: usize) {
+        self.items.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Log the progress if it's not logged for an interval.
+    fn tick(&self) {
+        // another task is logging
+        let Ok(mut last_log) = self.last_log.try_lock() else {
+            return;
+        };
+        if last_log.elapsed() >= self.interval {
+            *last_log = Instant::now();
+            tracing::info!("{}", self.status(self.start.elapsed()));
+        }
+    }
+
+    /// Log the progress, and add the stage into the import report.
+    pub(crate) fn finish(&self) {
+        let elapsed = self.start.elapsed();
+        tracing::info!("{}, finished", self.status(elapsed));
+        record_stage(
+            self.stage,
+            elapsed,
+            self.done.load(Ordering::Relaxed),
+            self.failed.load(Ordering::Relaxed),
+            self.items.load(Ordering::Relaxed),
+        );
+    }
+
+    fn eta(&self, elapsed: Duration) -> Option<Duration> {
+        let processed = self.done.load(Ordering::Relaxed) + self.failed.load(Ordering::Relaxed);
+        let remaining = self.total?.checked_sub(processed)?;
+        if processed == 0 {
+            return None;
+        }
+        Some(elapsed.mul_f64(remaining as f64 / processed as f64))
+    }
+
+    fn status(&self, elapsed: Duration) -> String {
+        let done = self.done.load(Ordering::Relaxed);
+        let failed = self.failed.load(Ordering::Relaxed);
+        let mut status = match self.total {
+            Some(total) if total > 0 => format!(
+                "{}: {}/{} {} ({:.1}%)",
+                self.stage,
+                done + failed,
+                total,
+                self.unit,
+                (done + failed) as f64 * 100.0 / total as f64
+            ),
+            _ => format!("{}: {} {}", self.stage, done + failed, self.unit),
+        };
+        if failed > 0 {
+            status.push_str(&format!(", {} failed", failed));
+        }
+        if let Some(items_unit) = self.items_unit {
+            status.push_str(&format!(
+                ", {} {}",
+                self.items.load(Ordering::Relaxed),
+                items_unit
+            ));
+        }
+        if self.total.is_some() && elapsed.as_secs_f64() > 0.0 {
+            let rate = (done + failed) as f64 / elapsed.as_secs_f64();
+            status.push_str(&format!(", {:.1} {}/s", rate, self.unit));
+        }
+        status.push_str(&format!(", elapsed {}", format_duration(elapsed)));
+        if let Some(eta) = self.eta(elapsed).filter(|eta| !eta.is_zero()) {
+            status.push_str(&format!(", ETA {}", format_duration(eta)));
+        }
+        status
+    }
+}
+
+/// Count the tables and the records exported by an exporter.
+pub(crate) struct ProgressExporter<'a, E> {
+    exporter: &'a mut E,
+    progress: StageProgress,
+}
+
+impl<'a, E: GraphExporter> ProgressExporter<'a, E> {
+    pub(crate) fn new(exporter: &'a mut E) -> Self {
+        ProgressExporter {
+            exporter,
+            progress: StageProgress::new("export", "tables", None).with_items("records"),
+        }
+    }
+}
+
+impl<E: GraphExporter> GraphExporter for ProgressExporter<'_, E> {
+    fn vertex<T: Record>(
+        &mut self,
+        label: &str,
+        primary: &str,
+        file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>> {
+        self.exporter.vertex(label, primary, file_name, items)?;
+        record_records(file_name, items.len());
+        self.progress.add_items(items.len());
+        self.progress.inc();
+        Ok(())
+    }
+
+    fn edge<T: Record>(
+        &mut self,
+        label: &str,
+        src_label: &str,
+        dst_label: &str,
+        file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>> {
+        self.exporter
+            .edge(label, src_label, dst_label, file_name, items)?;
+        record_records(file_name, items.len());
+        self.progress.add_items(items.len());
+        self.progress.inc();
+        Ok(())
+    }
+
+    /// The wrapped exporter is finished by its owner.
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.progress.finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        let progress = StageProgress::new("import", "repos", Some(10)).with_items("crates");
+        for _ in 0..4 {
+            progress.inc();
+        }
+        progress.fail();
+        progress.add_items(12);
+        assert_eq!(
+            progress.status(Duration::from_secs(50)),
+            "import: 5/10 repos (50.0%), 1 failed, 12 crates, 0.1 repos/s, elapsed 50s, ETA 50s"
+        );
+
+        let progress = StageProgress::new("export", "tables", None).with_items("records");
+        progress.add_items(3);
+        progress.inc();
+        assert_eq!(
+            progress.status(Duration::from_secs(3725)),
+            "export: 1 tables, 3 records, elapsed 1h 02m"
+        );
+    }
+}
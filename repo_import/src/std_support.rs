@@ -0,0 +1,95 @@
+//! Classify a library by its support of `no_std`, from the attributes of its crate root:
+//! - `no_std`, with `#![no_std]`, or `#![cfg_attr(not(feature = "std"), no_std)]` if
+//!   the standard library is behind a feature,
+//! - `no_std+alloc`, a `no_std` library requiring `extern crate alloc` without a `#[cfg]`,
+//! - `std` otherwise.
+
+use std::fs;
+use std::path::Path;
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Item, Meta, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StdSupport {
+    Std,
+    NoStd,
+    NoStdAlloc,
+}
+
+impl StdSupport {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            StdSupport::Std => "std",
+            StdSupport::NoStd => "no_std",
+            StdSupport::NoStdAlloc => "no_std+alloc",
+        }
+    }
+}
+
+/// Whether it's `#![no_std]` or `#![cfg_attr(..., no_std)]`.
+fn is_no_std(attr: &Attribute) -> bool {
+    if attr.path().is_ident("no_std") {
+        return true;
+    }
+    if !attr.path().is_ident("cfg_attr") {
+        return false;
+    }
+    // the predicate, then the attributes
+    attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+        .is_ok_and(|metas| metas.iter().skip(1).any(|m| m.path().is_ident("no_std")))
+}
+
+fn classify(source: &str) -> Result<StdSupport, syn::Error> {
+    let file = syn::parse_file(source)?;
+    if !file.attrs.iter().any(is_no_std) {
+        return Ok(StdSupport::Std);
+    }
+    let requires_alloc = file.items.iter().any(|item| {
+        matches!(item, Item::ExternCrate(c)
+            if c.ident == "alloc" && !c.attrs.iter().any(|a| a.path().is_ident("cfg")))
+    });
+    Ok(if requires_alloc {
+        StdSupport::NoStdAlloc
+    } else {
+        StdSupport::NoStd
+    })
+}
+
+/// The `no_std` support of the library whose crate root is `lib_path`, none if it's not
+/// a library or the crate root fails to parse.
+pub(crate) fn detect_std_support(lib_path: &Path) -> Option<StdSupport> {
+    let source = fs::read_to_string(lib_path).ok()?;
+    match classify(&source) {
+        Ok(support) => Some(support),
+        Err(e) => {
+            tracing::debug!("Failed to parse {:?}: {}", lib_path, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("pub fn f() {}").unwrap(), StdSupport::Std);
+        assert_eq!(
+            classify("#![no_std]\npub fn f() {}").unwrap(),
+            StdSupport::NoStd
+        );
+        assert_eq!(
+            classify("#![cfg_attr(not(feature = \"std\"), no_std)]\n#[cfg(feature = \"alloc\")]\nextern crate alloc;").unwrap(),
+            StdSupport::NoStd
+        );
+        assert_eq!(
+            classify("#![no_std]\nextern crate alloc;").unwrap(),
+            StdSupport::NoStdAlloc
+        );
+        assert_eq!(
+            classify("#![cfg_attr(docsrs, feature(doc_cfg))]").unwrap(),
+            StdSupport::Std
+        );
+    }
+}
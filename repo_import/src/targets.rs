@@ -0,0 +1,241 @@
+//! The build targets of a package, discovered as cargo does.
+//!
+//! The binaries are the `[[bin]]` targets, and unless `autobins = false`,
+//! `src/main.rs` named after the package, `src/bin/*.rs` and `src/bin/*/main.rs`.
+//! The same for the examples in `examples/`, the integration tests in `tests/`
+//! and the benchmarks in `benches/`, by `[[example]]`, `[[test]]` and `[[bench]]`.
+//! A declared target replaces the discovered one of the same name or path.
+
+use crate::manifest::{Manifest, TargetSpec};
+use model::tugraph_model::Target;
+use std::fs;
+use std::path::Path;
+
+/// A target by its name and the path of its root source file, relative to the package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TargetPath {
+    name: String,
+    path: String,
+}
+
+impl TargetPath {
+    fn new(name: &str, path: &str) -> Self {
+        TargetPath {
+            name: name.to_string(),
+            path: path.to_string(),
+        }
+    }
+}
+
+/// `src/bin/foo.rs` or `src/bin/foo/main.rs` are named `foo`.
+fn name_of_path(path: &str) -> Option<String> {
+    let path = Path::new(path);
+    let stem = path.file_stem()?.to_str()?;
+    if stem != "main" {
+        return Some(stem.to_string());
+    }
+    let dir = path.parent()?.file_name()?.to_str()?;
+    (dir != "src").then(|| dir.to_string())
+}
+
+/// The targets in `dir`, such as `examples/foo.rs` and `examples/bar/main.rs`.
+fn discover_targets(crate_path: &Path, dir: &str) -> Vec<TargetPath> {
+    let mut res = vec![];
+    let Ok(entries) = fs::read_dir(crate_path.join(dir)) else {
+        return res;
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if path.is_file() {
+            if let Some(name) = file_name.strip_suffix(".rs") {
+                res.push(TargetPath::new(name, &format!("{}/{}", dir, file_name)));
+            }
+        } else if path.join("main.rs").is_file() {
+            let main = format!("{}/{}/main.rs", dir, file_name);
+            res.push(TargetPath::new(file_name, &main));
+        }
+    }
+    res
+}
+
+/// The binaries in `src/main.rs` and `src/bin/`.
+fn discover_binaries(crate_path: &Path, package_name: &str) -> Vec<TargetPath> {
+    let mut res = discover_targets(crate_path, "src/bin");
+    if crate_path.join("src/main.rs").is_file() {
+        res.push(TargetPath::new(package_name, "src/main.rs"));
+    }
+    res
+}
+
+/// Merge the declared targets and the discovered ones, sorted by name.
+fn merge_targets(
+    declared: &[TargetSpec],
+    discovered: Vec<TargetPath>,
+    autodiscover: bool,
+    default_path: impl Fn(&str) -> String,
+) -> Vec<TargetPath> {
+    let mut res: Vec<TargetPath> = declared
+        .iter()
+        .filter_map(|spec| {
+            let name = match (&spec.name, &spec.path) {
+                (Some(name), _) => name.clone(),
+                (None, Some(path)) => name_of_path(path)?,
+                (None, None) => return None,
+            };
+            let path = spec
+                .path
+                .clone()
+                .or_else(|| {
+                    let found = discovered.iter().find(|t| t.name == name);
+                    found.map(|t| t.path.clone())
+                })
+                .unwrap_or_else(|| default_path(&name));
+            Some(TargetPath { name, path })
+        })
+        .collect();
+    if autodiscover {
+        for target in discovered {
+            if !res
+                .iter()
+                .any(|t| t.name == target.name || t.path == target.path)
+            {
+                res.push(target);
+            }
+        }
+    }
+    res.sort_by(|a, b| a.name.cmp(&b.name));
+    res
+}
+
+/// The binary targets of a package.
+fn binary_targets(crate_path: &Path, manifest: &Manifest) -> Vec<TargetPath> {
+    let Some(package) = &manifest.package else {
+        return vec![];
+    };
+    merge_targets(
+        &manifest.bin,
+        discover_binaries(crate_path, &package.name),
+        package.autobins.unwrap_or(true),
+        |name| format!("src/bin/{}.rs", name),
+    )
+}
+
+/// The targets of a kind in its own directory, such as the examples in `examples/`.
+fn other_targets(
+    crate_path: &Path,
+    declared: &[TargetSpec],
+    autodiscover: Option<bool>,
+    dir: &str,
+) -> Vec<TargetPath> {
+    merge_targets(
+        declared,
+        discover_targets(crate_path, dir),
+        autodiscover.unwrap_or(true),
+        |name| format!("{}/{}.rs", dir, name),
+    )
+}
+
+/// The targets of the package in `crate_path`, none if its Cargo.toml is invalid.
+pub(crate) fn package_targets(program_id: &str, crate_path: &Path) -> Vec<Target> {
+    let manifest: Manifest = match fs::read_to_string(crate_path.join("Cargo.toml"))
+        .map_err(|e| e.to_string())
+        .and_then(|content| toml::from_str(&content).map_err(|e| e.to_string()))
+    {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            tracing::warn!("Failed to read the targets of {:?}: {}", crate_path, e);
+            return vec![];
+        }
+    };
+    let Some(package) = &manifest.package else {
+        return vec![];
+    };
+    [
+        ("bin", binary_targets(crate_path, &manifest)),
+        (
+            "example",
+            other_targets(
+                crate_path,
+                &manifest.example,
+                package.autoexamples,
+                "examples",
+            ),
+        ),
+        (
+            "test",
+            other_targets(crate_path, &manifest.test, package.autotests, "tests"),
+        ),
+        (
+            "bench",
+            other_targets(crate_path, &manifest.bench, package.autobenches, "benches"),
+        ),
+    ]
+    .into_iter()
+    .flat_map(|(kind, targets)| {
+        targets
+            .into_iter()
+            .map(move |t| Target::new(program_id, kind, &t.name, &t.path))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/bin/b")).unwrap();
+        for file in ["src/main.rs", "src/bin/a.rs", "src/bin/b/main.rs"] {
+            fs::write(root.join(file), "fn main() {}").unwrap();
+        }
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [package]
+            name = "foo"
+
+            [[bin]]
+            name = "a"
+
+            [[bin]]
+            name = "tool"
+            path = "tools/tool.rs"
+            "#,
+        )
+        .unwrap();
+        let targets = binary_targets(root, &manifest);
+        assert_eq!(
+            targets,
+            [
+                TargetPath::new("a", "src/bin/a.rs"),
+                TargetPath::new("b", "src/bin/b/main.rs"),
+                TargetPath::new("foo", "src/main.rs"),
+                TargetPath::new("tool", "tools/tool.rs"),
+            ]
+        );
+
+        let manifest: Manifest =
+            toml::from_str("[package]\nname = \"foo\"\nautobins = false\n[[bin]]\nname = \"foo\"")
+                .unwrap();
+        assert_eq!(
+            binary_targets(root, &manifest),
+            [TargetPath::new("foo", "src/main.rs")]
+        );
+
+        fs::write(root.join("Cargo.toml"), "[package]\nname = \"foo\"").unwrap();
+        fs::create_dir_all(root.join("tests")).unwrap();
+        fs::write(root.join("tests/it.rs"), "").unwrap();
+        fs::write(root.join("tests/README.md"), "").unwrap();
+        let targets = package_targets("p", root);
+        let tests: Vec<&str> = targets
+            .iter()
+            .filter(|t| t.kind == "test")
+            .map(|t| t.id.as_str())
+            .collect();
+        assert_eq!(tests, ["p/test/it"]);
+    }
+}
@@ -0,0 +1,173 @@
+//! Export the extracted model in the bulk import format of TuGraph,
+//! i.e. the vertex and edge csv files, and the `import.config` describing them,
+//! which can be loaded by `lgraph_import -c import.config`.
+//!
+//! The schema is derived from the model types, so it always matches the csv files.
+
+use crate::output::{columns_of, fields_of, write_table, GraphExporter, OutputFormat, Record};
+use arrow::datatypes::DataType;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const IMPORT_CONFIG_FILE: &str = "import.config";
+
+/// A property as `(name, tugraph type, optional)`
+type Property = (String, String, bool);
+
+/// The properties of `T`
+fn properties_of<T: Record>() -> Result<Vec<Property>, Box<dyn Error>> {
+    Ok(fields_of::<T>()?
+        .iter()
+        .map(|f| {
+            let ty = match f.data_type() {
+                DataType::Boolean => "BOOL",
+                DataType::Int8 => "INT8",
+                DataType::Int16 => "INT16",
+                DataType::Int32 | DataType::UInt8 | DataType::UInt16 => "INT32",
+                DataType::Int64 | DataType::UInt32 | DataType::UInt64 => "INT64",
+                DataType::Float32 => "FLOAT",
+                DataType::Float64 => "DOUBLE",
+                _ => "STRING",
+            };
+            (f.name().clone(), ty.to_string(), f.is_nullable())
+        })
+        .collect())
+}
+
+fn to_property_json(properties: &[(String, String, bool)]) -> Vec<Value> {
+    properties
+        .iter()
+        .map(|(name, ty, optional)| {
+            if *optional {
+                json!({ "name": name, "type": ty, "optional": true })
+            } else {
+                json!({ "name": name, "type": ty })
+            }
+        })
+        .collect()
+}
+
+pub(crate) struct TuGraphExporter<'a> {
+    dir: &'a Path,
+    formats: &'a [OutputFormat],
+    schema: Vec<Value>,
+    files: Vec<Value>,
+}
+
+impl<'a> TuGraphExporter<'a> {
+    /// The csv files are always written, since they are what TuGraph imports.
+    pub(crate) fn new(dir: &'a Path, formats: &'a [OutputFormat]) -> Self {
+        Self {
+            dir,
+            formats,
+            schema: vec![],
+            files: vec![],
+        }
+    }
+
+    fn csv_path(&self, file_name: &str) -> PathBuf {
+        let dir = fs::canonicalize(self.dir).unwrap_or_else(|_| self.dir.to_path_buf());
+        dir.join(format!("{}.csv", file_name))
+    }
+
+    fn write<T: Record>(&self, file_name: &str, items: &[T]) -> Result<(), Box<dyn Error>> {
+        if self.formats.contains(&OutputFormat::Csv) {
+            write_table(self.dir, file_name, items, self.formats)
+        } else {
+            let mut formats = self.formats.to_vec();
+            formats.push(OutputFormat::Csv);
+            write_table(self.dir, file_name, items, &formats)
+        }
+    }
+}
+
+impl GraphExporter for TuGraphExporter<'_> {
+    /// Write the vertices into `<file_name>.csv`, and register the label.
+    fn vertex<T: Record>(
+        &mut self,
+        label: &str,
+        primary: &str,
+        file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>> {
+        self.write(file_name, items)?;
+
+        if !self.schema.iter().any(|s| s["label"] == label) {
+            let properties = properties_of::<T>()?;
+            self.schema.push(json!({
+                "label": label,
+                "type": "VERTEX",
+                "properties": to_property_json(&properties),
+                "primary": primary,
+            }));
+        }
+        self.files.push(json!({
+            "path": self.csv_path(file_name),
+            "header": 1,
+            "format": "CSV",
+            "label": label,
+            "columns": columns_of::<T>()?,
+        }));
+        Ok(())
+    }
+
+    /// Write the edges into `<file_name>.csv`, and register the label.
+    fn edge<T: Record>(
+        &mut self,
+        label: &str,
+        src_label: &str,
+        dst_label: &str,
+        file_name: &str,
+        items: &[T],
+    ) -> Result<(), Box<dyn Error>> {
+        self.write(file_name, items)?;
+
+        match self.schema.iter_mut().find(|s| s["label"] == label) {
+            Some(edge) => {
+                if let Some(constraints) = edge["constraints"].as_array_mut() {
+                    let constraint = json!([src_label, dst_label]);
+                    if !constraints.contains(&constraint) {
+                        constraints.push(constraint);
+                    }
+                }
+            }
+            None => {
+                let properties: Vec<_> = properties_of::<T>()?
+                    .into_iter()
+                    .filter(|(name, _, _)| name != "SRC_ID" && name != "DST_ID")
+                    .collect();
+                self.schema.push(json!({
+                    "label": label,
+                    "type": "EDGE",
+                    "properties": to_property_json(&properties),
+                    "constraints": [[src_label, dst_label]],
+                }));
+            }
+        }
+        self.files.push(json!({
+            "path": self.csv_path(file_name),
+            "header": 1,
+            "format": "CSV",
+            "label": label,
+            "SRC_ID": src_label,
+            "DST_ID": dst_label,
+            "columns": columns_of::<T>()?,
+        }));
+        Ok(())
+    }
+
+    /// Write the `import.config` into the directory.
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        let config = json!({
+            "schema": self.schema,
+            "files": self.files,
+        });
+        fs::write(
+            self.dir.join(IMPORT_CONFIG_FILE),
+            serde_json::to_string_pretty(&config)?,
+        )?;
+        Ok(())
+    }
+}
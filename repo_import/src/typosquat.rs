@@ -0,0 +1,249 @@
+//! Flag the libraries whose names look like a typo of a popular one, such as `serde_jsom`
+//! for `serde_json`, as `typosquats` edges to the popular library with a confidence.
+//!
+//! It's opt-in by `IMPORT_TYPOSQUAT_CHECK=1`. The popular libraries are the
+//! `IMPORT_TYPOSQUAT_POPULAR` (1000 by default) most downloaded ones, and a name is compared
+//! with them by the Damerau-Levenshtein distance, where a single edit is classified:
+//! a keyboard-adjacent key, swapped letters, a repeated letter, a separator, an omitted or an
+//! inserted letter. The confidence decreases with the downloads of the suspect relative to
+//! the popular one, and the libraries of the same owner are not suspected, such as
+//! `tokio-util` and `tokio` of `tokio-rs`.
+
+use crate::ImportContext;
+use model::tugraph_model::Typosquats;
+use std::collections::HashMap;
+use std::env;
+
+const DEFAULT_POPULAR: usize = 1000;
+
+/// The shorter names are too close to each other to tell a typo.
+const MIN_NAME_LEN: usize = 4;
+
+/// The edges below it are not recorded.
+const MIN_CONFIDENCE: f64 = 0.3;
+
+const QWERTY: [&str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Typo {
+    AdjacentKey,
+    Transposition,
+    RepeatedLetter,
+    Separator,
+    Omission,
+    Insertion,
+    Substitution,
+    TwoEdits,
+}
+
+impl Typo {
+    fn as_str(self) -> &'static str {
+        match self {
+            Typo::AdjacentKey => "adjacent_key",
+            Typo::Transposition => "transposition",
+            Typo::RepeatedLetter => "repeated_letter",
+            Typo::Separator => "separator",
+            Typo::Omission => "omission",
+            Typo::Insertion => "insertion",
+            Typo::Substitution => "substitution",
+            Typo::TwoEdits => "two_edits",
+        }
+    }
+
+    /// How likely such a name is a typosquat, before the downloads are considered.
+    fn likelihood(self) -> f64 {
+        match self {
+            Typo::AdjacentKey => 0.9,
+            Typo::Transposition => 0.85,
+            Typo::RepeatedLetter | Typo::Separator => 0.8,
+            Typo::Omission => 0.7,
+            Typo::Insertion => 0.6,
+            Typo::Substitution => 0.5,
+            Typo::TwoEdits => 0.35,
+        }
+    }
+}
+
+fn key_position(c: char) -> Option<(i32, i32)> {
+    QWERTY.iter().enumerate().find_map(|(row, keys)| {
+        keys.find(c.to_ascii_lowercase())
+            .map(|col| (row as i32, col as i32))
+    })
+}
+
+/// Whether two keys are next to each other on a QWERTY keyboard, diagonally included.
+fn keyboard_adjacent(a: char, b: char) -> bool {
+    match (key_position(a), key_position(b)) {
+        (Some((ra, ca)), Some((rb, cb))) => a != b && (ra - rb).abs() <= 1 && (ca - cb).abs() <= 1,
+        _ => false,
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c == '-' || c == '_'
+}
+
+/// The Damerau-Levenshtein distance, by the optimal string alignment.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// The single edit turning `popular` into `suspect`, whose distance is 1.
+fn classify_edit(suspect: &[char], popular: &[char]) -> Typo {
+    // the first difference
+    let i = suspect
+        .iter()
+        .zip(popular)
+        .position(|(a, b)| a != b)
+        .unwrap_or(suspect.len().min(popular.len()));
+    match suspect.len() as isize - popular.len() as isize {
+        0 if i + 1 < suspect.len()
+            && suspect[i] == popular[i + 1]
+            && suspect[i + 1] == popular[i] =>
+        {
+            Typo::Transposition
+        }
+        0 if keyboard_adjacent(suspect[i], popular[i]) => Typo::AdjacentKey,
+        0 if is_separator(suspect[i]) && is_separator(popular[i]) => Typo::Separator,
+        0 => Typo::Substitution,
+        1 if is_separator(suspect[i]) => Typo::Separator,
+        1 if (i > 0 && suspect[i] == suspect[i - 1])
+            || (i + 1 < suspect.len() && suspect[i] == suspect[i + 1]) =>
+        {
+            Typo::RepeatedLetter
+        }
+        1 => Typo::Insertion,
+        _ if is_separator(popular[i]) => Typo::Separator,
+        _ => Typo::Omission,
+    }
+}
+
+/// The typo of `suspect` looking like `popular`, with the edit distance.
+fn classify(suspect: &str, popular: &str) -> Option<(Typo, usize)> {
+    if suspect.len().abs_diff(popular.len()) > 2 {
+        return None;
+    }
+    let suspect: Vec<char> = suspect.to_lowercase().chars().collect();
+    let popular: Vec<char> = popular.to_lowercase().chars().collect();
+    if suspect.len().min(popular.len()) < MIN_NAME_LEN {
+        return None;
+    }
+    // `-` and `_` are the same in the names of crates.io
+    let normalize = |c: &char| if *c == '_' { '-' } else { *c };
+    if suspect
+        .iter()
+        .map(normalize)
+        .eq(popular.iter().map(normalize))
+    {
+        return None;
+    }
+    match edit_distance(&suspect, &popular) {
+        1 => Some((classify_edit(&suspect, &popular), 1)),
+        2 if popular.len() >= 2 * MIN_NAME_LEN => Some((Typo::TwoEdits, 2)),
+        _ => None,
+    }
+}
+
+/// The confidence of a typosquat, lower if the suspect is downloaded as much as the popular one.
+fn confidence(typo: Typo, suspect_downloads: i64, popular_downloads: i64) -> f64 {
+    let ratio = if popular_downloads > 0 {
+        (suspect_downloads.max(0) as f64 / popular_downloads as f64).min(1.0)
+    } else {
+        1.0
+    };
+    typo.likelihood() * (1.0 - ratio)
+}
+
+impl ImportContext {
+    /// Build the `typosquats` edges from the suspected libraries to the popular ones.
+    pub(crate) fn detect_typosquats(&mut self) {
+        let popular_count = env::var("IMPORT_TYPOSQUAT_POPULAR")
+            .ok()
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(DEFAULT_POPULAR);
+        // program id -> the owner of its repo, such as `tokio-rs` of `tokio-rs/tokio`
+        let owners: HashMap<&str, &str> = self
+            .programs
+            .iter()
+            .filter_map(|p| {
+                let (owner, _) = p.namespace.as_deref()?.split_once('/')?;
+                Some((p.id.as_str(), owner))
+            })
+            .collect();
+        let mut popular: Vec<_> = self.libraries.iter().filter(|l| l.downloads > 0).collect();
+        popular.sort_by_key(|l| std::cmp::Reverse(l.downloads));
+        popular.truncate(popular_count);
+
+        let mut typosquats = vec![];
+        for suspect in &self.libraries {
+            for target in &popular {
+                let owner = owners.get(suspect.id.as_str());
+                if suspect.id == target.id
+                    || (owner.is_some() && owner == owners.get(target.id.as_str()))
+                {
+                    continue;
+                }
+                let Some((typo, distance)) = classify(&suspect.name, &target.name) else {
+                    continue;
+                };
+                let confidence = confidence(typo, suspect.downloads, target.downloads);
+                if confidence >= MIN_CONFIDENCE {
+                    typosquats.push(Typosquats {
+                        SRC_ID: suspect.id.clone(),
+                        DST_ID: target.id.clone(),
+                        kind: typo.as_str().to_string(),
+                        distance: distance as i64,
+                        confidence,
+                    });
+                }
+            }
+        }
+        tracing::info!("Found {} likely typosquats", typosquats.len());
+        self.typosquats = typosquats;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let kind = |suspect: &str, popular: &str| classify(suspect, popular).map(|(t, _)| t);
+        assert_eq!(kind("serde_jsom", "serde_json"), Some(Typo::AdjacentKey));
+        assert_eq!(kind("sedre", "serde"), Some(Typo::Transposition));
+        assert_eq!(kind("tokkio", "tokio"), Some(Typo::RepeatedLetter));
+        assert_eq!(kind("serdejson", "serde_json"), Some(Typo::Separator));
+        assert_eq!(kind("serde-json", "serde_json"), None);
+        assert_eq!(kind("reqest", "reqwest"), Some(Typo::Omission));
+        assert_eq!(kind("reqzwest", "reqwest"), Some(Typo::Insertion));
+        assert_eq!(kind("lazy_statik", "lazy_static"), Some(Typo::Substitution));
+        assert_eq!(kind("syn", "sym"), None);
+        assert_eq!(kind("tonic", "tokio"), None);
+        assert_eq!(
+            kind("crossbeem-chanel", "crossbeam-channel"),
+            Some(Typo::TwoEdits)
+        );
+
+        assert!(confidence(Typo::AdjacentKey, 10, 1_000_000) > 0.89);
+        assert_eq!(confidence(Typo::AdjacentKey, 2_000_000, 1_000_000), 0.0);
+    }
+}
@@ -0,0 +1,107 @@
+//! Count the `unsafe` code of a crate by parsing its sources with syn,
+//! so that crates can be ranked by their unsafe density.
+
+use crate::crate_info::crate_files;
+use std::fs;
+use std::path::Path;
+use syn::visit::{self, Visit};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct UnsafeMetrics {
+    pub(crate) unsafe_blocks: i64,
+    pub(crate) unsafe_fns: i64,
+    pub(crate) unsafe_impls: i64,
+    pub(crate) unsafe_traits: i64,
+}
+
+impl<'ast> Visit<'ast> for UnsafeMetrics {
+    fn visit_expr_unsafe(&mut self, node: &'ast syn::ExprUnsafe) {
+        self.unsafe_blocks += 1;
+        visit::visit_expr_unsafe(self, node);
+    }
+
+    fn visit_signature(&mut self, node: &'ast syn::Signature) {
+        // the signatures of free functions, methods and trait methods
+        if node.unsafety.is_some() {
+            self.unsafe_fns += 1;
+        }
+        visit::visit_signature(self, node);
+    }
+
+    fn visit_item_impl(&mut self, node: &'ast syn::ItemImpl) {
+        if node.unsafety.is_some() {
+            self.unsafe_impls += 1;
+        }
+        visit::visit_item_impl(self, node);
+    }
+
+    fn visit_item_trait(&mut self, node: &'ast syn::ItemTrait) {
+        if node.unsafety.is_some() {
+            self.unsafe_traits += 1;
+        }
+        visit::visit_item_trait(self, node);
+    }
+}
+
+impl UnsafeMetrics {
+    fn count_source(&mut self, source: &str) -> Result<(), syn::Error> {
+        let file = syn::parse_file(source)?;
+        self.visit_file(&file);
+        Ok(())
+    }
+}
+
+/// Count the unsafe code in the `.rs` files of a crate.
+///
+/// The nested crates, which have their own `Cargo.toml`, are not counted,
+/// and the files failing to parse are skipped.
+pub(crate) fn count_unsafe(crate_dir: &Path) -> UnsafeMetrics {
+    let mut metrics = UnsafeMetrics::default();
+    let files = crate_files(crate_dir)
+        .into_iter()
+        .filter(|f| f.extension().is_some_and(|ext| ext == "rs"));
+    for file in files {
+        let source = match fs::read_to_string(&file) {
+            Ok(source) => source,
+            Err(_) => continue,
+        };
+        if let Err(e) = metrics.count_source(&source) {
+            tracing::debug!("Failed to parse {:?}: {}", file, e);
+        }
+    }
+    metrics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_unsafe() {
+        let source = r#"
+            unsafe trait Zeroable {}
+            unsafe impl Zeroable for u8 {}
+            unsafe fn read(p: *const u8) -> u8 { *p }
+            struct S;
+            impl S {
+                unsafe fn get(&self) -> u8 { 0 }
+                fn safe(&self) -> u8 { unsafe { read(&0) } }
+            }
+            fn main() {
+                let x = unsafe { read(&1) };
+                let f = || unsafe { read(&x) };
+            }
+        "#;
+        let mut metrics = UnsafeMetrics::default();
+        metrics.count_source(source).unwrap();
+        assert_eq!(
+            metrics,
+            UnsafeMetrics {
+                unsafe_blocks: 3,
+                unsafe_fns: 2,
+                unsafe_impls: 1,
+                unsafe_traits: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Aggregate the unsafe metrics over the dependency closure of each version in the style of
+//! cargo-geiger, so that the alternatives can be compared by the unsafe code they pull in.
+//!
+//! The metrics are the ones counted at HEAD with `IMPORT_UNSAFE_METRICS=1`, shared by all
+//! the versions of a crate, and the crates not counted are skipped. The score is the number
+//! of unsafe items per thousand lines of Rust code over the closure, if the lines are
+//! counted with `IMPORT_LOC_METRICS=1` as well.
+
+use crate::dependency_closure::ClosureCache;
+use crate::ImportContext;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TransitiveUnsafety {
+    /// the unsafe blocks, functions, impls and traits of the version and its dependencies
+    unsafe_items: i64,
+    /// the dependencies with any unsafe item
+    unsafe_dependencies: i64,
+    score: Option<f64>,
+}
+
+/// The unsafety of `root` and its `dependencies`, by the `(unsafe items, lines of Rust code)`
+/// of the crates, none if no crate of them is counted.
+fn transitive_unsafety<'a>(
+    root: &'a str,
+    dependencies: impl Iterator<Item = &'a str>,
+    metrics: &HashMap<String, (i64, Option<i64>)>,
+) -> Option<TransitiveUnsafety> {
+    let mut counted = false;
+    let mut unsafety = TransitiveUnsafety {
+        unsafe_items: 0,
+        unsafe_dependencies: 0,
+        score: None,
+    };
+    // the items of the crates whose lines are counted as well
+    let (mut items, mut lines) = (0, 0);
+    for (i, name_and_version) in std::iter::once(root).chain(dependencies).enumerate() {
+        let name = name_and_version
+            .split_once('/')
+            .map_or(name_and_version, |(name, _)| name);
+        let Some(&(unsafe_items, code_lines)) = metrics.get(name) else {
+            continue;
+        };
+        counted = true;
+        unsafety.unsafe_items += unsafe_items;
+        if i > 0 && unsafe_items > 0 {
+            unsafety.unsafe_dependencies += 1;
+        }
+        if let Some(code_lines) = code_lines {
+            items += unsafe_items;
+            lines += code_lines;
+        }
+    }
+    if lines > 0 {
+        unsafety.score = Some(items as f64 * 1000.0 / lines as f64);
+    }
+    counted.then_some(unsafety)
+}
+
+impl ImportContext {
+    /// Fill the transitive unsafety of the library and application versions.
+    pub(crate) fn score_transitive_unsafety(&mut self) {
+        let metrics: HashMap<String, (i64, Option<i64>)> = self
+            .programs
+            .iter()
+            .filter_map(|p| {
+                let items = p.unsafe_blocks?
+                    + p.unsafe_fns.unwrap_or(0)
+                    + p.unsafe_impls.unwrap_or(0)
+                    + p.unsafe_traits.unwrap_or(0);
+                Some((p.name.clone(), (items, p.rust_code_lines)))
+            })
+            .collect();
+        if metrics.is_empty() {
+            return;
+        }
+
+        let mut dependencies: HashMap<&str, Vec<String>> = HashMap::new();
+        for edge in &self.depends_on {
+            dependencies
+                .entry(edge.SRC_ID.as_str())
+                .or_default()
+                .push(edge.DST_ID.clone());
+        }
+        let mut cache = ClosureCache::default();
+        let mut scores: HashMap<String, TransitiveUnsafety> = HashMap::new();
+        for name_and_version in self
+            .library_versions
+            .iter()
+            .map(|v| &v.name_and_version)
+            .chain(
+                self.application_versions
+                    .iter()
+                    .map(|v| &v.name_and_version),
+            )
+        {
+            let closure = cache.closure(name_and_version, |v| {
+                dependencies.get(v).cloned().unwrap_or_default()
+            });
+            let entries = closure.iter().map(|e| e.name_and_version.as_str());
+            if let Some(unsafety) = transitive_unsafety(name_and_version, entries, &metrics) {
+                scores.insert(name_and_version.clone(), unsafety);
+            }
+        }
+        tracing::info!(
+            "Scored the transitive unsafety of {} versions",
+            scores.len()
+        );
+
+        for version in &mut self.library_versions {
+            if let Some(unsafety) = scores.get(&version.name_and_version) {
+                version.transitive_unsafe = Some(unsafety.unsafe_items);
+                version.unsafe_dependencies = Some(unsafety.unsafe_dependencies);
+                version.unsafety_score = unsafety.score;
+            }
+        }
+        for version in &mut self.application_versions {
+            if let Some(unsafety) = scores.get(&version.name_and_version) {
+                version.transitive_unsafe = Some(unsafety.unsafe_items);
+                version.unsafe_dependencies = Some(unsafety.unsafe_dependencies);
+                version.unsafety_score = unsafety.score;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitive_unsafety() {
+        let metrics = HashMap::from([
+            ("app".to_string(), (0, Some(1000))),
+            ("libc".to_string(), (30, Some(2000))),
+            ("memchr".to_string(), (10, None)),
+        ]);
+        let dependencies = ["libc/0.2.155", "memchr/2.7.4", "cfg-if/1.0.0"];
+        let unsafety =
+            transitive_unsafety("app/0.1.0", dependencies.into_iter(), &metrics).unwrap();
+        assert_eq!(unsafety.unsafe_items, 40);
+        assert_eq!(unsafety.unsafe_dependencies, 2);
+        // memchr is not in the score, whose lines are unknown
+        assert_eq!(unsafety.score, Some(10.0));
+
+        assert_eq!(
+            transitive_unsafety("cfg-if/1.0.0", std::iter::empty(), &metrics),
+            None
+        );
+    }
+}
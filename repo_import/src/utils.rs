@@ -1,15 +1,25 @@
+use crate::error::ImportError;
+use arrow::array::RecordBatch;
+use arrow::datatypes::FieldRef;
+use arrow::ipc::writer::StreamWriter;
 use csv::WriterBuilder;
 use lazy_static::lazy_static;
 use model::tugraph_model::{Program, UProgram};
-use serde::Serialize;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::{Deserialize, Serialize};
+use serde_arrow::schema::{SchemaLike, TracingOptions};
 use serde_json::json;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::Debug;
-use std::fs::OpenOptions;
-use std::path::PathBuf;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use url::Url;
+use uuid::Uuid;
 
 lazy_static! {
     pub static ref NAMESPACE_HASHMAP: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
@@ -40,50 +50,132 @@ pub fn get_program_by_name(key: &str) -> Option<(Program, UProgram)> {
     map.get(key).cloned()
 }
 
+/// The number of records buffered before each flush
+const CSV_FLUSH_BATCH_SIZE: usize = 10_000;
+
 pub(crate) fn write_into_csv<T: Serialize + Default + Debug>(
     csv_path: PathBuf,
-    programs: Vec<T>,
+    programs: &[T],
 ) -> Result<(), Box<dyn Error>> {
-    let serialized = serde_json::to_value(T::default()).unwrap();
+    let mut writer = CsvStreamWriter::create::<T>(&csv_path)?;
+    for program in programs {
+        writer.write(program)?;
+    }
+    writer.finish()
+}
+
+/// A csv writer which keeps the file open,
+/// the records are buffered and flushed in batches.
+pub(crate) struct CsvStreamWriter {
+    writer: csv::Writer<BufWriter<File>>,
+    pending: usize,
+}
+
+impl CsvStreamWriter {
+    /// Create (or truncate) the file, and write the header of `T`.
+    pub(crate) fn create<T: Serialize + Default>(csv_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(csv_path)?;
 
-    if let serde_json::Value::Object(map) = serialized {
-        let field_names: Vec<&str> = map.keys().map(|s| s.as_str()).collect();
-        write_to_csv(field_names, csv_path.to_str().unwrap(), false)?;
+        let mut writer = WriterBuilder::new()
+            .quote_style(csv::QuoteStyle::Necessary)
+            .double_quote(true)
+            .from_writer(BufWriter::new(file));
+
+        let serialized = serde_json::to_value(T::default())?;
+        if let serde_json::Value::Object(map) = serialized {
+            writer.write_record(map.keys())?;
+        }
+
+        Ok(Self { writer, pending: 0 })
     }
 
-    for program in &programs {
-        let fields = get_fields(program);
-        let fields = fields.iter().map(|s| s.as_str()).collect::<Vec<_>>();
-        write_to_csv(fields, csv_path.to_str().unwrap(), true)?;
+    pub(crate) fn write<T: Serialize>(&mut self, item: &T) -> Result<(), Box<dyn Error>> {
+        self.writer.write_record(get_fields(item))?;
+        self.pending += 1;
+        if self.pending >= CSV_FLUSH_BATCH_SIZE {
+            self.writer.flush()?;
+            self.pending = 0;
+        }
+        Ok(())
     }
 
+    pub(crate) fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Write one json object per line (NDJSON),
+/// so the nested values keep their types, unlike the flattened csv fields.
+pub(crate) fn write_into_jsonl<T: Serialize>(
+    jsonl_path: PathBuf,
+    items: &[T],
+) -> Result<(), Box<dyn Error>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(jsonl_path)?;
+    let mut writer = BufWriter::new(file);
+    for item in items {
+        serde_json::to_writer(&mut writer, item)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
     Ok(())
 }
 
-fn write_to_csv(data: Vec<&str>, file_path: &str, append: bool) -> Result<(), Box<dyn Error>> {
-    let file = if append {
-        OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(file_path)?
-    } else {
-        OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(file_path)?
-    };
+/// Convert the records into an arrow record batch,
+/// the schema is traced from the type so empty tables still have the columns.
+pub(crate) fn to_record_batch<T: Serialize + for<'de> Deserialize<'de>>(
+    items: &[T],
+) -> Result<RecordBatch, Box<dyn Error>> {
+    let fields =
+        Vec::<FieldRef>::from_type::<T>(TracingOptions::default().allow_null_fields(true))?;
+    Ok(serde_arrow::to_record_batch(&fields, &items)?)
+}
 
-    let mut wtr = WriterBuilder::new()
-        .quote_style(csv::QuoteStyle::Necessary)
-        .double_quote(true)
-        .from_writer(file);
+/// Write the records into a parquet file, which can be loaded by Spark/DuckDB directly.
+pub(crate) fn write_into_parquet<T: Serialize + for<'de> Deserialize<'de>>(
+    parquet_path: PathBuf,
+    items: &[T],
+) -> Result<(), Box<dyn Error>> {
+    let batch = to_record_batch(items)?;
+    let file = File::create(parquet_path)?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(props))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
 
-    wtr.write_record(&data)?;
-    wtr.flush()?;
+/// Write the records as an arrow IPC stream into any writer, e.g. a pipe,
+/// so the consumer can read the columns without reparsing.
+pub(crate) fn write_arrow_ipc_stream<W: Write, T: Serialize + for<'de> Deserialize<'de>>(
+    writer: W,
+    items: &[T],
+) -> Result<(), Box<dyn Error>> {
+    let batch = to_record_batch(items)?;
+    let mut writer = StreamWriter::try_new(writer, &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()?;
     Ok(())
 }
 
+pub(crate) fn write_into_arrow_ipc<T: Serialize + for<'de> Deserialize<'de>>(
+    ipc_path: PathBuf,
+    items: &[T],
+) -> Result<(), Box<dyn Error>> {
+    let file = File::create(ipc_path)?;
+    write_arrow_ipc_stream(BufWriter::new(file), items)
+}
+
 fn get_fields<T: Serialize>(item: &T) -> Vec<String> {
     let mut fields = Vec::new();
     let json = json!(item);
@@ -99,55 +191,249 @@ fn get_fields<T: Serialize>(item: &T) -> Vec<String> {
     fields
 }
 
+/// How the namespace is found in the path of a repo url
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamespaceRule {
+    /// the first two segments `owner/repo`, such as GitHub, Bitbucket and Gitea
+    OwnerRepo,
+    /// all the segments before `/-/`, such as `group/subgroup/project` of GitLab
+    GitLab,
+    /// all the segments without the `cgit` prefix and the page such as `tree`,
+    /// such as `pub/scm/git/git` of git.kernel.org
+    Cgit,
+    /// the last two segments, for the unknown hosts such as mega
+    LastTwo,
+}
+
+impl NamespaceRule {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "owner_repo" | "github" | "bitbucket" | "gitea" => Some(NamespaceRule::OwnerRepo),
+            "gitlab" => Some(NamespaceRule::GitLab),
+            "cgit" => Some(NamespaceRule::Cgit),
+            "last_two" => Some(NamespaceRule::LastTwo),
+            _ => None,
+        }
+    }
+
+    /// The rule of a well-known host, or guessed by the host name.
+    fn of_known_host(host: &str) -> Self {
+        match host {
+            "github.com" | "bitbucket.org" | "codeberg.org" | "gitea.com" | "git.sr.ht" => {
+                NamespaceRule::OwnerRepo
+            }
+            "git.kernel.org" => NamespaceRule::Cgit,
+            _ if host.contains("gitlab") => NamespaceRule::GitLab,
+            _ if host.contains("gitea") || host.contains("forgejo") => NamespaceRule::OwnerRepo,
+            _ if host.starts_with("cgit.") => NamespaceRule::Cgit,
+            _ => NamespaceRule::LastTwo,
+        }
+    }
+
+    fn namespace<'a>(&self, segments: &[&'a str]) -> Option<Vec<&'a str>> {
+        let segments = match self {
+            NamespaceRule::OwnerRepo => segments.get(..2)?.to_vec(),
+            NamespaceRule::GitLab => segments.split(|s| *s == "-").next()?.to_vec(),
+            NamespaceRule::Cgit => {
+                let start = usize::from(segments.first().is_some_and(|s| s.starts_with("cgit")));
+                let end = segments
+                    .iter()
+                    .position(|s| CGIT_PAGES.contains(s))
+                    .unwrap_or(segments.len());
+                segments.get(start..end)?.to_vec()
+            }
+            NamespaceRule::LastTwo => segments.get(segments.len().checked_sub(2)?..)?.to_vec(),
+        };
+        Some(segments)
+    }
+}
+
+/// The pages of a cgit repo, such as `/linux.git/tree/README`
+const CGIT_PAGES: &[&str] = &[
+    "about", "summary", "refs", "log", "tree", "commit", "diff", "stats", "plain", "patch",
+];
+
+lazy_static! {
+    /// the host patterns registered, such as `git.example.com` or `*.example.com`,
+    /// initialized by `NAMESPACE_RULES`, such as `git.example.com=gitlab,*.example.com=gitea`
+    static ref NAMESPACE_RULES: Mutex<Vec<(String, NamespaceRule)>> = Mutex::new(
+        std::env::var("NAMESPACE_RULES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|rule| {
+                let (pattern, name) = rule.split_once('=')?;
+                Some((pattern.trim().to_lowercase(), NamespaceRule::from_name(name)?))
+            })
+            .collect()
+    );
+}
+
+/// Register the namespace rule of a host pattern, such as `git.example.com` or `*.example.com`.
+/// The patterns registered later take precedence.
+pub fn register_namespace_rule(pattern: &str, rule: NamespaceRule) {
+    let mut rules = NAMESPACE_RULES.lock().unwrap();
+    rules.insert(0, (pattern.to_lowercase(), rule));
+}
+
+fn namespace_rule(host: &str) -> NamespaceRule {
+    let host = host.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    let rules = NAMESPACE_RULES.lock().unwrap();
+    let registered = rules
+        .iter()
+        .find(|(pattern, _)| match pattern.strip_prefix("*.") {
+            Some(suffix) => host.ends_with(&format!(".{}", suffix)),
+            None => pattern == host,
+        });
+    match registered {
+        Some((_, rule)) => *rule,
+        None => NamespaceRule::of_known_host(host),
+    }
+}
+
 /// An auxiliary function
 ///
 /// Extracts namespace e.g. "tokio-rs/tokio" from the git url https://www.github.com/tokio-rs/tokio
-pub(crate) fn extract_namespace(url_str: &str) -> Result<String, String> {
+///
+/// SSH remotes are also supported, both `ssh://git@github.com/tokio-rs/tokio.git`
+/// and the scp-like syntax `git@github.com:tokio-rs/tokio.git`.
+///
+/// The namespace is found by the [`NamespaceRule`] of the host, e.g.
+/// `gitlab.com/group/subgroup/project` yields `group/subgroup/project`.
+pub(crate) fn extract_namespace(url_str: &str) -> Result<String, ImportError> {
     /// auxiliary function
-    fn remove_dot_git_suffix(input: &str) -> String {
-        let input = if input.ends_with('/') {
-            input.strip_suffix('/').unwrap()
-        } else {
-            input
-        };
-
-        let input = if input.ends_with(".git") {
-            input.strip_suffix(".git").unwrap().to_string()
-        } else {
-            input.to_string()
-        };
-        input
+    /// git@github.com:tokio-rs/tokio => ssh://git@github.com/tokio-rs/tokio
+    fn scp_like_to_ssh_url(input: &str) -> Option<String> {
+        if input.contains("://") {
+            return None;
+        }
+        let (host, path) = input.split_once(':')?;
+        // `C:/path` is a windows path, not a host
+        if host.is_empty() || host.len() == 1 || host.contains('/') {
+            return None;
+        }
+        Some(format!("ssh://{}/{}", host, path.trim_start_matches('/')))
     }
 
-    let url = Url::parse(&remove_dot_git_suffix(url_str))
-        .map_err(|e| format!("Failed to parse URL {}: {}", url_str, e))?;
+    let url_str_normalized = scp_like_to_ssh_url(url_str).unwrap_or_else(|| url_str.to_string());
+    let url = Url::parse(&url_str_normalized).map_err(|e| ImportError::url(url_str, e))?;
 
     // /tokio-rs/tokio
     let path_segments = url
         .path_segments()
-        .ok_or("Cannot extract path segments from URL")?;
-
-    let segments: Vec<&str> = path_segments.collect();
-    //println!("{:?}", segments);
+        .ok_or_else(|| ImportError::url(url_str, "Cannot extract path segments from URL"))?;
+    let segments: Vec<&str> = path_segments.filter(|s| !s.is_empty()).collect();
 
-    // github URLs is of the format "/user/repo"
-    if segments.len() < 2 {
-        return Err(format!(
-            "URL {} does not include a namespace and a repository name",
-            url_str
-        ));
+    let rule = namespace_rule(url.host_str().unwrap_or_default());
+    let mut namespace = match rule.namespace(&segments) {
+        Some(namespace) if namespace.len() >= 2 => namespace,
+        _ => {
+            return Err(ImportError::url(
+                url_str,
+                "URL does not include a namespace and a repository name",
+            ))
+        }
+    };
+    // the repo name without `.git`
+    if let Some(repo) = namespace.last_mut() {
+        *repo = repo.strip_suffix(".git").unwrap_or(repo);
     }
 
-    // join owner name and repo name
-    let namespace = format!(
-        "{}/{}",
-        segments[segments.len() - 2],
-        segments[segments.len() - 1]
-    );
+    Ok(namespace.join("/"))
+}
 
-    Ok(namespace)
+/// Generate a deterministic program id from the namespace and the crate name,
+/// e.g. ("tokio-rs/tokio", "tokio-util") always yields the same UUIDv5.
+pub(crate) fn generate_program_id(namespace: &str, crate_name: &str) -> String {
+    let name = format!("{}/{}", namespace, crate_name);
+    Uuid::new_v5(&Uuid::NAMESPACE_URL, name.as_bytes()).to_string()
 }
 
 pub(crate) fn name_join_version(crate_name: &str, version: &str) -> String {
     crate_name.to_string() + "/" + version
 }
+
+pub(crate) fn feature_id(crate_name: &str, version: &str, feature: &str) -> String {
+    name_join_version(crate_name, version) + "/" + feature
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_namespace, generate_program_id, register_namespace_rule, NamespaceRule};
+
+    #[test]
+    fn test_extract_namespace() {
+        for url in [
+            "https://github.com/tokio-rs/tokio",
+            "https://github.com/tokio-rs/tokio.git",
+            "https://github.com/tokio-rs/tokio/",
+            "ssh://git@github.com/tokio-rs/tokio.git",
+            "ssh://git@github.com:22/tokio-rs/tokio.git",
+            "git@github.com:tokio-rs/tokio.git",
+            "git@github.com:/tokio-rs/tokio",
+        ] {
+            assert_eq!(extract_namespace(url).unwrap(), "tokio-rs/tokio");
+        }
+
+        assert!(extract_namespace("git@github.com:tokio").is_err());
+        assert!(extract_namespace("tokio").is_err());
+    }
+
+    #[test]
+    fn test_extract_namespace_by_host() {
+        for (url, namespace) in [
+            (
+                "https://github.com/tokio-rs/tokio/tree/master",
+                "tokio-rs/tokio",
+            ),
+            (
+                "https://gitlab.com/group/subgroup/project.git",
+                "group/subgroup/project",
+            ),
+            (
+                "https://gitlab.com/group/subgroup/project/-/tree/main",
+                "group/subgroup/project",
+            ),
+            (
+                "git@gitlab.com:group/subgroup/project.git",
+                "group/subgroup/project",
+            ),
+            (
+                "https://bitbucket.org/workspace/repo/src/main/",
+                "workspace/repo",
+            ),
+            (
+                "https://codeberg.org/owner/repo/src/branch/main",
+                "owner/repo",
+            ),
+            (
+                "https://git.kernel.org/pub/scm/linux/kernel/git/torvalds/linux.git/tree/README",
+                "pub/scm/linux/kernel/git/torvalds/linux",
+            ),
+            (
+                "https://mega.example.com/third-party/crates/tokio",
+                "crates/tokio",
+            ),
+        ] {
+            assert_eq!(extract_namespace(url).unwrap(), namespace);
+        }
+
+        register_namespace_rule("*.corp.example.com", NamespaceRule::GitLab);
+        assert_eq!(
+            extract_namespace("https://git.corp.example.com/a/b/c").unwrap(),
+            "a/b/c"
+        );
+    }
+
+    #[test]
+    fn test_generate_program_id() {
+        assert_eq!(
+            generate_program_id("tokio-rs/tokio", "tokio"),
+            generate_program_id("tokio-rs/tokio", "tokio")
+        );
+        assert_ne!(
+            generate_program_id("tokio-rs/tokio", "tokio"),
+            generate_program_id("tokio-rs/tokio", "tokio-util")
+        );
+    }
+}
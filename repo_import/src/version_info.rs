@@ -1,11 +1,28 @@
-use crate::git::get_all_git_tags_with_time_sorted;
-use crate::utils::name_join_version;
+use crate::api_surface::extract_api_from_tree;
+use crate::crate_info::{
+    is_excluded_dir_name, parse_dependencies, parse_overrides, resolve_path_dependencies,
+};
+use crate::discovery_filter::discovery_filter;
+use crate::git::{get_all_git_tags_with_time_sorted, GitTag};
+use crate::lockfile::{locked_dependencies, read_lockfile_in_tree, LockedDependency};
+use crate::manifest::{
+    find_workspace_root_in_tree, inherit_from_workspace, read_manifest_in_tree, Manifest,
+};
+use crate::msrv::{bisect_msrv, bisect_toolchains, detect_msrv_in_tree, msrv_from_manifest, Msrv};
+use crate::package_size::{package_size_enabled, package_size_in_tree};
+use crate::sandbox::sandbox;
+use crate::semver_range::semver_range;
+use crate::utils::{extract_namespace, name_join_version};
 use crate::ImportContext;
-use git2::{Oid, Repository};
+use git2::{ObjectType, Repository};
 use git2::{TreeWalkMode, TreeWalkResult};
-use model::tugraph_model::DependsOn;
+use model::general_model::{Dependency, DependencyKind, DependencyOverride, DependencySource};
+use model::tugraph_model::{
+    ApiItem, BuildDependsOn, DependsOn, DevDependsOn, Overrides, ResolvesTo,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
 use std::mem;
 use std::path::PathBuf;
 use toml::Value;
@@ -17,10 +34,31 @@ use toml::Value;
 pub struct Dependencies {
     pub(crate) crate_name: String,
     pub(crate) version: String,
-    pub(crate) dependencies: Vec<(String, String)>,
+    pub(crate) dependencies: Vec<model::general_model::Dependency>,
+    /// the `[patch]` and `[replace]` of its workspace
+    pub(crate) overrides: Vec<DependencyOverride>,
+    /// the `[features]` table
+    pub(crate) features: BTreeMap<String, Vec<String>>,
 
     pub(crate) git_url: String,
     pub(crate) tag_name: String,
+    /// the public API, extracted only if `IMPORT_API_SURFACE` is set
+    pub(crate) api: Vec<ApiItem>,
+    pub(crate) msrv: Option<Msrv>,
+    /// the commit of the tag, unknown if not from a git repo
+    pub(crate) commit: Option<String>,
+    /// the commit time in seconds since the epoch
+    pub(crate) commit_time: Option<i64>,
+    /// the time it's published to the registry, in seconds since the epoch
+    pub(crate) published_at: Option<i64>,
+    /// the direct dependencies resolved by the `Cargo.lock` of the repo
+    pub(crate) locked: Vec<LockedDependency>,
+    /// the size of the `.crate` in bytes, unknown if not from a registry
+    pub(crate) crate_size: Option<i64>,
+    /// the total size of the packaged files in bytes
+    pub(crate) unpacked_size: Option<i64>,
+    /// the sha256 of the `.crate` in the registry
+    pub(crate) checksum: Option<String>,
 }
 
 impl ImportContext {
@@ -36,9 +74,9 @@ impl ImportContext {
         let versions = get_all_git_tags_with_time_sorted(repo_path).await; //tag id time
 
         // parse each version of a repository with an order of time, walk all the packages of it
-        for (tag_name, tree, _) in versions.iter() {
+        for tag in versions.iter() {
             let all_packages_dependencies = self
-                .parse_a_repo_of_a_version(repo_path, git_url, tag_name, *tree)
+                .parse_a_repo_of_a_version(repo_path, git_url, tag)
                 .await;
 
             // NOTE: At certain time, a version in Cargo.toml will exists in several tags,
@@ -59,29 +97,99 @@ impl ImportContext {
         &self,
         repo_path: &PathBuf,
         git_url: &str,
-        tag_name: &str,
-        tree: Oid,
+        tag: &GitTag,
     ) -> Vec<Dependencies> {
         let mut res = Vec::new();
 
         // Lock the repository and tree for reading
         let repo = Repository::open(repo_path).unwrap();
-        let tree = repo.find_tree(tree).expect("Failed to find tree");
+        let tree = repo.find_tree(tag.tree).expect("Failed to find tree");
+        let extract_api = env::var("IMPORT_API_SURFACE").is_ok_and(|v| v == "1");
+        let toolchains = bisect_toolchains();
+        let measure_size = package_size_enabled();
 
         // Walk the tree to find Cargo.toml
-        tree.walk(TreeWalkMode::PostOrder, |_, entry| {
-            if entry.name() == Some("Cargo.toml") {
+        tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+            let name = entry.name().unwrap_or_default();
+            let relative = format!("{}{}", root, name);
+            if entry.kind() == Some(ObjectType::Tree)
+                && (is_excluded_dir_name(name) || discovery_filter().is_path_excluded(&relative))
+            {
+                return TreeWalkResult::Skip;
+            }
+
+            if name == "Cargo.toml"
+                && !discovery_filter().is_path_excluded(&relative)
+                && discovery_filter().is_manifest_included(&relative)
+            {
                 // for each Cargo.toml in repo of given commit
-                let obj = entry
-                    .to_object(&repo)
-                    .expect("Failed to convert TreeEntry to Object");
-                let blob = obj.as_blob().expect("Failed to interpret object as blob");
-                let content = std::str::from_utf8(blob.content())
-                    .expect("Cargo.toml content is not valid UTF-8");
-
-                if let Some(dependencies) =
-                    self.parse_a_package_of_a_version(content, git_url, tag_name)
-                {
+                let obj = entry.to_object(&repo).ok();
+                let content = match obj.as_ref().and_then(|o| o.as_blob()) {
+                    Some(blob) => match std::str::from_utf8(blob.content()) {
+                        Ok(content) => content,
+                        Err(_) => {
+                            tracing::warn!("Skip {} at {}, not valid UTF-8", relative, tag.name);
+                            return TreeWalkResult::Ok;
+                        }
+                    },
+                    // such as a submodule named Cargo.toml
+                    None => return TreeWalkResult::Ok,
+                };
+
+                let workspace_root = find_workspace_root_in_tree(&repo, &tree, root);
+                if let Some(mut dependencies) = self.parse_a_package_of_a_version(
+                    content,
+                    workspace_root.as_ref(),
+                    git_url,
+                    tag,
+                ) {
+                    let read_manifest = |dir: &str| read_manifest_in_tree(&repo, &tree, dir);
+                    resolve_path_dependencies(&mut dependencies.dependencies, root, read_manifest);
+                    resolve_path_dependencies(
+                        dependencies
+                            .overrides
+                            .iter_mut()
+                            .map(|o| &mut o.replacement),
+                        root,
+                        read_manifest,
+                    );
+                    if extract_api {
+                        if let Ok(cargo_toml) = toml::from_str::<Manifest>(content) {
+                            dependencies.api = extract_api_from_tree(
+                                &repo,
+                                &tree,
+                                root,
+                                &cargo_toml,
+                                &dependencies.crate_name,
+                                &dependencies.version,
+                            );
+                        }
+                    }
+                    if let Some(lockfile) = read_lockfile_in_tree(&repo, &tree, root) {
+                        dependencies.locked = locked_dependencies(
+                            &lockfile,
+                            &dependencies.crate_name,
+                            &dependencies.version,
+                        );
+                    }
+                    if measure_size {
+                        dependencies.unpacked_size = package_size_in_tree(
+                            &repo,
+                            &tree,
+                            root,
+                            content,
+                            workspace_root.as_ref(),
+                        );
+                    }
+                    if dependencies.msrv.is_none() {
+                        dependencies.msrv = detect_msrv_in_tree(&repo, &tree, root);
+                    }
+                    if dependencies.msrv.is_none() && !toolchains.is_empty() {
+                        if let Some(sandbox) = sandbox() {
+                            dependencies.msrv =
+                                bisect_msrv(sandbox, &repo, &tree, root, &toolchains);
+                        }
+                    }
                     res.push(dependencies);
                 }
             }
@@ -96,105 +204,447 @@ impl ImportContext {
     fn parse_a_package_of_a_version(
         &self,
         cargo_toml_content: &str,
+        workspace_root: Option<&Value>,
         git_url: &str,
-        tag_name: &str,
+        tag: &GitTag,
     ) -> Option<Dependencies> {
-        match cargo_toml_content.parse::<Value>() {
-            Ok(toml) => {
-                if let Some(package) = toml.get("package") {
-                    if let Some(crate_name) = package.get("name") {
-                        let crate_name = crate_name.as_str()?.to_string();
-                        let version = package.get("version")?.as_str()?.to_string();
-
-                        // e.g. 0.1.53a2 is invalid version number.
-                        if semver::Version::parse(&version).is_err() {
-                            return None;
-                        }
-
-                        // dedup
-                        if self
-                            .version_updater
-                            .version_parser
-                            .exists(&crate_name, &version)
-                        {
-                            return None;
-                        }
-
-                        let mut dependencies = vec![];
-
-                        if let Some(dep_table) = toml.get("dependencies") {
-                            if let Some(deps_table) = dep_table.as_table() {
-                                for (name, val) in deps_table {
-                                    if let Some(version) = val.as_str() {
-                                        dependencies.push((name.clone(), version.to_owned()));
-                                    } else if let Some(ver_tab) = val.as_table() {
-                                        if let Some(val) = ver_tab.get("version") {
-                                            if let Some(version) = val.as_str() {
-                                                dependencies
-                                                    .push((name.clone(), version.to_owned()));
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-
-                        let dependencies = Dependencies {
-                            crate_name,
-                            version,
-                            dependencies,
-                            git_url: git_url.to_string(),
-                            tag_name: tag_name.to_string(),
-                        };
-
-                        return Some(dependencies);
-                    }
-                }
+        let mut toml = match cargo_toml_content.parse::<Value>() {
+            Ok(toml) => toml,
+            Err(e) => {
+                tracing::error!("Invalid Cargo.toml in {} at {}: {}", git_url, tag.name, e);
+                return None;
             }
-            Err(_) => tracing::error!("Failed to parse Cargo.toml for {:?}", cargo_toml_content),
+        };
+        if let Some(workspace_root) = workspace_root {
+            inherit_from_workspace(&mut toml, workspace_root);
         }
-        None
+        let manifest = match Manifest::from_value(&toml) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                tracing::error!("Invalid manifest in {} at {}: {}", git_url, tag.name, e);
+                return None;
+            }
+        };
+        let package = manifest.package.as_ref()?;
+        let crate_name = package.name.clone();
+        if !discovery_filter().is_crate_included(&crate_name) {
+            return None;
+        }
+        // unknown if it's inherited from a workspace which does not define it
+        let version = package.resolved_version()?.to_string();
+
+        // e.g. 0.1.53a2 is invalid version number.
+        if semver::Version::parse(&version).is_err() {
+            return None;
+        }
+
+        Some(Dependencies {
+            crate_name,
+            version,
+            dependencies: parse_dependencies(&manifest),
+            overrides: parse_overrides(&manifest),
+            features: manifest.features.clone(),
+            git_url: git_url.to_string(),
+            tag_name: tag.name.clone(),
+            api: vec![],
+            msrv: msrv_from_manifest(&manifest),
+            commit: Some(tag.commit.to_string()),
+            commit_time: Some(tag.time),
+            published_at: None,
+            locked: vec![],
+            crate_size: None,
+            unpacked_size: None,
+            checksum: None,
+        })
     }
 }
 
+type ActualDependsOnMap =
+    HashMap<model::general_model::Version, Vec<model::general_model::Version>>;
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct VersionUpdater {
-    /// a reverse record: who depends on the key?
-    pub reverse_depends_on_map: HashMap<String, Vec<(String, model::general_model::Version)>>,
+    /// a reverse record: who depends on the key, and in which kind?
+    #[allow(clippy::type_complexity)]
+    pub reverse_depends_on_map:
+        HashMap<String, Vec<(String, model::general_model::Version, DependencyKind)>>,
 
     /// a actual map: a crate **actually** depends on which?
     /// it is used to build `depends_on` edges.
-    pub actually_depends_on_map:
-        HashMap<model::general_model::Version, Vec<model::general_model::Version>>,
+    pub actually_depends_on_map: ActualDependsOnMap,
+
+    /// the same as `actually_depends_on_map`, but for `[dev-dependencies]`.
+    /// it is used to build `dev_depends_on` edges.
+    #[serde(default)]
+    pub actually_dev_depends_on_map: ActualDependsOnMap,
+
+    /// the same as `actually_depends_on_map`, but for `[build-dependencies]`.
+    /// it is used to build `build_depends_on` edges.
+    #[serde(default)]
+    pub actually_build_depends_on_map: ActualDependsOnMap,
 
     pub version_parser: VersionParser,
+
+    /// the git dependencies of a crate, `(name, git url)`,
+    /// which only match the versions found in that repo.
+    #[serde(default)]
+    pub git_dependencies: HashMap<model::general_model::Version, Vec<(String, String)>>,
+
+    /// the `[patch]` and `[replace]` of a version, resolved when building the `overrides` edges,
+    /// since the replacements may be imported later.
+    #[serde(default)]
+    pub overrides: HashMap<model::general_model::Version, Vec<DependencyOverride>>,
+
+    /// the dependencies only for some platforms, `(name, kind, platforms)`
+    #[serde(default)]
+    pub dependency_targets: HashMap<model::general_model::Version, Vec<DependencyTarget>>,
+
+    /// the optional dependencies, `(name, kind)`, activated only by the features
+    #[serde(default)]
+    pub optional_dependencies:
+        HashMap<model::general_model::Version, Vec<(String, DependencyKind)>>,
+
+    /// the declared requirements and the enabled features,
+    /// `(name, kind, req, features, default_features)`
+    #[serde(default)]
+    pub dependency_requirements: HashMap<model::general_model::Version, Vec<DependencyRequirement>>,
+
+    /// the direct dependencies resolved by the lockfiles,
+    /// the ones not imported are skipped when building the `resolves_to` edges.
+    #[serde(default)]
+    pub locked_dependencies: HashMap<model::general_model::Version, Vec<LockedDependency>>,
+}
+
+type DependencyTarget = (String, DependencyKind, String);
+
+type DependencyRequirement = (String, DependencyKind, String, Vec<String>, bool);
+
+/// The platforms of the dependencies declared only in `[target.'cfg(...)']` tables,
+/// joined by `, ` if it's declared for several ones.
+fn dependency_targets(dependencies: &[Dependency]) -> Vec<DependencyTarget> {
+    let mut targets: BTreeMap<(&str, DependencyKind), Option<Vec<&str>>> = BTreeMap::new();
+    for dep in dependencies {
+        let platforms = targets
+            .entry((dep.name.as_str(), dep.kind))
+            .or_insert_with(|| Some(vec![]));
+        match &dep.target {
+            Some(target) => {
+                if let Some(platforms) = platforms {
+                    platforms.push(target.as_str());
+                }
+            }
+            // it's for all the platforms
+            None => *platforms = None,
+        }
+    }
+    targets
+        .into_iter()
+        .filter_map(|((name, kind), platforms)| {
+            Some((name.to_string(), kind, platforms?.join(", ")))
+        })
+        .collect()
+}
+
+/// The dependencies which are optional in all the tables they're declared in.
+fn optional_dependencies(dependencies: &[Dependency]) -> Vec<(String, DependencyKind)> {
+    let mut optional: BTreeMap<(&str, DependencyKind), bool> = BTreeMap::new();
+    for dep in dependencies {
+        *optional
+            .entry((dep.name.as_str(), dep.kind))
+            .or_insert(true) &= dep.optional;
+    }
+    optional
+        .into_iter()
+        .filter(|(_, optional)| *optional)
+        .map(|((name, kind), _)| (name.to_string(), kind))
+        .collect()
+}
+
+/// The requirement of each dependency in the first table it's declared in,
+/// and the features enabled by any of the tables, the default ones included
+/// unless all of them say `default-features = false`, as cargo unifies them.
+fn dependency_requirements(dependencies: &[Dependency]) -> Vec<DependencyRequirement> {
+    let mut requirements: BTreeMap<(&str, DependencyKind), (&str, BTreeSet<&str>, bool)> =
+        BTreeMap::new();
+    for dep in dependencies {
+        let (_, features, default_features) = requirements
+            .entry((dep.name.as_str(), dep.kind))
+            .or_insert_with(|| (dep.version_req.as_str(), BTreeSet::new(), false));
+        features.extend(dep.features.iter().map(String::as_str));
+        *default_features |= dep.default_features;
+    }
+    requirements
+        .into_iter()
+        .map(|((name, kind), (req, features, default_features))| {
+            let features = features.into_iter().map(String::from).collect();
+            (
+                name.to_string(),
+                kind,
+                req.to_string(),
+                features,
+                default_features,
+            )
+        })
+        .collect()
+}
+
+/// The version requirement of a dependency, `*` for a git dependency without one,
+/// since it's whatever version in the repo.
+fn version_requirement(dep: &Dependency) -> Option<&str> {
+    match (&dep.source, dep.version_req.as_str()) {
+        (DependencySource::Git(_), "") => Some("*"),
+        (_, "") => None,
+        (_, req) => Some(req),
+    }
+}
+
+/// The repo of a url, such as `tokio-rs/tokio` for `https://github.com/tokio-rs/tokio.git`.
+fn repo_key(git_url: &str) -> String {
+    extract_namespace(git_url).unwrap_or_else(|_| {
+        git_url
+            .trim_end_matches('/')
+            .trim_end_matches(".git")
+            .to_lowercase()
+    })
+}
+
+/// The properties shared by the edges of all the dependency kinds
+struct DependencyEdge {
+    src: String,
+    dst: String,
+    target: Option<String>,
+    optional: bool,
+    req: Option<String>,
+    range: Option<String>,
+    features: Option<String>,
+    default_features: bool,
 }
 
 impl VersionUpdater {
     pub async fn to_depends_on_edges(&self) -> Vec<DependsOn> {
+        self.to_edges(DependencyKind::Normal)
+            .into_iter()
+            .map(|e| DependsOn {
+                SRC_ID: e.src,
+                DST_ID: e.dst,
+                target: e.target,
+                optional: e.optional,
+                req: e.req,
+                range: e.range,
+                features: e.features,
+                default_features: e.default_features,
+                // flagged by `detect_cycles`
+                in_cycle: false,
+            })
+            .collect()
+    }
+
+    pub async fn to_dev_depends_on_edges(&self) -> Vec<DevDependsOn> {
+        self.to_edges(DependencyKind::Dev)
+            .into_iter()
+            .map(|e| DevDependsOn {
+                SRC_ID: e.src,
+                DST_ID: e.dst,
+                target: e.target,
+                optional: e.optional,
+                req: e.req,
+                range: e.range,
+                features: e.features,
+                default_features: e.default_features,
+                in_cycle: false,
+            })
+            .collect()
+    }
+
+    pub async fn to_build_depends_on_edges(&self) -> Vec<BuildDependsOn> {
+        self.to_edges(DependencyKind::Build)
+            .into_iter()
+            .map(|e| BuildDependsOn {
+                SRC_ID: e.src,
+                DST_ID: e.dst,
+                target: e.target,
+                optional: e.optional,
+                req: e.req,
+                range: e.range,
+                features: e.features,
+                default_features: e.default_features,
+                in_cycle: false,
+            })
+            .collect()
+    }
+
+    /// All the edges of a kind
+    fn to_edges(&self, kind: DependencyKind) -> Vec<DependencyEdge> {
+        let actual_map = match kind {
+            DependencyKind::Normal => &self.actually_depends_on_map,
+            DependencyKind::Dev => &self.actually_dev_depends_on_map,
+            DependencyKind::Build => &self.actually_build_depends_on_map,
+        };
         let mut edges = vec![];
-        for (src, dsts) in &self.actually_depends_on_map {
+        for (src, dsts) in actual_map {
+            let targets = self.dependency_targets.get(src);
+            let optional = self.optional_dependencies.get(src);
+            let requirements = self.dependency_requirements.get(src);
             for dst in dsts {
-                #[allow(non_snake_case)]
-                let SRC_ID = name_join_version(&src.name, &src.version);
+                let target = targets
+                    .into_iter()
+                    .flatten()
+                    .find(|(name, k, _)| name == &dst.name && *k == kind)
+                    .map(|(_, _, target)| target.clone());
+                let optional = optional
+                    .into_iter()
+                    .flatten()
+                    .any(|(name, k)| name == &dst.name && *k == kind);
+                let (req, features, default_features) = requirements
+                    .into_iter()
+                    .flatten()
+                    .find(|(name, k, _, _, _)| name == &dst.name && *k == kind)
+                    .map(|(_, _, req, features, default_features)| {
+                        (req.as_str(), features.as_slice(), *default_features)
+                    })
+                    .unwrap_or(("", &[], true));
+                // a git dependency may have no requirement
+                let req = Some(req.to_string()).filter(|r| !r.is_empty());
+                edges.push(DependencyEdge {
+                    src: name_join_version(&src.name, &src.version),
+                    dst: name_join_version(&dst.name, &dst.version),
+                    target,
+                    optional,
+                    range: req.as_deref().and_then(semver_range),
+                    req,
+                    features: Some(features.join(",")).filter(|f| !f.is_empty()),
+                    default_features,
+                });
+            }
+        }
+        edges
+    }
 
-                #[allow(non_snake_case)]
-                let DST_ID = name_join_version(&dst.name, &dst.version);
-                let depends_on = DependsOn { SRC_ID, DST_ID };
-                edges.push(depends_on);
+    pub async fn to_overrides_edges(&self) -> Vec<Overrides> {
+        let mut edges = vec![];
+        for (src, overrides) in &self.overrides {
+            for o in overrides {
+                if let Some(version) = self.resolve_version(&o.replacement).await {
+                    edges.push(Overrides {
+                        SRC_ID: name_join_version(&src.name, &src.version),
+                        DST_ID: name_join_version(&o.replacement.name, &version),
+                        kind: o.kind.as_str().to_string(),
+                        source: o.source.clone(),
+                    });
+                }
             }
         }
         edges
     }
 
+    pub async fn to_resolves_to_edges(&self) -> Vec<ResolvesTo> {
+        let mut edges = vec![];
+        for (src, locked) in &self.locked_dependencies {
+            for dep in locked {
+                if !self.version_parser.exists(&dep.name, &dep.version) {
+                    continue;
+                }
+                edges.push(ResolvesTo {
+                    SRC_ID: name_join_version(&src.name, &src.version),
+                    DST_ID: name_join_version(&dep.name, &dep.version),
+                    source: dep.source.clone(),
+                    checksum: dep.checksum.clone(),
+                });
+            }
+        }
+        edges
+    }
+
+    /// Which version of `dependency_name` does `src` actually depend on?
+    pub(crate) fn resolve_dependency(
+        &self,
+        src: &model::general_model::Version,
+        dependency_name: &str,
+    ) -> Option<&model::general_model::Version> {
+        [
+            &self.actually_depends_on_map,
+            &self.actually_dev_depends_on_map,
+            &self.actually_build_depends_on_map,
+        ]
+        .into_iter()
+        .filter_map(|actual_map| actual_map.get(src))
+        .flatten()
+        .find(|dst| dst.name == dependency_name)
+    }
+
+    fn actual_map_mut(&mut self, kind: DependencyKind) -> &mut ActualDependsOnMap {
+        match kind {
+            DependencyKind::Normal => &mut self.actually_depends_on_map,
+            DependencyKind::Dev => &mut self.actually_dev_depends_on_map,
+            DependencyKind::Build => &mut self.actually_build_depends_on_map,
+        }
+    }
+
     /// Given a dependency list,
     pub async fn update_depends_on(&mut self, info: &Dependencies) {
         self.version_parser
             .insert_version(&info.crate_name, &info.version)
             .await;
+        if !info.git_url.is_empty() {
+            self.version_parser
+                .insert_repo(&info.crate_name, &info.git_url);
+        }
         let cur_release = model::general_model::Version::new(&info.crate_name, &info.version);
-        self.ensure_dependencies(&cur_release, info).await;
-        self.ensure_dependents(&cur_release).await;
+        self.record_dependencies(&cur_release, info).await;
+        self.ensure_dependents(&cur_release, &info.git_url).await;
+    }
+
+    /// Replace the dependencies of a version already imported by those of another copy of it,
+    /// such as the copy of the latest commit in a fork. Its dependents are left as they are.
+    pub(crate) async fn replace_depends_on(&mut self, info: &Dependencies) {
+        let cur_release = model::general_model::Version::new(&info.crate_name, &info.version);
+        self.overrides.remove(&cur_release);
+        self.dependency_targets.remove(&cur_release);
+        self.optional_dependencies.remove(&cur_release);
+        self.locked_dependencies.remove(&cur_release);
+        self.dependency_requirements.remove(&cur_release);
+        self.git_dependencies.remove(&cur_release);
+        for dependents in self.reverse_depends_on_map.values_mut() {
+            dependents.retain(|(_, dependent, _)| dependent != &cur_release);
+        }
+        for kind in [
+            DependencyKind::Normal,
+            DependencyKind::Dev,
+            DependencyKind::Build,
+        ] {
+            self.actual_map_mut(kind).remove(&cur_release);
+        }
+        self.record_dependencies(&cur_release, info).await;
+    }
+
+    async fn record_dependencies(
+        &mut self,
+        cur_release: &model::general_model::Version,
+        info: &Dependencies,
+    ) {
+        if !info.overrides.is_empty() {
+            self.overrides
+                .insert(cur_release.clone(), info.overrides.clone());
+        }
+        let targets = dependency_targets(&info.dependencies);
+        if !targets.is_empty() {
+            self.dependency_targets.insert(cur_release.clone(), targets);
+        }
+        let optional = optional_dependencies(&info.dependencies);
+        if !optional.is_empty() {
+            self.optional_dependencies
+                .insert(cur_release.clone(), optional);
+        }
+        if !info.locked.is_empty() {
+            self.locked_dependencies
+                .insert(cur_release.clone(), info.locked.clone());
+        }
+        let requirements = dependency_requirements(&info.dependencies);
+        if !requirements.is_empty() {
+            self.dependency_requirements
+                .insert(cur_release.clone(), requirements);
+        }
+        self.ensure_dependencies(cur_release, info).await;
     }
 
     async fn ensure_dependencies(
@@ -202,89 +652,154 @@ impl VersionUpdater {
         cur_release: &model::general_model::Version,
         info: &Dependencies,
     ) {
-        for (name, version) in &info.dependencies {
+        for dep in &info.dependencies {
+            let Some(version_req) = version_requirement(dep) else {
+                continue;
+            };
             //let dep = model::general_model::Version::new(&name, &version);
-            self.insert_reverse_dep(name, version, &cur_release.name, &cur_release.version)
-                .await;
+            self.insert_reverse_dep(
+                &dep.name,
+                version_req,
+                &cur_release.name,
+                &cur_release.version,
+                dep.kind,
+            )
+            .await;
+            if let DependencySource::Git(git_url) = &dep.source {
+                self.git_dependencies
+                    .entry(cur_release.clone())
+                    .or_default()
+                    .push((dep.name.clone(), git_url.clone()));
+            }
         }
 
-        // a new version should not exist before.
-        assert!(!self.actually_depends_on_map.contains_key(cur_release));
-        let cur_dependencies = self.search_dependencies(info).await;
-        self.actually_depends_on_map
-            .insert(cur_release.clone(), cur_dependencies);
+        for kind in [
+            DependencyKind::Normal,
+            DependencyKind::Dev,
+            DependencyKind::Build,
+        ] {
+            // a new version should not exist before.
+            assert!(!self.actual_map_mut(kind).contains_key(cur_release));
+            let cur_dependencies = self.search_dependencies(info, kind).await;
+            self.actual_map_mut(kind)
+                .insert(cur_release.clone(), cur_dependencies);
+        }
     }
 
-    async fn search_dependencies(&self, info: &Dependencies) -> Vec<model::general_model::Version> {
+    async fn search_dependencies(
+        &self,
+        info: &Dependencies,
+        kind: DependencyKind,
+    ) -> Vec<model::general_model::Version> {
         let mut res: Vec<model::general_model::Version> = vec![];
-        for (dependency_name, dependency_version) in &info.dependencies {
-            let version_option = self
-                .version_parser
-                .find_latest_matching_version(dependency_name, dependency_version)
-                .await;
+        for dep in info.dependencies.iter().filter(|d| d.kind == kind) {
+            let version_option = self.resolve_version(dep).await;
 
             if let Some(dependency_actual_version) = &version_option {
                 let dependency =
-                    model::general_model::Version::new(dependency_name, dependency_actual_version);
-                res.push(dependency);
+                    model::general_model::Version::new(&dep.name, dependency_actual_version);
+                // declared for several platforms
+                if !res.contains(&dependency) {
+                    res.push(dependency);
+                }
             }
         }
         res
     }
 
-    async fn ensure_dependents(&mut self, cur_release: &model::general_model::Version) {
+    /// The latest imported version matching a dependency.
+    async fn resolve_version(&self, dep: &Dependency) -> Option<String> {
+        let version_req = version_requirement(dep)?;
+        // not the crate of the same name in the registry or another repo
+        if let DependencySource::Git(git_url) = &dep.source {
+            if !self.version_parser.is_in_repo(&dep.name, git_url) {
+                return None;
+            }
+        }
+        self.version_parser
+            .find_latest_matching_version(&dep.name, version_req)
+            .await
+    }
+
+    async fn ensure_dependents(
+        &mut self,
+        cur_release: &model::general_model::Version,
+        git_url: &str,
+    ) {
         let sem_ver = semver::Version::parse(&cur_release.version)
             .unwrap_or_else(|_| panic!("failed to parse version {:?}", &cur_release));
-        let wrapped_reverse_map = self.reverse_depends_on_map.get(&cur_release.name);
-        if let Some(reverse_map) = wrapped_reverse_map {
-            for (required_version, reverse_dep) in reverse_map {
-                let requirement = match semver::VersionReq::parse(required_version) {
-                    Ok(req) => req,
-                    Err(_) => {
-                        tracing::error!("failed to transform to VersionReq");
-                        continue;
-                    }
-                };
+        let Self {
+            reverse_depends_on_map,
+            actually_depends_on_map,
+            actually_dev_depends_on_map,
+            actually_build_depends_on_map,
+            git_dependencies,
+            ..
+        } = self;
+        let reverse_map = match reverse_depends_on_map.get(&cur_release.name) {
+            Some(reverse_map) => reverse_map,
+            None => return,
+        };
+        for (required_version, reverse_dep, kind) in reverse_map {
+            let other_repo = git_dependencies.get(reverse_dep).is_some_and(|deps| {
+                deps.iter().any(|(name, url)| {
+                    name == &cur_release.name && repo_key(url) != repo_key(git_url)
+                })
+            });
+            if other_repo {
+                continue;
+            }
+            let actual_map = match kind {
+                DependencyKind::Normal => &mut *actually_depends_on_map,
+                DependencyKind::Dev => &mut *actually_dev_depends_on_map,
+                DependencyKind::Build => &mut *actually_build_depends_on_map,
+            };
+            let requirement = match semver::VersionReq::parse(required_version) {
+                Ok(req) => req,
+                Err(_) => {
+                    tracing::error!("failed to transform to VersionReq");
+                    continue;
+                }
+            };
 
-                if requirement.matches(&sem_ver) {
-                    if let Some(v) = self.actually_depends_on_map.get_mut(reverse_dep) {
-                        let mut found = false;
-                        let mut exist = false;
-                        for x in &mut *v {
-                            if x.name == cur_release.name {
-                                found = true;
-                                let prev_sem_ver = semver::Version::parse(&x.version).unwrap();
-                                if sem_ver == prev_sem_ver {
-                                    exist = true;
-                                    //replace
-                                    //x.version.clone_from(&cur_release.version);
-                                }
-                                //found break;
-                                //break;
+            if requirement.matches(&sem_ver) {
+                if let Some(v) = actual_map.get_mut(reverse_dep) {
+                    let mut found = false;
+                    let mut exist = false;
+                    for x in &mut *v {
+                        if x.name == cur_release.name {
+                            found = true;
+                            let prev_sem_ver = semver::Version::parse(&x.version).unwrap();
+                            if sem_ver == prev_sem_ver {
+                                exist = true;
+                                //replace
+                                //x.version.clone_from(&cur_release.version);
                             }
+                            //found break;
+                            //break;
                         }
-                        #[allow(clippy::if_same_then_else)]
-                        if !found {
-                            v.push(model::general_model::Version::new(
-                                &cur_release.name,
-                                &cur_release.version,
-                            ));
-                        } else if !exist {
-                            v.push(model::general_model::Version::new(
-                                &cur_release.name,
-                                &cur_release.version,
-                            ));
-                        }
-                    } else {
-                        // No vec
-                        self.actually_depends_on_map.insert(
-                            reverse_dep.clone(),
-                            vec![model::general_model::Version::new(
-                                &cur_release.name,
-                                &cur_release.version,
-                            )],
-                        );
                     }
+                    #[allow(clippy::if_same_then_else)]
+                    if !found {
+                        v.push(model::general_model::Version::new(
+                            &cur_release.name,
+                            &cur_release.version,
+                        ));
+                    } else if !exist {
+                        v.push(model::general_model::Version::new(
+                            &cur_release.name,
+                            &cur_release.version,
+                        ));
+                    }
+                } else {
+                    // No vec
+                    actual_map.insert(
+                        reverse_dep.clone(),
+                        vec![model::general_model::Version::new(
+                            &cur_release.name,
+                            &cur_release.version,
+                        )],
+                    );
                 }
             }
         }
@@ -298,19 +813,23 @@ impl VersionUpdater {
         dependency_version: &str,
         dependent_name: &str,
         dependent_version: &str,
+        kind: DependencyKind,
     ) {
         //let dependency = model::general_model::Version::new(dependency_name, dependency_version);
         let dependent = model::general_model::Version::new(dependent_name, dependent_version);
         self.reverse_depends_on_map
             .entry(dependency_name.to_string())
             .or_default()
-            .push((dependency_version.to_string(), dependent));
+            .push((dependency_version.to_string(), dependent, kind));
     }
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct VersionParser {
     version_map: HashMap<String, Vec<String>>,
+    /// the repos which a crate is found in
+    #[serde(default)]
+    repo_map: HashMap<String, HashSet<String>>,
 }
 
 impl VersionParser {
@@ -321,6 +840,19 @@ impl VersionParser {
             .push(version.to_string());
     }
 
+    pub(crate) fn insert_repo(&mut self, crate_name: &str, git_url: &str) {
+        self.repo_map
+            .entry(crate_name.to_string())
+            .or_default()
+            .insert(repo_key(git_url));
+    }
+
+    pub(crate) fn is_in_repo(&self, crate_name: &str, git_url: &str) -> bool {
+        self.repo_map
+            .get(crate_name)
+            .is_some_and(|repos| repos.contains(&repo_key(git_url)))
+    }
+
     pub(crate) fn exists(&self, name: &str, version: &str) -> bool {
         if let Some(map) = self.version_map.get(name) {
             return map.contains(&version.to_string());
@@ -339,7 +871,10 @@ impl VersionParser {
     ) -> Option<String> {
         if let Some(lib_map) = self.version_map.get(target_lib) {
             // if the lib exists
-            let req_str = if target_version.contains('.') {
+            let req_str = if !target_version.starts_with(|c: char| c.is_ascii_digit()) {
+                // with an operator, such as `=1.2.3`, `>=1, <2` or `*`
+                target_version.to_string()
+            } else if target_version.contains('.') {
                 format!("^{}", target_version)
             } else {
                 format!("{}.*", target_version)
@@ -374,15 +909,20 @@ impl VersionUpdater {
         // Calculate heap size for reverse_depends_on_map
         for (key, value) in &self.reverse_depends_on_map {
             heap_size += key.capacity() * mem::size_of::<char>(); // String capacity
-            heap_size +=
-                value.capacity() * mem::size_of::<(String, model::general_model::Version)>();
-            for (s, _) in value {
+            heap_size += value.capacity()
+                * mem::size_of::<(String, model::general_model::Version, DependencyKind)>();
+            for (s, _, _) in value {
                 heap_size += s.capacity() * mem::size_of::<char>(); // String capacity
             }
         }
 
-        // Calculate heap size for actually_depends_on_map
-        for value in self.actually_depends_on_map.values() {
+        // Calculate heap size for actually_depends_on_map and its dev/build counterparts
+        for value in self
+            .actually_depends_on_map
+            .values()
+            .chain(self.actually_dev_depends_on_map.values())
+            .chain(self.actually_build_depends_on_map.values())
+        {
             heap_size += mem::size_of::<model::general_model::Version>(); // Key size
             heap_size += value.capacity() * mem::size_of::<model::general_model::Version>();
         }
@@ -413,7 +953,7 @@ impl VersionParser {
 
 #[cfg(test)]
 mod tests {
-    use super::VersionParser;
+    use super::*;
 
     #[tokio::test]
     async fn test_insert_and_find_version() {
@@ -458,4 +998,143 @@ mod tests {
             None
         );
     }
+
+    #[tokio::test]
+    async fn test_find_version_with_an_operator() {
+        let mut parser = VersionParser::default();
+        parser.insert_version("crate_a", "1.2.2").await;
+        parser.insert_version("crate_b", "2.0.0").await;
+        parser.insert_version("crate_b", "2.0.1").await;
+        assert_eq!(
+            parser
+                .find_latest_matching_version("crate_b", "=2.0.0")
+                .await,
+            Some("2.0.0".to_string())
+        );
+        assert_eq!(
+            parser.find_latest_matching_version("crate_a", "*").await,
+            Some("1.2.2".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replace_depends_on() {
+        let info = |name: &str, dependencies: &[(&str, bool)]| Dependencies {
+            crate_name: name.to_string(),
+            version: "1.0.0".to_string(),
+            dependencies: dependencies
+                .iter()
+                .map(|(name, optional)| {
+                    Dependency::new(name, "1", vec![], *optional, DependencyKind::Normal)
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let mut updater = VersionUpdater::default();
+        updater.update_depends_on(&info("b", &[])).await;
+        updater.update_depends_on(&info("c", &[])).await;
+        updater.update_depends_on(&info("a", &[("b", true)])).await;
+        updater
+            .replace_depends_on(&info("a", &[("c", false)]))
+            .await;
+
+        let a = model::general_model::Version::new("a", "1.0.0");
+        let c = model::general_model::Version::new("c", "1.0.0");
+        assert_eq!(updater.actually_depends_on_map[&a], [c]);
+        assert!(!updater.optional_dependencies.contains_key(&a));
+        assert!(updater.reverse_depends_on_map["b"].is_empty());
+        assert_eq!(updater.reverse_depends_on_map["c"].len(), 1);
+    }
+
+    #[test]
+    fn test_is_in_repo() {
+        let mut parser = VersionParser::default();
+        parser.insert_repo("crate_b", "https://github.com/foo/bar");
+        assert!(parser.is_in_repo("crate_b", "https://github.com/foo/bar.git"));
+        assert!(!parser.is_in_repo("crate_b", "https://github.com/foo/baz"));
+        assert!(!parser.is_in_repo("crate_a", "https://github.com/foo/bar"));
+    }
+
+    #[test]
+    fn test_dependency_targets() {
+        let dep = |name: &str, kind, target: Option<&str>| {
+            Dependency::new(name, "1", vec![], false, kind).with_target(target.map(String::from))
+        };
+        let targets = dependency_targets(&[
+            dep("libc", DependencyKind::Normal, Some("cfg(unix)")),
+            dep("windows-sys", DependencyKind::Normal, Some("cfg(windows)")),
+            dep(
+                "windows-sys",
+                DependencyKind::Normal,
+                Some("x86_64-pc-windows-gnu"),
+            ),
+            dep("cc", DependencyKind::Build, Some("cfg(unix)")),
+            dep("cc", DependencyKind::Build, None),
+            dep("serde", DependencyKind::Normal, None),
+        ]);
+        assert_eq!(
+            targets,
+            [
+                (
+                    "libc".to_string(),
+                    DependencyKind::Normal,
+                    "cfg(unix)".to_string()
+                ),
+                (
+                    "windows-sys".to_string(),
+                    DependencyKind::Normal,
+                    "cfg(windows), x86_64-pc-windows-gnu".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dependency_requirements() {
+        let requirements = dependency_requirements(&[
+            Dependency::new(
+                "tokio",
+                "1.38",
+                vec!["rt".to_string()],
+                false,
+                DependencyKind::Normal,
+            ),
+            Dependency::new(
+                "tokio",
+                "1",
+                vec!["macros".to_string(), "rt".to_string()],
+                false,
+                DependencyKind::Normal,
+            )
+            .with_target(Some("cfg(unix)".to_string()))
+            .with_default_features(false),
+            Dependency::new(
+                "tokio",
+                "1",
+                vec!["full".to_string()],
+                false,
+                DependencyKind::Dev,
+            )
+            .with_default_features(false),
+        ]);
+        assert_eq!(
+            requirements,
+            [
+                (
+                    "tokio".to_string(),
+                    DependencyKind::Normal,
+                    "1.38".to_string(),
+                    vec!["macros".to_string(), "rt".to_string()],
+                    true
+                ),
+                (
+                    "tokio".to_string(),
+                    DependencyKind::Dev,
+                    "1".to_string(),
+                    vec!["full".to_string()],
+                    false
+                ),
+            ]
+        );
+    }
 }
@@ -0,0 +1,330 @@
+//! Detect whether a crate supports `wasm32`, by any of
+//! - a dependency of the wasm ecosystem, such as `wasm-bindgen` or `web-sys`,
+//! - the dependencies for `cfg(target_arch = "wasm32")` or a `wasm32-*` triple,
+//! - the code for `target_arch = "wasm32"` or `target_family = "wasm"` in `src/`,
+//! - a `wasm32` target built for the package by the CI of the repo.
+//!
+//! The cfg predicates are evaluated rather than searched, so that `not(target_arch = "wasm32")`
+//! isn't taken for wasm support.
+
+use crate::ci_summary::read_ci_configs;
+use crate::crate_info::crate_files;
+use crate::manifest::{DependencySpec, Manifest};
+use std::fs;
+use std::path::Path;
+use syn::parse::ParseStream;
+use syn::punctuated::Punctuated;
+use syn::visit::{self, Visit};
+use syn::{token, Attribute, Expr, ExprLit, Lit, Macro, Meta, Token};
+
+/// The crates only used for the web and wasm runtimes, not the hosts of wasm such as wasmtime
+const WASM_CRATES: &[&str] = &[
+    "wasm-bindgen",
+    "wasm-bindgen-futures",
+    "js-sys",
+    "web-sys",
+    "gloo",
+];
+
+/// The `target_os` of the wasm targets, such as `wasm32-unknown-unknown` and `wasm32-wasi`
+const WASM_OSES: &[&str] = &["unknown", "wasi", "emscripten"];
+
+/// Whether the cfg predicate holds on the target of `target_arch` and `target_os`,
+/// with the other cfgs, such as `unix` or the features, unset.
+fn cfg_holds(predicate: &Meta, arch: &str, os: &str) -> bool {
+    match predicate {
+        Meta::Path(_) => false,
+        Meta::NameValue(nv) => {
+            let Expr::Lit(ExprLit {
+                lit: Lit::Str(value),
+                ..
+            }) = &nv.value
+            else {
+                return false;
+            };
+            let value = value.value();
+            if nv.path.is_ident("target_arch") {
+                value == arch
+            } else if nv.path.is_ident("target_family") {
+                value == "wasm" && arch.starts_with("wasm")
+            } else if nv.path.is_ident("target_os") {
+                value == os
+            } else {
+                false
+            }
+        }
+        Meta::List(list) => {
+            let Ok(args) = list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            else {
+                return false;
+            };
+            let holds: Vec<bool> = args.iter().map(|a| cfg_holds(a, arch, os)).collect();
+            if list.path.is_ident("all") {
+                holds.iter().all(|h| *h)
+            } else if list.path.is_ident("any") {
+                holds.iter().any(|h| *h)
+            } else if list.path.is_ident("not") {
+                holds == [false]
+            } else {
+                false
+            }
+        }
+    }
+}
+
+/// Whether the cfg predicate selects the wasm targets, i.e. it holds on a wasm target but not
+/// on the others, so that `not(target_arch = "wasm32")` or `not(windows)` isn't wasm support.
+fn is_wasm_cfg(predicate: &Meta) -> bool {
+    WASM_OSES
+        .iter()
+        .any(|os| cfg_holds(predicate, "wasm32", os))
+        && !cfg_holds(predicate, "", "")
+}
+
+/// The predicate of `cfg(...)`, or of `cfg_attr(..., ...)`.
+fn cfg_predicate(meta: &Meta) -> Option<Meta> {
+    let Meta::List(list) = meta else {
+        return None;
+    };
+    if list.path.is_ident("cfg") {
+        list.parse_args().ok()
+    } else if list.path.is_ident("cfg_attr") {
+        list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            .ok()?
+            .into_iter()
+            .next()
+    } else {
+        None
+    }
+}
+
+/// Whether a target table of the manifest, `cfg(...)` or a triple, is for wasm.
+fn is_wasm_platform(platform: &str) -> bool {
+    if platform.starts_with("cfg(") {
+        syn::parse_str::<Meta>(platform)
+            .ok()
+            .and_then(|meta| cfg_predicate(&meta))
+            .is_some_and(|predicate| is_wasm_cfg(&predicate))
+    } else {
+        platform.starts_with("wasm32-") || platform.starts_with("wasm64-")
+    }
+}
+
+fn has_wasm_dependency(manifest: &Manifest) -> bool {
+    let targets = manifest.target.iter();
+    if targets
+        .clone()
+        .any(|(platform, _)| is_wasm_platform(platform))
+    {
+        return true;
+    }
+    let tables = [&manifest.dependencies, &manifest.dev_dependencies]
+        .into_iter()
+        .chain(targets.map(|(_, t)| &t.dependencies));
+    tables.flat_map(|deps| deps.iter()).any(|(name, spec)| {
+        let name = match spec {
+            DependencySpec::Detailed(detail) => detail.package.as_deref().unwrap_or(name),
+            _ => name,
+        };
+        WASM_CRATES.contains(&name)
+    })
+}
+
+/// The attributes in the body of a macro such as `cfg_if!`, in any group of braces,
+/// brackets or parentheses.
+fn macro_attrs(input: ParseStream) -> syn::Result<Vec<Attribute>> {
+    let mut attrs = Vec::new();
+    while !input.is_empty() {
+        if input.peek(Token![#]) && input.peek2(token::Bracket) {
+            attrs.extend(Attribute::parse_outer(input)?);
+        } else if input.peek(token::Brace) {
+            let content;
+            syn::braced!(content in input);
+            attrs.extend(macro_attrs(&content)?);
+        } else if input.peek(token::Bracket) {
+            let content;
+            syn::bracketed!(content in input);
+            attrs.extend(macro_attrs(&content)?);
+        } else if input.peek(token::Paren) {
+            let content;
+            syn::parenthesized!(content in input);
+            attrs.extend(macro_attrs(&content)?);
+        } else {
+            input.step(|cursor| {
+                cursor
+                    .token_tree()
+                    .map(|(_, rest)| ((), rest))
+                    .ok_or_else(|| cursor.error("unexpected end"))
+            })?;
+        }
+    }
+    Ok(attrs)
+}
+
+/// Find the `#[cfg]`, `#[cfg_attr]` and `cfg!` for wasm in the code, and in the
+/// bodies of the macros such as `cfg_if!`.
+#[derive(Debug, Default)]
+struct WasmCfgs {
+    found: bool,
+}
+
+impl<'ast> Visit<'ast> for WasmCfgs {
+    fn visit_attribute(&mut self, node: &'ast Attribute) {
+        self.found |= cfg_predicate(&node.meta).is_some_and(|p| is_wasm_cfg(&p));
+        visit::visit_attribute(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        if node.path.is_ident("cfg") {
+            self.found |= node.parse_body::<Meta>().is_ok_and(|p| is_wasm_cfg(&p));
+        } else if let Ok(attrs) = node.parse_body_with(macro_attrs) {
+            attrs.iter().for_each(|attr| self.visit_attribute(attr));
+        }
+        visit::visit_macro(self, node);
+    }
+}
+
+fn has_wasm_cfg_in(source: &str) -> bool {
+    let mut cfgs = WasmCfgs::default();
+    match syn::parse_file(source) {
+        Ok(file) => cfgs.visit_file(&file),
+        Err(e) => tracing::debug!("Failed to parse a source for the wasm cfgs: {}", e),
+    }
+    cfgs.found
+}
+
+fn has_wasm_cfg(crate_dir: &Path) -> bool {
+    crate_files(&crate_dir.join("src"))
+        .into_iter()
+        .filter(|f| f.extension().is_some_and(|ext| ext == "rs"))
+        .any(|f| fs::read_to_string(f).is_ok_and(|source| has_wasm_cfg_in(&source)))
+}
+
+/// Whether a command of the CI builds the package `name` in `dir` of the repo, `""` for the
+/// root: it selects the package by `-p`, `--package` or `--manifest-path`, or builds the
+/// workspace, or runs in `dir` otherwise.
+fn builds_package(command: &str, name: &str, dir: &str) -> bool {
+    let words: Vec<&str> = command
+        .split(|c: char| c.is_whitespace() || c == '=')
+        .filter(|w| !w.is_empty())
+        .collect();
+    let packages: Vec<&str> = words
+        .windows(2)
+        .filter(|w| w[0] == "-p" || w[0] == "--package")
+        .map(|w| w[1])
+        .collect();
+    if !packages.is_empty() {
+        return packages.contains(&name);
+    }
+    if let Some(manifest_path) = words
+        .windows(2)
+        .find(|w| w[0] == "--manifest-path")
+        .map(|w| w[1])
+    {
+        return Path::new(manifest_path.trim_start_matches("./"))
+            == Path::new(dir).join("Cargo.toml");
+    }
+    words.contains(&"--workspace")
+        || words.contains(&"--all")
+        || dir.is_empty()
+        || command.contains(dir)
+}
+
+/// Whether the CI of the repo builds the package for `wasm32`,
+/// such as `cargo build -p <name> --target wasm32-unknown-unknown`.
+fn ci_builds_wasm(repo_path: &Path, crate_dir: &Path, name: &str) -> bool {
+    let dir = crate_dir
+        .strip_prefix(repo_path)
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    read_ci_configs(repo_path)
+        .iter()
+        .flat_map(|(_, config)| config.lines())
+        .filter(|line| line.contains("wasm32"))
+        .any(|line| builds_package(line, name, &dir))
+}
+
+/// Whether the crate in `crate_dir` of the repo in `repo_path` supports `wasm32`.
+pub(crate) fn detect_wasm_support(manifest: &Manifest, crate_dir: &Path, repo_path: &Path) -> bool {
+    has_wasm_dependency(manifest)
+        || has_wasm_cfg(crate_dir)
+        || manifest
+            .package
+            .as_ref()
+            .is_some_and(|package| ci_builds_wasm(repo_path, crate_dir, &package.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_wasm_dependency() {
+        let manifest = |toml: &str| -> Manifest { toml::from_str(toml).unwrap() };
+        assert!(has_wasm_dependency(&manifest(
+            "[dependencies]\nbindgen = { package = \"wasm-bindgen\", version = \"0.2\" }"
+        )));
+        assert!(has_wasm_dependency(&manifest(
+            "[target.'cfg(target_arch = \"wasm32\")'.dependencies]\ngetrandom = \"0.2\""
+        )));
+        assert!(has_wasm_dependency(&manifest(
+            "[target.'cfg(unix)'.dependencies]\nweb-sys = \"0.3\""
+        )));
+        assert!(!has_wasm_dependency(&manifest(
+            "[dependencies]\nserde = \"1\"\n[build-dependencies]\nwasm-bindgen = \"0.2\""
+        )));
+        assert!(!has_wasm_dependency(&manifest(
+            "[target.'cfg(not(target_arch = \"wasm32\"))'.dependencies]\ntokio = \"1\""
+        )));
+        assert!(!has_wasm_dependency(&manifest(
+            "[dependencies]\nwasmtime-wasi = \"20\""
+        )));
+        assert!(has_wasm_dependency(&manifest(
+            "[target.wasm32-unknown-unknown.dependencies]\ngetrandom = \"0.2\""
+        )));
+    }
+
+    #[test]
+    fn test_has_wasm_cfg() {
+        assert!(has_wasm_cfg_in(
+            "#[cfg(target_arch = \"wasm32\")]\nfn f() {}"
+        ));
+        assert!(has_wasm_cfg_in(
+            "fn f() -> bool { cfg!(all(target_family = \"wasm\", target_os = \"wasi\")) }"
+        ));
+        assert!(has_wasm_cfg_in(
+            "cfg_if::cfg_if! { if #[cfg(any(unix, target_arch = \"wasm32\"))] { fn f() {} } }"
+        ));
+        assert!(!has_wasm_cfg_in(
+            "#[cfg(not(target_arch = \"wasm32\"))]\nfn f() {}"
+        ));
+        assert!(!has_wasm_cfg_in(
+            "#[cfg_attr(not(windows), derive(Debug))]\nstruct S;"
+        ));
+        // only in a comment and a string
+        assert!(!has_wasm_cfg_in(
+            "// target_arch = \"wasm32\"\nconst S: &str = \"wasm32\";"
+        ));
+    }
+
+    #[test]
+    fn test_builds_package() {
+        let command = "cargo build -p foo --target wasm32-unknown-unknown";
+        assert!(builds_package(command, "foo", "crates/foo"));
+        assert!(!builds_package(command, "bar", "crates/bar"));
+        assert!(!builds_package(command, "root", ""));
+
+        let command = "cargo check --manifest-path crates/foo/Cargo.toml --target wasm32-wasi";
+        assert!(builds_package(command, "foo", "crates/foo"));
+        assert!(!builds_package(command, "root", ""));
+
+        let command = "cargo build --target wasm32-unknown-unknown";
+        assert!(builds_package(command, "root", ""));
+        assert!(!builds_package(command, "bar", "crates/bar"));
+        assert!(builds_package(
+            "cargo build --workspace --target=wasm32-unknown-unknown",
+            "bar",
+            "crates/bar"
+        ));
+    }
+}
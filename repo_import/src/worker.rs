@@ -0,0 +1,182 @@
+//! A worker importing the repos requested by the messages of a kafka topic,
+//! so that many workers in a consumer group share the requests.
+//!
+//! A request is a [`RepoImportRequest`] in json or Avro, or a plain url. The repos are
+//! cloned and imported into one context for the lifetime of the worker, starting from the
+//! checkpoint `CHECKPOINT_DIR/latest.json` if any, so that the dependencies on the crates
+//! imported before are resolved. After each request, the messages of the graph changed since
+//! the last one are published by [`crate::kafka_sink`], the whole graph for the first, and a
+//! [`RepoImportResult`] is sent to the result topic. The repos imported at the same time by
+//! other workers are only linked once they are imported into the checkpoint.
+//!
+//! It's configured by
+//! - `KAFKA_BROKER` and `KAFKA_REPO_REQUEST_TOPIC`,
+//! - `KAFKA_WORKER_GROUP_ID`, `crates_pro_import_worker` by default,
+//! - `KAFKA_REPO_RESULT_TOPIC`, where the results are sent if it's set,
+//! - `NEW_CRATES_DIR`, where the repos are cloned, and the `CLONE_*` of [`CloneOptions`].
+//!
+//! The offsets are committed once a request is received, so a request is
+//! handled at most once.
+
+use crate::clone::CloneOptions;
+use crate::error::ImportError;
+use crate::kafka_handler::KafkaHandler;
+use crate::metrics::update_queue_depth;
+use crate::progress::{latest_checkpoint_path, load_checkpoint};
+use crate::report::record_repo_failed;
+use crate::ImportContext;
+use model::general_model::{RepoImportRequest, RepoImportResult};
+use model::message::decode;
+use rdkafka::error::KafkaError;
+use rdkafka::Message;
+use std::collections::HashSet;
+use std::env;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub struct ImportWorker {
+    consumer: KafkaHandler,
+    /// the producer and the topic of the results
+    results: Option<(KafkaHandler, String)>,
+    dir: PathBuf,
+    options: CloneOptions,
+    /// the context shared by the requests
+    context: ImportContext,
+    /// the hashes of the messages published
+    published: HashSet<u64>,
+}
+
+/// A request in json or Avro, or a plain url.
+fn parse_request(payload: &[u8]) -> Option<RepoImportRequest> {
+    if let Ok(request) = decode::<RepoImportRequest>(payload) {
+        return Some(request);
+    }
+    let payload = String::from_utf8_lossy(payload);
+    let url = payload.trim();
+    if url.is_empty() || url.starts_with('{') {
+        return None;
+    }
+    Some(RepoImportRequest {
+        url: url.to_string(),
+        job_id: None,
+    })
+}
+
+impl ImportWorker {
+    pub async fn from_env() -> Result<Self, Box<dyn Error>> {
+        let brokers = env::var("KAFKA_BROKER")?;
+        let topic = env::var("KAFKA_REPO_REQUEST_TOPIC")?;
+        let group_id = env::var("KAFKA_WORKER_GROUP_ID")
+            .unwrap_or_else(|_| "crates_pro_import_worker".to_string());
+        let consumer = KafkaHandler::new_consumer(&brokers, &group_id, &topic)?;
+        let results = match env::var("KAFKA_REPO_RESULT_TOPIC") {
+            Ok(topic) => Some((KafkaHandler::new_producer(&brokers)?, topic)),
+            Err(_) => None,
+        };
+
+        let latest = latest_checkpoint_path();
+        let mut context = if latest.is_file() {
+            load_checkpoint(&latest).await?
+        } else {
+            ImportContext::default()
+        };
+        context.load_import_cache();
+        Ok(ImportWorker {
+            consumer,
+            results,
+            dir: env::var("NEW_CRATES_DIR")
+                .unwrap_or_else(|_| "/mnt/crates/local_crates_file/".to_string())
+                .into(),
+            options: CloneOptions::from_env(),
+            context,
+            published: HashSet::new(),
+        })
+    }
+
+    /// Handle the requests forever.
+    pub async fn run(&mut self) {
+        tracing::info!("Start the import worker");
+        loop {
+            update_queue_depth(&self.consumer).await;
+            let payload = match self.consumer.consume_once().await {
+                Ok(message) => message.payload().map(|p| p.to_vec()),
+                Err(KafkaError::NoMessageReceived) => {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+                Err(e) => {
+                    tracing::error!("Failed to consume a request: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+            let request = match payload.as_deref().and_then(parse_request) {
+                Some(request) => request,
+                None => {
+                    tracing::warn!("Skip an invalid request: {:?}", payload);
+                    continue;
+                }
+            };
+
+            let result = self.handle(&request).await;
+            if let Some((producer, topic)) = &self.results {
+                let key = result.job_id.as_deref().unwrap_or(&result.url);
+                producer.send_encoded(topic, key, &result).await;
+            }
+        }
+    }
+
+    #[tracing::instrument(name = "request", skip_all, fields(repo = %request.url))]
+    async fn handle(&mut self, request: &RepoImportRequest) -> RepoImportResult {
+        tracing::info!("Import the requested repo {}", request.url);
+        let programs = self.context.programs.len();
+        let versions = self.versions();
+        let res = self.import(request).await;
+        self.context.save_import_cache();
+
+        let mut result = RepoImportResult {
+            url: request.url.clone(),
+            job_id: request.job_id.clone(),
+            programs: (self.context.programs.len() - programs) as i64,
+            versions: (self.versions() - versions) as i64,
+            ..Default::default()
+        };
+        match res {
+            Ok(()) => result.success = true,
+            Err(e) => {
+                tracing::error!("Failed to import {}: {}", request.url, e);
+                record_repo_failed(&request.url, &e);
+                result.error = Some(e.to_string());
+            }
+        }
+        result
+    }
+
+    fn versions(&self) -> usize {
+        self.context.library_versions.len() + self.context.application_versions.len()
+    }
+
+    async fn import(&mut self, request: &RepoImportRequest) -> Result<(), ImportError> {
+        self.context
+            .import_repo(&request.url, &self.dir, &self.options)
+            .await?;
+        self.context.normalize().await;
+        self.context.publish_changes_to_kafka(&mut self.published)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let url = "https://github.com/tokio-rs/tokio";
+        let request = parse_request(format!(r#"{{"url": "{}"}}"#, url).as_bytes()).unwrap();
+        assert_eq!(request.url, url);
+        assert_eq!(parse_request(url.as_bytes()).unwrap().url, url);
+        assert!(parse_request(br#"{"repo": 1}"#).is_none());
+        assert!(parse_request(b" ").is_none());
+    }
+}
@@ -133,3 +133,23 @@ pub(crate) async fn search_crates_by_embedding(
 
     Ok(results)
 }
+
+/// The crates like `name` by the embeddings written by the import, the most similar first,
+/// with the cosine similarity.
+pub async fn find_similar_crates(
+    client: &PgClient,
+    name: &str,
+    n: i64,
+) -> Result<Vec<(String, f64)>, Box<dyn std::error::Error>> {
+    let query = r#"SELECT e."name", 1 - (e."embedding" <=> x."embedding") AS "similarity"
+        FROM "import_embeddings" e,
+            (SELECT "embedding" FROM "import_embeddings" WHERE "name" = $1 LIMIT 1) x
+        WHERE e."name" <> $1
+        ORDER BY e."embedding" <=> x."embedding"
+        LIMIT $2"#;
+    let rows = client.query(query, &[&name, &n]).await?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get("name"), row.get("similarity")))
+        .collect())
+}